@@ -0,0 +1,38 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use num_bigint::BigInt;
+use oriac::cairo::lang::vm::{memory_dict::MemoryDict, relocatable::RelocatableValue};
+
+const N_CELLS: usize = 100_000;
+
+fn fill(mut memory: MemoryDict) -> MemoryDict {
+    for offset in 0..N_CELLS {
+        memory
+            .index_set(
+                RelocatableValue::new(1, offset).into(),
+                BigInt::from(offset).into(),
+            )
+            .unwrap();
+    }
+    memory
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    c.bench_function("memory_dict_fill", |b| {
+        b.iter_batched(
+            MemoryDict::new,
+            |memory| black_box(fill(memory)),
+            BatchSize::LargeInput,
+        );
+    });
+
+    c.bench_function("memory_dict_fill_with_capacity", |b| {
+        b.iter_batched(
+            || MemoryDict::with_capacity(N_CELLS),
+            |memory| black_box(fill(memory)),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,54 @@
+use std::{collections::HashMap, rc::Rc};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use oriac::cairo::lang::{
+    compiler::program::{FullProgram, Program},
+    instances::CairoLayout,
+    vm::{cairo_runner::CairoRunner, memory_dict::MemoryDict},
+};
+
+/// The number of words appended to the program, in the range of large contracts.
+const N_EXTRA_WORDS: usize = 200_000;
+
+/// Measures the setup of a runner for a large program, up to the creation of the VM.
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut program = serde_json::from_str::<serde_json::Value>(include_str!(
+        "../test-data/artifacts/run_past_end.json"
+    ))
+    .unwrap();
+    program["data"]
+        .as_array_mut()
+        .unwrap()
+        .extend((0..N_EXTRA_WORDS).map(|_| serde_json::Value::from("0x1")));
+    let program: Rc<Program> = Rc::new(
+        serde_json::from_value::<FullProgram>(program)
+            .unwrap()
+            .into(),
+    );
+
+    c.bench_function("vm_init", |b| {
+        b.iter_batched(
+            || {
+                let mut runner = CairoRunner::new(
+                    program.clone(),
+                    CairoLayout::plain_instance(),
+                    MemoryDict::new(),
+                    false,
+                    false,
+                )
+                .unwrap();
+                runner.initialize_segments();
+                runner.initialize_main_entrypoint().unwrap();
+                runner
+            },
+            |mut runner| {
+                runner.initialize_vm(HashMap::new(), ()).unwrap();
+                black_box(runner)
+            },
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+use std::{collections::HashMap, rc::Rc};
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use num_bigint::BigInt;
+use oriac::cairo::lang::{
+    compiler::program::{FullProgram, Program},
+    instances::CairoLayout,
+    vm::{cairo_runner::CairoRunner, memory_dict::MemoryDict, vm_core::InstructionCache},
+};
+
+const N_STEPS: u32 = 10_000;
+
+/// Unlike `run_past_end`, which is dominated by the setup of the runner, this only measures the
+/// execution of steps.
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let program: Rc<Program> = Rc::new(
+        serde_json::from_str::<FullProgram>(include_str!(
+            "../test-data/artifacts/infinite_loop.json"
+        ))
+        .unwrap()
+        .into(),
+    );
+
+    let new_runner = |cached: bool| {
+        let mut runner = CairoRunner::new(
+            program.clone(),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        if !cached {
+            runner.vm.as_mut().unwrap().instruction_cache = InstructionCache::default();
+        }
+        runner
+    };
+
+    for (name, cached) in [("vm_steps", true), ("vm_steps_uncached", false)] {
+        c.bench_function(name, |b| {
+            b.iter_batched(
+                || new_runner(cached),
+                |mut runner| {
+                    runner.run_for_steps(BigInt::from(N_STEPS)).unwrap();
+                    black_box(runner)
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
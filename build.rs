@@ -0,0 +1,35 @@
+fn main() {
+    #[cfg(feature = "ffi")]
+    generate_ffi_header();
+}
+
+/// Generates `oriac.h` in `OUT_DIR` from the functions exported by `src/ffi.rs`. Build scripts
+/// must not write outside of `OUT_DIR`, as the source directory may be read-only.
+#[cfg(feature = "ffi")]
+fn generate_ffi_header() {
+    let crate_dir = std::path::PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        include_guard: Some(String::from("ORIAC_H")),
+        autogen_warning: Some(String::from(
+            "/* Generated by cbindgen from src/ffi.rs. Do not edit. */",
+        )),
+        documentation_style: cbindgen::DocumentationStyle::C99,
+        enumeration: cbindgen::EnumConfig {
+            rename_variants: cbindgen::RenameRule::ScreamingSnakeCase,
+            prefix_with_name: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(crate_dir.join("src/ffi.rs"))
+        .generate()
+        .expect("Unable to generate the ffi header")
+        .write_to_file(out_dir.join("oriac.h"));
+}
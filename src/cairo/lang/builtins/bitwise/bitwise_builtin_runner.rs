@@ -0,0 +1,193 @@
+use crate::cairo::lang::{
+    builtins::bitwise::instance_def::{CELLS_PER_BITWISE, INPUT_CELLS_PER_BITWISE},
+    vm::{
+        air_public_input::MemorySegmentAddresses,
+        builtin_runner::{BuiltinRunner, Error, SimpleBuiltinRunner},
+        cairo_runner::CairoRunner,
+        memory_dict::{Error as MemoryError, MemoryDict},
+        memory_segments::MemorySegmentManager,
+        relocatable::{MaybeRelocatable, RelocatableValue},
+        validated_memory_dict::{ValidatedMemoryDict, ValidationRule},
+        vm_core::{Rule, VirtualMachine},
+    },
+};
+
+use num_bigint::BigInt;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
+
+/// A builtin deducing the bitwise and, xor and or of each pair of inputs written to its segment.
+#[derive(Debug)]
+pub struct BitwiseBuiltinRunner {
+    pub inner: SimpleBuiltinRunner,
+    /// The number of bits of the inputs, which must be smaller than 2^total_n_bits.
+    pub total_n_bits: u32,
+}
+
+impl BitwiseBuiltinRunner {
+    pub fn new(name: &str, included: bool, ratio: u32, total_n_bits: u32) -> Self {
+        Self {
+            inner: SimpleBuiltinRunner::new(name, included, ratio, CELLS_PER_BITWISE, &["x", "y"]),
+            total_n_bits,
+        }
+    }
+}
+
+impl BuiltinRunner for BitwiseBuiltinRunner {
+    fn initialize_segments(&mut self, segments: &mut MemorySegmentManager) {
+        self.inner.initialize_segments(segments)
+    }
+
+    fn initial_stack(&self) -> Vec<MaybeRelocatable> {
+        self.inner.initial_stack()
+    }
+
+    fn final_stack(
+        &mut self,
+        runner: &CairoRunner,
+        pointer: MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        self.inner.final_stack(runner, pointer)
+    }
+
+    fn add_validation_rules(&self, validated_memory: &mut ValidatedMemoryDict) {
+        let base = match &self.inner.base {
+            Some(base) => base.to_owned(),
+            None => return,
+        };
+        let builtin_name = self.inner.name.clone();
+        let total_n_bits = self.total_n_bits;
+
+        validated_memory.add_validation_rule(
+            base.segment_index,
+            ValidationRule {
+                inner: Box::new(move |memory, addr, _| {
+                    bitwise_validation_rule(memory, addr, &builtin_name, total_n_bits)
+                }),
+            },
+            (),
+        );
+    }
+
+    fn add_auto_deduction_rules(&self, vm: &mut VirtualMachine) {
+        if let Some(base) = &self.inner.base {
+            vm.add_auto_deduction_rule(
+                base.segment_index,
+                Rule {
+                    inner: bitwise_deduction_rule,
+                },
+                (),
+            );
+        }
+    }
+
+    fn segment_base(&self) -> Option<RelocatableValue> {
+        self.inner.segment_base()
+    }
+
+    fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_cells(runner)
+    }
+
+    fn get_used_instances(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_instances(runner)
+    }
+
+    fn get_used_cells_and_allocated_size(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<(BigInt, BigInt), Error> {
+        self.inner.get_used_cells_and_allocated_size(runner)
+    }
+
+    fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error> {
+        self.inner.get_memory_segment_addresses()
+    }
+
+    fn air_private_input(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, Error> {
+        self.inner.air_private_input(runner)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Checks that the input cells hold integers of at most `total_n_bits` bits. The output cells are
+/// checked by the auto deduction rule instead.
+fn bitwise_validation_rule(
+    memory: &MemoryDict,
+    addr: &RelocatableValue,
+    builtin_name: &str,
+    total_n_bits: u32,
+) -> Result<HashSet<RelocatableValue>, Error> {
+    if addr.offset % CELLS_PER_BITWISE as usize >= INPUT_CELLS_PER_BITWISE as usize {
+        return Ok(HashSet::new());
+    }
+
+    let addr_key = MaybeRelocatable::from(addr.to_owned());
+    match memory
+        .data
+        .get(&addr_key)
+        .ok_or(Error::MemoryError(MemoryError::UnknownMemory {
+            addr: addr_key,
+        }))? {
+        MaybeRelocatable::Int(value) => {
+            if value < &BigInt::from(0u32) || value >= &(BigInt::from(1u32) << total_n_bits) {
+                return Err(Error::BitwiseInputOutOfRange {
+                    addr: addr.to_owned(),
+                    value: value.to_owned(),
+                    total_n_bits,
+                });
+            }
+        }
+        MaybeRelocatable::RelocatableValue(_) => {
+            return Err(Error::NonIntegerBuiltinValue {
+                builtin_name: builtin_name.to_owned(),
+                addr: addr.to_owned(),
+            })
+        }
+    }
+
+    Ok(HashSet::from([addr.to_owned()]))
+}
+
+/// Deduces the and, xor and or cells of an instance from its two input cells, once both are
+/// written.
+///
+/// The segment of the builtin starts at offset 0, so the offset of `addr` gives its cell within
+/// the instance.
+fn bitwise_deduction_rule(vm: &VirtualMachine, addr: &RelocatableValue, _: &()) -> Option<BigInt> {
+    let index = addr.offset % CELLS_PER_BITWISE as usize;
+    if index < INPUT_CELLS_PER_BITWISE as usize {
+        return None;
+    }
+
+    let mut memory = vm.validated_memory.borrow_mut();
+    let mut input = |offset: usize| match memory.get(
+        &RelocatableValue::new(addr.segment_index, addr.offset - index + offset).into(),
+        None,
+    ) {
+        Some(MaybeRelocatable::Int(value)) => Some(value),
+        _ => None,
+    };
+    let x = input(0)?;
+    let y = input(1)?;
+
+    match index {
+        2 => Some(x & y),
+        3 => Some(x ^ y),
+        _ => Some(x | y),
+    }
+}
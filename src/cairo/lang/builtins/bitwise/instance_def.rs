@@ -0,0 +1,12 @@
+// Each bitwise operation consists of 5 cells (two inputs and three outputs - and, xor, or).
+pub const CELLS_PER_BITWISE: u32 = 5;
+pub const INPUT_CELLS_PER_BITWISE: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitwiseInstanceDef {
+    /// Defines the ratio between the number of steps to the number of bitwise instances.
+    /// For every ratio steps, we have one instance.
+    pub ratio: u32,
+    /// The number of bits in a single field element that are supported by the bitwise builtin.
+    pub total_n_bits: u32,
+}
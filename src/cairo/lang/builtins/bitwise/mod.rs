@@ -0,0 +1,2 @@
+pub mod bitwise_builtin_runner;
+pub mod instance_def;
@@ -0,0 +1,17 @@
+use num_bigint::BigInt;
+
+// Each EC operation P + m * Q = R contains 7 cells:
+// P_x, P_y, Q_x, Q_y, m, R_x, R_y.
+pub const CELLS_PER_EC_OP: u32 = 7;
+pub const INPUT_CELLS_PER_EC_OP: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcOpInstanceDef {
+    /// Defines the ratio between the number of steps to the number of EC op instances.
+    /// For every ratio steps, we have one instance.
+    pub ratio: u32,
+    /// Size of coefficient.
+    pub scalar_height: u32,
+    pub scalar_bits: u32,
+    pub scalar_limit: BigInt,
+}
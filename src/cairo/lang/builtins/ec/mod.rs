@@ -0,0 +1 @@
+pub mod instance_def;
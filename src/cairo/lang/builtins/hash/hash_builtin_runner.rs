@@ -0,0 +1,124 @@
+use crate::{
+    cairo::lang::{
+        builtins::hash::instance_def::{CELLS_PER_HASH, INPUT_CELLS_PER_HASH},
+        vm::{
+            air_public_input::MemorySegmentAddresses,
+            builtin_runner::{BuiltinRunner, Error, SimpleBuiltinRunner},
+            cairo_runner::CairoRunner,
+            memory_segments::MemorySegmentManager,
+            relocatable::{MaybeRelocatable, RelocatableValue},
+            vm_core::{Rule, VirtualMachine},
+        },
+    },
+    crypto::signature::fast_pedersen_hash::pedersen_hash,
+};
+
+use num_bigint::BigInt;
+use std::{any::Any, collections::HashMap};
+
+/// A builtin deducing the Pedersen hash of each pair of inputs written to its segment.
+#[derive(Debug)]
+pub struct HashBuiltinRunner {
+    pub inner: SimpleBuiltinRunner,
+}
+
+impl HashBuiltinRunner {
+    pub fn new(name: &str, included: bool, ratio: u32) -> Self {
+        Self {
+            inner: SimpleBuiltinRunner::new(name, included, ratio, CELLS_PER_HASH, &["x", "y"]),
+        }
+    }
+}
+
+impl BuiltinRunner for HashBuiltinRunner {
+    fn initialize_segments(&mut self, segments: &mut MemorySegmentManager) {
+        self.inner.initialize_segments(segments)
+    }
+
+    fn initial_stack(&self) -> Vec<MaybeRelocatable> {
+        self.inner.initial_stack()
+    }
+
+    fn final_stack(
+        &mut self,
+        runner: &CairoRunner,
+        pointer: MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        self.inner.final_stack(runner, pointer)
+    }
+
+    fn add_auto_deduction_rules(&self, vm: &mut VirtualMachine) {
+        if let Some(base) = &self.inner.base {
+            vm.add_auto_deduction_rule(
+                base.segment_index,
+                Rule {
+                    inner: hash_deduction_rule,
+                },
+                (),
+            );
+        }
+    }
+
+    fn segment_base(&self) -> Option<RelocatableValue> {
+        self.inner.segment_base()
+    }
+
+    fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_cells(runner)
+    }
+
+    fn get_used_instances(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_instances(runner)
+    }
+
+    fn get_used_cells_and_allocated_size(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<(BigInt, BigInt), Error> {
+        self.inner.get_used_cells_and_allocated_size(runner)
+    }
+
+    fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error> {
+        self.inner.get_memory_segment_addresses()
+    }
+
+    fn air_private_input(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, Error> {
+        self.inner.air_private_input(runner)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Deduces the output cell of an instance from its two input cells, once both are written.
+///
+/// The segment of the builtin starts at offset 0, so the offset of `addr` gives its cell within
+/// the instance.
+fn hash_deduction_rule(vm: &VirtualMachine, addr: &RelocatableValue, _: &()) -> Option<BigInt> {
+    if addr.offset % CELLS_PER_HASH as usize != INPUT_CELLS_PER_HASH as usize {
+        return None;
+    }
+
+    let mut memory = vm.validated_memory.borrow_mut();
+    let mut input = |offset: usize| match memory.get(
+        &RelocatableValue::new(addr.segment_index, addr.offset - offset).into(),
+        None,
+    ) {
+        Some(MaybeRelocatable::Int(value)) => Some(value),
+        _ => None,
+    };
+    let x = input(2)?;
+    let y = input(1)?;
+
+    pedersen_hash(&x, &y).ok()
+}
@@ -4,7 +4,7 @@ use num_bigint::BigInt;
 pub const CELLS_PER_HASH: u32 = 3;
 pub const INPUT_CELLS_PER_HASH: u32 = 2;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PedersenInstanceDef {
     /// Defines the ratio between the number of steps to the number of pedersen instances.
     /// For every ratio steps, we have one instance.
@@ -1 +1,3 @@
+#[cfg(feature = "builtin-pedersen")]
+pub mod hash_builtin_runner;
 pub mod instance_def;
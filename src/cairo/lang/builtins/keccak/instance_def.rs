@@ -0,0 +1,18 @@
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeccakInstanceDef {
+    /// Defines the ratio between the number of steps to the number of Keccak instances.
+    /// For every ratio steps, we have one instance.
+    pub ratio: u32,
+    /// The input and output are 1600 bits that are represented using a sequence of field
+    /// elements. For example [64] * 25 means 25 field elements each containing 64 bits.
+    pub state_rep: Vec<u32>,
+    /// Should equal n_diluted_bits.
+    pub instances_per_component: u32,
+}
+
+impl KeccakInstanceDef {
+    /// The number of cells of an instance: an input and an output cell per state element.
+    pub fn cells_per_builtin(&self) -> u32 {
+        2 * self.state_rep.len() as u32
+    }
+}
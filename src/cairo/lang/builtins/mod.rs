@@ -1,18 +1,28 @@
 use crate::cairo::lang::builtins::{
-    hash::instance_def::PedersenInstanceDef, range_check::instance_def::RangeCheckInstanceDef,
-    signature::instance_def::EcdsaInstanceDef,
+    bitwise::instance_def::BitwiseInstanceDef, ec::instance_def::EcOpInstanceDef,
+    hash::instance_def::PedersenInstanceDef, keccak::instance_def::KeccakInstanceDef,
+    range_check::instance_def::RangeCheckInstanceDef, signature::instance_def::EcdsaInstanceDef,
 };
 
+pub mod bitwise;
+
+pub mod ec;
+
 pub mod hash;
 
+pub mod keccak;
+
 pub mod range_check;
 
 pub mod signature;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BuiltinDefinition {
     Bool(bool),
     PedersenInstanceDef(PedersenInstanceDef),
     RangeCheckInstanceDef(RangeCheckInstanceDef),
     EcdsaInstanceDef(EcdsaInstanceDef),
+    BitwiseInstanceDef(BitwiseInstanceDef),
+    EcOpInstanceDef(EcOpInstanceDef),
+    KeccakInstanceDef(KeccakInstanceDef),
 }
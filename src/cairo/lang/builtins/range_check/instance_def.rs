@@ -1,6 +1,6 @@
 pub const CELLS_PER_RANGE_CHECK: u32 = 1;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RangeCheckInstanceDef {
     /// Defines the ratio between the number of steps to the number of range check instances.
     /// For every ratio steps, we have one instance.
@@ -1 +1,2 @@
 pub mod instance_def;
+pub mod range_check_builtin_runner;
@@ -0,0 +1,189 @@
+use crate::cairo::lang::{
+    builtins::range_check::instance_def::CELLS_PER_RANGE_CHECK,
+    vm::{
+        air_public_input::MemorySegmentAddresses,
+        builtin_runner::{BuiltinRunner, Error, SimpleBuiltinRunner},
+        cairo_runner::CairoRunner,
+        memory_dict::{Error as MemoryError, MemoryDict},
+        memory_segments::MemorySegmentManager,
+        relocatable::{MaybeRelocatable, RelocatableValue},
+        validated_memory_dict::{ValidatedMemoryDict, ValidationRule},
+    },
+};
+
+use num_bigint::BigInt;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
+
+const INNER_RC_BOUND_BITS: u32 = 16;
+const INNER_RC_BOUND: u32 = 1 << INNER_RC_BOUND_BITS;
+
+/// A builtin checking that the values written to its segment are in the range [0, bound).
+#[derive(Debug)]
+pub struct RangeCheckBuiltinRunner {
+    pub inner: SimpleBuiltinRunner,
+    /// The range check builtin asserts that 0 <= value < bound. For example, if the layout uses 8
+    /// 16-bit range-checks per instance, bound will be 2**(16 * 8) = 2**128.
+    pub bound: BigInt,
+    /// The number of 16-bit parts each value is split into.
+    pub n_parts: u32,
+}
+
+impl RangeCheckBuiltinRunner {
+    pub fn new(name: &str, included: bool, ratio: u32, n_parts: u32) -> Self {
+        Self {
+            inner: SimpleBuiltinRunner::new(
+                name,
+                included,
+                ratio,
+                CELLS_PER_RANGE_CHECK,
+                &["value"],
+            ),
+            bound: BigInt::from(1u32) << (16 * n_parts),
+            n_parts,
+        }
+    }
+}
+
+impl BuiltinRunner for RangeCheckBuiltinRunner {
+    fn initialize_segments(&mut self, segments: &mut MemorySegmentManager) {
+        self.inner.initialize_segments(segments)
+    }
+
+    fn initial_stack(&self) -> Vec<MaybeRelocatable> {
+        self.inner.initial_stack()
+    }
+
+    fn final_stack(
+        &mut self,
+        runner: &CairoRunner,
+        pointer: MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        self.inner.final_stack(runner, pointer)
+    }
+
+    fn add_validation_rules(&self, validated_memory: &mut ValidatedMemoryDict) {
+        let base = match &self.inner.base {
+            Some(base) => base.to_owned(),
+            None => return,
+        };
+        let bound = self.bound.clone();
+
+        validated_memory.add_validation_rule(
+            base.segment_index,
+            ValidationRule {
+                inner: Box::new(move |memory, addr, _| {
+                    range_check_validation_rule(memory, addr, &base, &bound)
+                }),
+            },
+            (),
+        );
+    }
+
+    fn segment_base(&self) -> Option<RelocatableValue> {
+        self.inner.segment_base()
+    }
+
+    fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_cells(runner)
+    }
+
+    fn get_used_instances(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_instances(runner)
+    }
+
+    fn get_used_cells_and_allocated_size(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<(BigInt, BigInt), Error> {
+        self.inner.get_used_cells_and_allocated_size(runner)
+    }
+
+    fn get_range_check_usage(&self, runner: &CairoRunner) -> Result<Option<(u16, u16)>, Error> {
+        let base = self.inner.base.as_ref().ok_or(Error::UnexpectedNoneValue)?;
+
+        let mut usage: Option<(u16, u16)> = None;
+        for (addr, value) in runner.memory.borrow().data.iter() {
+            let value = match (addr, value) {
+                (MaybeRelocatable::RelocatableValue(addr), MaybeRelocatable::Int(value))
+                    if addr.segment_index == base.segment_index =>
+                {
+                    value
+                }
+                _ => continue,
+            };
+
+            // Split the value into n_parts parts of 16 bits each.
+            let mut value = value.to_owned();
+            for _ in 0..self.n_parts {
+                let part = u16::try_from(&value & BigInt::from(INNER_RC_BOUND - 1)).unwrap();
+                usage = Some(match usage {
+                    Some((rc_min, rc_max)) => (rc_min.min(part), rc_max.max(part)),
+                    None => (part, part),
+                });
+                value >>= INNER_RC_BOUND_BITS;
+            }
+        }
+
+        Ok(usage)
+    }
+
+    fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error> {
+        self.inner.get_memory_segment_addresses()
+    }
+
+    fn air_private_input(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, Error> {
+        self.inner.air_private_input(runner)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+fn range_check_validation_rule(
+    memory: &MemoryDict,
+    addr: &RelocatableValue,
+    base: &RelocatableValue,
+    bound: &BigInt,
+) -> Result<HashSet<RelocatableValue>, Error> {
+    let addr_key = MaybeRelocatable::from(addr.to_owned());
+    let value =
+        memory
+            .data
+            .get(&addr_key)
+            .ok_or(Error::MemoryError(MemoryError::UnknownMemory {
+                addr: addr_key,
+            }))?;
+
+    match value {
+        MaybeRelocatable::Int(value) => {
+            if value < &BigInt::from(0u32) || value >= bound {
+                return Err(Error::RangeCheckOutOfRange {
+                    value: value.to_owned(),
+                    index: (addr.offset - base.offset).into(),
+                    bound: bound.to_owned(),
+                });
+            }
+        }
+        MaybeRelocatable::RelocatableValue(_) => {
+            return Err(Error::RangeCheckNonIntegerValue {
+                addr: addr.to_owned(),
+                value: value.to_owned(),
+            })
+        }
+    }
+
+    Ok(HashSet::from([addr.to_owned()]))
+}
@@ -2,7 +2,7 @@
 pub const CELLS_PER_SIGNATURE: u32 = 2;
 pub const INPUT_CELLS_PER_SIGNATURE: u32 = 2;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EcdsaInstanceDef {
     /// Defines the ratio between the number of steps to the number of ECDSA instances.
     /// For every ratio steps, we have one instance.
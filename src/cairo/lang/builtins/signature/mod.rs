@@ -1 +1,3 @@
 pub mod instance_def;
+#[cfg(feature = "builtin-ecdsa")]
+pub mod signature_builtin_runner;
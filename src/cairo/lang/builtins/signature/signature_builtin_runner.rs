@@ -0,0 +1,219 @@
+use crate::{
+    cairo::lang::{
+        builtins::signature::instance_def::CELLS_PER_SIGNATURE,
+        vm::{
+            air_public_input::MemorySegmentAddresses,
+            builtin_runner::{BuiltinRunner, Error, SimpleBuiltinRunner},
+            cairo_runner::CairoRunner,
+            memory_dict::MemoryDict,
+            memory_segments::MemorySegmentManager,
+            relocatable::{MaybeRelocatable, RelocatableValue},
+            validated_memory_dict::{ValidatedMemoryDict, ValidationRule},
+        },
+    },
+    crypto::signature::signature::{div_mod_n, get_y_coordinate, verify, FIELD_PRIME},
+    python::math_utils::ec_neg,
+};
+
+use num_bigint::BigInt;
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// A builtin checking that each pair of public key and message written to its segment comes with
+/// a valid signature.
+#[derive(Debug)]
+pub struct SignatureBuiltinRunner {
+    pub inner: SimpleBuiltinRunner,
+    /// The `(r, s)` signatures, keyed by the address of the public key of their instance. Shared
+    /// with the validation rule, so that signatures can be added after the VM is initialized.
+    pub signatures: Rc<RefCell<HashMap<RelocatableValue, (BigInt, BigInt)>>>,
+}
+
+impl SignatureBuiltinRunner {
+    pub fn new(name: &str, included: bool, ratio: u32) -> Self {
+        Self {
+            inner: SimpleBuiltinRunner::new(
+                name,
+                included,
+                ratio,
+                CELLS_PER_SIGNATURE,
+                &["pubkey", "msg"],
+            ),
+            signatures: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Adds the signature of the instance whose public key is at `addr`. It must be added before
+    /// both the public key and the message are written.
+    pub fn add_signature(&self, addr: RelocatableValue, signature: (BigInt, BigInt)) {
+        self.signatures.borrow_mut().insert(addr, signature);
+    }
+}
+
+impl BuiltinRunner for SignatureBuiltinRunner {
+    fn initialize_segments(&mut self, segments: &mut MemorySegmentManager) {
+        self.inner.initialize_segments(segments)
+    }
+
+    fn initial_stack(&self) -> Vec<MaybeRelocatable> {
+        self.inner.initial_stack()
+    }
+
+    fn final_stack(
+        &mut self,
+        runner: &CairoRunner,
+        pointer: MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        self.inner.final_stack(runner, pointer)
+    }
+
+    fn add_validation_rules(&self, validated_memory: &mut ValidatedMemoryDict) {
+        let base = match &self.inner.base {
+            Some(base) => base.to_owned(),
+            None => return,
+        };
+        let builtin_name = self.inner.name.clone();
+        let signatures = self.signatures.clone();
+
+        validated_memory.add_validation_rule(
+            base.segment_index,
+            ValidationRule {
+                inner: Box::new(move |memory, addr, _| {
+                    signature_validation_rule(memory, addr, &builtin_name, &signatures.borrow())
+                }),
+            },
+            (),
+        );
+    }
+
+    fn segment_base(&self) -> Option<RelocatableValue> {
+        self.inner.segment_base()
+    }
+
+    fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_cells(runner)
+    }
+
+    fn get_used_instances(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.inner.get_used_instances(runner)
+    }
+
+    fn get_used_cells_and_allocated_size(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<(BigInt, BigInt), Error> {
+        self.inner.get_used_cells_and_allocated_size(runner)
+    }
+
+    fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error> {
+        self.inner.get_memory_segment_addresses()
+    }
+
+    fn air_private_input(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, Error> {
+        let base = self.inner.base.ok_or(Error::UnexpectedNoneValue)?;
+        let signatures = self.signatures.borrow();
+
+        let mut res = self.inner.air_private_input(runner)?;
+        for instance in res.values_mut().flatten() {
+            let index = instance
+                .get("index")
+                .and_then(serde_json::Value::as_u64)
+                .ok_or(Error::UnexpectedNoneValue)?;
+            let addr = base + index as usize * CELLS_PER_SIGNATURE as usize;
+            let (r, s) = signatures
+                .get(&addr)
+                .ok_or(Error::SignatureHintMissing { addr })?;
+            // The validation rule made sure that 0 < s < EC_ORDER, so that s is invertible.
+            let w = div_mod_n(&BigInt::from(1u32), s).unwrap();
+
+            let mut signature_input = serde_json::Map::new();
+            signature_input.insert(String::from("r"), format!("{:#x}", r).into());
+            signature_input.insert(String::from("w"), format!("{:#x}", w).into());
+            if let serde_json::Value::Object(instance) = instance {
+                instance.insert(
+                    String::from("signature_input"),
+                    serde_json::Value::Object(signature_input),
+                );
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Verifies the signature of the instance of `addr`, once both its public key and its message are
+/// written.
+fn signature_validation_rule(
+    memory: &MemoryDict,
+    addr: &RelocatableValue,
+    builtin_name: &str,
+    signatures: &HashMap<RelocatableValue, (BigInt, BigInt)>,
+) -> Result<HashSet<RelocatableValue>, Error> {
+    let pubkey_addr = RelocatableValue::new(
+        addr.segment_index,
+        addr.offset - addr.offset % CELLS_PER_SIGNATURE as usize,
+    );
+    let msg_addr = pubkey_addr + 1;
+
+    let read =
+        |addr: &RelocatableValue| match memory.data.get(&MaybeRelocatable::from(addr.to_owned())) {
+            Some(MaybeRelocatable::Int(value)) => Ok(Some(value)),
+            Some(MaybeRelocatable::RelocatableValue(_)) => Err(Error::NonIntegerBuiltinValue {
+                builtin_name: builtin_name.to_owned(),
+                addr: addr.to_owned(),
+            }),
+            None => Ok(None),
+        };
+    let (pubkey, msg) = match (read(&pubkey_addr)?, read(&msg_addr)?) {
+        (Some(pubkey), Some(msg)) => (pubkey, msg),
+        _ => return Ok(HashSet::new()),
+    };
+
+    let (r, s) = signatures
+        .get(&pubkey_addr)
+        .ok_or(Error::SignatureHintMissing { addr: pubkey_addr })?;
+    if !is_valid_signature(msg, r, s, pubkey) {
+        return Err(Error::InvalidSignature {
+            r: r.to_owned(),
+            s: s.to_owned(),
+            pubkey: pubkey.to_owned(),
+            msg: msg.to_owned(),
+        });
+    }
+
+    Ok(HashSet::from([pubkey_addr, msg_addr]))
+}
+
+/// Verifies a signature against the x coordinate of a public key, trying both of the points with
+/// that x coordinate. Signatures out of range are invalid.
+fn is_valid_signature(msg: &BigInt, r: &BigInt, s: &BigInt, pubkey: &BigInt) -> bool {
+    let point = match get_y_coordinate(pubkey) {
+        Ok(y) => (pubkey.to_owned(), y),
+        Err(_) => return false,
+    };
+    let neg_point = match ec_neg(&point, &FIELD_PRIME) {
+        Ok(point) => point,
+        Err(_) => return false,
+    };
+
+    [point, neg_point]
+        .iter()
+        .any(|point| matches!(verify(msg, r, s, point), Ok(true)))
+}
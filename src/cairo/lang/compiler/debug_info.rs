@@ -1,4 +1,26 @@
-use serde::Deserialize;
+use crate::cairo::lang::compiler::error_handling::Location;
 
-#[derive(Debug, Deserialize)]
-pub struct DebugInfo {}
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::HashMap;
+
+/// Source information for an instruction of the program.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InstructionLocation {
+    pub inst: Location,
+    // TODO: deserialize `hints`, `accessible_scopes` and `flow_tracking_data`
+}
+
+/// Debug information generated by the compiler.
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// A map from (relative) program counter to its location in the source code.
+    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
+    pub instruction_locations: HashMap<BigInt, InstructionLocation>,
+    /// A map from file name to its content. Only programs compiled with
+    /// `--debug_info_with_source` contain the content of their source files.
+    #[serde(default)]
+    pub file_contents: HashMap<String, String>,
+}
@@ -1,6 +1,6 @@
 use crate::cairo::lang::compiler::instruction::{
-    decode_instruction_values, ApUpdate, FpUpdate, Instruction, Op1Addr, Opcode, PcUpdate,
-    Register, Res, OFFSET_BITS,
+    decode_instruction_values, ApUpdate, FpUpdate, Instruction, InstructionDecodeError, Op1Addr,
+    Opcode, PcUpdate, Register, Res, OFFSET_BITS,
 };
 
 use num_bigint::BigInt;
@@ -23,9 +23,11 @@ const OPCODE_ASSERT_EQ_BIT: u32 = 14;
 
 /// Given 1 or 2 integers representing an instruction, returns the Instruction. If imm is given for
 /// an instruction with no immediate, it will be ignored.
-#[allow(unused)]
-pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction {
-    let (flags, off0_enc, off1_enc, off2_enc) = decode_instruction_values(&encoding);
+pub fn decode_instruction(
+    encoding: BigInt,
+    imm: Option<BigInt>,
+) -> Result<Instruction, InstructionDecodeError> {
+    let (flags, off0_enc, off1_enc, off2_enc) = decode_instruction_values(&encoding)?;
 
     // Get dst_register.
     let dst_register = if (&flags >> DST_REG_BIT) & BigInt::from(1) > BigInt::from(0) {
@@ -51,15 +53,13 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
         (false, true, false) => Op1Addr::AP,
         (false, false, true) => Op1Addr::FP,
         (false, false, false) => Op1Addr::OP0,
-        // TODO: switch to proper error handling
-        _ => panic!("invalid op1 encoding"),
+        _ => return Err(InstructionDecodeError::InvalidOp1Encoding),
     };
 
     let imm = match &op1_addr {
         Op1Addr::IMM => {
             if imm.is_none() {
-                // TODO: switch to proper error handling
-                panic!("op1_addr is Op1Addr.IMM, but no immediate given");
+                return Err(InstructionDecodeError::MissingImmediate);
             }
             imm
         }
@@ -76,8 +76,7 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
         (false, true, false) => PcUpdate::JUMP_REL,
         (false, false, true) => PcUpdate::JNZ,
         (false, false, false) => PcUpdate::REGULAR,
-        // TODO: switch to proper error handling
-        _ => panic!("invalid pc_update encoding"),
+        _ => return Err(InstructionDecodeError::InvalidPcUpdateEncoding),
     };
 
     // Get res.
@@ -91,14 +90,12 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
             PcUpdate::JNZ => Res::UNCONSTRAINED,
             _ => Res::OP1,
         },
-        // TODO: switch to proper error handling
-        _ => panic!("invalid res encoding"),
+        _ => return Err(InstructionDecodeError::InvalidResEncoding),
     };
 
     // JNZ opcode means res must be UNCONSTRAINED.
     if matches!(pc_update, PcUpdate::JNZ) && !matches!(res, Res::UNCONSTRAINED) {
-        // TODO: switch to proper error handling
-        panic!("JNZ opcode means res must be UNCONSTRAINED");
+        return Err(InstructionDecodeError::JnzWithConstrainedRes);
     }
 
     // Get ap_update.
@@ -109,8 +106,7 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
         (true, false) => ApUpdate::ADD,
         (false, true) => ApUpdate::ADD1,
         (false, false) => ApUpdate::REGULAR, // OR ADD2, depending if we have CALL opcode.
-        // TODO: switch to proper error handling
-        _ => panic!("invalid ap_update encoding"),
+        _ => return Err(InstructionDecodeError::InvalidApUpdateEncoding),
     };
 
     // Get opcode.
@@ -123,15 +119,13 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
         (false, true, false) => Opcode::RET,
         (false, false, true) => Opcode::ASSERT_EQ,
         (false, false, false) => Opcode::NOP,
-        // TODO: switch to proper error handling
-        _ => panic!("invalid opcode encoding"),
+        _ => return Err(InstructionDecodeError::InvalidOpcodeEncoding),
     };
 
     // CALL opcode means ap_update must be ADD2.
     if matches!(opcode, Opcode::CALL) {
         if !matches!(ap_update, ApUpdate::REGULAR) {
-            // TODO: switch to proper error handling
-            panic!("CALL must have update_ap is ADD2");
+            return Err(InstructionDecodeError::CallWithApUpdate);
         }
         ap_update = ApUpdate::ADD2;
     }
@@ -143,7 +137,7 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
         _ => FpUpdate::REGULAR,
     };
 
-    Instruction {
+    Ok(Instruction {
         off0: (off0_enc as i32 - 2i32.pow(OFFSET_BITS - 1)) as i16,
         off1: (off1_enc as i32 - 2i32.pow(OFFSET_BITS - 1)) as i16,
         off2: (off2_enc as i32 - 2i32.pow(OFFSET_BITS - 1)) as i16,
@@ -156,5 +150,199 @@ pub fn decode_instruction(encoding: BigInt, imm: Option<BigInt>) -> Instruction
         ap_update,
         fp_update,
         opcode,
+    })
+}
+
+/// Decodes all the instructions in the program data, returning each of them along with its pc.
+///
+/// Values that don't decode to an instruction (e.g. data embedded with `dw`) are returned as errors
+/// and skipped one cell at a time, so that decoding resumes right after them.
+pub fn decode_all(data: &[BigInt]) -> Vec<(usize, Result<Instruction, InstructionDecodeError>)> {
+    let mut instructions = vec![];
+
+    let mut pc = 0;
+    while pc < data.len() {
+        let result = decode_instruction(data[pc].clone(), data.get(pc + 1).cloned());
+        let size = match &result {
+            Ok(instruction) => instruction.size() as usize,
+            Err(_) => 1,
+        };
+        instructions.push((pc, result));
+        pc += size;
+    }
+
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an instruction encoding with the given flags and all offsets set to 0.
+    fn encode_flags(flags: u64) -> BigInt {
+        let zero_offset = 2u64.pow(OFFSET_BITS - 1);
+        BigInt::from(
+            (flags << (3 * OFFSET_BITS))
+                | (zero_offset << (2 * OFFSET_BITS))
+                | (zero_offset << OFFSET_BITS)
+                | zero_offset,
+        )
+    }
+
+    fn decode_flags(
+        flags: u64,
+        imm: Option<BigInt>,
+    ) -> Result<Instruction, InstructionDecodeError> {
+        decode_instruction(encode_flags(flags), imm)
+    }
+
+    #[test]
+    fn test_decode_ret() {
+        let instruction = decode_instruction(BigInt::from(0x208b7fff7fff7ffeu64), None).unwrap();
+
+        assert_eq!(
+            instruction,
+            Instruction {
+                off0: -2,
+                off1: -1,
+                off2: -1,
+                imm: None,
+                dst_register: Register::FP,
+                op0_register: Register::FP,
+                op1_addr: Op1Addr::FP,
+                res: Res::OP1,
+                pc_update: PcUpdate::JUMP,
+                ap_update: ApUpdate::REGULAR,
+                fp_update: FpUpdate::DST,
+                opcode: Opcode::RET,
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_all() {
+        // [ap + 0] = 1; ap++
+        // call rel -2
+        // ret
+        let data = [
+            BigInt::from(0x480680017fff8000u64),
+            BigInt::from(1),
+            BigInt::from(0x1104800180018000u64),
+            BigInt::from(-2),
+            BigInt::from(0x208b7fff7fff7ffeu64),
+        ];
+
+        let lines = decode_all(&data)
+            .into_iter()
+            .map(|(pc, instruction)| format!("{}: {}", pc, instruction.unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec!["0: [ap + 0] = 1; ap++", "2: call rel -2", "4: ret",]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let instruction =
+            decode_instruction(BigInt::from(0x400680017fff7fffu64), Some(BigInt::from(2))).unwrap();
+        assert_eq!(instruction.to_string(), "[ap + (-1)] = 2");
+
+        let instruction =
+            decode_instruction(BigInt::from(0x40780017fff7fffu64), Some(BigInt::from(3))).unwrap();
+        assert_eq!(instruction.to_string(), "ap += 3");
+    }
+
+    #[test]
+    fn test_decode_unsupported_instruction() {
+        assert!(matches!(
+            decode_instruction(BigInt::from(-1), None),
+            Err(InstructionDecodeError::UnsupportedInstruction)
+        ));
+        assert!(matches!(
+            decode_instruction(BigInt::from(1u64 << 63), None),
+            Err(InstructionDecodeError::UnsupportedInstruction)
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_op1() {
+        assert!(matches!(
+            decode_flags(1 << OP1_IMM_BIT | 1 << OP1_AP_BIT, Some(BigInt::from(1))),
+            Err(InstructionDecodeError::InvalidOp1Encoding)
+        ));
+        assert!(matches!(
+            decode_flags(1 << OP1_FP_BIT | 1 << OP1_AP_BIT, None),
+            Err(InstructionDecodeError::InvalidOp1Encoding)
+        ));
+    }
+
+    #[test]
+    fn test_decode_missing_immediate() {
+        assert!(matches!(
+            decode_flags(1 << OP1_IMM_BIT, None),
+            Err(InstructionDecodeError::MissingImmediate)
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_pc_update() {
+        assert!(matches!(
+            decode_flags(1 << PC_JUMP_ABS_BIT | 1 << PC_JUMP_REL_BIT, None),
+            Err(InstructionDecodeError::InvalidPcUpdateEncoding)
+        ));
+        assert!(matches!(
+            decode_flags(1 << PC_JUMP_REL_BIT | 1 << PC_JNZ_BIT, None),
+            Err(InstructionDecodeError::InvalidPcUpdateEncoding)
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_res() {
+        assert!(matches!(
+            decode_flags(1 << RES_ADD_BIT | 1 << RES_MUL_BIT, None),
+            Err(InstructionDecodeError::InvalidResEncoding)
+        ));
+    }
+
+    #[test]
+    fn test_decode_jnz_with_constrained_res() {
+        assert!(matches!(
+            decode_flags(1 << PC_JNZ_BIT | 1 << RES_ADD_BIT, None),
+            Err(InstructionDecodeError::JnzWithConstrainedRes)
+        ));
+        assert!(matches!(
+            decode_flags(1 << PC_JNZ_BIT | 1 << RES_MUL_BIT, None),
+            Err(InstructionDecodeError::JnzWithConstrainedRes)
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_ap_update() {
+        assert!(matches!(
+            decode_flags(1 << AP_ADD_BIT | 1 << AP_ADD1_BIT, None),
+            Err(InstructionDecodeError::InvalidApUpdateEncoding)
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_opcode() {
+        assert!(matches!(
+            decode_flags(1 << OPCODE_CALL_BIT | 1 << OPCODE_RET_BIT, None),
+            Err(InstructionDecodeError::InvalidOpcodeEncoding)
+        ));
+        assert!(matches!(
+            decode_flags(1 << OPCODE_RET_BIT | 1 << OPCODE_ASSERT_EQ_BIT, None),
+            Err(InstructionDecodeError::InvalidOpcodeEncoding)
+        ));
+    }
+
+    #[test]
+    fn test_decode_call_with_ap_update() {
+        assert!(matches!(
+            decode_flags(1 << OPCODE_CALL_BIT | 1 << AP_ADD1_BIT, None),
+            Err(InstructionDecodeError::CallWithApUpdate)
+        ));
     }
 }
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InputFile {
+    pub filename: String,
+    /// The content of the file, if known. It's not part of the serialized form, and is filled in
+    /// from `DebugInfo::file_contents` when the debug info is loaded.
+    #[serde(skip)]
+    pub content: Option<String>,
+}
+
+/// Represents a location in a Cairo source file.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Location {
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize,
+    pub end_col: usize,
+    pub input_file: InputFile,
+    // TODO: deserialize and display `parent_location`
+}
+
+impl Location {
+    pub fn to_string(&self, message: &str) -> String {
+        format!(
+            "{}:{}:{}: {}",
+            self.input_file.filename, self.start_line, self.start_col, message
+        )
+    }
+
+    /// Like `to_string`, followed by the marked source code if the file content is known.
+    pub fn to_string_with_content(&self, message: &str) -> String {
+        let mut res = self.to_string(message);
+
+        if let Some(content) = &self.input_file.content {
+            let location_marks = get_location_marks(content, self);
+            if !location_marks.is_empty() {
+                for line in location_marks.lines() {
+                    res.push_str("\n    ");
+                    res.push_str(line);
+                }
+            }
+        }
+
+        res
+    }
+}
+
+/// Returns the line of `location` in `content`, followed by a line marking its columns, e.g.
+///
+/// ```text
+/// [ap] = 2
+/// ^******^
+/// ```
+///
+/// Returns an empty string if the location is outside of `content`.
+pub fn get_location_marks(content: &str, location: &Location) -> String {
+    let lines = content.lines().collect::<Vec<_>>();
+    if location.start_line == 0 || location.start_line > lines.len() {
+        return String::new();
+    }
+
+    let line = lines[location.start_line - 1];
+    let start_col = location.start_col.max(1);
+    let length = if location.start_line == location.end_line {
+        location.end_col.saturating_sub(start_col)
+    } else {
+        (line.chars().count() + 1).saturating_sub(start_col)
+    };
+
+    let marks = if length <= 1 {
+        String::from("^")
+    } else {
+        format!("^{}^", "*".repeat(length - 2))
+    };
+
+    format!("{}\n{}{}", line, " ".repeat(start_col - 1), marks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_to_string_with_content() {
+        let mut location = Location {
+            start_line: 2,
+            start_col: 5,
+            end_line: 2,
+            end_col: 13,
+            input_file: InputFile {
+                filename: String::from("main.cairo"),
+                content: None,
+            },
+        };
+        assert_eq!(
+            location.to_string_with_content("Error."),
+            "main.cairo:2:5: Error."
+        );
+
+        location.input_file.content =
+            Some(String::from("func main():\n    [ap] = 2\n    ret\nend\n"));
+        assert_eq!(
+            location.to_string_with_content("Error."),
+            "main.cairo:2:5: Error.\n        [ap] = 2\n        ^******^"
+        );
+    }
+}
@@ -1,11 +1,15 @@
-use crate::{cairo::lang::compiler::scoped_name::ScopedName, serde::big_int::BigIntNumber};
+use crate::{
+    cairo::lang::compiler::{references::Reference, scoped_name::ScopedName},
+    serde::big_int::{BigIntNumber, LossyBigIntNumber},
+};
 
 use num_bigint::BigInt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
+use std::collections::HashMap;
 
 #[serde_as]
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum IdentifierDefinition {
     /// Represents an identifier that will be defined later in the code.
@@ -13,8 +17,18 @@ pub enum IdentifierDefinition {
     Alias {
         destination: ScopedName,
     },
-    Const,
-    Member,
+    Const {
+        /// Values that don't fit into 64 bits are missing, as JSON numbers are only read as 64-bit
+        /// integers or as floats.
+        #[serde_as(as = "LossyBigIntNumber")]
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<BigInt>,
+    },
+    Member {
+        cairo_type: String,
+        #[serde_as(as = "BigIntNumber")]
+        offset: BigInt,
+    },
     /// Represents a struct definition.
     ///
     ///```cairo
@@ -22,8 +36,15 @@ pub enum IdentifierDefinition {
     ///     ...
     /// end
     ///```
-    Struct,
-    TypeDefinition,
+    Struct {
+        full_name: ScopedName,
+        members: HashMap<String, MemberDefinition>,
+        #[serde_as(as = "BigIntNumber")]
+        size: BigInt,
+    },
+    TypeDefinition {
+        cairo_type: String,
+    },
     Label {
         #[serde_as(as = "BigIntNumber")]
         pc: BigInt,
@@ -31,12 +52,28 @@ pub enum IdentifierDefinition {
     Function {
         #[serde_as(as = "BigIntNumber")]
         pc: BigInt,
+        #[serde(default)]
+        decorators: Vec<String>,
     },
     Namespace,
-    Reference,
+    Reference {
+        full_name: ScopedName,
+        cairo_type: String,
+        references: Vec<Reference>,
+    },
     Scope,
 }
 
+/// A member of a struct definition.
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct MemberDefinition {
+    /// The type of the member, e.g. `felt` or `__main__.MyStruct*`.
+    pub cairo_type: String,
+    #[serde_as(as = "BigIntNumber")]
+    pub offset: BigInt,
+}
+
 impl IdentifierDefinition {
     pub fn is_label(&self) -> bool {
         // `Function` inherits from `Label` in Python
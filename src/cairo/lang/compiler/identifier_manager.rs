@@ -2,10 +2,10 @@ use crate::cairo::lang::compiler::{
     identifier_definition::IdentifierDefinition, scoped_name::ScopedName,
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     rc::Rc,
 };
 
@@ -31,7 +31,7 @@ pub struct MissingIdentifierError {
 #[error("identifier '{fullname}' is |definition.TYPE|, expected a scope.")]
 pub struct NotAScopeError {
     fullname: ScopedName,
-    definition: IdentifierDefinition,
+    definition: Box<IdentifierDefinition>,
     non_parsed: ScopedName,
 }
 
@@ -175,7 +175,9 @@ impl<'de> Deserialize<'de> for IdentifierManager {
     where
         D: serde::Deserializer<'de>,
     {
-        let items: HashMap<ScopedName, IdentifierDefinition> = HashMap::deserialize(deserializer)?;
+        // Sorted, so that identifiers are added in the same order on every run.
+        let items: BTreeMap<ScopedName, IdentifierDefinition> =
+            BTreeMap::deserialize(deserializer)?;
 
         let mut manager = Self::new();
         for (key, value) in items.iter() {
@@ -186,6 +188,25 @@ impl<'de> Deserialize<'de> for IdentifierManager {
     }
 }
 
+/// Flattens the identifiers back into a map keyed by their full names, sorted by name like in
+/// cairo-lang artifacts.
+impl Serialize for IdentifierManager {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let shared_state = self.shared_state.borrow();
+        let mut items = shared_state
+            .dict
+            .iter()
+            .map(|(name, definition)| (name.to_string(), definition))
+            .collect::<Vec<_>>();
+        items.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+
+        serializer.collect_map(items)
+    }
+}
+
 impl IdentifierScope {
     /// Returns the direct child scope by name, or None if not present.
     pub fn get_single_scope(&self, name: &str) -> Option<&IdentifierScope> {
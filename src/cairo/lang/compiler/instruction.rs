@@ -1,15 +1,38 @@
 use num_bigint::BigInt;
+use std::fmt::Display;
 
 pub const OFFSET_BITS: u32 = 16;
 const N_FLAGS: u32 = 15;
 
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
+pub enum InstructionDecodeError {
+    #[error("Unsupported instruction.")]
+    UnsupportedInstruction,
+    #[error("Invalid op1 encoding.")]
+    InvalidOp1Encoding,
+    #[error("op1_addr is Op1Addr.IMM, but no immediate given")]
+    MissingImmediate,
+    #[error("Invalid pc_update encoding.")]
+    InvalidPcUpdateEncoding,
+    #[error("Invalid res encoding.")]
+    InvalidResEncoding,
+    #[error("JNZ opcode means res must be UNCONSTRAINED.")]
+    JnzWithConstrainedRes,
+    #[error("Invalid ap_update encoding.")]
+    InvalidApUpdateEncoding,
+    #[error("Invalid opcode encoding.")]
+    InvalidOpcodeEncoding,
+    #[error("CALL must have update_ap is ADD2.")]
+    CallWithApUpdate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Register {
     AP = 0,
     FP = 1,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Op1Addr {
     /// op1 = [pc + 1].
     IMM = 0,
@@ -21,7 +44,7 @@ pub enum Op1Addr {
     OP0 = 3,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Res {
     /// res = operand_1.
     OP1 = 0,
@@ -35,7 +58,7 @@ pub enum Res {
 
 /// Flags for register update.
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PcUpdate {
     /// Next pc: pc + op_size.
     REGULAR = 0,
@@ -47,7 +70,7 @@ pub enum PcUpdate {
     JNZ = 3,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ApUpdate {
     /// Next ap: ap.
     REGULAR = 0,
@@ -60,7 +83,7 @@ pub enum ApUpdate {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FpUpdate {
     /// Next fp: fp.
     REGULAR = 0,
@@ -71,7 +94,7 @@ pub enum FpUpdate {
 }
 
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
     NOP = 0,
     ASSERT_EQ = 1,
@@ -79,7 +102,7 @@ pub enum Opcode {
     RET = 3,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Instruction {
     /// Offset. In the range [-2**15, 2*15) = [-2**(OFFSET_BITS-1), 2**(OFFSET_BITS-1)).
     pub off0: i16,
@@ -111,13 +134,93 @@ impl Instruction {
     }
 }
 
+impl Instruction {
+    fn format_dst(&self) -> String {
+        format_deref(&self.dst_register.to_string(), self.off0)
+    }
+
+    fn format_op0(&self) -> String {
+        format_deref(&self.op0_register.to_string(), self.off1)
+    }
+
+    fn format_op1(&self) -> String {
+        match self.op1_addr {
+            Op1Addr::IMM => match &self.imm {
+                Some(imm) => imm.to_string(),
+                None => String::from("?"),
+            },
+            Op1Addr::AP => format_deref("ap", self.off2),
+            Op1Addr::FP => format_deref("fp", self.off2),
+            Op1Addr::OP0 => format_deref(&self.format_op0(), self.off2),
+        }
+    }
+
+    fn format_res(&self) -> String {
+        match self.res {
+            Res::OP1 | Res::UNCONSTRAINED => self.format_op1(),
+            Res::ADD => format!("{} + {}", self.format_op0(), self.format_op1()),
+            Res::MUL => format!("{} * {}", self.format_op0(), self.format_op1()),
+        }
+    }
+
+    fn format_jump(&self) -> String {
+        match self.pc_update {
+            PcUpdate::JUMP => format!("abs {}", self.format_res()),
+            PcUpdate::JNZ => format!("rel {} if {} != 0", self.format_op1(), self.format_dst()),
+            _ => format!("rel {}", self.format_res()),
+        }
+    }
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Register::AP => write!(f, "ap"),
+            Register::FP => write!(f, "fp"),
+        }
+    }
+}
+
+/// Formats the instruction in Cairo assembly syntax, e.g. `[ap + 0] = [fp + (-3)] + 1; ap++`.
+/// Immediates are printed as the raw field elements.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.opcode {
+            Opcode::ASSERT_EQ => write!(f, "{} = {}", self.format_dst(), self.format_res())?,
+            Opcode::CALL => write!(f, "call {}", self.format_jump())?,
+            Opcode::RET => write!(f, "ret")?,
+            Opcode::NOP => match (&self.pc_update, &self.ap_update) {
+                (PcUpdate::REGULAR, ApUpdate::ADD) => write!(f, "ap += {}", self.format_res())?,
+                (PcUpdate::REGULAR, _) => write!(f, "nop")?,
+                _ => write!(f, "jmp {}", self.format_jump())?,
+            },
+        }
+
+        if matches!(self.ap_update, ApUpdate::ADD1) {
+            write!(f, "; ap++")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a memory access, e.g. `[fp + (-3)]`.
+fn format_deref(base: &str, offset: i16) -> String {
+    if offset < 0 {
+        format!("[{} + ({})]", base, offset)
+    } else {
+        format!("[{} + {}]", base, offset)
+    }
+}
+
 /// Returns a tuple (flags, off0, off1, off2) according to the given encoded instruction.
-pub fn decode_instruction_values(encoded_instruction: &BigInt) -> (BigInt, u16, u16, u16) {
-    // TODO: switch to proper error handling
+pub fn decode_instruction_values(
+    encoded_instruction: &BigInt,
+) -> Result<(BigInt, u16, u16, u16), InstructionDecodeError> {
     if encoded_instruction < &BigInt::from(0)
         || encoded_instruction >= &BigInt::from(2u128.pow(3 * OFFSET_BITS + N_FLAGS))
     {
-        panic!("Unsupported instruction.");
+        return Err(InstructionDecodeError::UnsupportedInstruction);
     }
 
     let off0: u16 = (encoded_instruction & BigInt::from(2u32.pow(OFFSET_BITS) - 1))
@@ -133,5 +236,5 @@ pub fn decode_instruction_values(encoded_instruction: &BigInt) -> (BigInt, u16,
     .unwrap();
     let flags_val = encoded_instruction >> (3 * OFFSET_BITS);
 
-    (flags_val, off0, off1, off2)
+    Ok((flags_val, off0, off1, off2))
 }
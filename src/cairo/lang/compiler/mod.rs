@@ -2,6 +2,7 @@ pub mod preprocessor;
 
 pub mod debug_info;
 pub mod encode;
+pub mod error_handling;
 pub mod identifier_definition;
 pub mod identifier_manager;
 pub mod instruction;
@@ -1,11 +1,53 @@
-use crate::cairo::lang::compiler::references::Reference;
+use crate::{
+    cairo::lang::compiler::{references::Reference, scoped_name::ScopedName},
+    serde::big_int::BigIntNumber,
+};
 
-use serde::Deserialize;
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::collections::HashMap;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct ReferenceManager {
     pub references: Vec<Reference>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct FlowTrackingDataActual {}
+/// Tracks the changes to a register (ap) within a group of instructions where its value changes by
+/// known offsets.
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RegTrackingData {
+    /// A unique identifier of the group. Offsets can only be compared within the same group.
+    pub group: usize,
+    /// The offset of the register from its value at the beginning of the group.
+    #[serde_as(as = "BigIntNumber")]
+    pub offset: BigInt,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FlowTrackingDataActual {
+    pub ap_tracking: RegTrackingData,
+    /// The ids of the references in `ReferenceManager` that are valid at this point, keyed by
+    /// their full names.
+    pub reference_ids: HashMap<ScopedName, usize>,
+}
+
+impl ReferenceManager {
+    pub fn get_ref(&self, ref_id: usize) -> Option<&Reference> {
+        self.references.get(ref_id)
+    }
+}
+
+impl FlowTrackingDataActual {
+    /// Returns the reference that the name points to at this point of the program, if any.
+    pub fn resolve_reference<'a>(
+        &self,
+        reference_manager: &'a ReferenceManager,
+        name: &ScopedName,
+    ) -> Option<&'a Reference> {
+        self.reference_ids
+            .get(name)
+            .and_then(|ref_id| reference_manager.get_ref(*ref_id))
+    }
+}
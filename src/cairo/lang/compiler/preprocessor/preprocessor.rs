@@ -1,10 +1,10 @@
 use crate::{
     cairo::lang::compiler::{preprocessor::flow::FlowTrackingDataActual, scoped_name::ScopedName},
-    serde::big_int::BigIntHex,
+    serde::big_int::BigIntNumber,
 };
 
 use num_bigint::BigInt;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 pub struct AttributeBase {
@@ -13,13 +13,13 @@ pub struct AttributeBase {
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct AttributeScope {
     pub name: String,
     pub value: String,
-    #[serde_as(as = "BigIntHex")]
+    #[serde_as(as = "BigIntNumber")]
     pub start_pc: BigInt,
-    #[serde_as(as = "BigIntHex")]
+    #[serde_as(as = "BigIntNumber")]
     pub end_pc: BigInt,
     pub flow_tracking_data: Option<FlowTrackingDataActual>,
     pub accessible_scopes: Vec<ScopedName>,
@@ -9,14 +9,40 @@ use crate::{
         },
         scoped_name::ScopedName,
     },
-    serde::big_int::BigIntHex,
+    hint_support::hint_analysis::HintReport,
+    serde::big_int::{BigIntHexOrDecimal, BigIntNumber},
 };
 
 use num_bigint::BigInt;
-use serde::Deserialize;
-use serde_with::serde_as;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use std::collections::HashMap;
 
+/// Fields found in cairo-lang artifacts that are not modeled by oriac yet. They're always ignored,
+/// even when loading programs strictly. `*` matches any single path segment.
+const UNMODELED_FIELDS: &[&[&str]] = &[
+    &[
+        "debug_info",
+        "instruction_locations",
+        "*",
+        "accessible_scopes",
+    ],
+    &[
+        "debug_info",
+        "instruction_locations",
+        "*",
+        "flow_tracking_data",
+    ],
+    &["debug_info", "instruction_locations", "*", "hints"],
+    &[
+        "debug_info",
+        "instruction_locations",
+        "*",
+        "inst",
+        "parent_location",
+    ],
+];
+
 #[derive(Debug)]
 // Simulate inheritance
 pub enum Program {
@@ -24,7 +50,34 @@ pub enum Program {
     Full(Box<FullProgram>),
 }
 
-#[derive(Debug, Deserialize)]
+/// How unknown fields in program artifacts are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Unknown fields are silently ignored.
+    Permissive,
+    /// Unknown fields are returned to the caller as warnings.
+    Warn,
+    /// Unknown fields are rejected.
+    Deny,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProgramLoadError {
+    #[error(transparent)]
+    Json(serde_json::Error),
+    #[error("Unknown fields in program: {}", .paths.join(", "))]
+    UnknownFields { paths: Vec<String> },
+    #[error("Program data word {index} is not a field element: {value}.")]
+    InvalidDataWord { index: usize, value: BigInt },
+    #[error("Hint pc {pc} is outside of the program data.")]
+    HintPcOutOfBounds { pc: BigInt },
+    #[error("The pc {pc} of {name} is outside of the program data.")]
+    LabelPcOutOfBounds { name: ScopedName, pc: BigInt },
+    #[error("Missing main().")]
+    MissingMain,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct CairoHint {
     pub code: String,
     pub accessible_scopes: Vec<ScopedName>,
@@ -33,23 +86,29 @@ pub struct CairoHint {
 
 /// Cairo program minimal information (stripped from hints, identifiers, etc.). The absence of hints
 /// is crucial for security reasons. Can be used for verifying execution.
-#[derive(Debug)]
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct StrippedProgram {
+    #[serde_as(as = "BigIntHexOrDecimal")]
     pub prime: BigInt,
+    #[serde_as(as = "Vec<BigIntHexOrDecimal>")]
     pub data: Vec<BigInt>,
     pub builtins: Vec<String>,
+    #[serde_as(as = "BigIntNumber")]
     pub main: BigInt,
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 /// Correspond to `Program` in `cairo-lang`.
 pub struct FullProgram {
-    #[serde_as(as = "BigIntHex")]
+    #[serde_as(as = "BigIntHexOrDecimal")]
     pub prime: BigInt,
-    #[serde_as(as = "Vec<BigIntHex>")]
+    #[serde_as(as = "Vec<BigIntHexOrDecimal>")]
     pub data: Vec<BigInt>,
-    #[serde_as(as = "HashMap<BigIntHex, Vec<_>>")]
+    /// Keyed by pc. Like the keys of `instruction_locations`, the pcs are written as decimal
+    /// strings by cairo-compile.
+    #[serde_as(as = "HashMap<DisplayFromStr, Vec<_>>")]
     pub hints: HashMap<BigInt, Vec<CairoHint>>,
     pub builtins: Vec<String>,
     pub main_scope: ScopedName,
@@ -60,6 +119,13 @@ pub struct FullProgram {
 }
 
 impl Program {
+    /// Loads a program in the stripped format, which only contains the fields needed to run it.
+    pub fn load_stripped(json: &str) -> Result<Self, ProgramLoadError> {
+        let program = serde_json::from_str::<StrippedProgram>(json)?;
+        program.validate()?;
+        Ok(Program::Stripped(program))
+    }
+
     pub fn prime(&self) -> &BigInt {
         match self {
             Self::Stripped(program) => &program.prime,
@@ -101,7 +167,64 @@ impl From<FullProgram> for Program {
     }
 }
 
+impl StrippedProgram {
+    /// Checks that the data words are field elements and that `main` points within the data.
+    pub fn validate(&self) -> Result<(), ProgramLoadError> {
+        validate_data(&self.prime, &self.data)?;
+        if !is_within_data(&self.main, &self.data) {
+            return Err(ProgramLoadError::LabelPcOutOfBounds {
+                name: ScopedName::new(vec![String::from("main")]).unwrap(),
+                pc: self.main.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
 impl FullProgram {
+    /// Checks that the data words are field elements, and that the pcs of the hints, labels and
+    /// functions point within the data. A label other than main may also point right after the last
+    /// word.
+    pub fn validate(&self) -> Result<(), ProgramLoadError> {
+        validate_data(&self.prime, &self.data)?;
+
+        let mut hint_pcs = self.hints.keys().collect::<Vec<_>>();
+        hint_pcs.sort();
+        if let Some(pc) = hint_pcs
+            .into_iter()
+            .find(|pc| !is_within_data(pc, &self.data))
+        {
+            return Err(ProgramLoadError::HintPcOutOfBounds { pc: pc.to_owned() });
+        }
+
+        for (name, definition) in self.identifiers.shared_state.borrow().dict.iter() {
+            match definition {
+                IdentifierDefinition::Label { pc } | IdentifierDefinition::Function { pc, .. }
+                    if !is_within_data(pc, &self.data) && pc != &BigInt::from(self.data.len()) =>
+                {
+                    return Err(ProgramLoadError::LabelPcOutOfBounds {
+                        name: name.to_owned(),
+                        pc: pc.to_owned(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // Unlike other labels, main() must point at an instruction to run.
+        if let Some(main) = self.main() {
+            if !is_within_data(&main, &self.data) {
+                return Err(ProgramLoadError::LabelPcOutOfBounds {
+                    name: &self.main_scope + String::from("main"),
+                    pc: main,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn get_identifier(
         &self,
         name: ScopedName,
@@ -139,6 +262,132 @@ impl FullProgram {
     pub fn main(&self) -> Option<BigInt> {
         self.get_label(ScopedName::new(vec![String::from("main")]).unwrap(), false)
     }
+
+    /// The pc of the `__start__` label, which only exists in programs compiled in proof mode.
+    pub fn start(&self) -> Option<BigInt> {
+        self.get_label(
+            ScopedName::new(vec![String::from("__start__")]).unwrap(),
+            false,
+        )
+    }
+
+    /// The pc of the `__end__` label, where programs compiled in proof mode loop forever once main()
+    /// returns.
+    pub fn end(&self) -> Option<BigInt> {
+        self.get_label(
+            ScopedName::new(vec![String::from("__end__")]).unwrap(),
+            false,
+        )
+    }
+
+    /// Drops the hints, identifiers and debug info of the program, keeping only what is needed to
+    /// run it from main().
+    pub fn to_stripped(&self) -> Result<StrippedProgram, ProgramLoadError> {
+        Ok(StrippedProgram {
+            prime: self.prime.clone(),
+            data: self.data.clone(),
+            builtins: self.builtins.clone(),
+            main: self.main().ok_or(ProgramLoadError::MissingMain)?,
+        })
+    }
+
+    /// Reports what the hints of the program do, without running them. The analysis is
+    /// heuristic, see [HintReport].
+    pub fn analyze_hints(&self) -> HintReport {
+        HintReport::new(&self.hints)
+    }
+
+    /// Parses a program artifact while keeping track of the fields that are not recognized.
+    ///
+    /// With `Strictness::Warn`, the paths of the unknown fields (e.g. `hints.0.0.typo`) are
+    /// returned along with the program. With `Strictness::Deny`, loading fails instead.
+    pub fn from_json_strict(
+        json: &str,
+        strictness: Strictness,
+    ) -> Result<(Self, Vec<String>), ProgramLoadError> {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+
+        if strictness == Strictness::Permissive {
+            let program = Self::deserialize(&mut deserializer)?;
+            deserializer.end()?;
+            program.validate()?;
+            return Ok((program, vec![]));
+        }
+
+        let mut unknown_fields = vec![];
+        let program: Self = serde_ignored::deserialize(&mut deserializer, |path| {
+            let path = path_segments(&path);
+            if !UNMODELED_FIELDS
+                .iter()
+                .any(|pattern| path_matches(&path, pattern))
+            {
+                unknown_fields.push(path.join("."));
+            }
+        })?;
+        deserializer.end()?;
+
+        if strictness == Strictness::Deny && !unknown_fields.is_empty() {
+            return Err(ProgramLoadError::UnknownFields {
+                paths: unknown_fields,
+            });
+        }
+
+        program.validate()?;
+        Ok((program, unknown_fields))
+    }
+}
+
+impl From<serde_json::Error> for ProgramLoadError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+/// Checks that all the words of `data` are in the range [0, prime).
+fn validate_data(prime: &BigInt, data: &[BigInt]) -> Result<(), ProgramLoadError> {
+    match data
+        .iter()
+        .enumerate()
+        .find(|(_, value)| value < &&BigInt::from(0u32) || value >= &prime)
+    {
+        Some((index, value)) => Err(ProgramLoadError::InvalidDataWord {
+            index,
+            value: value.to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
+
+fn is_within_data(pc: &BigInt, data: &[BigInt]) -> bool {
+    pc >= &BigInt::from(0u32) && pc < &BigInt::from(data.len())
+}
+
+/// Flattens a path reported by `serde_ignored` into its map keys and sequence indices.
+fn path_segments(path: &serde_ignored::Path) -> Vec<String> {
+    match path {
+        serde_ignored::Path::Root => vec![],
+        serde_ignored::Path::Seq { parent, index } => {
+            let mut segments = path_segments(parent);
+            segments.push(index.to_string());
+            segments
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            let mut segments = path_segments(parent);
+            segments.push(key.to_owned());
+            segments
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => path_segments(parent),
+    }
+}
+
+fn path_matches(path: &[String], pattern: &[&str]) -> bool {
+    path.len() == pattern.len()
+        && path
+            .iter()
+            .zip(pattern.iter())
+            .all(|(segment, pattern)| *pattern == "*" || segment == pattern)
 }
 
 #[cfg(test)]
@@ -153,6 +402,91 @@ mod tests {
         .unwrap();
     }
 
+    #[test]
+    fn test_program_deser_references() {
+        for json in [
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            include_str!("../../../../test-data/artifacts/ids.json"),
+        ] {
+            let program = serde_json::from_str::<FullProgram>(json).unwrap();
+            let raw = serde_json::from_str::<serde_json::Value>(json).unwrap();
+            assert_eq!(
+                program.reference_manager.references.len(),
+                raw["reference_manager"]["references"]
+                    .as_array()
+                    .unwrap()
+                    .len()
+            );
+        }
+
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/ids.json"
+        ))
+        .unwrap();
+        let reference = &program.reference_manager.references[0];
+        assert_eq!(reference.pc, Some(BigInt::from(2u32)));
+        assert_eq!(reference.value, "[cast(ap + (-1), felt*)]");
+        assert_eq!(reference.ap_tracking_data.group, 0);
+        assert_eq!(reference.ap_tracking_data.offset, BigInt::from(1u32));
+    }
+
+    #[test]
+    fn test_program_deser_decimal() {
+        let hex = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/deduce_op0_mul.json"
+        ))
+        .unwrap();
+        let decimal = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/deduce_op0_mul.decimal.json"
+        ))
+        .unwrap();
+
+        assert_eq!(decimal.prime, hex.prime);
+        assert_eq!(decimal.data, hex.data);
+    }
+
+    #[test]
+    fn test_load_stripped() {
+        let program = Program::load_stripped(include_str!(
+            "../../../../test-data/artifacts/output.stripped.json"
+        ))
+        .unwrap();
+
+        assert!(matches!(program, Program::Stripped(_)));
+        assert_eq!(program.main(), Some(BigInt::from(0)));
+        assert_eq!(program.builtins(), &[String::from("output")]);
+        assert_eq!(program.data().len(), 6);
+    }
+
+    #[test]
+    fn test_to_stripped() {
+        let mut json = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let program = serde_json::from_value::<FullProgram>(json.clone()).unwrap();
+        let stripped = program.to_stripped().unwrap();
+        assert_eq!(stripped.main, BigInt::from(0));
+        assert_eq!(stripped.data, program.data);
+
+        // The stripped program survives a round trip through its json format.
+        let program = Program::load_stripped(&serde_json::to_string(&stripped).unwrap()).unwrap();
+        assert_eq!(program.main(), Some(BigInt::from(0)));
+        assert_eq!(program.data(), &stripped.data[..]);
+        assert_eq!(program.prime(), &stripped.prime);
+
+        json["identifiers"]
+            .as_object_mut()
+            .unwrap()
+            .remove("__main__.main");
+        let program = serde_json::from_value::<FullProgram>(json).unwrap();
+        assert!(matches!(
+            program.to_stripped(),
+            Err(ProgramLoadError::MissingMain)
+        ));
+    }
+
     #[test]
     fn test_program_main() {
         let program = serde_json::from_str::<FullProgram>(include_str!(
@@ -162,4 +496,265 @@ mod tests {
 
         assert_eq!(program.main(), Some(BigInt::from(0)));
     }
+
+    #[test]
+    fn test_program_start_end() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+        assert_eq!(program.start(), None);
+        assert_eq!(program.end(), None);
+
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/proof_mode.json"
+        ))
+        .unwrap();
+        assert_eq!(program.start(), Some(BigInt::from(0)));
+        assert_eq!(program.end(), Some(BigInt::from(4)));
+        assert_eq!(program.main(), Some(BigInt::from(6)));
+    }
+
+    #[test]
+    fn test_analyze_hints() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/scopes.json"
+        ))
+        .unwrap();
+        let report = program.analyze_hints();
+
+        let pcs = report
+            .hints
+            .iter()
+            .map(|hint| (hint.pc.clone(), hint.index))
+            .collect::<Vec<_>>();
+        let mut sorted_pcs = pcs.clone();
+        sorted_pcs.sort();
+        assert_eq!(pcs, sorted_pcs);
+        assert_eq!(
+            report.hints.len(),
+            program.hints.values().map(Vec::len).sum::<usize>()
+        );
+        assert!(report.hints.iter().all(|hint| hint.suspicious.is_empty()));
+
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/ec_recover.json"
+        ))
+        .unwrap();
+        let report = program.analyze_hints();
+        assert_eq!(report.hints.len(), 1);
+        assert!(report.hints[0].native);
+        assert!(!report.has_unknown_hints());
+    }
+
+    #[test]
+    fn test_from_json_strict() {
+        for json in [
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            include_str!("../../../../test-data/artifacts/scopes.json"),
+            include_str!("../../../../test-data/artifacts/ids.json"),
+            include_str!("../../../../test-data/artifacts/program_input.json"),
+            include_str!("../../../../test-data/artifacts/assert_fail.json"),
+            include_str!("../../../../test-data/artifacts/error_message.json"),
+            include_str!("../../../../test-data/artifacts/print_output.json"),
+        ] {
+            let (_, unknown_fields) =
+                FullProgram::from_json_strict(json, Strictness::Deny).unwrap();
+            assert!(unknown_fields.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_from_json_strict_unknown_fields() {
+        let mut json = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../../../../test-data/artifacts/scopes.json"
+        ))
+        .unwrap();
+        json["compiler_version"] = "0.10.0".into();
+        json["hints"]["2"][0]["cdoe"] = "memory[ap] = n".into();
+        let json = json.to_string();
+
+        let (_, unknown_fields) =
+            FullProgram::from_json_strict(&json, Strictness::Permissive).unwrap();
+        assert!(unknown_fields.is_empty());
+
+        let (_, mut unknown_fields) =
+            FullProgram::from_json_strict(&json, Strictness::Warn).unwrap();
+        unknown_fields.sort();
+        assert_eq!(unknown_fields, vec!["compiler_version", "hints.2.0.cdoe"]);
+
+        match FullProgram::from_json_strict(&json, Strictness::Deny) {
+            Err(ProgramLoadError::UnknownFields { mut paths }) => {
+                paths.sort();
+                assert_eq!(paths, vec!["compiler_version", "hints.2.0.cdoe"]);
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_from_json_strict_trailing_characters() {
+        // Same as `serde_json::from_str()`, only whitespace may follow the program.
+        let json = include_str!("../../../../test-data/artifacts/run_past_end.json");
+        for strictness in [Strictness::Permissive, Strictness::Warn, Strictness::Deny] {
+            assert!(FullProgram::from_json_strict(&format!("{}\n", json), strictness).is_ok());
+            assert!(matches!(
+                FullProgram::from_json_strict(&format!("{} {{}}", json), strictness),
+                Err(ProgramLoadError::Json(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_program_serde_round_trip() {
+        /// Removes the values at the paths matching `pattern`.
+        fn remove_matching(value: &mut serde_json::Value, pattern: &[&str]) {
+            match pattern {
+                [] => {}
+                [key] => {
+                    if let Some(object) = value.as_object_mut() {
+                        object.remove(*key);
+                    }
+                }
+                [key, rest @ ..] => {
+                    if let Some(object) = value.as_object_mut() {
+                        for (name, child) in object.iter_mut() {
+                            if *key == "*" || name.as_str() == *key {
+                                remove_matching(child, rest);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for json in [
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            include_str!("../../../../test-data/artifacts/ids.json"),
+            include_str!("../../../../test-data/artifacts/scopes.json"),
+            include_str!("../../../../test-data/artifacts/error_message.json"),
+        ] {
+            let program = serde_json::from_str::<FullProgram>(json).unwrap();
+            let serialized = serde_json::to_value(&program).unwrap();
+
+            // Only the fields that are not modeled are lost.
+            let mut expected = serde_json::from_str::<serde_json::Value>(json).unwrap();
+            for pattern in UNMODELED_FIELDS.iter() {
+                remove_matching(&mut expected, pattern);
+            }
+            assert_eq!(serialized, expected);
+
+            let program = serde_json::from_value::<FullProgram>(serialized.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&program).unwrap(), serialized);
+        }
+    }
+
+    #[test]
+    fn test_hint_pcs_round_trip() {
+        // The pc of the hint is 12, which would be 0x12 if read as hex.
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/ec_recover.json"
+        ))
+        .unwrap();
+        assert_eq!(
+            program.hints.keys().collect::<Vec<_>>(),
+            vec![&BigInt::from(12u32)]
+        );
+
+        let serialized = serde_json::to_value(&program).unwrap();
+        assert_eq!(
+            serialized["hints"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["12"]
+        );
+
+        let program = serde_json::from_value::<FullProgram>(serialized).unwrap();
+        assert_eq!(
+            program.hints.keys().collect::<Vec<_>>(),
+            vec![&BigInt::from(12u32)]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let corrupt = |f: &dyn Fn(&mut serde_json::Value)| {
+            let mut json = serde_json::from_str::<serde_json::Value>(include_str!(
+                "../../../../test-data/artifacts/run_past_end.json"
+            ))
+            .unwrap();
+            f(&mut json);
+            FullProgram::from_json_strict(&json.to_string(), Strictness::Permissive)
+        };
+
+        corrupt(&|_| {}).unwrap();
+
+        match corrupt(&|json| {
+            json["data"][0] = json["prime"].clone();
+        }) {
+            Err(ProgramLoadError::InvalidDataWord { index, value }) => {
+                assert_eq!(index, 0);
+                assert_eq!(
+                    value,
+                    (BigInt::from(1u32) << 251) + (BigInt::from(17u32) << 192) + 1u32
+                );
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        match corrupt(&|json| {
+            json["hints"]["1"] = serde_json::json!([{
+                "accessible_scopes": ["__main__", "__main__.main"],
+                "code": "memory[ap] = 1",
+                "flow_tracking_data": {
+                    "ap_tracking": {"group": 0, "offset": 0},
+                    "reference_ids": {},
+                },
+            }]);
+        }) {
+            Err(ProgramLoadError::HintPcOutOfBounds { pc }) => {
+                assert_eq!(pc, BigInt::from(1u32));
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        match corrupt(&|json| {
+            json["identifiers"]["__main__.main"]["pc"] = 2.into();
+        }) {
+            Err(ProgramLoadError::LabelPcOutOfBounds { name, pc }) => {
+                assert_eq!(name.to_string(), "__main__.main");
+                assert_eq!(pc, BigInt::from(2u32));
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        // A label may point right after the last instruction, but main() may not.
+        corrupt(&|json| {
+            json["identifiers"]["__main__.end"] = serde_json::json!({"pc": 1, "type": "label"});
+        })
+        .unwrap();
+        match corrupt(&|json| {
+            json["identifiers"]["__main__.main"]["pc"] = 1.into();
+        }) {
+            Err(ProgramLoadError::LabelPcOutOfBounds { name, pc }) => {
+                assert_eq!(name.to_string(), "__main__.main");
+                assert_eq!(pc, BigInt::from(1u32));
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        // No instructions at all.
+        match corrupt(&|json| {
+            json["data"] = serde_json::json!([]);
+        }) {
+            Err(ProgramLoadError::LabelPcOutOfBounds { name, pc }) => {
+                assert_eq!(name.to_string(), "__main__.main");
+                assert_eq!(pc, BigInt::from(0u32));
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
 }
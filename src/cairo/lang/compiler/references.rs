@@ -1,4 +1,11 @@
-use serde::Deserialize;
+use crate::{
+    cairo::lang::compiler::{instruction::Register, preprocessor::flow::RegTrackingData},
+    serde::big_int::BigIntNumber,
+};
+
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
 
 /// A reference to a memory address that is defined for a specific location in the program (pc).
 /// The reference may be evaluated for other locations in the program, as long as its value is well
@@ -13,5 +20,92 @@ use serde::Deserialize;
 ///   [ap] = [x] * 2; ap++ # Thus, this instruction will translate to '[ap] = [ap - 1] * 2; ap++'
 ///                        # and will set [ap] to 10.
 /// ```
-#[derive(Debug, Deserialize)]
-pub struct Reference {}
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Reference {
+    #[serde_as(as = "Option<BigIntNumber>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pc: Option<BigInt>,
+    /// The expression of the reference, e.g. `[cast(fp + (-3), felt*)]`.
+    pub value: String,
+    pub ap_tracking_data: RegTrackingData,
+}
+
+/// A reference to the felt at a fixed offset from ap or fp, i.e. `[cast(reg + offset, felt*)]`.
+/// This is what most `local`, `tempvar` and argument references compile to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleReference {
+    pub register: Register,
+    pub offset: BigInt,
+}
+
+impl Reference {
+    /// Parses the reference as a `SimpleReference`. Returns `None` for any other expression.
+    ///
+    /// Only the expressions generated by the compiler are accepted: `[cast(ap, felt*)]`,
+    /// `[cast(fp + 2, felt*)]`, `[cast(fp + (-3), felt*)]` and so on.
+    pub fn parse_simple(&self) -> Option<SimpleReference> {
+        let inner = self
+            .value
+            .strip_prefix("[cast(")?
+            .strip_suffix(", felt*)]")?
+            .trim();
+
+        let (register, offset) = match inner.split_once('+') {
+            Some((register, offset)) => {
+                let offset = offset.trim();
+                let offset = offset
+                    .strip_prefix('(')
+                    .and_then(|offset| offset.strip_suffix(')'))
+                    .unwrap_or(offset);
+                (register.trim(), offset.trim().parse::<BigInt>().ok()?)
+            }
+            None => (inner, BigInt::from(0u32)),
+        };
+
+        let register = match register {
+            "ap" => Register::AP,
+            "fp" => Register::FP,
+            _ => return None,
+        };
+
+        Some(SimpleReference { register, offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(value: &str) -> Reference {
+        Reference {
+            pc: None,
+            value: value.to_owned(),
+            ap_tracking_data: RegTrackingData {
+                group: 0,
+                offset: BigInt::from(0u32),
+            },
+        }
+    }
+
+    #[test]
+    fn test_parse_simple() {
+        for (value, expected_register, expected_offset) in [
+            ("[cast(fp + (-3), felt*)]", Register::FP, -3),
+            ("[cast(ap + 2, felt*)]", Register::AP, 2),
+            ("[cast(ap, felt*)]", Register::AP, 0),
+        ] {
+            let SimpleReference { register, offset } = reference(value).parse_simple().unwrap();
+            assert_eq!(register, expected_register);
+            assert_eq!(offset, BigInt::from(expected_offset));
+        }
+
+        for value in [
+            "[cast(fp + (-3), __main__.MyStruct*)]",
+            "cast(fp + (-3), felt)",
+            "[cast([fp + (-3)] + 1, felt*)]",
+        ] {
+            assert!(reference(value).parse_simple().is_none());
+        }
+    }
+}
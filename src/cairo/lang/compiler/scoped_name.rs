@@ -1,7 +1,8 @@
 use serde::{de::Error as DeError, Deserialize, Serialize, Serializer};
 use std::{fmt::Display, ops::Range, str::FromStr};
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+/// Scoped names are ordered by comparing their paths segment by segment.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ScopedName {
     pub path: Vec<String>,
 }
@@ -10,6 +11,8 @@ pub struct ScopedName {
 pub enum Error {
     #[error("empty namespace is not supported")]
     EmptyNamespace,
+    #[error("\".\" may not appear in the namespace \"{segment}\"")]
+    SeparatorInNamespace { segment: String },
 }
 
 impl ScopedName {
@@ -20,10 +23,24 @@ impl ScopedName {
             if segment.is_empty() {
                 return Err(Error::EmptyNamespace);
             }
+            if segment.contains(Self::SEPARATOR) {
+                return Err(Error::SeparatorInNamespace {
+                    segment: segment.to_owned(),
+                });
+            }
         }
         Ok(Self { path })
     }
 
+    /// Parses a dot-separated name, e.g. `__main__.main`.
+    pub fn from_string(s: &str) -> Result<Self, Error> {
+        Self::new(
+            s.split(Self::SEPARATOR)
+                .map(|item| item.to_owned())
+                .collect::<Vec<_>>(),
+        )
+    }
+
     pub fn len(&self) -> usize {
         self.path.len()
     }
@@ -48,6 +65,23 @@ impl ScopedName {
             self.path[0..other.len()] == other.path
         }
     }
+
+    pub fn endswith(&self, other: &ScopedName) -> bool {
+        if self.len() < other.len() {
+            false
+        } else {
+            self.path[self.len() - other.len()..] == other.path
+        }
+    }
+
+    /// Returns the name without its last segment. The parent of an empty name is empty too.
+    pub fn parent(&self) -> Self {
+        self.slice(0..self.len().saturating_sub(1))
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.path.last().map(String::as_str)
+    }
 }
 
 impl std::ops::Add<String> for &ScopedName {
@@ -84,11 +118,7 @@ impl FromStr for ScopedName {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Self::new(
-            s.split(Self::SEPARATOR)
-                .map(|item| item.to_owned())
-                .collect::<Vec<_>>(),
-        )
+        Self::from_string(s)
     }
 }
 
@@ -112,3 +142,58 @@ impl<'de> Deserialize<'de> for ScopedName {
             .map_err(|err| DeError::custom(format!("invalid scoped name string: {}", err)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> ScopedName {
+        ScopedName::from_string(s).unwrap()
+    }
+
+    #[test]
+    fn test_from_string() {
+        assert_eq!(
+            name("__main__.main").path,
+            vec![String::from("__main__"), String::from("main")]
+        );
+        assert!(matches!(
+            ScopedName::from_string("__main__..main"),
+            Err(Error::EmptyNamespace)
+        ));
+        assert!(matches!(
+            ScopedName::new(vec![String::from("__main__.main")]),
+            Err(Error::SeparatorInNamespace { segment }) if segment == "__main__.main"
+        ));
+    }
+
+    #[test]
+    fn test_parent_and_last() {
+        assert_eq!(name("a.b.c").parent(), name("a.b"));
+        assert_eq!(name("a").parent(), ScopedName::default());
+        assert_eq!(ScopedName::default().parent(), ScopedName::default());
+
+        assert_eq!(name("a.b.c").last(), Some("c"));
+        assert_eq!(ScopedName::default().last(), None);
+    }
+
+    #[test]
+    fn test_startswith_and_endswith() {
+        let full_name = name("a.b.c");
+        assert!(full_name.startswith(&name("a.b")));
+        assert!(!full_name.startswith(&name("b.c")));
+        assert!(full_name.endswith(&name("b.c")));
+        assert!(full_name.endswith(&ScopedName::default()));
+        assert!(!full_name.endswith(&name("a.b")));
+        assert!(!name("c").endswith(&full_name));
+    }
+
+    #[test]
+    fn test_ord() {
+        // Paths are compared segment by segment, unlike their string forms: "." sorts after "-".
+        assert!(name("a.b") < name("a-c"));
+        assert!(name("a") < name("a.b"));
+        assert!(name("a.b") < name("a.c"));
+        assert!(name("a.z") < name("b"));
+    }
+}
@@ -1,18 +1,21 @@
+use indexmap::IndexMap;
 use num_bigint::BigInt;
-use std::{collections::HashMap, str::FromStr};
+use std::str::FromStr;
 
 use crate::cairo::lang::builtins::{
-    hash::instance_def::PedersenInstanceDef, range_check::instance_def::RangeCheckInstanceDef,
-    signature::instance_def::EcdsaInstanceDef, BuiltinDefinition,
+    bitwise::instance_def::BitwiseInstanceDef, ec::instance_def::EcOpInstanceDef,
+    hash::instance_def::PedersenInstanceDef, keccak::instance_def::KeccakInstanceDef,
+    range_check::instance_def::RangeCheckInstanceDef, signature::instance_def::EcdsaInstanceDef,
+    BuiltinDefinition,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CpuInstanceDef {
     /// Verifies that each 'call' instruction returns, even if the called function is malicious.
     pub safe_call: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DilutedPoolInstanceDef {
     /// The ratio between the number of diluted cells in the pool and the number of cpu steps.
     pub units_per_step: BigInt,
@@ -23,13 +26,29 @@ pub struct DilutedPoolInstanceDef {
     pub n_bits: BigInt,
 }
 
-#[derive(Debug)]
+/// All supported layouts, ordered from the smallest to the largest.
+const LAYOUTS: [fn() -> CairoLayout; 5] = [
+    CairoLayout::plain_instance,
+    CairoLayout::small_instance,
+    CairoLayout::dex_instance,
+    CairoLayout::recursive_instance,
+    CairoLayout::all_cairo_instance,
+];
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unknown layout: {name}")]
+pub struct UnknownLayoutError {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CairoLayout {
     pub layout_name: &'static str,
     pub cpu_component_step: BigInt,
     /// Range check units.
     pub rc_units: BigInt,
-    pub builtins: HashMap<String, BuiltinDefinition>,
+    /// The builtins of the layout, in the order their segments are allocated in.
+    pub builtins: IndexMap<String, BuiltinDefinition>,
     /// The ratio between the number of public memory cells and the total number of memory cells.
     pub public_memory_fraction: BigInt,
     pub memory_units_per_step: BigInt,
@@ -39,12 +58,23 @@ pub struct CairoLayout {
 }
 
 impl CairoLayout {
+    /// Returns the layout with the given name (e.g. `small`).
+    pub fn from_name(name: &str) -> Result<Self, UnknownLayoutError> {
+        LAYOUTS
+            .iter()
+            .map(|instance| instance())
+            .find(|layout| layout.layout_name == name)
+            .ok_or_else(|| UnknownLayoutError {
+                name: name.to_owned(),
+            })
+    }
+
     pub fn plain_instance() -> Self {
         Self {
             layout_name: "plain",
             cpu_component_step: 1u32.into(),
             rc_units: 16u32.into(),
-            builtins: HashMap::new(),
+            builtins: IndexMap::new(),
             public_memory_fraction: 4u32.into(),
             memory_units_per_step: 8u32.into(),
             diluted_pool_instance_def: None,
@@ -97,9 +127,266 @@ impl CairoLayout {
             cpu_instance_def: CpuInstanceDef { safe_call: true },
         }
     }
+
+    pub fn dex_instance() -> Self {
+        Self {
+            layout_name: "dex",
+            cpu_component_step: 1u32.into(),
+            rc_units: 4u32.into(),
+            builtins: vec![
+                (String::from("output"), BuiltinDefinition::Bool(true)),
+                (
+                    String::from("pedersen"),
+                    BuiltinDefinition::PedersenInstanceDef(PedersenInstanceDef {
+                        ratio: 8,
+                        repetitions: 4,
+                        element_height: 256,
+                        element_bits: 252,
+                        n_inputs: 2,
+                        hash_limit: Some(prime()),
+                    }),
+                ),
+                (
+                    String::from("range_check"),
+                    BuiltinDefinition::RangeCheckInstanceDef(RangeCheckInstanceDef {
+                        ratio: 8,
+                        n_parts: 8,
+                    }),
+                ),
+                (
+                    String::from("ecdsa"),
+                    BuiltinDefinition::EcdsaInstanceDef(EcdsaInstanceDef {
+                        ratio: 512,
+                        repetitions: 1,
+                        height: 256,
+                        n_hash_bits: 251,
+                    }),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            public_memory_fraction: 4u32.into(),
+            memory_units_per_step: 8u32.into(),
+            diluted_pool_instance_def: None,
+            n_trace_columns: Some(22u32.into()),
+            cpu_instance_def: CpuInstanceDef { safe_call: true },
+        }
+    }
+
+    pub fn recursive_instance() -> Self {
+        Self {
+            layout_name: "recursive",
+            cpu_component_step: 1u32.into(),
+            rc_units: 4u32.into(),
+            builtins: vec![
+                (String::from("output"), BuiltinDefinition::Bool(true)),
+                (
+                    String::from("pedersen"),
+                    BuiltinDefinition::PedersenInstanceDef(PedersenInstanceDef {
+                        ratio: 128,
+                        repetitions: 1,
+                        element_height: 256,
+                        element_bits: 252,
+                        n_inputs: 2,
+                        hash_limit: Some(prime()),
+                    }),
+                ),
+                (
+                    String::from("range_check"),
+                    BuiltinDefinition::RangeCheckInstanceDef(RangeCheckInstanceDef {
+                        ratio: 8,
+                        n_parts: 8,
+                    }),
+                ),
+                (
+                    String::from("bitwise"),
+                    BuiltinDefinition::BitwiseInstanceDef(BitwiseInstanceDef {
+                        ratio: 8,
+                        total_n_bits: 251,
+                    }),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            public_memory_fraction: 8u32.into(),
+            memory_units_per_step: 8u32.into(),
+            diluted_pool_instance_def: Some(DilutedPoolInstanceDef {
+                units_per_step: 16u32.into(),
+                spacing: 4u32.into(),
+                n_bits: 16u32.into(),
+            }),
+            n_trace_columns: Some(11u32.into()),
+            cpu_instance_def: CpuInstanceDef { safe_call: true },
+        }
+    }
+
+    pub fn all_cairo_instance() -> Self {
+        Self {
+            layout_name: "all_cairo",
+            cpu_component_step: 1u32.into(),
+            rc_units: 4u32.into(),
+            builtins: vec![
+                (String::from("output"), BuiltinDefinition::Bool(true)),
+                (
+                    String::from("pedersen"),
+                    BuiltinDefinition::PedersenInstanceDef(PedersenInstanceDef {
+                        ratio: 256,
+                        repetitions: 1,
+                        element_height: 256,
+                        element_bits: 252,
+                        n_inputs: 2,
+                        hash_limit: Some(prime()),
+                    }),
+                ),
+                (
+                    String::from("range_check"),
+                    BuiltinDefinition::RangeCheckInstanceDef(RangeCheckInstanceDef {
+                        ratio: 8,
+                        n_parts: 8,
+                    }),
+                ),
+                (
+                    String::from("ecdsa"),
+                    BuiltinDefinition::EcdsaInstanceDef(EcdsaInstanceDef {
+                        ratio: 2048,
+                        repetitions: 1,
+                        height: 256,
+                        n_hash_bits: 251,
+                    }),
+                ),
+                (
+                    String::from("bitwise"),
+                    BuiltinDefinition::BitwiseInstanceDef(BitwiseInstanceDef {
+                        ratio: 16,
+                        total_n_bits: 251,
+                    }),
+                ),
+                (
+                    String::from("ec_op"),
+                    BuiltinDefinition::EcOpInstanceDef(EcOpInstanceDef {
+                        ratio: 1024,
+                        scalar_height: 256,
+                        scalar_bits: 252,
+                        scalar_limit: prime(),
+                    }),
+                ),
+                (
+                    String::from("keccak"),
+                    BuiltinDefinition::KeccakInstanceDef(KeccakInstanceDef {
+                        ratio: 2048,
+                        state_rep: vec![200; 8],
+                        instances_per_component: 16,
+                    }),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            public_memory_fraction: 8u32.into(),
+            memory_units_per_step: 8u32.into(),
+            diluted_pool_instance_def: Some(DilutedPoolInstanceDef {
+                units_per_step: 16u32.into(),
+                spacing: 4u32.into(),
+                n_bits: 16u32.into(),
+            }),
+            n_trace_columns: Some(11u32.into()),
+            cpu_instance_def: CpuInstanceDef { safe_call: true },
+        }
+    }
+
+    /// Returns a layout with the given builtins, in the order their segments are allocated in, for
+    /// experimenting with builtins outside of the predefined layouts.
+    ///
+    /// The rest of the layout is taken from the plain layout, with the diluted pool of the
+    /// recursive layout if a builtin needs one (`bitwise` or `keccak`). The number of trace
+    /// columns is left unknown.
+    pub fn custom(
+        layout_name: &'static str,
+        builtins: Vec<(String, BuiltinDefinition)>,
+        rc_units: BigInt,
+    ) -> Self {
+        let needs_diluted_pool = builtins
+            .iter()
+            .any(|(name, _)| name == "bitwise" || name == "keccak");
+
+        Self {
+            layout_name,
+            rc_units,
+            builtins: builtins.into_iter().collect(),
+            diluted_pool_instance_def: if needs_diluted_pool {
+                Self::recursive_instance().diluted_pool_instance_def
+            } else {
+                None
+            },
+            n_trace_columns: None,
+            ..Self::plain_instance()
+        }
+    }
+
+    /// Returns whether the layout includes the builtin with the given name (e.g. `pedersen`).
+    pub fn has_builtin(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+}
+
+/// Returns the smallest layout that includes all of the given builtins, if any.
+pub fn suggest_layout(builtins: &[String]) -> Option<CairoLayout> {
+    LAYOUTS
+        .iter()
+        .map(|instance| instance())
+        .find(|layout| builtins.iter().all(|builtin| layout.has_builtin(builtin)))
 }
 
 fn prime() -> BigInt {
     BigInt::from_str("3618502788666131213697322783095070105623107215331596699973092056135872020481")
         .unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_builtin() {
+        assert!(CairoLayout::small_instance().has_builtin("pedersen"));
+        assert!(!CairoLayout::small_instance().has_builtin("bitwise"));
+        assert!(!CairoLayout::plain_instance().has_builtin("pedersen"));
+        assert!(CairoLayout::recursive_instance().has_builtin("bitwise"));
+        assert!(!CairoLayout::recursive_instance().has_builtin("ecdsa"));
+        assert!(CairoLayout::all_cairo_instance().has_builtin("keccak"));
+    }
+
+    #[test]
+    fn test_from_name() {
+        for name in ["plain", "small", "dex", "recursive", "all_cairo"] {
+            assert_eq!(CairoLayout::from_name(name).unwrap().layout_name, name);
+        }
+        assert_eq!(
+            CairoLayout::from_name("starknet").unwrap_err().name,
+            "starknet"
+        );
+    }
+
+    #[test]
+    fn test_suggest_layout() {
+        assert_eq!(suggest_layout(&[]).unwrap().layout_name, "plain");
+        assert_eq!(
+            suggest_layout(&[String::from("output"), String::from("pedersen")])
+                .unwrap()
+                .layout_name,
+            "small"
+        );
+        assert_eq!(
+            suggest_layout(&[String::from("bitwise")])
+                .unwrap()
+                .layout_name,
+            "recursive"
+        );
+        assert_eq!(
+            suggest_layout(&[String::from("ecdsa"), String::from("bitwise")])
+                .unwrap()
+                .layout_name,
+            "all_cairo"
+        );
+        assert!(suggest_layout(&[String::from("poseidon")]).is_none());
+    }
+}
@@ -0,0 +1,37 @@
+use crate::serde::big_int::BigIntHex;
+
+use num_bigint::BigInt;
+use serde::Serialize;
+use serde_with::serde_as;
+use std::collections::BTreeMap;
+
+/// The public input of the Cairo AIR, in the format of the `air_public_input.json` file written by
+/// cairo-run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicInput {
+    pub layout: String,
+    pub rc_min: u16,
+    pub rc_max: u16,
+    pub n_steps: u64,
+    /// The relocated addresses of the program, execution and builtin segments, keyed by the
+    /// segment name (e.g. `pedersen`).
+    pub memory_segments: BTreeMap<String, MemorySegmentAddresses<u64>>,
+    pub public_memory: Vec<PublicMemoryEntry>,
+}
+
+/// The first address of a memory segment and the address right after its last used cell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemorySegmentAddresses<T> {
+    pub begin_addr: T,
+    pub stop_ptr: T,
+}
+
+/// A relocated public memory cell.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicMemoryEntry {
+    pub address: u64,
+    #[serde_as(as = "BigIntHex")]
+    pub value: BigInt,
+    pub page: u64,
+}
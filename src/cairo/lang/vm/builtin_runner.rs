@@ -1,12 +1,15 @@
 use crate::cairo::lang::vm::{
+    air_public_input::MemorySegmentAddresses,
     cairo_runner::CairoRunner,
     memory_dict::Error as MemoryError,
     memory_segments::{Error as MemorySegmentError, MemorySegmentManager},
     relocatable::{MaybeRelocatable, RelocatableValue},
+    validated_memory_dict::ValidatedMemoryDict,
+    vm_core::VirtualMachine,
 };
 
 use num_bigint::BigInt;
-use std::any::Any;
+use std::{any::Any, collections::HashMap};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -16,6 +19,82 @@ pub enum Error {
     MemorySegmentError(MemorySegmentError),
     #[error("unexpected None value")]
     UnexpectedNoneValue,
+    #[error("VM not initialized.")]
+    VmNotInitialized,
+    #[error(
+        "Expected the stop pointer of {builtin_name} to be a relocatable value, found: {found}"
+    )]
+    StopPointerNotRelocatable { builtin_name: String, found: BigInt },
+    #[error("Expected an integer value at {addr} for the {builtin_name} builtin.")]
+    NonIntegerBuiltinValue {
+        builtin_name: String,
+        addr: RelocatableValue,
+    },
+    #[error("Offset of {addr} is too large for the {builtin_name} builtin.")]
+    OffsetTooLarge {
+        builtin_name: String,
+        addr: RelocatableValue,
+    },
+    #[error("Missing {builtin_name} input for index {index}.")]
+    MissingBuiltinInput { builtin_name: String, index: u64 },
+    #[error("The {builtin_name} builtin used {used} cells but the capacity is {size}.")]
+    InsufficientAllocatedCells {
+        builtin_name: String,
+        used: BigInt,
+        size: BigInt,
+    },
+    #[error("The output builtin used {used} cells, exceeding the limit of {max}.")]
+    OutputLimitExceeded { used: BigInt, max: BigInt },
+    #[error(
+        "Page must be in the output segment (segment_index={segment_index}). Found: {page_start}."
+    )]
+    PageOutsideOutputSegment {
+        segment_index: isize,
+        page_start: MaybeRelocatable,
+    },
+    #[error("Page {page_id} was already used.")]
+    PageAlreadyUsed { page_id: BigInt },
+    #[error("Page {page_id} overlaps with page {other_page_id}.")]
+    OverlappingPages {
+        page_id: BigInt,
+        other_page_id: BigInt,
+    },
+    #[error(
+        "Range-check builtin: Expected value at address {addr} to be an integer. Got: {value}."
+    )]
+    RangeCheckNonIntegerValue {
+        addr: RelocatableValue,
+        value: MaybeRelocatable,
+    },
+    #[error("Value {value}, in range check builtin {index}, is out of range [0, {bound}).")]
+    RangeCheckOutOfRange {
+        value: BigInt,
+        index: BigInt,
+        bound: BigInt,
+    },
+    #[error(
+        "Expected integer at address {addr} to be smaller than 2^{total_n_bits}. Got: {value}."
+    )]
+    BitwiseInputOutOfRange {
+        addr: RelocatableValue,
+        value: BigInt,
+        total_n_bits: u32,
+    },
+    #[error(
+        "Signature hint is missing for ECDSA builtin at address {addr}. Add it using \
+        `SignatureBuiltinRunner::add_signature`."
+    )]
+    SignatureHintMissing { addr: RelocatableValue },
+    #[error(
+        "Signature ({r}, {s}), is invalid, with respect to the public key {pubkey}, and the \
+        message hash {msg}."
+    )]
+    InvalidSignature {
+        r: BigInt,
+        s: BigInt,
+        pubkey: BigInt,
+        msg: BigInt,
+    },
     #[error("Invalid stop pointer for {builtin_name}. Expected: {expected}, found: {found}")]
     InvalidStopPointer {
         builtin_name: String,
@@ -40,17 +119,293 @@ pub trait BuiltinRunner: std::fmt::Debug {
         pointer: MaybeRelocatable,
     ) -> Result<MaybeRelocatable, Error>;
 
+    /// Adds the rules validating the values written to the builtin segment.
+    fn add_validation_rules(&self, _validated_memory: &mut ValidatedMemoryDict) {}
+
+    /// Adds the rules deducing the values of the builtin segment from other memory cells.
+    fn add_auto_deduction_rules(&self, _vm: &mut VirtualMachine) {}
+
+    /// Returns the start of the builtin segment, if the segments are initialized.
+    fn segment_base(&self) -> Option<RelocatableValue>;
+
     /// Returns the number of used cells.
     fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, Error>;
 
+    /// Returns the number of used instances.
+    ///
+    /// By default, every cell is an instance of its own.
+    fn get_used_instances(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        self.get_used_cells(runner)
+    }
+
     /// Returns the number of used cells and the allocated size, and raises
     /// InsufficientAllocatedCells if there are more used cells than allocated cells.
+    ///
+    /// By default, the allocated size is exactly the number of used cells.
     fn get_used_cells_and_allocated_size(
         &self,
         runner: &CairoRunner,
-    ) -> Result<(BigInt, BigInt), Error>;
+    ) -> Result<(BigInt, BigInt), Error> {
+        let used = self.get_used_cells(runner)?;
+        Ok((used.clone(), used))
+    }
+
+    /// Returns the smallest and largest 16-bit parts of the values checked by the builtin, or None
+    /// if it doesn't use the range-check units of the AIR.
+    fn get_range_check_usage(&self, _runner: &CairoRunner) -> Result<Option<(u16, u16)>, Error> {
+        Ok(None)
+    }
+
+    /// Calls `runner.segments.finalize()` for the memory segments added in
+    /// `initialize_segments()`.
+    fn finalize_segments(&self, _runner: &CairoRunner) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Returns the addresses of the builtin segment, keyed by the builtin name. These are part of
+    /// the AIR public input.
+    ///
+    /// Note: read_return_values() must precede a call to this method.
+    fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error>;
+
+    /// Returns the builtin-specific part of the AIR private input, keyed by the builtin name.
+    fn air_private_input(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, Error>;
 
     fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// A builtin whose memory segment is made of fixed-size instances, `ratio` steps apart. Each
+/// instance starts with its input cells, followed by the cells deduced from them.
+#[derive(Debug)]
+pub struct SimpleBuiltinRunner {
+    pub name: String,
+    pub included: bool,
+    pub ratio: u32,
+    pub cells_per_instance: u32,
+    /// The names of the input cells of an instance, as they appear in the AIR private input.
+    pub input_cell_names: &'static [&'static str],
+    pub base: Option<RelocatableValue>,
+    pub stop_ptr: Option<RelocatableValue>,
+}
+
+impl SimpleBuiltinRunner {
+    pub fn new(
+        name: &str,
+        included: bool,
+        ratio: u32,
+        cells_per_instance: u32,
+        input_cell_names: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            included,
+            ratio,
+            cells_per_instance,
+            input_cell_names,
+            base: None,
+            stop_ptr: None,
+        }
+    }
+
+    pub fn n_input_cells(&self) -> u32 {
+        self.input_cell_names.len() as u32
+    }
+
+    fn base(&self) -> Result<&RelocatableValue, Error> {
+        self.base.as_ref().ok_or(Error::UnexpectedNoneValue)
+    }
+}
+
+impl BuiltinRunner for SimpleBuiltinRunner {
+    fn initialize_segments(&mut self, segments: &mut MemorySegmentManager) {
+        self.base = Some(segments.add(None));
+        self.stop_ptr = None;
+    }
+
+    fn initial_stack(&self) -> Vec<MaybeRelocatable> {
+        match (self.included, &self.base) {
+            (true, Some(base)) => vec![base.to_owned().into()],
+            _ => vec![],
+        }
+    }
+
+    fn final_stack(
+        &mut self,
+        runner: &CairoRunner,
+        pointer: MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        if self.included {
+            let pointer_minus_one = pointer - &BigInt::from(1u32).into();
+
+            let stop_ptr = match runner.memory.borrow_mut().index(&pointer_minus_one)? {
+                MaybeRelocatable::RelocatableValue(value) => value,
+                MaybeRelocatable::Int(value) => {
+                    return Err(Error::StopPointerNotRelocatable {
+                        builtin_name: self.name.clone(),
+                        found: value,
+                    })
+                }
+            };
+            self.stop_ptr = Some(stop_ptr);
+
+            let expected = self.base()?.to_owned() + &self.get_used_cells(runner)?;
+            if stop_ptr != expected {
+                return Err(Error::InvalidStopPointer {
+                    builtin_name: self.name.clone(),
+                    expected,
+                    found: stop_ptr,
+                });
+            }
+
+            Ok(pointer_minus_one)
+        } else {
+            self.stop_ptr = self.base;
+            Ok(pointer)
+        }
+    }
+
+    fn segment_base(&self) -> Option<RelocatableValue> {
+        self.base
+    }
+
+    fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        Ok(runner
+            .segments
+            .borrow()
+            .get_segment_used_size(self.base()?.segment_index)?)
+    }
+
+    fn get_used_instances(&self, runner: &CairoRunner) -> Result<BigInt, Error> {
+        let used_cells = self.get_used_cells(runner)?;
+        let cells_per_instance = BigInt::from(self.cells_per_instance);
+        Ok((used_cells + &cells_per_instance - 1u32) / cells_per_instance)
+    }
+
+    fn get_used_cells_and_allocated_size(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<(BigInt, BigInt), Error> {
+        let used = self.get_used_cells(runner)?;
+        let current_step = &runner
+            .vm
+            .as_ref()
+            .ok_or(Error::VmNotInitialized)?
+            .current_step;
+        let size = BigInt::from(self.cells_per_instance) * (current_step / self.ratio);
+        if used > size {
+            return Err(Error::InsufficientAllocatedCells {
+                builtin_name: self.name.clone(),
+                used,
+                size,
+            });
+        }
+
+        Ok((used, size))
+    }
+
+    fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error> {
+        let mut addresses = HashMap::new();
+        addresses.insert(
+            self.name.clone(),
+            MemorySegmentAddresses {
+                begin_addr: self.base()?.to_owned(),
+                stop_ptr: self.stop_ptr.ok_or(Error::UnexpectedNoneValue)?,
+            },
+        );
+        Ok(addresses)
+    }
+
+    fn air_private_input(
+        &self,
+        runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, Error> {
+        let base = self.base()?;
+        let cells_per_instance = u64::from(self.cells_per_instance);
+
+        let mut instances: HashMap<u64, serde_json::Map<String, serde_json::Value>> =
+            HashMap::new();
+        for (addr, value) in runner.memory.borrow().data.iter() {
+            let addr = match addr {
+                MaybeRelocatable::RelocatableValue(addr)
+                    if addr.segment_index == base.segment_index =>
+                {
+                    addr
+                }
+                _ => continue,
+            };
+
+            let offset = u64::try_from(addr.offset).map_err(|_| Error::OffsetTooLarge {
+                builtin_name: self.name.clone(),
+                addr: addr.to_owned(),
+            })?;
+            let index = offset / cells_per_instance;
+            let cell = (offset % cells_per_instance) as usize;
+            let cell_name = match self.input_cell_names.get(cell) {
+                Some(cell_name) => cell_name,
+                None => continue,
+            };
+            let value = match value {
+                MaybeRelocatable::Int(value) => value,
+                MaybeRelocatable::RelocatableValue(_) => {
+                    return Err(Error::NonIntegerBuiltinValue {
+                        builtin_name: self.name.clone(),
+                        addr: addr.to_owned(),
+                    })
+                }
+            };
+
+            instances
+                .entry(index)
+                .or_insert_with(|| {
+                    let mut instance = serde_json::Map::new();
+                    instance.insert(String::from("index"), index.into());
+                    instance
+                })
+                .insert(
+                    String::from(*cell_name),
+                    serde_json::Value::String(format!("{:#x}", value)),
+                );
+        }
+
+        let mut instances = instances.into_iter().collect::<Vec<_>>();
+        instances.sort_by_key(|(index, _)| *index);
+
+        let mut private_input = vec![];
+        for (index, instance) in instances.into_iter() {
+            if self
+                .input_cell_names
+                .iter()
+                .any(|cell_name| !instance.contains_key(*cell_name))
+            {
+                return Err(Error::MissingBuiltinInput {
+                    builtin_name: self.name.clone(),
+                    index,
+                });
+            }
+            private_input.push(serde_json::Value::Object(instance));
+        }
+
+        let mut res = HashMap::new();
+        res.insert(self.name.clone(), private_input);
+        Ok(res)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
 impl From<MemoryError> for Error {
@@ -1,31 +1,69 @@
 use crate::{
     cairo::lang::{
-        compiler::program::Program,
+        builtins::{
+            bitwise::bitwise_builtin_runner::BitwiseBuiltinRunner,
+            range_check::range_check_builtin_runner::RangeCheckBuiltinRunner, BuiltinDefinition,
+        },
+        compiler::{
+            instruction::{decode_instruction_values, InstructionDecodeError},
+            program::Program,
+            scoped_name::ScopedName,
+        },
         instances::CairoLayout,
         vm::{
+            air_public_input::{MemorySegmentAddresses, PublicInput, PublicMemoryEntry},
             builtin_runner::{BuiltinRunner, Error as BuiltinRunnerError},
+            dump_style::DumpStyle,
             memory_dict::{Error as MemoryDictError, MemoryDict},
             memory_segments::{Error as MemorySegmentError, MemorySegmentManager},
+            output::{write_le_bytes, Error as OutputError, ADDR_SIZE_IN_BYTES},
             output_builtin_runner::OutputBuiltinRunner,
-            relocatable::{MaybeRelocatable, RelocatableValue},
-            utils::RunResources,
+            relocatable::{
+                relocate_value, AddressContext, MaybeRelocatable, RelocatableValue,
+                RelocationError, SubtractionError,
+            },
+            trace_entry::{relocate_trace, TraceEntry},
+            unreached_report::UnreachedReport,
+            utils::{next_power_of_2, RunResources},
             vm_core::{RunContext, VirtualMachine, VirtualMachineError},
             vm_exceptions::VmException,
         },
     },
-    hint_support::StaticLocals,
+    hint_support::{HintOutputSink, StaticLocals, StdoutHintOutputSink},
+    python::utils::is_subsequence,
 };
 
+#[cfg(feature = "builtin-pedersen")]
+use crate::cairo::lang::builtins::hash::hash_builtin_runner::HashBuiltinRunner;
+#[cfg(feature = "builtin-ecdsa")]
+use crate::cairo::lang::builtins::signature::signature_builtin_runner::SignatureBuiltinRunner;
+
+use indexmap::IndexMap;
 use num_bigint::BigInt;
+use rustpython_vm::PyObjectRef;
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
     rc::Rc,
 };
 
-pub type BuiltinRunnerMap = HashMap<String, Box<dyn BuiltinRunner>>;
+/// The builtin runners by name (e.g. `output_builtin`), in the order of the layout, which is also
+/// the order their segments are allocated in.
+pub type BuiltinRunnerMap = IndexMap<String, Box<dyn BuiltinRunner>>;
 
-type BuiltinRunnerFactory = dyn Fn(&str, bool) -> Box<dyn BuiltinRunner>;
+/// Returns the key in [BuiltinRunnerMap] of the builtin named `builtin_name` in programs and
+/// layouts, e.g. `output_builtin` for `output`. All lookups go through this function so that a key
+/// can't be misspelled into a builtin looking missing.
+pub fn builtin_runner_key(builtin_name: &str) -> String {
+    format!("{}_builtin", builtin_name)
+}
+
+type BuiltinRunnerFactory =
+    dyn Fn(&str, bool, &BuiltinDefinition) -> Result<Box<dyn BuiltinRunner>, Error>;
 
 #[derive(Debug)]
 pub struct CairoRunner {
@@ -37,10 +75,17 @@ pub struct CairoRunner {
     pub allow_missing_builtins: bool,
     pub memory: Rc<RefCell<MemoryDict>>,
     pub segments: Rc<RefCell<MemorySegmentManager>>,
-    pub segment_offsets: Option<HashMap<BigInt, BigInt>>,
+    pub segment_offsets: Option<HashMap<isize, BigInt>>,
+    /// The memory after relocation, indexed by the relocated address. Cells that were never
+    /// written to are `None`.
+    pub relocated_memory: Option<Vec<Option<BigInt>>>,
+    pub relocated_trace: Option<Vec<TraceEntry<BigInt>>>,
     pub final_pc: Option<RelocatableValue>,
     /// Flag used to ensure a safe use.
     pub run_ended: bool,
+    /// Set by end_run(), even when it skips finalizing the run. The run is only complete once
+    /// `run_ended` is set too.
+    pub vm_run_ended: bool,
     /// Flag used to ensure a safe use.
     pub segments_finalized: bool,
     /// A set of memory addresses accessed by the VM, after relocation of temporary segments into
@@ -53,6 +98,53 @@ pub struct CairoRunner {
     pub initial_ap: Option<RelocatableValue>,
     pub initial_fp: Option<RelocatableValue>,
     pub vm: Option<VirtualMachine>,
+    /// Where the output of hints goes. Passed on to the VM by `initialize_vm`.
+    pub hint_output_sink: Rc<dyn HintOutputSink>,
+    /// The maximum depth of nested calls, unbounded if `None`. Passed on to the VM by
+    /// `initialize_vm`.
+    pub max_call_depth: Option<usize>,
+    /// Whether `initialize_vm` leaves the Python hints to be compiled when they first run, so that
+    /// hints failing to compile only fail the run if they are reached.
+    pub lenient_hint_loading: bool,
+}
+
+/// The resources used by a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResources {
+    pub n_steps: BigInt,
+    // TODO: add `n_memory_holes` once memory holes can be counted
+    /// The number of used instances of each builtin, keyed by the builtin name.
+    pub builtin_instance_counter: HashMap<String, BigInt>,
+}
+
+/// The state of a run before a given step, see [CairoRunner::state_at_step].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepState {
+    pub registers: TraceEntry<MaybeRelocatable>,
+    /// The memory cells written before the step, including by the hints of the previous steps.
+    pub memory: HashMap<MaybeRelocatable, MaybeRelocatable>,
+}
+
+/// The registers and memory before or after running a fragment of a program, see
+/// [CairoRunner::run_fragment].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentState {
+    pub ap: RelocatableValue,
+    pub fp: RelocatableValue,
+    /// The memory cells outside of the program segment.
+    pub memory: HashMap<MaybeRelocatable, MaybeRelocatable>,
+}
+
+/// Everything a caller of `run_until_end` usually needs from a completed run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunSummary {
+    pub steps: BigInt,
+    /// The values written to the output builtin, or empty if the program doesn't use it.
+    pub output: Vec<BigInt>,
+    pub execution_resources: ExecutionResources,
+    pub final_pc: RelocatableValue,
+    /// The deepest nesting of calls reached during the run.
+    pub peak_call_depth: usize,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -64,6 +156,8 @@ pub enum Error {
     },
     #[error("The {name} builtin is not supported.")]
     BuiltinNotSupported { name: String },
+    #[error("The {name} builtin is not compiled in. Enable the \"{feature}\" feature to use it.")]
+    BuiltinNotCompiled { name: String, feature: &'static str },
     #[error("The builtins specified by the %builtins directive must be subsequence of {supported_builtin_list:?}. Got {program_builtins:?}.")]
     BuiltinsNotSubsequence {
         supported_builtin_list: Vec<String>,
@@ -73,6 +167,19 @@ pub enum Error {
     MissingBuiltin,
     #[error("Missing main().")]
     MissingMain,
+    #[error("--proof_mode cannot be used with a StrippedProgram.")]
+    ProofModeWithStrippedProgram,
+    #[error("Missing label {label}. Was the program compiled with --proof_mode?")]
+    MissingProofModeLabel { label: String },
+    #[error("Missing label {label}.")]
+    MissingLabel { label: String },
+    #[error("The precondition cell {addr} is in the program segment.")]
+    PreconditionInProgramSegment { addr: RelocatableValue },
+    #[error("The fragment ended with {register} = {value}, which is not an address.")]
+    NonRelocatableFragmentRegister {
+        register: &'static str,
+        value: MaybeRelocatable,
+    },
     #[error("Segments not initialized.")]
     SegmentsNotInitialized,
     #[error("Function entrypoint not initialized.")]
@@ -88,21 +195,69 @@ pub enum Error {
     #[error(transparent)]
     VmError(VmException),
     #[error(transparent)]
-    VirtualMachineError(VirtualMachineError),
-    #[error(transparent)]
     BuiltinRunnerError(BuiltinRunnerError),
+    #[error(
+        "Execution reached the end of the program at {final_pc} without reaching pc {target}."
+    )]
+    EndOfProgramReachedBeforeTarget {
+        final_pc: RelocatableValue,
+        target: MaybeRelocatable,
+    },
     #[error("end_run called twice")]
     EndRunCalledTwice,
-    #[error("Run must be ended before calling read_return_values.")]
+    #[error("The run must be ended with end_run() first.")]
     RunNotEnded,
+    #[error("The run was ended without finalizing it. Call finalize_all() first.")]
+    RunNotFinalized,
     #[error("The stop pointer of the missing builtin \"{builtin_name}\" must be 0.")]
     NonZeroMissingBuiltinStopPointer { builtin_name: String },
+    #[error(
+        "The return values must be in the execution segment, found a stack pointer of {addr}."
+    )]
+    ReturnValuesOutsideExecutionSegment { addr: MaybeRelocatable },
+    #[error(transparent)]
+    SubtractionError(SubtractionError),
     #[error("Cannot add the return values to the public memory after segment finalization.")]
     CannotAddReturnValuesAfterSegmentFinalization,
+    #[error("The builtin stop pointers were not read. Call read_return_values() first.")]
+    ReturnValuesNotRead,
+    #[error("The program does not use the output builtin.")]
+    MissingOutputBuiltin,
+    #[error("Output cell {index} does not hold an integer.")]
+    NonIntegerOutput {
+        index: usize,
+        value: Option<MaybeRelocatable>,
+    },
     #[error("Unexpected builtin type")]
     UnexpectedBuiltinType,
     #[error("Unexpected None value")]
     UnexpectedNoneValue,
+    #[error(transparent)]
+    RelocationError(RelocationError),
+    #[error("Relocated value {value} does not fit into {n_bytes} bytes.")]
+    RelocatedValueTooLarge { value: BigInt, n_bytes: usize },
+    #[error("relocate must be called before writing the relocated trace or memory.")]
+    NotRelocated,
+    #[error("Time travel must be enabled before the run starts.")]
+    TimeTravelDisabled,
+    #[error("Step {step} is beyond the current step {current_step}.")]
+    StepOutOfRange { step: usize, current_step: usize },
+    #[error("Memory cell {addr} holds {value}, which cannot be relocated.")]
+    UnrelocatedValue {
+        addr: MaybeRelocatable,
+        value: MaybeRelocatable,
+    },
+    #[error("The instruction at pc {pc} is not an integer: {value}.")]
+    NonIntegerInstruction {
+        pc: MaybeRelocatable,
+        value: MaybeRelocatable,
+    },
+    #[error(transparent)]
+    InstructionDecodeError(InstructionDecodeError),
+    #[error(transparent)]
+    Io(std::io::Error),
+    #[error(transparent)]
+    Json(serde_json::Error),
 }
 
 impl CairoRunner {
@@ -128,9 +283,9 @@ impl CairoRunner {
             }
         }
 
-        let mut builtin_runners = HashMap::new();
+        let mut builtin_runners = BuiltinRunnerMap::new();
 
-        let mut builtin_factories: HashMap<String, Box<BuiltinRunnerFactory>> = HashMap::new();
+        let mut builtin_factories: IndexMap<String, Box<BuiltinRunnerFactory>> = IndexMap::new();
         builtin_factories.insert(String::from("output"), Box::new(output_builtin_factory));
         builtin_factories.insert(String::from("pedersen"), Box::new(pedersen_builtin_factory));
         builtin_factories.insert(
@@ -139,49 +294,21 @@ impl CairoRunner {
         );
         builtin_factories.insert(String::from("ecdsa"), Box::new(ecdsa_builtin_factory));
         builtin_factories.insert(String::from("bitwise"), Box::new(bitwise_builtin_factory));
-
-        // TODO: implement the following builtin factories
-        //
-        // ```python
-        // builtin_factories = dict(
-        //     pedersen=lambda name, included: HashBuiltinRunner(
-        //         name=name,
-        //         included=included,
-        //         ratio=instance.builtins["pedersen"].ratio,
-        //         hash_func=pedersen_hash,
-        //     ),
-        //     range_check=lambda name, included: RangeCheckBuiltinRunner(
-        //         included=included,
-        //         ratio=instance.builtins["range_check"].ratio,
-        //         inner_rc_bound=2 ** 16,
-        //         n_parts=instance.builtins["range_check"].n_parts,
-        //     ),
-        //     ecdsa=lambda name, included: SignatureBuiltinRunner(
-        //         name=name,
-        //         included=included,
-        //         ratio=instance.builtins["ecdsa"].ratio,
-        //         process_signature=process_ecdsa,
-        //         verify_signature=verify_ecdsa_sig,
-        //     ),
-        //     bitwise=lambda name, included: BitwiseBuiltinRunner(
-        //         included=included, bitwise_builtin=instance.builtins["bitwise"]
-        //     ),
-        // )
-        // ```
+        builtin_factories.insert(String::from("ec_op"), Box::new(unsupported_builtin_factory));
+        builtin_factories.insert(
+            String::from("keccak"),
+            Box::new(unsupported_builtin_factory),
+        );
 
         let supported_builtin_list: Vec<String> = builtin_factories.keys().cloned().collect();
-        if program
-            .builtins()
-            .iter()
-            .any(|item| !supported_builtin_list.contains(item))
-        {
+        if !is_subsequence(program.builtins(), &supported_builtin_list) {
             return Err(Error::BuiltinsNotSubsequence {
                 supported_builtin_list,
                 program_builtins: program.builtins().to_vec(),
             });
         }
 
-        for (name, _) in instance.builtins.iter() {
+        for (name, definition) in instance.builtins.iter() {
             let factory = builtin_factories
                 .get(name)
                 .ok_or(Error::BuiltinNotSupported {
@@ -191,7 +318,10 @@ impl CairoRunner {
 
             // In proof mode all the builtin_runners are required.
             if included || proof_mode {
-                builtin_runners.insert(format!("{}_builtin", &name), factory(name, included));
+                builtin_runners.insert(
+                    builtin_runner_key(name),
+                    factory(name, included, definition)?,
+                );
             }
         }
 
@@ -212,8 +342,11 @@ impl CairoRunner {
             memory,
             segments,
             segment_offsets: None,
+            relocated_memory: None,
+            relocated_trace: None,
             final_pc: None,
             run_ended: false,
+            vm_run_ended: false,
             segments_finalized: false,
             accessed_addresses: None,
             program_base: None,
@@ -223,9 +356,20 @@ impl CairoRunner {
             initial_ap: None,
             initial_fp: None,
             vm: None,
+            hint_output_sink: Rc::new(StdoutHintOutputSink),
+            max_call_depth: None,
+            lenient_hint_loading: false,
         })
     }
 
+    /// Reserves memory for the program and a run of about `n_steps` steps, to avoid repeatedly
+    /// growing the memory during long runs. A step usually writes a single new memory cell, so
+    /// the memory may still grow afterwards if the estimate is exceeded.
+    pub fn reserve_memory(&mut self, n_steps: usize) {
+        let n_cells = self.program.data().len() + n_steps;
+        self.memory.borrow_mut().data.reserve(n_cells);
+    }
+
     pub fn initialize_segments(&mut self) {
         // Program segment.
         self.program_base = Some(self.segments.borrow_mut().add(None));
@@ -251,7 +395,7 @@ impl CairoRunner {
             match self
                 .builtin_runners
                 .borrow_mut()
-                .get_mut(&format!("{}_builtin", builtin_name))
+                .get_mut(&builtin_runner_key(builtin_name))
             {
                 Some(builtin_runner) => {
                     for item in builtin_runner.initial_stack().into_iter() {
@@ -269,23 +413,35 @@ impl CairoRunner {
         }
 
         if self.proof_mode {
-            // TODO: implement the following Python code
-            //
-            // ```python
-            // # Add the dummy last fp and pc to the public memory, so that the verifier can enforce
-            // # [fp - 2] = fp.
-            // stack_prefix: List[MaybeRelocatable] = [self.execution_base + 2, 0]
-            // stack = stack_prefix + stack
-            // self.execution_public_memory = list(range(len(stack)))
-            //
-            // assert isinstance(
-            //     self.program, Program
-            // ), "--proof_mode cannot be used with a StrippedProgram."
-            // self.initialize_state(self.program.start, stack)
-            // self.initial_fp = self.initial_ap = self.execution_base + 2
-            // return self.program_base + self.program.get_label("__end__")
-            // ```
-            todo!()
+            // Add the dummy last fp and pc to the public memory, so that the verifier can enforce
+            // [fp - 2] = fp.
+            let mut stack_prefix: Vec<MaybeRelocatable> = vec![
+                (self.execution_base()?.to_owned() + &BigInt::from(2u32)).into(),
+                BigInt::from(0u32).into(),
+            ];
+            stack_prefix.append(&mut stack);
+            let stack = stack_prefix;
+            self.execution_public_memory = Some((0..stack.len()).map(BigInt::from).collect());
+
+            let program = self.program.clone();
+            let program = match program.as_ref() {
+                Program::Full(program) => program,
+                Program::Stripped(_) => return Err(Error::ProofModeWithStrippedProgram),
+            };
+            let start = program
+                .start()
+                .ok_or_else(|| Error::MissingProofModeLabel {
+                    label: String::from("__start__"),
+                })?;
+            let end = program.end().ok_or_else(|| Error::MissingProofModeLabel {
+                label: String::from("__end__"),
+            })?;
+
+            self.initialize_state(&start, &stack)?;
+            self.initial_fp = Some(self.execution_base()?.to_owned() + &BigInt::from(2u32));
+            self.initial_ap = self.initial_fp;
+
+            Ok(self.program_base()?.to_owned() + &end)
         } else {
             let return_fp = self.segments.borrow_mut().add(None);
 
@@ -305,12 +461,12 @@ impl CairoRunner {
         let end = self.segments.borrow_mut().add(None);
         let mut stack = args;
         stack.push(return_fp);
-        stack.push(end.clone().into());
+        stack.push(end.into());
 
         self.initialize_state(entrypoint, &stack)?;
         self.initial_fp = Some(self.execution_base()?.to_owned() + &BigInt::from(stack.len()));
-        self.initial_ap = self.initial_fp.clone();
-        self.final_pc = Some(end.clone());
+        self.initial_ap = self.initial_fp;
+        self.final_pc = Some(end);
 
         Ok(end)
     }
@@ -331,20 +487,20 @@ impl CairoRunner {
                 .iter()
                 .map(|item| item.to_owned().into())
                 .collect::<Vec<_>>(),
-        );
+        )?;
 
         // Load stack.
         self.load_data(
             self.execution_base()?.to_owned().into(),
             &stack.iter().map(|item| item.to_owned()).collect::<Vec<_>>(),
-        );
+        )?;
 
         Ok(())
     }
 
     pub fn initialize_vm(
         &mut self,
-        hint_locals: HashMap<String, ()>,
+        hint_locals: HashMap<String, PyObjectRef>,
         _static_locals: (),
     ) -> Result<(), Error> {
         let context = RunContext::new(
@@ -359,22 +515,28 @@ impl CairoRunner {
             self.program.clone(),
             Rc::new(RefCell::new(context)),
             hint_locals,
-            StaticLocals {
-                segments: self.segments.clone(),
-            },
+            StaticLocals::new(self.segments.clone()),
             Some(self.builtin_runners.clone()),
             Some(self.program_base()?.to_owned().into()),
         ));
 
-        // TODO: implement the following Python code
-        //
-        // ```python
-        // for builtin_runner in self.builtin_runners.values():
-        //     builtin_runner.add_validation_rules(self)
-        //     builtin_runner.add_auto_deduction_rules(self)
-        //
-        // self.vm.validate_existing_memory()
-        // ```
+        let vm = self.vm.as_mut().ok_or(Error::VmNotInitialized)?;
+        vm.hint_output_sink = self.hint_output_sink.clone();
+        vm.max_call_depth = self.max_call_depth;
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            builtin_runner.add_validation_rules(&mut vm.validated_memory.borrow_mut());
+            builtin_runner.add_auto_deduction_rules(vm);
+        }
+
+        if let Err(err) = vm.validate_existing_memory() {
+            return Err(self.as_vm_error(err, false));
+        }
+
+        if !self.lenient_hint_loading {
+            if let Err(err) = self.vm()?.compile_hints() {
+                return Err(self.as_vm_error(err, false));
+            }
+        }
 
         Ok(())
     }
@@ -388,37 +550,235 @@ impl CairoRunner {
         let mut run_resources = run_resources.unwrap_or(RunResources { n_steps: None });
 
         while self.vm()?.run_context.borrow().pc != addr && !run_resources.consumed() {
+            // Reaching the end of the program first means the target can never be reached, which
+            // most likely indicates a wrong target rather than a failure of the program itself.
+            if self.final_pc.is_some() && &self.vm()?.run_context.borrow().pc == self.final_pc()? {
+                return Err(Error::EndOfProgramReachedBeforeTarget {
+                    final_pc: self.final_pc()?.to_owned(),
+                    target: addr,
+                });
+            }
+
             self.vm_step()?;
             run_resources.consume_step();
         }
 
-        if self.vm()?.run_context.borrow().pc != addr {
-            // TODO: implement `as_vm_exception` on `vm` and switch over
-            //       Error: End of program was not reached
-            Err(Error::VmError(VmException {}))
+        let pc = self.vm()?.run_context.borrow().pc.clone();
+        if pc != addr {
+            Err(self.as_vm_error(
+                VirtualMachineError::EndOfProgramNotReached { pc, target: addr },
+                false,
+            ))
         } else {
             Ok(())
         }
     }
 
+    /// Runs the program from `main()` to its end in one call, on a runner fresh out of `new()`,
+    /// and summarizes the run.
+    pub fn run_until_end(
+        &mut self,
+        hint_locals: HashMap<String, PyObjectRef>,
+    ) -> Result<RunSummary, Error> {
+        self.initialize_segments();
+        let end = self.initialize_main_entrypoint()?;
+        self.initialize_vm(hint_locals, ())?;
+        self.run_until_pc(end.into(), None)?;
+        self.end_run(false, false)?;
+        self.read_return_values()?;
+
+        let output = self.get_int_output()?;
+        let execution_resources = self.get_execution_resources()?;
+
+        Ok(RunSummary {
+            steps: execution_resources.n_steps.clone(),
+            output,
+            execution_resources,
+            final_pc: self.final_pc()?.to_owned(),
+            peak_call_depth: self.vm()?.peak_call_depth,
+        })
+    }
+
+    /// Runs the function at `entrypoint` with `args` to its end in one call, and ends the run.
+    ///
+    /// The arguments are pushed on the stack as is, so the function's implicit arguments must be
+    /// passed explicitly: builtin pointers can be taken from `initial_stack()` of the builtin
+    /// runners, which requires the segments to be initialized before calling this. The return
+    /// values are then found right below the final ap.
+    pub fn run_from_entrypoint(
+        &mut self,
+        entrypoint: &BigInt,
+        args: &[MaybeRelocatable],
+        hint_locals: HashMap<String, PyObjectRef>,
+        static_locals: (),
+    ) -> Result<(), Error> {
+        if self.program_base.is_none() {
+            self.initialize_segments();
+        }
+        let end = self.initialize_function_entrypoint(
+            entrypoint,
+            args.to_vec(),
+            BigInt::from(0u32).into(),
+        )?;
+        self.initialize_vm(hint_locals, static_locals)?;
+        self.run_until_pc(end.into(), None)?;
+        self.end_run(false, false)
+    }
+
+    /// Runs the program from the label `start_label` until it reaches `end_label`, e.g. to run the
+    /// body of a function in isolation. Unlike the other entrypoints, no stack is set up: the run
+    /// starts with the registers and memory cells of `preconditions`, whose addresses can be
+    /// taken from the segments once initialized. The run is ended, and the state it ends in is
+    /// returned.
+    ///
+    /// The labels are looked up from the main scope, or by their full names.
+    pub fn run_fragment(
+        &mut self,
+        start_label: &str,
+        end_label: &str,
+        preconditions: FragmentState,
+    ) -> Result<FragmentState, Error> {
+        let start = self.get_label_pc(start_label)?;
+        let end = self.get_label_pc(end_label)?;
+
+        if self.program_base.is_none() {
+            self.initialize_segments();
+        }
+        let program_base = self.program_base()?.to_owned();
+        // The program segment only holds the program data.
+        for addr in preconditions.memory.keys() {
+            match addr {
+                MaybeRelocatable::RelocatableValue(addr)
+                    if addr.segment_index == program_base.segment_index =>
+                {
+                    return Err(Error::PreconditionInProgramSegment {
+                        addr: addr.to_owned(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        self.initialize_state(&start, &[])?;
+        for (addr, value) in preconditions.memory.into_iter() {
+            self.memory.borrow_mut().index_set(addr, value)?;
+        }
+        self.initial_ap = Some(preconditions.ap);
+        self.initial_fp = Some(preconditions.fp);
+
+        self.initialize_vm(HashMap::new(), ())?;
+        self.run_until_pc((program_base.clone() + &end).into(), None)?;
+        self.end_run(false, false)?;
+
+        let as_relocatable = |register: &'static str, value: MaybeRelocatable| match value {
+            MaybeRelocatable::RelocatableValue(value) => Ok(value),
+            MaybeRelocatable::Int(_) => {
+                Err(Error::NonRelocatableFragmentRegister { register, value })
+            }
+        };
+        let run_context = self.vm()?.run_context.borrow();
+        Ok(FragmentState {
+            ap: as_relocatable("ap", run_context.ap.clone())?,
+            fp: as_relocatable("fp", run_context.fp.clone())?,
+            memory: self
+                .memory
+                .borrow()
+                .data
+                .iter()
+                .filter(|(addr, _)| match addr {
+                    MaybeRelocatable::RelocatableValue(addr) => {
+                        addr.segment_index != program_base.segment_index
+                    }
+                    MaybeRelocatable::Int(_) => true,
+                })
+                .map(|(addr, value)| (addr.to_owned(), value.to_owned()))
+                .collect(),
+        })
+    }
+
     pub fn vm_step(&mut self) -> Result<(), Error> {
-        if &self.vm()?.run_context.borrow().pc == self.final_pc()? {
-            // TODO: implement `as_vm_exception` on `vm` and switch over
-            //       Error: Execution reached the end of the program.
-            return Err(Error::VmError(VmException {}));
+        // There's no final pc in proof mode, as the program ends with an infinite loop instead.
+        if self.final_pc.is_some() && &self.vm()?.run_context.borrow().pc == self.final_pc()? {
+            return Err(self.as_vm_error(VirtualMachineError::EndOfProgramReached, false));
+        }
+
+        if let Err(err) = self.vm_mut()?.step() {
+            return Err(self.as_vm_error(err, true));
+        }
+
+        Ok(())
+    }
+
+    /// Wraps an error raised by the VM into an `Error::VmError`, with the context of the current
+    /// pc. All the errors of the VM go through here, as in `cairo-lang`.
+    fn as_vm_error(&self, exc: VirtualMachineError, with_traceback: bool) -> Error {
+        match self.vm() {
+            Ok(vm) => Error::VmError(vm.as_vm_exception(exc, with_traceback)),
+            Err(err) => err,
         }
+    }
+
+    /// Runs `n_steps` steps, or until the end of the program is reached if that happens first.
+    pub fn run_for_steps(&mut self, n_steps: BigInt) -> Result<(), Error> {
+        let mut run_resources = RunResources {
+            n_steps: Some(n_steps),
+        };
 
-        self.vm_mut()?.step()?;
+        while !run_resources.consumed() {
+            if self.final_pc.is_some() && &self.vm()?.run_context.borrow().pc == self.final_pc()? {
+                break;
+            }
+
+            self.vm_step()?;
+            run_resources.consume_step();
+        }
 
         Ok(())
     }
 
+    /// Runs until the number of executed steps reaches `n_steps`.
+    pub fn run_until_steps(&mut self, n_steps: BigInt) -> Result<(), Error> {
+        let current_step = self.vm()?.current_step.clone();
+        self.run_for_steps(n_steps - current_step)
+    }
+
+    /// Runs until the number of executed steps is a power of 2.
+    pub fn run_until_next_power_of_2(&mut self) -> Result<(), Error> {
+        let n_steps = next_power_of_2(&self.vm()?.current_step);
+        self.run_until_steps(n_steps)
+    }
+
+    /// Returns the `InsufficientAllocatedCells` error of the first builtin that uses more cells
+    /// than the steps allocate for it, in which case the run should be extended, or `None` if
+    /// there are enough steps.
+    pub fn check_used_cells(&self) -> Result<Option<BuiltinRunnerError>, Error> {
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            match builtin_runner.get_used_cells_and_allocated_size(self) {
+                Ok(_) => {}
+                Err(err @ BuiltinRunnerError::InsufficientAllocatedCells { .. }) => {
+                    return Ok(Some(err));
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        // TODO: implement the following Python code
+        //
+        // ```python
+        // self.check_range_check_usage()
+        // self.check_memory_usage()
+        // self.check_diluted_check_usage()
+        // ```
+
+        Ok(None)
+    }
+
     pub fn end_run(
         &mut self,
         disable_trace_padding: bool,
         disable_finalize_all: bool,
     ) -> Result<(), Error> {
-        if self.run_ended {
+        if self.vm_run_ended {
             return Err(Error::EndRunCalledTwice);
         }
 
@@ -428,7 +788,7 @@ impl CairoRunner {
                 self.vm()?
                     .accessed_addresses
                     .iter()
-                    .map(|addr| match vm_memory.relocate_value(addr.to_owned()) {
+                    .map(|addr| match vm_memory.relocate_value(addr.into()) {
                         MaybeRelocatable::Int(_) => {
                             panic!("unexpected variant: MaybeRelocatable::Int")
                         }
@@ -438,29 +798,95 @@ impl CairoRunner {
             )
         };
         self.memory.borrow_mut().relocate_memory()?;
-        self.vm_mut()?.end_run()?;
+        if let Err(err) = self.vm_mut()?.end_run() {
+            return Err(self.as_vm_error(err, false));
+        }
+        self.vm_run_ended = true;
 
         if disable_finalize_all {
-            // For tests.
+            // For tests. finalize_all() completes the run afterwards.
+            return Ok(());
+        }
+
+        self.finalize_run(disable_trace_padding)
+    }
+
+    /// Completes a run ended by `end_run(_, true)` and finalizes the segments. Does nothing if the
+    /// segments are already finalized.
+    ///
+    /// Note: as the segments are finalized, read_return_values() must precede a call to this
+    /// method for the return values to be included in the public memory.
+    pub fn finalize_all(&mut self) -> Result<(), Error> {
+        if self.segments_finalized {
+            return Ok(());
+        }
+        if !self.vm_run_ended {
+            return Err(Error::RunNotEnded);
+        }
+
+        if !self.run_ended {
+            self.finalize_run(true)?;
+        }
+        self.finalize_segments()
+    }
+
+    /// Finalizes the segments.
+    ///
+    /// Note:
+    /// 1. end_run() must precede a call to this method.
+    /// 2. Call read_return_values() *before* finalize_segments(), otherwise the return values
+    ///    will not be included in the public memory.
+    pub fn finalize_segments(&mut self) -> Result<(), Error> {
+        if self.segments_finalized {
             return Ok(());
         }
+        self.check_run_ended()?;
+
+        let program_size = self.program.data().len();
+        let program_segment = self.program_base()?.segment_index;
+        let execution_base = self.execution_base()?.to_owned();
+        {
+            let mut segments = self.segments.borrow_mut();
+            segments.finalize(
+                program_segment,
+                Some(BigInt::from(program_size)),
+                (0..program_size)
+                    .map(|i| [BigInt::from(i), BigInt::from(0u32)])
+                    .collect(),
+            );
+            segments.finalize(
+                execution_base.segment_index,
+                None,
+                self.execution_public_memory
+                    .iter()
+                    .flatten()
+                    .map(|offset| [offset + execution_base.offset, BigInt::from(0u32)])
+                    .collect(),
+            );
+        }
 
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            builtin_runner.finalize_segments(self)?;
+        }
+
+        self.segments_finalized = true;
+
+        Ok(())
+    }
+
+    /// The part of end_run() skipped when `disable_finalize_all` is set.
+    fn finalize_run(&mut self, disable_trace_padding: bool) -> Result<(), Error> {
         // Freeze to enable caching; No changes in memory should be made from now on.
         self.memory.borrow_mut().freeze();
         // Deduce the size of each segment from its usage.
         self.segments.borrow_mut().compute_effective_sizes(false)?;
 
         if self.proof_mode && !disable_trace_padding {
-            // TODO: implement the following Python code
-            //
-            // ```python
-            // self.run_until_next_power_of_2()
-            // while not self.check_used_cells():
-            //     self.run_for_steps(1)
-            //     self.run_until_next_power_of_2()
-            // ```
-
-            todo!()
+            self.run_until_next_power_of_2()?;
+            while self.check_used_cells()?.is_some() {
+                self.run_for_steps(1u32.into())?;
+                self.run_until_next_power_of_2()?;
+            }
         }
 
         self.run_ended = true;
@@ -470,9 +896,10 @@ impl CairoRunner {
 
     /// Reads builtin return values (end pointers) and adds them to the public memory.
     /// Note: end_run() must precede a call to this method.
-    pub fn read_return_values(&self) -> Result<(), Error> {
-        if !self.run_ended {
-            return Err(Error::RunNotEnded);
+    pub fn read_return_values(&mut self) -> Result<(), Error> {
+        self.check_run_ended()?;
+        if self.segments_finalized {
+            return Err(Error::CannotAddReturnValuesAfterSegmentFinalization);
         }
 
         let mut pointer = self.vm()?.run_context.borrow().ap.clone();
@@ -480,7 +907,7 @@ impl CairoRunner {
             match self
                 .builtin_runners
                 .borrow_mut()
-                .get_mut(&format!("{}_builtin", builtin_name))
+                .get_mut(&builtin_runner_key(builtin_name))
             {
                 Some(builtin_runner) => {
                     pointer = builtin_runner.final_stack(self, pointer)?;
@@ -489,7 +916,7 @@ impl CairoRunner {
                     if !self.allow_missing_builtins {
                         return Err(Error::MissingBuiltin);
                     }
-                    pointer = pointer - &BigInt::from(1u32).into();
+                    pointer = pointer.checked_sub(&BigInt::from(1u32).into())?;
                     if self.memory.borrow_mut().index(&pointer)?
                         != MaybeRelocatable::Int(BigInt::from(0u32))
                     {
@@ -501,161 +928,3815 @@ impl CairoRunner {
             }
         }
 
-        if self.segments_finalized {
-            return Err(Error::CannotAddReturnValuesAfterSegmentFinalization);
+        // Builtins that are not used by the program (which only happens in proof mode) don't
+        // have a pointer on the stack, and their segments are left empty.
+        for (name, builtin_runner) in self.builtin_runners.borrow_mut().iter_mut() {
+            if !self
+                .program
+                .builtins()
+                .iter()
+                .any(|builtin_name| name == &builtin_runner_key(builtin_name))
+            {
+                builtin_runner.final_stack(self, pointer.clone())?;
+            }
         }
 
-        // TODO: implement the following Python code
-        //
-        // ```python
-        // # Add return values to public memory.
-        // self.execution_public_memory += list(
-        //     range(pointer - self.execution_base, self.vm.run_context.ap - self.execution_base)
-        // )
-        // ```
+        // Add return values to public memory.
+        let execution_base = MaybeRelocatable::from(self.execution_base()?.to_owned());
+        let execution_offset = |addr: MaybeRelocatable| match addr.checked_sub(&execution_base) {
+            Ok(MaybeRelocatable::Int(offset)) => Ok(offset),
+            _ => Err(Error::ReturnValuesOutsideExecutionSegment { addr }),
+        };
+        let ap = self.vm()?.run_context.borrow().ap.clone();
+        let mut offset = execution_offset(pointer)?;
+        let end = execution_offset(ap)?;
+        let execution_public_memory = self.execution_public_memory.get_or_insert_with(Vec::new);
+        while offset < end {
+            execution_public_memory.push(offset.clone());
+            offset += 1u32;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the run halted at the end of the program, and that every builtin stop pointer
+    /// points right after the cells used by its builtin. In proof mode, where the program ends with
+    /// an infinite loop instead, only the builtins are checked.
+    ///
+    /// Note: end_run() and read_return_values() must precede a call to this method.
+    pub fn assert_clean_halt(&self) -> Result<(), Error> {
+        if !self.vm_run_ended {
+            return Err(Error::RunNotEnded);
+        }
+
+        if let Some(final_pc) = &self.final_pc {
+            let pc = self.vm()?.run_context.borrow().pc.clone();
+            if pc != final_pc.to_owned().into() {
+                return Err(self.as_vm_error(
+                    VirtualMachineError::EndOfProgramNotReached {
+                        pc,
+                        target: final_pc.to_owned().into(),
+                    },
+                    false,
+                ));
+            }
+        }
+
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            let addresses = match builtin_runner.get_memory_segment_addresses() {
+                Ok(addresses) => addresses,
+                Err(BuiltinRunnerError::UnexpectedNoneValue) => {
+                    return Err(Error::ReturnValuesNotRead)
+                }
+                Err(err) => return Err(err.into()),
+            };
+            for (builtin_name, addresses) in addresses.into_iter() {
+                let expected = addresses.begin_addr + &builtin_runner.get_used_cells(self)?;
+                if addresses.stop_ptr != expected {
+                    return Err(BuiltinRunnerError::InvalidStopPointer {
+                        builtin_name,
+                        expected,
+                        found: addresses.stop_ptr,
+                    }
+                    .into());
+                }
+            }
+        }
 
         Ok(())
     }
 
+    /// Returns the number of memory holes, i.e. the cells below the used size of each segment that
+    /// were never accessed by an instruction.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn get_memory_holes(&self) -> Result<BigInt, Error> {
+        let accessed_addresses = self.accessed_addresses.as_ref().ok_or(Error::RunNotEnded)?;
+        Ok(self
+            .segments
+            .borrow()
+            .get_memory_holes(accessed_addresses)?)
+    }
+
     /// Writes data into the memory at address ptr and returns the first address after the data.
     pub fn load_data(
         &mut self,
         ptr: MaybeRelocatable,
         data: &[MaybeRelocatable],
-    ) -> MaybeRelocatable {
-        self.segments.borrow_mut().load_data(ptr, data)
+    ) -> Result<MaybeRelocatable, Error> {
+        Ok(self.segments.borrow_mut().load_data(ptr, data)?)
     }
 
-    // TODO: implement `output_callback`
+    /// Prints the cells of the output builtin to stdout. Does nothing if the program doesn't use the
+    /// output builtin.
     pub fn print_output(&self) -> Result<(), Error> {
-        if let Some(output_runner) = self.builtin_runners.borrow().get("output_builtin") {
-            let output_runner = output_runner
-                .as_any()
-                .downcast_ref::<OutputBuiltinRunner>()
-                .ok_or(Error::UnexpectedBuiltinType)?;
-
-            println!("Program output:");
-
-            let (_, size) = output_runner.get_used_cells_and_allocated_size(self)?;
-            let mut i = BigInt::from(0u32);
-            while i < size {
-                match self.memory.borrow_mut().get(
-                    &(output_runner
-                        .base
-                        .clone()
-                        .ok_or(Error::UnexpectedNoneValue)?
-                        + &i)
-                        .into(),
-                    None,
-                ) {
-                    Some(val) => {
-                        println!("  {}", val);
-                    }
-                    None => {
-                        println!("  <missing>");
-                    }
-                }
+        self.print_output_styled(&DumpStyle::default())
+    }
 
-                i += BigInt::from(1u32);
+    /// Like [print_output](Self::print_output), with the cells written in the given style.
+    pub fn print_output_styled(&self, style: &DumpStyle) -> Result<(), Error> {
+        if !self
+            .builtin_runners
+            .borrow()
+            .contains_key(&builtin_runner_key("output"))
+        {
+            return Ok(());
+        }
+
+        println!("Program output:");
+        self.print_output_with(|value| match value {
+            Some(val) => {
+                println!("  {}", style.format_value(val));
             }
+            None => {
+                println!("  <missing>");
+            }
+        })?;
+        println!();
+
+        Ok(())
+    }
 
-            println!();
+    /// Calls `output_callback` with each cell of the output builtin in order, or with `None` for
+    /// the cells that were never written to. Does nothing if the program doesn't use the output
+    /// builtin.
+    pub fn print_output_with<F: FnMut(Option<&MaybeRelocatable>)>(
+        &self,
+        mut output_callback: F,
+    ) -> Result<(), Error> {
+        if !self
+            .builtin_runners
+            .borrow()
+            .contains_key(&builtin_runner_key("output"))
+        {
+            return Ok(());
+        }
+
+        for value in self.get_output()?.iter() {
+            output_callback(value.as_ref());
         }
 
         Ok(())
     }
 
-    fn program_base(&self) -> Result<&RelocatableValue, Error> {
-        self.program_base
-            .as_ref()
-            .ok_or(Error::SegmentsNotInitialized)
-    }
+    /// Limits the number of cells the program may write to the output builtin, so that reading the
+    /// output of a program writing too much fails instead of going through all of it. Does nothing
+    /// if the program doesn't use the output builtin.
+    pub fn set_max_output_cells(&self, max_output_cells: Option<BigInt>) -> Result<(), Error> {
+        if let Some(output_runner) = self
+            .builtin_runners
+            .borrow_mut()
+            .get_mut(&builtin_runner_key("output"))
+        {
+            output_runner
+                .as_any_mut()
+                .downcast_mut::<OutputBuiltinRunner>()
+                .ok_or(Error::UnexpectedBuiltinType)?
+                .max_output_cells = max_output_cells;
+        }
 
-    fn execution_base(&self) -> Result<&RelocatableValue, Error> {
-        self.execution_base
-            .as_ref()
-            .ok_or(Error::SegmentsNotInitialized)
+        Ok(())
     }
 
-    fn final_pc(&self) -> Result<&RelocatableValue, Error> {
-        self.final_pc
-            .as_ref()
-            .ok_or(Error::FunctionEntrypointNotInitialized)
-    }
+    /// Returns the values written to the output builtin segment, with `None` for the cells that
+    /// were left unset.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn get_output(&self) -> Result<Vec<Option<MaybeRelocatable>>, Error> {
+        let builtin_runners = self.builtin_runners.borrow();
+        let output_runner = builtin_runners
+            .get(&builtin_runner_key("output"))
+            .ok_or(Error::MissingOutputBuiltin)?
+            .as_any()
+            .downcast_ref::<OutputBuiltinRunner>()
+            .ok_or(Error::UnexpectedBuiltinType)?;
+        let base = output_runner.base.ok_or(Error::UnexpectedNoneValue)?;
 
-    fn initial_pc(&self) -> Result<&RelocatableValue, Error> {
-        self.initial_pc.as_ref().ok_or(Error::StateNotInitialized)
-    }
+        let (_, size) = output_runner.get_used_cells_and_allocated_size(self)?;
+        let mut output = vec![];
+        let mut i = BigInt::from(0u32);
+        while i < size {
+            output.push(self.memory.borrow_mut().get(&(base + &i).into(), None));
+            i += BigInt::from(1u32);
+        }
 
-    fn initial_ap(&self) -> Result<&RelocatableValue, Error> {
-        self.initial_ap.as_ref().ok_or(Error::StateNotInitialized)
+        Ok(output)
     }
 
-    fn initial_fp(&self) -> Result<&RelocatableValue, Error> {
-        self.initial_fp.as_ref().ok_or(Error::StateNotInitialized)
-    }
+    pub fn get_execution_resources(&self) -> Result<ExecutionResources, Error> {
+        let n_steps = match &self.original_steps {
+            Some(original_steps) => original_steps.to_owned(),
+            None => BigInt::from(self.vm()?.trace.len()),
+        };
 
-    fn vm(&self) -> Result<&VirtualMachine, Error> {
-        self.vm.as_ref().ok_or(Error::VmNotInitialized)
+        let mut builtin_instance_counter = HashMap::new();
+        for (builtin_name, builtin_runner) in self.builtin_runners.borrow().iter() {
+            builtin_instance_counter.insert(
+                builtin_name.to_owned(),
+                builtin_runner.get_used_instances(self)?,
+            );
+        }
+
+        Ok(ExecutionResources {
+            n_steps,
+            builtin_instance_counter,
+        })
     }
 
-    fn vm_mut(&mut self) -> Result<&mut VirtualMachine, Error> {
-        self.vm.as_mut().ok_or(Error::VmNotInitialized)
+    /// Returns the values written to the output builtin segment, which must all be integers. The
+    /// output is empty if the program doesn't use the output builtin.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn get_int_output(&self) -> Result<Vec<BigInt>, Error> {
+        if !self
+            .builtin_runners
+            .borrow()
+            .contains_key(&builtin_runner_key("output"))
+        {
+            return Ok(vec![]);
+        }
+
+        let mut output = vec![];
+        for (index, value) in self.get_output()?.into_iter().enumerate() {
+            match value {
+                Some(MaybeRelocatable::Int(value)) => output.push(value),
+                value => return Err(Error::NonIntegerOutput { index, value }),
+            }
+        }
+
+        Ok(output)
     }
-}
 
-impl From<MemoryDictError> for Error {
-    fn from(value: MemoryDictError) -> Self {
-        Self::MemoryDictError(value)
+    /// Records the step at which each memory cell is written, so that the state of the run at any
+    /// past step can be inspected with state_at_step(). Must be called before the run starts.
+    pub fn enable_time_travel(&mut self) {
+        self.memory.borrow_mut().enable_write_log();
     }
-}
 
-impl From<MemorySegmentError> for Error {
-    fn from(value: MemorySegmentError) -> Self {
+    /// Returns the registers and the memory as they were when the run reached `step`, i.e. before
+    /// the hints of the step were executed. `step` may also be the current step.
+    ///
+    /// Memory is write-once, so the memory at a past step is the current memory without the cells
+    /// written since.
+    pub fn state_at_step(&self, step: usize) -> Result<StepState, Error> {
+        let vm = self.vm()?;
+        let memory = self.memory.borrow();
+        let write_log = memory.write_log.as_ref().ok_or(Error::TimeTravelDisabled)?;
+
+        let (registers, n_writes) = match step.cmp(&vm.trace.len()) {
+            Ordering::Less => (
+                vm.trace[step].clone(),
+                vm.memory_write_marks
+                    .get(step)
+                    .cloned()
+                    .ok_or(Error::TimeTravelDisabled)?,
+            ),
+            Ordering::Equal => {
+                let run_context = vm.run_context.borrow();
+                let registers = TraceEntry {
+                    pc: run_context.pc.clone(),
+                    ap: run_context.ap.clone(),
+                    fp: run_context.fp.clone(),
+                };
+                (registers, write_log.len())
+            }
+            Ordering::Greater => {
+                return Err(Error::StepOutOfRange {
+                    step,
+                    current_step: vm.trace.len(),
+                })
+            }
+        };
+
+        Ok(StepState {
+            registers,
+            memory: write_log[..n_writes]
+                .iter()
+                .filter_map(|addr| {
+                    memory
+                        .data
+                        .get(addr)
+                        .map(|value| (addr.to_owned(), value.to_owned()))
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns the hints and functions of the program that the run hasn't reached so far, i.e.
+    /// whose pc is in no step of the trace. Stripped programs have neither.
+    pub fn unreached_report(&self) -> Result<UnreachedReport, Error> {
+        let program = match self.program.as_ref() {
+            Program::Full(program) => program,
+            Program::Stripped(_) => return Ok(UnreachedReport::default()),
+        };
+        let program_base = self.program_base()?;
+
+        let executed_pcs = self
+            .vm()?
+            .trace
+            .iter()
+            .filter_map(|entry| match &entry.pc {
+                MaybeRelocatable::RelocatableValue(pc)
+                    if pc.segment_index == program_base.segment_index
+                        && pc.offset >= program_base.offset =>
+                {
+                    Some(BigInt::from(pc.offset - program_base.offset))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(UnreachedReport::new(program, &executed_pcs))
+    }
+
+    /// Reads the memory cell `offset` cells after the label (or function) `label`, e.g. a value
+    /// stored with `dw` right after a label. The label is looked up from the main scope, or by its
+    /// full name.
+    pub fn get_memory_at_label(
+        &self,
+        label: &str,
+        offset: usize,
+    ) -> Result<Option<MaybeRelocatable>, Error> {
+        let pc = self.get_label_pc(label)?;
+        let addr = self.program_base()?.to_owned() + &(pc + offset);
+        Ok(self.memory.borrow_mut().get(&addr.into(), None))
+    }
+
+    /// Returns the addresses of the builtin segments, keyed by the builtin name.
+    ///
+    /// Note: read_return_values() must precede a call to this method.
+    pub fn get_memory_segment_addresses(
+        &self,
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, Error> {
+        self.check_run_ended()?;
+        let mut addresses = HashMap::new();
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            addresses.extend(builtin_runner.get_memory_segment_addresses()?);
+        }
+        Ok(addresses)
+    }
+
+    /// Returns the builtin-specific part of the AIR private input, keyed by the builtin name.
+    pub fn get_air_private_input(&self) -> Result<HashMap<String, Vec<serde_json::Value>>, Error> {
+        self.check_run_ended()?;
+        let mut private_input = HashMap::new();
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            private_input.extend(builtin_runner.air_private_input(self)?);
+        }
+        Ok(private_input)
+    }
+
+    /// Returns the smallest and largest values checked by the range-check units of the AIR: the
+    /// biased offsets of the executed instructions and the 16-bit parts of the values checked by
+    /// the builtins. Returns `None` if nothing was checked, i.e. no step was run and the builtins
+    /// are unused.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn get_perm_range_check_limits(&self) -> Result<Option<(u16, u16)>, Error> {
+        self.check_run_ended()?;
+
+        let mut limits: Option<(u16, u16)> = None;
+        let mut update_limits = |min: u16, max: u16| {
+            limits = Some(match limits {
+                Some((rc_min, rc_max)) => (rc_min.min(min), rc_max.max(max)),
+                None => (min, max),
+            });
+        };
+        {
+            let mut memory = self.memory.borrow_mut();
+            for entry in self.vm()?.trace.iter() {
+                let encoding = match memory.index(&entry.pc)? {
+                    MaybeRelocatable::Int(encoding) => encoding,
+                    value => {
+                        return Err(Error::NonIntegerInstruction {
+                            pc: entry.pc.clone(),
+                            value,
+                        })
+                    }
+                };
+                let (_, off0, off1, off2) = decode_instruction_values(&encoding)?;
+                update_limits(off0.min(off1).min(off2), off0.max(off1).max(off2));
+            }
+        }
+
+        for builtin_runner in self.builtin_runners.borrow().values() {
+            if let Some((builtin_min, builtin_max)) = builtin_runner.get_range_check_usage(self)? {
+                update_limits(builtin_min, builtin_max);
+            }
+        }
+
+        Ok(limits)
+    }
+
+    /// Returns the public input of the Cairo AIR, as written to `air_public_input.json` by
+    /// cairo-run.
+    ///
+    /// Note: relocate() and finalize_segments() must precede a call to this method.
+    pub fn get_air_public_input(&self) -> Result<PublicInput, Error> {
+        if !self.segments_finalized {
+            return Err(Error::RunNotFinalized);
+        }
+        let relocated_trace = self.relocated_trace.as_ref().ok_or(Error::NotRelocated)?;
+        let relocated_memory = self.relocated_memory.as_ref().ok_or(Error::NotRelocated)?;
+        let segment_offsets = self.segment_offsets.as_ref().ok_or(Error::NotRelocated)?;
+        let prime = self.program.prime();
+
+        let (rc_min, rc_max) = self
+            .get_perm_range_check_limits()?
+            .ok_or(Error::UnexpectedNoneValue)?;
+
+        let (first, last) = match (relocated_trace.first(), relocated_trace.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Error::UnexpectedNoneValue),
+        };
+        let mut memory_segments = BTreeMap::new();
+        memory_segments.insert(
+            String::from("program"),
+            MemorySegmentAddresses {
+                begin_addr: relocated_addr(&first.pc)?,
+                stop_ptr: relocated_addr(&last.pc)?,
+            },
+        );
+        memory_segments.insert(
+            String::from("execution"),
+            MemorySegmentAddresses {
+                begin_addr: relocated_addr(&first.ap)?,
+                stop_ptr: relocated_addr(&last.ap)?,
+            },
+        );
+        for (name, addresses) in self.get_memory_segment_addresses()? {
+            let relocate = |addr: RelocatableValue| {
+                relocate_value(&addr.into(), segment_offsets, prime)
+                    .map_err(Error::from)
+                    .and_then(|addr| relocated_addr(&addr))
+            };
+            memory_segments.insert(
+                name,
+                MemorySegmentAddresses {
+                    begin_addr: relocate(addresses.begin_addr)?,
+                    stop_ptr: relocate(addresses.stop_ptr)?,
+                },
+            );
+        }
+
+        let mut public_memory = vec![];
+        for (address, page) in self
+            .segments
+            .borrow()
+            .get_public_memory_addresses(segment_offsets)?
+        {
+            let value = usize::try_from(&address)
+                .ok()
+                .and_then(|addr| relocated_memory.get(addr).cloned().flatten())
+                .ok_or_else(|| {
+                    Error::MemoryDictError(MemoryDictError::UnknownMemory {
+                        addr: address.clone().into(),
+                    })
+                })?;
+            public_memory.push(PublicMemoryEntry {
+                address: relocated_addr(&address)?,
+                value,
+                page: relocated_addr(&page)?,
+            });
+        }
+
+        Ok(PublicInput {
+            layout: self.instance.layout_name.to_owned(),
+            rc_min,
+            rc_max,
+            n_steps: relocated_trace.len() as u64,
+            memory_segments,
+            public_memory,
+        })
+    }
+
+    /// Relocates the memory and the trace into plain integers. The results are stored in
+    /// `relocated_memory` and `relocated_trace`.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn relocate(&mut self) -> Result<(), Error> {
+        self.check_run_ended()?;
+        let segment_offsets = self.segments.borrow().relocate_segments()?;
+        let prime = self.program.prime();
+
+        let mut relocated_memory: Vec<Option<BigInt>> = vec![];
+        for (addr, value) in self.memory.borrow().data.iter() {
+            let addr = relocate_value(addr, &segment_offsets, prime)?;
+            let addr = usize::try_from(&addr).map_err(|_| Error::RelocatedValueTooLarge {
+                value: addr.clone(),
+                n_bytes: std::mem::size_of::<usize>(),
+            })?;
+
+            if addr >= relocated_memory.len() {
+                relocated_memory.resize(addr + 1, None);
+            }
+            relocated_memory[addr] = Some(relocate_value(value, &segment_offsets, prime)?);
+        }
+
+        let relocated_trace = relocate_trace(&self.vm()?.trace, &segment_offsets, prime)?;
+
+        // TODO: implement the following Python code
+        //
+        // ```python
+        // for builtin_runner in self.builtin_runners.values():
+        //     builtin_runner.relocate(self.relocate_value)
+        // ```
+
+        self.segment_offsets = Some(segment_offsets);
+        self.relocated_memory = Some(relocated_memory);
+        self.relocated_trace = Some(relocated_trace);
+
+        Ok(())
+    }
+
+    /// Writes the files consumed by the cairo-lang tracer into `dir`:
+    /// * `trace.json` - the relocated trace, as a list of `{"pc", "ap", "fp"}` objects.
+    /// * `memory.json` - the relocated memory, as a map from address to hex value.
+    /// * `air_public_input.json` - a stub AIR public input holding the layout, the number of
+    ///   steps and the public memory, which the tracer uses to highlight public cells.
+    ///
+    /// The program is not written, as only the original artifact contains the debug info used by
+    /// the tracer to map instructions to source code. It should be copied as `program.json`.
+    ///
+    /// Note: relocate() must precede a call to this method.
+    pub fn write_tracer_data<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        let relocated_trace = self.relocated_trace.as_ref().ok_or(Error::NotRelocated)?;
+        let relocated_memory = self.relocated_memory.as_ref().ok_or(Error::NotRelocated)?;
+
+        std::fs::create_dir_all(dir)?;
+
+        let mut trace = vec![];
+        for entry in relocated_trace.iter() {
+            trace.push(serde_json::json!({
+                "pc": json_addr(&entry.pc)?,
+                "ap": json_addr(&entry.ap)?,
+                "fp": json_addr(&entry.fp)?,
+            }));
+        }
+        write_json(dir.join("trace.json"), &trace)?;
+
+        let memory = relocated_memory
+            .iter()
+            .enumerate()
+            .filter_map(|(addr, value)| {
+                value
+                    .as_ref()
+                    .map(|value| (addr.to_string(), format!("{:#x}", value).into()))
+            })
+            .collect::<serde_json::Map<_, _>>();
+        write_json(dir.join("memory.json"), &memory)?;
+
+        let segment_offsets = self.segments.borrow().relocate_segments()?;
+        let execution_base = relocate_value(
+            &self.execution_base()?.to_owned().into(),
+            &segment_offsets,
+            self.program.prime(),
+        )?;
+        let mut public_memory = vec![];
+        for offset in self.execution_public_memory.iter().flatten() {
+            let address = &execution_base + offset;
+            let value = usize::try_from(&address)
+                .ok()
+                .and_then(|addr| relocated_memory.get(addr).cloned().flatten());
+            public_memory.push(serde_json::json!({
+                "address": json_addr(&address)?,
+                "value": value.map(|value| format!("{:#x}", value)),
+                "page": 0,
+            }));
+        }
+        write_json(
+            dir.join("air_public_input.json"),
+            &serde_json::json!({
+                "layout": self.instance.layout_name,
+                "n_steps": json_addr(&self.vm()?.current_step)?,
+                "public_memory": public_memory,
+            }),
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the execution trace with pc, ap and fp relocated into their final addresses.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn relocate_trace(&self) -> Result<Vec<TraceEntry<BigInt>>, Error> {
+        self.check_run_ended()?;
+        let segment_offsets = self.segments.borrow().relocate_segments()?;
+        Ok(relocate_trace(
+            &self.vm()?.trace,
+            &segment_offsets,
+            self.program.prime(),
+        )?)
+    }
+
+    /// Relocates the execution trace and writes it to `writer` in the binary format consumed by
+    /// provers, entry by entry and without building the relocated trace in memory first. Each
+    /// entry is written as the little-endian 8-byte encodings of ap, fp and pc, in that order.
+    /// Fails with `RelocatedValueTooLarge` if a register does not fit into 8 bytes.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn relocate_and_stream_trace<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.check_run_ended()?;
+        let segment_offsets = self.segments.borrow().relocate_segments()?;
+        let prime = self.program.prime();
+
+        for entry in self.vm()?.trace.iter() {
+            let entry = entry.relocate(&segment_offsets, prime)?;
+            write_le_bytes(writer, &entry.ap, ADDR_SIZE_IN_BYTES)?;
+            write_le_bytes(writer, &entry.fp, ADDR_SIZE_IN_BYTES)?;
+            write_le_bytes(writer, &entry.pc, ADDR_SIZE_IN_BYTES)?;
+        }
+
+        Ok(())
+    }
+
+    /// Same as `relocate_and_stream_trace`.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn write_binary_trace<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.check_run_ended()?;
+        self.relocate_and_stream_trace(writer)
+    }
+
+    /// Relocates the execution trace and writes it to `writer` as CSV, with a `pc,ap,fp` header
+    /// followed by one row per step.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn write_trace_csv<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.check_run_ended()?;
+        let segment_offsets = self.segments.borrow().relocate_segments()?;
+        let prime = self.program.prime();
+
+        writeln!(writer, "pc,ap,fp")?;
+        for entry in self.vm()?.trace.iter() {
+            let entry = entry.relocate(&segment_offsets, prime)?;
+            writeln!(writer, "{},{},{}", entry.pc, entry.ap, entry.fp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Relocates the memory and writes it to `writer` in the binary format consumed by provers,
+    /// cell by cell in ascending address order and without building the relocated memory in
+    /// memory first. Each cell is written as the little-endian 8-byte encoding of its address,
+    /// followed by the little-endian encoding of its value using as many bytes as needed for a
+    /// field element.
+    ///
+    /// Fails with `UnrelocatedValue` if a cell or its value still points into a segment without a
+    /// relocation offset (e.g. a temporary segment that was never relocated).
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn relocate_and_stream_memory<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.check_run_ended()?;
+        let segments = self.segments.borrow();
+        let segment_offsets = segments.relocate_segments()?;
+        let prime = self.program.prime();
+        let field_bytes = prime.bits().div_ceil(8) as usize;
+
+        let mut memory = self.memory.borrow_mut();
+
+        // Cells of segments without an offset would otherwise be skipped silently.
+        let unrelocated = memory.data.iter().find(|(addr, _)| match addr {
+            MaybeRelocatable::RelocatableValue(addr) => {
+                !segment_offsets.contains_key(&addr.segment_index)
+            }
+            MaybeRelocatable::Int(_) => false,
+        });
+        if let Some((addr, value)) = unrelocated {
+            return Err(Error::UnrelocatedValue {
+                addr: addr.to_owned(),
+                value: value.to_owned(),
+            });
+        }
+
+        for segment_index in 0..segments.n_segments {
+            // Offsets beyond usize::MAX can't be addressed, let alone hold values.
+            let segment_size =
+                usize::try_from(&segments.get_segment_size(segment_index)?).unwrap_or(usize::MAX);
+
+            for offset in 0..segment_size {
+                let addr: MaybeRelocatable = RelocatableValue::new(segment_index, offset).into();
+                let value = match memory.data.get(&addr).cloned() {
+                    Some(value) => memory.relocate_value(value),
+                    None => continue,
+                };
+                if let MaybeRelocatable::RelocatableValue(relocatable) = &value {
+                    if !segment_offsets.contains_key(&relocatable.segment_index) {
+                        return Err(Error::UnrelocatedValue { addr, value });
+                    }
+                }
+
+                write_le_bytes(
+                    writer,
+                    &relocate_value(&addr, &segment_offsets, prime)?,
+                    ADDR_SIZE_IN_BYTES,
+                )?;
+                write_le_bytes(
+                    writer,
+                    &relocate_value(&value, &segment_offsets, prime)?,
+                    field_bytes,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Same as `relocate_and_stream_memory`.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn write_binary_memory<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.check_run_ended()?;
+        self.relocate_and_stream_memory(writer)
+    }
+
+    /// Returns the segment aliases that can be used in user-supplied addresses: `program`,
+    /// `execution` and the builtin segments (e.g. `output_builtin`).
+    pub fn address_context(&self) -> Result<AddressContext, Error> {
+        let mut context = AddressContext::new()
+            .with_alias("program", self.program_base()?.segment_index)
+            .with_alias("execution", self.execution_base()?.segment_index);
+
+        for (name, builtin_runner) in self.builtin_runners.borrow().iter() {
+            if let Some(base) = builtin_runner.segment_base() {
+                context = context.with_alias(name, base.segment_index);
+            }
+        }
+
+        Ok(context)
+    }
+
+    /// Returns the initial values of the pc, ap and fp registers, as set up by the entrypoint
+    /// initialization.
+    pub fn get_initial_registers(
+        &self,
+    ) -> Result<(RelocatableValue, RelocatableValue, RelocatableValue), Error> {
+        Ok((
+            self.initial_pc()?.to_owned(),
+            self.initial_ap()?.to_owned(),
+            self.initial_fp()?.to_owned(),
+        ))
+    }
+
+    /// Looks up the pc of the label (or function) `label` from the main scope, or by its full name.
+    fn get_label_pc(&self, label: &str) -> Result<BigInt, Error> {
+        let missing_label = || Error::MissingLabel {
+            label: label.to_owned(),
+        };
+
+        // Stripped programs don't have identifiers.
+        let program = match self.program.as_ref() {
+            Program::Full(program) => program,
+            Program::Stripped(_) => return Err(missing_label()),
+        };
+        label
+            .parse::<ScopedName>()
+            .ok()
+            .and_then(|name| {
+                program
+                    .get_label(name.clone(), false)
+                    .or_else(|| program.get_label(name, true))
+            })
+            .ok_or_else(missing_label)
+    }
+
+    fn program_base(&self) -> Result<&RelocatableValue, Error> {
+        self.program_base
+            .as_ref()
+            .ok_or(Error::SegmentsNotInitialized)
+    }
+
+    fn execution_base(&self) -> Result<&RelocatableValue, Error> {
+        self.execution_base
+            .as_ref()
+            .ok_or(Error::SegmentsNotInitialized)
+    }
+
+    fn final_pc(&self) -> Result<&RelocatableValue, Error> {
+        self.final_pc
+            .as_ref()
+            .ok_or(Error::FunctionEntrypointNotInitialized)
+    }
+
+    fn initial_pc(&self) -> Result<&RelocatableValue, Error> {
+        self.initial_pc.as_ref().ok_or(Error::StateNotInitialized)
+    }
+
+    fn initial_ap(&self) -> Result<&RelocatableValue, Error> {
+        self.initial_ap.as_ref().ok_or(Error::StateNotInitialized)
+    }
+
+    fn initial_fp(&self) -> Result<&RelocatableValue, Error> {
+        self.initial_fp.as_ref().ok_or(Error::StateNotInitialized)
+    }
+
+    /// Returns an error unless the run was ended and finalized by end_run() or finalize_all().
+    fn check_run_ended(&self) -> Result<(), Error> {
+        if self.run_ended {
+            Ok(())
+        } else if self.vm_run_ended {
+            Err(Error::RunNotFinalized)
+        } else {
+            Err(Error::RunNotEnded)
+        }
+    }
+
+    fn vm(&self) -> Result<&VirtualMachine, Error> {
+        self.vm.as_ref().ok_or(Error::VmNotInitialized)
+    }
+
+    fn vm_mut(&mut self) -> Result<&mut VirtualMachine, Error> {
+        self.vm.as_mut().ok_or(Error::VmNotInitialized)
+    }
+}
+
+impl From<MemoryDictError> for Error {
+    fn from(value: MemoryDictError) -> Self {
+        Self::MemoryDictError(value)
+    }
+}
+
+impl From<MemorySegmentError> for Error {
+    fn from(value: MemorySegmentError) -> Self {
         Self::MemorySegmentError(value)
     }
 }
 
-impl From<VirtualMachineError> for Error {
-    fn from(value: VirtualMachineError) -> Self {
-        Self::VirtualMachineError(value)
-    }
-}
+impl From<BuiltinRunnerError> for Error {
+    fn from(value: BuiltinRunnerError) -> Self {
+        Self::BuiltinRunnerError(value)
+    }
+}
+
+impl From<SubtractionError> for Error {
+    fn from(value: SubtractionError) -> Self {
+        Self::SubtractionError(value)
+    }
+}
+
+impl From<RelocationError> for Error {
+    fn from(value: RelocationError) -> Self {
+        Self::RelocationError(value)
+    }
+}
+
+impl From<InstructionDecodeError> for Error {
+    fn from(value: InstructionDecodeError) -> Self {
+        Self::InstructionDecodeError(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<OutputError> for Error {
+    fn from(value: OutputError) -> Self {
+        match value {
+            OutputError::ValueTooLarge { value, n_bytes } => {
+                Self::RelocatedValueTooLarge { value, n_bytes }
+            }
+            OutputError::Io(value) => Self::Io(value),
+        }
+    }
+}
+
+/// Converts a relocated address into a JSON number.
+fn json_addr(value: &BigInt) -> Result<serde_json::Value, Error> {
+    relocated_addr(value).map(serde_json::Value::from)
+}
+
+fn relocated_addr(value: &BigInt) -> Result<u64, Error> {
+    u64::try_from(value).map_err(|_| Error::RelocatedValueTooLarge {
+        value: value.to_owned(),
+        n_bytes: 8,
+    })
+}
+
+fn write_json<P: AsRef<Path>, T: serde::Serialize>(path: P, value: &T) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn output_builtin_factory(
+    _name: &str,
+    included: bool,
+    _definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    Ok(Box::new(OutputBuiltinRunner::new(included)))
+}
+
+#[cfg(feature = "builtin-pedersen")]
+fn pedersen_builtin_factory(
+    name: &str,
+    included: bool,
+    definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    match definition {
+        BuiltinDefinition::PedersenInstanceDef(instance_def) => Ok(Box::new(
+            HashBuiltinRunner::new(name, included, instance_def.ratio),
+        )),
+        _ => Err(Error::UnexpectedBuiltinType),
+    }
+}
+
+#[cfg(not(feature = "builtin-pedersen"))]
+fn pedersen_builtin_factory(
+    name: &str,
+    _included: bool,
+    _definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    Err(Error::BuiltinNotCompiled {
+        name: name.to_owned(),
+        feature: "builtin-pedersen",
+    })
+}
+
+fn range_check_builtin_factory(
+    name: &str,
+    included: bool,
+    definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    match definition {
+        BuiltinDefinition::RangeCheckInstanceDef(instance_def) => Ok(Box::new(
+            RangeCheckBuiltinRunner::new(name, included, instance_def.ratio, instance_def.n_parts),
+        )),
+        _ => Err(Error::UnexpectedBuiltinType),
+    }
+}
+
+#[cfg(feature = "builtin-ecdsa")]
+fn ecdsa_builtin_factory(
+    name: &str,
+    included: bool,
+    definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    match definition {
+        BuiltinDefinition::EcdsaInstanceDef(instance_def) => Ok(Box::new(
+            SignatureBuiltinRunner::new(name, included, instance_def.ratio),
+        )),
+        _ => Err(Error::UnexpectedBuiltinType),
+    }
+}
+
+#[cfg(not(feature = "builtin-ecdsa"))]
+fn ecdsa_builtin_factory(
+    name: &str,
+    _included: bool,
+    _definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    Err(Error::BuiltinNotCompiled {
+        name: name.to_owned(),
+        feature: "builtin-ecdsa",
+    })
+}
+
+fn bitwise_builtin_factory(
+    name: &str,
+    included: bool,
+    definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    match definition {
+        BuiltinDefinition::BitwiseInstanceDef(instance_def) => {
+            Ok(Box::new(BitwiseBuiltinRunner::new(
+                name,
+                included,
+                instance_def.ratio,
+                instance_def.total_n_bits,
+            )))
+        }
+        _ => Err(Error::UnexpectedBuiltinType),
+    }
+}
+
+/// The factory of builtins that layouts define but that are not implemented yet.
+fn unsupported_builtin_factory(
+    name: &str,
+    _included: bool,
+    _definition: &BuiltinDefinition,
+) -> Result<Box<dyn BuiltinRunner>, Error> {
+    Err(Error::BuiltinNotSupported {
+        name: name.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        cairo::lang::{
+            builtins::{
+                bitwise::instance_def::BitwiseInstanceDef,
+                range_check::instance_def::RangeCheckInstanceDef,
+            },
+            compiler::program::FullProgram,
+            vm::{
+                memory_dict::{MemoryAccess, MemoryAccessKind},
+                memory_segments::Arg,
+                unreached_report::{UnreachedFunction, UnreachedHint},
+                vm_core::{InstructionCache, RunContextError},
+            },
+        },
+        hint_support::{program_input_locals, VecHintOutputSink},
+    };
+
+    use serde_json::value::RawValue;
+
+    #[cfg(feature = "builtin-ecdsa")]
+    use crate::crypto::signature::signature::private_key_to_ec_point_on_stark_curve;
+
+    #[test]
+    fn test_builtin_features() {
+        fn new_runner(builtin: &str) -> Result<CairoRunner, Error> {
+            let mut program = serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/run_past_end.json"
+            ))
+            .unwrap();
+            program.builtins = vec![builtin.to_owned()];
+            CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+        }
+
+        let result = new_runner("pedersen");
+        #[cfg(feature = "builtin-pedersen")]
+        assert!(result.is_ok());
+        #[cfg(not(feature = "builtin-pedersen"))]
+        assert!(matches!(
+            result,
+            Err(Error::BuiltinNotCompiled {
+                feature: "builtin-pedersen",
+                ..
+            })
+        ));
+
+        let result = new_runner("ecdsa");
+        #[cfg(feature = "builtin-ecdsa")]
+        assert!(result.is_ok());
+        #[cfg(not(feature = "builtin-ecdsa"))]
+        assert!(matches!(
+            result,
+            Err(Error::BuiltinNotCompiled {
+                feature: "builtin-ecdsa",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        let instance = CairoLayout::custom(
+            "range_check_only",
+            vec![(
+                String::from("range_check"),
+                BuiltinDefinition::RangeCheckInstanceDef(RangeCheckInstanceDef {
+                    ratio: 8,
+                    n_parts: 8,
+                }),
+            )],
+            16u32.into(),
+        );
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/range_check.json"),
+            instance,
+        );
+        assert!(runner
+            .builtin_runners
+            .borrow()
+            .contains_key(&builtin_runner_key("range_check")));
+        assert_eq!(
+            runner.get_execution_resources().unwrap().n_steps,
+            BigInt::from(4u32)
+        );
+
+        // A bitwise-only layout runs a program using the bitwise builtin, whose outputs are
+        // deduced from the inputs.
+        let instance = CairoLayout::custom(
+            "bitwise_only",
+            vec![(
+                String::from("bitwise"),
+                BuiltinDefinition::BitwiseInstanceDef(BitwiseInstanceDef {
+                    ratio: 8,
+                    total_n_bits: 251,
+                }),
+            )],
+            4u32.into(),
+        );
+        assert!(instance.diluted_pool_instance_def.is_some());
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bitwise.json"),
+            instance,
+        );
+        let base = runner.builtin_runners.borrow()[&builtin_runner_key("bitwise")]
+            .segment_base()
+            .unwrap();
+        for (offset, value) in [(2usize, 12 & 10), (3, 12 ^ 10), (4, 12 | 10)] {
+            assert_eq!(
+                runner
+                    .memory
+                    .borrow_mut()
+                    .index(&(base + offset).into())
+                    .unwrap(),
+                MaybeRelocatable::Int(BigInt::from(value))
+            );
+        }
+        assert_eq!(
+            runner
+                .get_execution_resources()
+                .unwrap()
+                .builtin_instance_counter["bitwise_builtin"],
+            BigInt::from(1u32)
+        );
+        assert_eq!(
+            runner.get_air_private_input().unwrap()["bitwise"],
+            vec![serde_json::json!({"index": 0, "x": "0xc", "y": "0xa"})]
+        );
+
+        // The inputs must fit in total_n_bits bits.
+        let instance = CairoLayout::custom(
+            "bitwise_only",
+            vec![(
+                String::from("bitwise"),
+                BuiltinDefinition::BitwiseInstanceDef(BitwiseInstanceDef {
+                    ratio: 8,
+                    total_n_bits: 3,
+                }),
+            )],
+            4u32.into(),
+        );
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/bitwise.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            instance,
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        match runner.run_until_pc(end.into(), None) {
+            Err(Error::VmError(exc)) => assert!(matches!(
+                *exc.inner_exc,
+                VirtualMachineError::BuiltinRunnerError(
+                    BuiltinRunnerError::BitwiseInputOutOfRange {
+                        total_n_bits: 3,
+                        ..
+                    }
+                )
+            )),
+            _ => panic!("unexpected result"),
+        }
+
+        // Builtins that are not implemented yet can't be used in a custom layout either.
+        let instance = CairoLayout::custom(
+            "ec_op_only",
+            vec![(
+                String::from("ec_op"),
+                CairoLayout::all_cairo_instance().builtins["ec_op"].clone(),
+            )],
+            4u32.into(),
+        );
+        let mut program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+        program.builtins = vec![String::from("ec_op")];
+        assert!(matches!(
+            CairoRunner::new(
+                Rc::new(program.into()),
+                instance,
+                MemoryDict::new(),
+                false,
+                false,
+            ),
+            Err(Error::BuiltinNotSupported { name }) if name == "ec_op"
+        ));
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/output.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        // The run is not ended yet.
+        assert!(matches!(
+            runner.read_return_values(),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(runner.relocate(), Err(Error::RunNotEnded)));
+        assert!(matches!(runner.finalize_all(), Err(Error::RunNotEnded)));
+        assert!(matches!(
+            runner.finalize_segments(),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(
+            runner.get_memory_segment_addresses(),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(
+            runner.get_air_private_input(),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(runner.relocate_trace(), Err(Error::RunNotEnded)));
+        assert!(matches!(
+            runner.relocate_and_stream_trace(&mut vec![]),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(
+            runner.write_binary_trace(&mut vec![]),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(
+            runner.write_trace_csv(&mut vec![]),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(
+            runner.relocate_and_stream_memory(&mut vec![]),
+            Err(Error::RunNotEnded)
+        ));
+        assert!(matches!(
+            runner.write_binary_memory(&mut vec![]),
+            Err(Error::RunNotEnded)
+        ));
+
+        // The run is ended without being finalized.
+        runner.end_run(false, true).unwrap();
+        assert!(matches!(
+            runner.end_run(false, true),
+            Err(Error::EndRunCalledTwice)
+        ));
+        assert!(matches!(
+            runner.end_run(false, false),
+            Err(Error::EndRunCalledTwice)
+        ));
+        assert!(matches!(
+            runner.read_return_values(),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(runner.relocate(), Err(Error::RunNotFinalized)));
+        assert!(matches!(
+            runner.finalize_segments(),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.get_memory_segment_addresses(),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.get_air_private_input(),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.relocate_trace(),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.relocate_and_stream_trace(&mut vec![]),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.write_binary_trace(&mut vec![]),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.write_trace_csv(&mut vec![]),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.relocate_and_stream_memory(&mut vec![]),
+            Err(Error::RunNotFinalized)
+        ));
+        assert!(matches!(
+            runner.write_binary_memory(&mut vec![]),
+            Err(Error::RunNotFinalized)
+        ));
+
+        // The run and the segments are finalized.
+        runner.finalize_all().unwrap();
+        runner.finalize_all().unwrap();
+        runner.finalize_segments().unwrap();
+        assert!(runner.run_ended);
+        assert!(runner.segments_finalized);
+        assert!(matches!(
+            runner.read_return_values(),
+            Err(Error::CannotAddReturnValuesAfterSegmentFinalization)
+        ));
+        assert!(matches!(
+            runner.end_run(false, false),
+            Err(Error::EndRunCalledTwice)
+        ));
+        runner.relocate().unwrap();
+    }
+
+    #[test]
+    fn test_finalize_segments() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/output.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+        runner.read_return_values().unwrap();
+        runner.finalize_all().unwrap();
+
+        let segments = runner.segments.borrow();
+        let program_segment = &runner.program_base().unwrap().segment_index;
+        assert_eq!(
+            segments.public_memory_offsets[program_segment].len(),
+            runner.program.data().len()
+        );
+
+        // The output builtin segment holds a single public cell.
+        let output_segment = runner.builtin_runners.borrow()["output_builtin"]
+            .segment_base()
+            .unwrap()
+            .segment_index;
+        assert_eq!(
+            segments.public_memory_offsets[&output_segment],
+            vec![[BigInt::from(0u32), BigInt::from(0u32)]]
+        );
+        assert_eq!(segments.segment_sizes[&output_segment], BigInt::from(1u32));
+    }
+
+    #[test]
+    fn test_public_memory_addresses() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/output.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+        runner.read_return_values().unwrap();
+
+        {
+            let mut builtin_runners = runner.builtin_runners.borrow_mut();
+            let output_runner = builtin_runners
+                .get_mut("output_builtin")
+                .unwrap()
+                .as_any_mut()
+                .downcast_mut::<OutputBuiltinRunner>()
+                .unwrap();
+            let output_base = output_runner.base.unwrap();
+
+            output_runner
+                .add_page(1u32.into(), output_base.into(), 1u32.into())
+                .unwrap();
+            assert!(matches!(
+                output_runner.add_page(
+                    1u32.into(),
+                    (output_base + &BigInt::from(1u32)).into(),
+                    1u32.into()
+                ),
+                Err(BuiltinRunnerError::PageAlreadyUsed { .. })
+            ));
+            assert!(matches!(
+                output_runner.add_page(2u32.into(), output_base.into(), 2u32.into()),
+                Err(BuiltinRunnerError::OverlappingPages { .. })
+            ));
+            assert!(matches!(
+                output_runner.add_page(
+                    2u32.into(),
+                    runner.execution_base.unwrap().into(),
+                    1u32.into()
+                ),
+                Err(BuiltinRunnerError::PageOutsideOutputSegment { .. })
+            ));
+        }
+
+        runner.finalize_all().unwrap();
+
+        // The program (relocated to 1-6), the return value of main() at execution offset 4
+        // (relocated to 11), and the output cell (relocated to 12) in page 1, as listed by
+        // cairo-lang.
+        let segments = runner.segments.borrow();
+        let segment_offsets = segments.relocate_segments().unwrap();
+        let mut expected = (1u32..=6)
+            .map(|addr| (BigInt::from(addr), BigInt::from(0u32)))
+            .collect::<Vec<_>>();
+        expected.push((BigInt::from(11u32), BigInt::from(0u32)));
+        expected.push((BigInt::from(12u32), BigInt::from(1u32)));
+        assert_eq!(
+            segments
+                .get_public_memory_addresses(&segment_offsets)
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_end_of_program_not_reached() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/countdown.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        // Runs out of steps in the loop body.
+        match runner.run_until_pc(
+            end.into(),
+            Some(RunResources {
+                n_steps: Some(2u32.into()),
+            }),
+        ) {
+            Err(Error::VmError(exc)) => {
+                assert_eq!(
+                    exc.to_string(),
+                    "Error at pc=0:5:\n\
+                    End of program was not reached: stopped at pc=0:5 before reaching pc=3:0."
+                );
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_instruction_cache() {
+        let run = |cached: bool| {
+            let mut runner = CairoRunner::new(
+                Rc::new(
+                    serde_json::from_str::<FullProgram>(include_str!(
+                        "../../../../test-data/artifacts/countdown.json"
+                    ))
+                    .unwrap()
+                    .into(),
+                ),
+                CairoLayout::plain_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+
+            runner.initialize_segments();
+            let end = runner.initialize_main_entrypoint().unwrap();
+            runner.initialize_vm(HashMap::new(), ()).unwrap();
+            if !cached {
+                runner.vm_mut().unwrap().instruction_cache = InstructionCache::default();
+            }
+            runner.run_until_pc(end.into(), None).unwrap();
+            runner.end_run(false, false).unwrap();
+            runner.relocate().unwrap();
+
+            let initial_pc = runner.initial_pc.unwrap().into();
+            assert_eq!(
+                runner
+                    .vm()
+                    .unwrap()
+                    .instruction_cache
+                    .get(&initial_pc)
+                    .is_some(),
+                cached
+            );
+
+            (runner.relocated_trace, runner.relocated_memory)
+        };
+
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    fn test_run_from_entrypoint() {
+        fn new_runner(program: &str) -> CairoRunner {
+            CairoRunner::new(
+                Rc::new(serde_json::from_str::<FullProgram>(program).unwrap().into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap()
+        }
+
+        fn return_value(runner: &CairoRunner) -> MaybeRelocatable {
+            let ap = runner.vm().unwrap().run_context.borrow().ap.clone();
+            runner
+                .memory
+                .borrow_mut()
+                .index(&(ap - &BigInt::from(1).into()))
+                .unwrap()
+        }
+
+        // array_sum() is not main().
+        let mut runner = new_runner(include_str!(
+            "../../../../test-data/artifacts/array_sum.json"
+        ));
+        runner.initialize_segments();
+        let arr = runner
+            .segments
+            .borrow_mut()
+            .gen_arg(
+                Arg::Array(vec![
+                    Arg::Int(BigInt::from(4)),
+                    Arg::Int(BigInt::from(5)),
+                    Arg::Int(BigInt::from(6)),
+                ]),
+                false,
+            )
+            .unwrap();
+        runner
+            .run_from_entrypoint(
+                &BigInt::from(0),
+                &[arr, BigInt::from(3).into()],
+                HashMap::new(),
+                (),
+            )
+            .unwrap();
+        assert_eq!(
+            return_value(&runner),
+            MaybeRelocatable::from(BigInt::from(15))
+        );
+
+        // Builtin pointers are passed like any other argument.
+        let mut runner = new_runner(include_str!("../../../../test-data/artifacts/output.json"));
+        runner.initialize_segments();
+        let output_ptr = runner.builtin_runners.borrow()["output_builtin"].initial_stack();
+        let main = runner.program.main().unwrap();
+        runner
+            .run_from_entrypoint(&main, &output_ptr, HashMap::new(), ())
+            .unwrap();
+        assert_eq!(
+            return_value(&runner),
+            output_ptr[0].clone() + &BigInt::from(1)
+        );
+        assert_eq!(runner.get_int_output().unwrap(), [BigInt::from(7)]);
+    }
+
+    #[test]
+    fn test_run_fragment() {
+        let new_runner = || {
+            let program = serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/countdown.json"
+            ))
+            .unwrap();
+            CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::plain_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap()
+        };
+
+        // Runs the body of the loop as part of main(), from the first iteration.
+        let mut runner = new_runner();
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        let program_base = runner.program_base.unwrap();
+        runner
+            .run_until_pc((program_base + &BigInt::from(5)).into(), None)
+            .unwrap();
+        let ap_before = runner.vm().unwrap().run_context.borrow().ap.clone();
+        runner
+            .run_until_pc((program_base + &BigInt::from(2)).into(), None)
+            .unwrap();
+        let ap_after = runner.vm().unwrap().run_context.borrow().ap.clone();
+        let counter = runner.memory.borrow_mut().index(&ap_before).unwrap();
+        assert_eq!(counter, MaybeRelocatable::from(BigInt::from(2)));
+
+        // Runs the body alone, with the counter as the only precondition.
+        let mut runner = new_runner();
+        runner.initialize_segments();
+        let counter_addr = runner.execution_base.unwrap();
+        let ap = counter_addr + 1usize;
+        let post_state = runner
+            .run_fragment(
+                "main.body",
+                "__main__.main.loop",
+                FragmentState {
+                    ap,
+                    fp: ap,
+                    memory: HashMap::from([(
+                        MaybeRelocatable::from(counter_addr),
+                        MaybeRelocatable::from(BigInt::from(3)),
+                    )]),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            post_state.ap - &MaybeRelocatable::from(ap),
+            ap_after - &ap_before
+        );
+        assert_eq!(post_state.fp, ap);
+        assert_eq!(post_state.memory.len(), 2);
+        assert_eq!(post_state.memory[&MaybeRelocatable::from(ap)], counter);
+
+        let mut runner = new_runner();
+        assert!(matches!(
+            runner.run_fragment(
+                "main.exit",
+                "main.loop",
+                FragmentState {
+                    ap,
+                    fp: ap,
+                    memory: HashMap::new(),
+                },
+            ),
+            Err(Error::MissingLabel { .. })
+        ));
+
+        let mut runner = new_runner();
+        runner.initialize_segments();
+        let program_base = runner.program_base.unwrap();
+        assert!(matches!(
+            runner.run_fragment(
+                "main.body",
+                "main.loop",
+                FragmentState {
+                    ap,
+                    fp: ap,
+                    memory: HashMap::from([(
+                        MaybeRelocatable::from(program_base),
+                        MaybeRelocatable::from(BigInt::from(3)),
+                    )]),
+                },
+            ),
+            Err(Error::PreconditionInProgramSegment { .. })
+        ));
+
+        // Returns from main() to an integer fp, with the counter at zero.
+        let mut runner = new_runner();
+        runner.initialize_segments();
+        let program_base = runner.program_base.unwrap();
+        let execution_base = runner.execution_base.unwrap();
+        let fp = execution_base + 2usize;
+        match runner.run_fragment(
+            "main.loop",
+            "main.body",
+            FragmentState {
+                ap: fp + 1usize,
+                fp,
+                memory: HashMap::from([
+                    (
+                        MaybeRelocatable::from(execution_base),
+                        MaybeRelocatable::from(BigInt::from(7)),
+                    ),
+                    (
+                        MaybeRelocatable::from(execution_base + 1usize),
+                        MaybeRelocatable::from(program_base + &BigInt::from(5)),
+                    ),
+                    (
+                        MaybeRelocatable::from(fp),
+                        MaybeRelocatable::from(BigInt::from(0)),
+                    ),
+                ]),
+            },
+        ) {
+            Err(Error::NonRelocatableFragmentRegister { register, value }) => {
+                assert_eq!(register, "fp");
+                assert_eq!(value, MaybeRelocatable::from(BigInt::from(7)));
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_memory_access_log() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/memory_hole.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.memory.borrow_mut().enable_access_log();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        let access_log = runner.memory.borrow().access_log.clone().unwrap();
+        let ptr = |segment_index: isize, offset: usize| {
+            MaybeRelocatable::from(RelocatableValue::new(segment_index, offset))
+        };
+
+        // Each step starts by reading its instruction.
+        for (step, pc) in [(0u32, 0), (1, 2), (2, 4)] {
+            let first = access_log
+                .iter()
+                .find(|access| access.step == BigInt::from(step))
+                .unwrap();
+            assert_eq!(first.kind, MemoryAccessKind::Read);
+            assert_eq!(first.addr, ptr(0, pc));
+        }
+        assert!(access_log
+            .windows(2)
+            .all(|accesses| accesses[0].step <= accesses[1].step));
+
+        // `[ap] = 5` reads the unknown cell at ap before deducing it and writing 5 to it.
+        let writes = access_log
+            .iter()
+            .enumerate()
+            .filter(|(_, access)| access.kind == MemoryAccessKind::Write)
+            .collect::<Vec<_>>();
+        assert_eq!(writes.len(), 1);
+        let (write_index, write) = writes[0];
+        assert_eq!(
+            write,
+            &MemoryAccess {
+                step: BigInt::from(1u32),
+                kind: MemoryAccessKind::Write,
+                addr: ptr(1, 3),
+                value: Some(BigInt::from(5u32).into()),
+            }
+        );
+        assert!(access_log[..write_index].contains(&MemoryAccess {
+            step: BigInt::from(1u32),
+            kind: MemoryAccessKind::Read,
+            addr: ptr(1, 3),
+            value: None,
+        }));
+    }
+
+    #[test]
+    fn test_state_at_step() {
+        let new_runner = || {
+            let mut runner = CairoRunner::new(
+                Rc::new(
+                    serde_json::from_str::<FullProgram>(include_str!(
+                        "../../../../test-data/artifacts/countdown.json"
+                    ))
+                    .unwrap()
+                    .into(),
+                ),
+                CairoLayout::plain_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+            runner.initialize_segments();
+            let end = runner.initialize_main_entrypoint().unwrap();
+            runner.initialize_vm(HashMap::new(), ()).unwrap();
+            (runner, end)
+        };
+
+        let (mut runner, end) = new_runner();
+        assert!(matches!(
+            runner.state_at_step(0),
+            Err(Error::TimeTravelDisabled)
+        ));
+        runner.enable_time_travel();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        // Counting down from 3 takes 12 steps.
+        let n_steps = runner.vm().unwrap().trace.len();
+        assert_eq!(n_steps, 12);
+
+        for step in 0..=n_steps {
+            let (mut fresh_runner, _) = new_runner();
+            fresh_runner.run_for_steps(step.into()).unwrap();
+
+            let run_context = fresh_runner.vm().unwrap().run_context.borrow().clone();
+            assert_eq!(
+                runner.state_at_step(step).unwrap(),
+                StepState {
+                    registers: TraceEntry {
+                        pc: run_context.pc,
+                        ap: run_context.ap,
+                        fp: run_context.fp,
+                    },
+                    memory: fresh_runner.memory.borrow().data.clone(),
+                },
+                "step {}",
+                step
+            );
+        }
+
+        assert!(matches!(
+            runner.state_at_step(n_steps + 1),
+            Err(Error::StepOutOfRange {
+                step: 13,
+                current_step: 12
+            })
+        ));
+    }
+
+    #[test]
+    fn test_run_stripped_program() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                Program::load_stripped(include_str!(
+                    "../../../../test-data/artifacts/output.stripped.json"
+                ))
+                .unwrap(),
+            ),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        let summary = runner.run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.output, vec![BigInt::from(7u32)]);
+        assert!(matches!(
+            runner.get_memory_at_label("main", 0),
+            Err(Error::MissingLabel { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unreached_report() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/unreached.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.run_until_end(HashMap::new()).unwrap();
+
+        // `helper()` and its hint are never reached, unlike `main()` and its hint.
+        let report = runner.unreached_report().unwrap();
+        assert_eq!(
+            report.hints,
+            vec![UnreachedHint {
+                pc: BigInt::from(0),
+                index: 0,
+                first_line: String::from("value = 1"),
+            }]
+        );
+        assert_eq!(
+            report.functions,
+            vec![UnreachedFunction {
+                name: "__main__.helper".parse().unwrap(),
+                pc: BigInt::from(0),
+            }]
+        );
+        assert_eq!(
+            report.to_string(),
+            "Unreached hints: 1\n  \
+            pc=0 #0: value = 1\n\
+            Unreached functions: 1\n  \
+            pc=0: __main__.helper\n"
+        );
+    }
+
+    #[test]
+    fn test_get_memory_at_label() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/output.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.run_until_end(HashMap::new()).unwrap();
+
+        // The immediate of `[ap] = 7; ap++`, the first instruction of `main`.
+        assert_eq!(
+            runner.get_memory_at_label("main", 1).unwrap(),
+            Some(BigInt::from(7u32).into())
+        );
+        assert_eq!(
+            runner.get_memory_at_label("__main__.main", 1).unwrap(),
+            Some(BigInt::from(7u32).into())
+        );
+        assert_eq!(runner.get_memory_at_label("main", 100).unwrap(), None);
+        assert!(matches!(
+            runner.get_memory_at_label("foo", 0),
+            Err(Error::MissingLabel { label }) if label == "foo"
+        ));
+    }
+
+    #[test]
+    fn test_assert_clean_halt() {
+        let new_runner = |program: &str, instance| {
+            CairoRunner::new(
+                Rc::new(serde_json::from_str::<FullProgram>(program).unwrap().into()),
+                instance,
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap()
+        };
+        let run_past_end = include_str!("../../../../test-data/artifacts/run_past_end.json");
+        let output = include_str!("../../../../test-data/artifacts/output.json");
+
+        let mut runner = new_runner(run_past_end, CairoLayout::plain_instance());
+        runner.run_until_end(HashMap::new()).unwrap();
+        runner.assert_clean_halt().unwrap();
+
+        let mut runner = new_runner(output, CairoLayout::small_instance());
+        runner.run_until_end(HashMap::new()).unwrap();
+        runner.assert_clean_halt().unwrap();
+
+        let mut runner = new_runner(output, CairoLayout::small_instance());
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        assert!(matches!(
+            runner.assert_clean_halt(),
+            Err(Error::RunNotEnded)
+        ));
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+        assert!(matches!(
+            runner.assert_clean_halt(),
+            Err(Error::ReturnValuesNotRead)
+        ));
+
+        // Ending the run before main() returns.
+        let mut runner = new_runner(run_past_end, CairoLayout::plain_instance());
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.end_run(false, false).unwrap();
+        runner.read_return_values().unwrap();
+        match runner.assert_clean_halt() {
+            Err(Error::VmError(exc)) => match *exc.inner_exc {
+                VirtualMachineError::EndOfProgramNotReached { target, .. } => {
+                    assert_eq!(target, end.into())
+                }
+                _ => panic!("unexpected error"),
+            },
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        // main() calls array_sum() on 3 elements, which recurses down to an empty array.
+        let new_runner = |max_call_depth| {
+            let mut runner = CairoRunner::new(
+                Rc::new(
+                    serde_json::from_str::<FullProgram>(include_str!(
+                        "../../../../test-data/artifacts/array_sum.json"
+                    ))
+                    .unwrap()
+                    .into(),
+                ),
+                CairoLayout::plain_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+            runner.max_call_depth = max_call_depth;
+            runner
+        };
+
+        match new_runner(Some(2)).run_until_end(HashMap::new()) {
+            Err(Error::VmError(exc)) => assert!(matches!(
+                *exc.inner_exc,
+                VirtualMachineError::CallDepthExceeded { depth: 3, .. }
+            )),
+            _ => panic!("unexpected result"),
+        }
+
+        let summary = new_runner(Some(4)).run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.peak_call_depth, 4);
+
+        let mut runner = new_runner(None);
+        let summary = runner.run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.peak_call_depth, 4);
+        assert_eq!(runner.vm.as_ref().unwrap().call_depth, 0);
+    }
+
+    #[test]
+    fn test_run_until_end() {
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/run_past_end.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        let summary = runner.run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.steps, BigInt::from(1u32));
+        assert_eq!(summary.output, vec![]);
+        assert_eq!(summary.execution_resources.n_steps, BigInt::from(1u32));
+        assert_eq!(&summary.final_pc, runner.final_pc().unwrap());
+
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/output.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        let summary = runner.run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.output, vec![BigInt::from(7u32)]);
+        assert_eq!(
+            summary
+                .execution_resources
+                .builtin_instance_counter
+                .get("output_builtin"),
+            Some(&BigInt::from(1u32))
+        );
+    }
+
+    #[test]
+    fn test_max_output_cells() {
+        let new_runner = || {
+            CairoRunner::new(
+                Rc::new(
+                    serde_json::from_str::<FullProgram>(include_str!(
+                        "../../../../test-data/artifacts/output.json"
+                    ))
+                    .unwrap()
+                    .into(),
+                ),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap()
+        };
+
+        let mut runner = new_runner();
+        runner.set_max_output_cells(Some(0u32.into())).unwrap();
+        match runner.run_until_end(HashMap::new()) {
+            Err(Error::BuiltinRunnerError(BuiltinRunnerError::OutputLimitExceeded {
+                used,
+                max,
+            })) => {
+                assert_eq!(used, BigInt::from(1u32));
+                assert_eq!(max, BigInt::from(0u32));
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        let mut runner = new_runner();
+        runner.set_max_output_cells(Some(1u32.into())).unwrap();
+        let summary = runner.run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.output, vec![BigInt::from(7u32)]);
+    }
+
+    #[test]
+    fn test_print_output_with() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/output.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let mut output = vec![];
+        runner
+            .print_output_with(|value| output.push(value.cloned()))
+            .unwrap();
+        assert_eq!(output, vec![Some(BigInt::from(7u32).into())]);
+
+        // No output builtin.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            CairoLayout::plain_instance(),
+        );
+        runner
+            .print_output_with(|_| panic!("unexpected output"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_vm_exception() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/assert_fail.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        match runner.run_until_pc(end.into(), None) {
+            Err(Error::VmError(exc)) => {
+                assert!(matches!(
+                    *exc.inner_exc,
+                    VirtualMachineError::AssertEqFailed { .. }
+                ));
+                assert_eq!(
+                    exc.encoded_instruction,
+                    Some(BigInt::from(0x400680017fff7fffu64))
+                );
+                assert_eq!(
+                    exc.instruction.as_ref().unwrap().to_string(),
+                    "[ap + (-1)] = 2"
+                );
+                assert_eq!(
+                    exc.to_string(),
+                    "/contracts/assert_fail.cairo:3:5: Error at pc=0:2:\n\
+                    An ASSERT_EQ instruction failed: 1 != 2.\n        \
+                    assert x = 2\n        \
+                    ^**********^\n\
+                    Cairo traceback (most recent call last):\n\
+                    /contracts/assert_fail.cairo:8:5: (pc=0:5)\n        \
+                    foo()\n        \
+                    ^***^"
+                );
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_vm_exception_error_message() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/error_message.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        match runner.run_until_pc(end.into(), None) {
+            Err(Error::VmError(exc)) => {
+                assert_eq!(
+                    exc.error_attr_value.as_deref(),
+                    Some("Error message: x must be positive, got 0\n")
+                );
+                assert!(exc
+                    .to_string()
+                    .starts_with("/contracts/error_message.cairo:3:9: Error message: x must be positive, got 0\nError at pc=0:0:\n"));
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_get_location() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/assert_fail.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        // The call to `foo()` in `main`.
+        let pc = runner.program_base().unwrap().to_owned() + &BigInt::from(5u32);
+        let location = &runner.vm().unwrap().get_location(&pc.into()).unwrap().inst;
+        assert_eq!(location.input_file.filename, "/contracts/assert_fail.cairo");
+        assert_eq!(
+            (
+                location.start_line,
+                location.start_col,
+                location.end_line,
+                location.end_col
+            ),
+            (8, 5, 8, 10)
+        );
+        assert!(location
+            .input_file
+            .content
+            .as_ref()
+            .unwrap()
+            .contains("func foo():"));
+
+        // Offsets past the end of the program have no location.
+        let pc = runner.program_base().unwrap().to_owned() + &BigInt::from(100u32);
+        assert!(runner.vm().unwrap().get_location(&pc.into()).is_none());
+    }
+
+    #[test]
+    fn test_vm_exception_without_debug_info() {
+        let mut program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/assert_fail.json"
+        ))
+        .unwrap();
+        program.debug_info = None;
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        match runner.run_until_pc(end.into(), None) {
+            Err(Error::VmError(exc)) => assert_eq!(
+                exc.to_string(),
+                "Error at pc=0:2:\n\
+                An ASSERT_EQ instruction failed: 1 != 2.\n\
+                Cairo traceback (most recent call last):\n\
+                Unknown location (pc=0:5)"
+            ),
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_run_past_end() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        runner.end_run(false, false).unwrap();
+
+        runner.read_return_values().unwrap();
+    }
+
+    #[test]
+    fn test_run_until_unreachable_pc() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        let target: MaybeRelocatable =
+            (runner.program_base().unwrap().to_owned() + &BigInt::from(100u32)).into();
+        match runner.run_until_pc(target.clone(), None) {
+            Err(Error::EndOfProgramReachedBeforeTarget {
+                final_pc,
+                target: err_target,
+            }) => {
+                assert_eq!(final_pc, end);
+                assert_eq!(err_target, target);
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_run_until_initial_pc() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        let initial_pc = runner.initial_pc().unwrap().to_owned();
+        runner.run_until_pc(initial_pc.into(), None).unwrap();
+        assert_eq!(runner.vm.as_ref().unwrap().current_step, BigInt::from(0u32));
+    }
+
+    #[test]
+    fn test_run_main_past_program_data() {
+        // Loading the program this way skips the validation rejecting such a main().
+        let mut program = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+        program["identifiers"]["__main__.main"]["pc"] = 1.into();
+        let program = serde_json::from_value::<FullProgram>(program).unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        match runner.run_until_pc(end.into(), None) {
+            Err(Error::VmError(exc)) => assert!(matches!(
+                *exc.inner_exc,
+                VirtualMachineError::RunContextError(RunContextError::MemoryDictError(
+                    MemoryDictError::UnknownMemory { .. }
+                ))
+            )),
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_run_to_stripped_program() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.to_stripped().unwrap().into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        let summary = runner.run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.steps, BigInt::from(1u32));
+        runner.assert_clean_halt().unwrap();
+    }
+
+    /// Runs the program until the end of main() and ends the run.
+    fn run_program(program: &str, instance: CairoLayout) -> CairoRunner {
+        let program = serde_json::from_str::<FullProgram>(program).unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            instance,
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+
+        runner
+    }
+
+    /// Encodes the cells as they're laid out in the binary memory file.
+    fn encode_memory(cells: &[(u64, u64)]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for (addr, value) in cells.iter() {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+            bytes.extend_from_slice(&value.to_le_bytes());
+            bytes.extend_from_slice(&[0u8; 24]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_relocate_and_stream_trace() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let mut trace = vec![];
+        runner.relocate_and_stream_trace(&mut trace).unwrap();
+
+        // (ap, fp, pc) for each step
+        let expected = [(10u64, 10u64, 1u64), (11, 10, 3), (11, 10, 4), (12, 10, 6)]
+            .iter()
+            .flat_map(|(ap, fp, pc)| [ap.to_le_bytes(), fp.to_le_bytes(), pc.to_le_bytes()])
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(trace, expected);
+    }
+
+    #[test]
+    fn test_write_binary_trace() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let mut trace = vec![];
+        runner.write_binary_trace(&mut trace).unwrap();
+
+        let entries = trace
+            .chunks(24)
+            .map(|entry| {
+                let mut registers = entry
+                    .chunks(8)
+                    .map(|register| u64::from_le_bytes(register.try_into().unwrap()));
+                (
+                    registers.next().unwrap(),
+                    registers.next().unwrap(),
+                    registers.next().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let expected = runner
+            .relocate_trace()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                (
+                    u64::try_from(&entry.ap).unwrap(),
+                    u64::try_from(&entry.fp).unwrap(),
+                    u64::try_from(&entry.pc).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected);
+        assert_eq!(entries[0], (10, 10, 1));
+    }
+
+    #[test]
+    fn test_relocate() {
+        let mut runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+        runner.relocate().unwrap();
+
+        // Program, execution and output segments are laid out contiguously, starting at 1. The
+        // last two segments are the empty return fp and end segments.
+        let segment_offsets = runner.segment_offsets.as_ref().unwrap();
+        assert_eq!(segment_offsets.len(), 5);
+        assert_eq!(segment_offsets[&0], BigInt::from(1u32));
+        assert_eq!(segment_offsets[&1], BigInt::from(7u32));
+        assert_eq!(segment_offsets[&2], BigInt::from(12u32));
+        assert_eq!(segment_offsets[&3], BigInt::from(13u32));
+        assert_eq!(segment_offsets[&4], BigInt::from(13u32));
+
+        let expected_memory = [
+            None,
+            // Program segment
+            Some(0x480680017fff8000u64),
+            Some(0x0),
+            Some(0x400280007ffd7fff),
+            Some(0x482680017ffd8000),
+            Some(0x3),
+            Some(0x208b7fff7fff7ffe),
+            // Execution segment
+            Some(12),
+            Some(13),
+            Some(13),
+            Some(0),
+            Some(15),
+            // Output segment
+            Some(0),
+        ]
+        .iter()
+        .map(|value| value.map(BigInt::from))
+        .collect::<Vec<_>>();
+        assert_eq!(runner.relocated_memory.as_ref().unwrap(), &expected_memory);
+
+        let relocated_trace = runner
+            .relocated_trace
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.pc.clone(), entry.ap.clone(), entry.fp.clone()))
+            .collect::<Vec<_>>();
+        let expected_trace = [(1u32, 10u32, 10u32), (3, 11, 10), (4, 11, 10), (6, 12, 10)]
+            .iter()
+            .map(|(pc, ap, fp)| (BigInt::from(*pc), BigInt::from(*ap), BigInt::from(*fp)))
+            .collect::<Vec<_>>();
+        assert_eq!(relocated_trace, expected_trace);
+    }
+
+    #[test]
+    fn test_write_trace_csv() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let mut csv = vec![];
+        runner.write_trace_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("pc,ap,fp"));
+
+        let rows = lines
+            .map(|line| {
+                line.split(',')
+                    .map(|value| value.parse::<u64>().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(BigInt::from(rows.len()), runner.vm().unwrap().current_step);
+        assert_eq!(
+            rows,
+            vec![
+                vec![1, 10, 10],
+                vec![3, 11, 10],
+                vec![4, 11, 10],
+                vec![6, 12, 10]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relocate_and_stream_memory() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let mut memory = vec![];
+        runner.relocate_and_stream_memory(&mut memory).unwrap();
+
+        let expected = encode_memory(&[
+            // Program segment
+            (1, 0x480680017fff8000),
+            (2, 0x0),
+            (3, 0x400280007ffd7fff),
+            (4, 0x482680017ffd8000),
+            (5, 0x3),
+            (6, 0x208b7fff7fff7ffe),
+            // Execution segment
+            (7, 12),
+            (8, 13),
+            (9, 13),
+            (10, 0),
+            (11, 15),
+            // Output segment
+            (12, 0),
+        ]);
+        assert_eq!(memory, expected);
+    }
+
+    #[test]
+    fn test_write_binary_memory() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let mut memory = vec![];
+        runner.write_binary_memory(&mut memory).unwrap();
+
+        // Same layout as the streamed memory, as the prime takes 32 bytes.
+        let mut expected = vec![];
+        runner.relocate_and_stream_memory(&mut expected).unwrap();
+        assert_eq!(memory, expected);
+    }
+
+    #[test]
+    fn test_relocate_and_stream_array_sum() {
+        // The hint of main() allocates the array in its own segment, after the two empty segments
+        // of the return fp and the end pc.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/array_sum.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let mut trace = vec![];
+        runner.relocate_and_stream_trace(&mut trace).unwrap();
+        assert_eq!(
+            trace,
+            include_bytes!("../../../../test-data/artifacts/array_sum_trace.bin")
+        );
+
+        let mut memory = vec![];
+        runner.relocate_and_stream_memory(&mut memory).unwrap();
+        assert_eq!(
+            memory,
+            include_bytes!("../../../../test-data/artifacts/array_sum_memory.bin")
+        );
+    }
+
+    #[test]
+    fn test_write_binary_memory_unrelocated_value() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/bad_stop_ptr.json"),
+            CairoLayout::small_instance(),
+        );
+
+        // A value pointing into a temporary segment, in the output cell of the run
+        let addr: MaybeRelocatable = RelocatableValue::new(2, 0).into();
+        let value: MaybeRelocatable = RelocatableValue::new(-1, 0).into();
+        // The memory is frozen after the run, so the value is written to the underlying map.
+        runner
+            .memory
+            .borrow_mut()
+            .data
+            .insert(addr.clone(), value.clone());
+
+        match runner.write_binary_memory(&mut vec![]) {
+            Err(Error::UnrelocatedValue {
+                addr: err_addr,
+                value: err_value,
+            }) => {
+                assert_eq!(err_addr, addr);
+                assert_eq!(err_value, value);
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        // A cell of a temporary segment
+        let mut memory = runner.memory.borrow_mut();
+        memory.data.insert(addr, BigInt::from(0).into());
+        memory.data.insert(value.clone(), BigInt::from(1).into());
+        drop(memory);
+
+        match runner.relocate_and_stream_memory(&mut vec![]) {
+            Err(Error::UnrelocatedValue { addr, .. }) => assert_eq!(addr, value),
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_get_initial_registers() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            runner.get_initial_registers(),
+            Err(Error::StateNotInitialized)
+        ));
+
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+
+        let (initial_pc, initial_ap, initial_fp) = runner.get_initial_registers().unwrap();
+
+        // The stack only contains the return fp and the return pc, as there are no builtins.
+        let expected_frame = runner.execution_base.unwrap() + &BigInt::from(2u32);
+        assert_eq!(initial_pc, runner.program_base.unwrap());
+        assert_eq!(initial_ap, expected_frame);
+        assert_eq!(initial_fp, expected_frame);
+    }
+
+    #[test]
+    fn test_bad_stop_ptr() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/bad_stop_ptr.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        runner.end_run(false, false).unwrap();
+
+        match runner.read_return_values() {
+            Err(Error::BuiltinRunnerError(BuiltinRunnerError::InvalidStopPointer {
+                builtin_name,
+                expected,
+                found,
+            })) => {
+                assert_eq!(builtin_name, "output");
+                assert_eq!(
+                    expected,
+                    RelocatableValue {
+                        segment_index: 2,
+                        offset: 1
+                    }
+                );
+                assert_eq!(
+                    found,
+                    RelocatableValue {
+                        segment_index: 2,
+                        offset: 3
+                    }
+                );
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_return_values_outside_execution_segment() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+
+        // The stack pointer is moved to the program segment, e.g. by a malformed program.
+        let program_base = MaybeRelocatable::from(runner.program_base.unwrap());
+        runner.vm.as_ref().unwrap().run_context.borrow_mut().ap = program_base.clone();
+        match runner.read_return_values() {
+            Err(Error::ReturnValuesOutsideExecutionSegment { addr }) => {
+                assert_eq!(addr, program_base)
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_lenient_hint_loading() {
+        let new_runner = |hint_pc: &str, lenient_hint_loading| {
+            let mut program = serde_json::from_str::<serde_json::Value>(include_str!(
+                "../../../../test-data/artifacts/output.json"
+            ))
+            .unwrap();
+            program["hints"][hint_pc] = serde_json::json!([{
+                "accessible_scopes": ["__main__", "__main__.main"],
+                "code": "memory[ap] = (",
+                "flow_tracking_data": {
+                    "ap_tracking": {"group": 0, "offset": 0},
+                    "reference_ids": {},
+                },
+            }]);
+            let program = serde_json::from_value::<FullProgram>(program).unwrap();
+
+            let mut runner = CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+            runner.lenient_hint_loading = lenient_hint_loading;
+            runner
+        };
+        let is_compile_error = |result: Result<RunSummary, Error>| match result {
+            Err(Error::VmError(exc)) => {
+                matches!(*exc.inner_exc, VirtualMachineError::HintCompileError(_))
+            }
+            _ => false,
+        };
+
+        // pc 1 holds the immediate of the first instruction, so the hint never runs.
+        assert!(is_compile_error(
+            new_runner("1", false).run_until_end(HashMap::new())
+        ));
+        let summary = new_runner("1", true).run_until_end(HashMap::new()).unwrap();
+        assert_eq!(summary.output, vec![BigInt::from(7u32)]);
+
+        // A hint that runs still fails.
+        assert!(is_compile_error(
+            new_runner("0", true).run_until_end(HashMap::new())
+        ));
+    }
+
+    #[test]
+    fn test_hint_scopes() {
+        // The first hint enters a scope with `n` defined, the second one writes `n` to memory and
+        // the last one exits the scope. The program asserts that the written value is 5.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/scopes.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        assert_eq!(runner.vm().unwrap().exec_scopes.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_hint_ids() {
+        // The hint writes 7 to the `tempvar x` defined before ap was incremented again.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/ids.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let x_addr = runner.execution_base().unwrap().to_owned() + &BigInt::from(2u32);
+        assert_eq!(
+            runner.memory.borrow_mut().get(&x_addr.into(), None),
+            Some(BigInt::from(7u32).into())
+        );
+    }
+
+    #[test]
+    fn test_hint_context() {
+        // The hint of `ids.json` is replaced with one reading the registers, the current step and a
+        // constant added to the program.
+        let mut program = serde_json::from_str::<serde_json::Value>(include_str!(
+            "../../../../test-data/artifacts/ids.json"
+        ))
+        .unwrap();
+        program["identifiers"]["__main__.SCALE"] = serde_json::json!({
+            "type": "const",
+            "value": 10
+        });
+        program["hints"]["4"][0]["code"] = serde_json::Value::from(
+            "memory[segments.add()] = pc\nmemory[ap] = fp\nids.x = ids.SCALE * current_step",
+        );
+        let runner = run_program(&program.to_string(), CairoLayout::plain_instance());
+
+        let program_base = runner.program_base().unwrap().to_owned();
+        let fp = runner.execution_base().unwrap().to_owned() + &BigInt::from(2u32);
+        let mut memory = runner.memory.borrow_mut();
+        assert_eq!(
+            memory.get(&RelocatableValue::new(2, 0).into(), None),
+            Some((program_base + &BigInt::from(4u32)).into())
+        );
+        assert_eq!(
+            memory.get(&(fp.clone() + &BigInt::from(2u32)).into(), None),
+            Some(fp.clone().into())
+        );
+        // The hint runs before the third step.
+        assert_eq!(
+            memory.get(&fp.into(), None),
+            Some(BigInt::from(20u32).into())
+        );
+    }
+
+    #[test]
+    fn test_hint_fsqrt() {
+        // The hint computes the root with the `fsqrt` static local, which the program then checks.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/sqrt.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let root_addr = runner.execution_base().unwrap().to_owned() + &BigInt::from(3u32);
+        assert_eq!(
+            runner.memory.borrow_mut().get(&root_addr.into(), None),
+            Some(BigInt::from(1234u32).into())
+        );
+    }
+
+    #[test]
+    fn test_gen_arg_entrypoint() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/array_sum.json"
+        ))
+        .unwrap();
+        let array_sum = program
+            .get_label(
+                ScopedName::new(vec![String::from("array_sum")]).unwrap(),
+                false,
+            )
+            .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+
+        let arr = runner
+            .segments
+            .borrow_mut()
+            .gen_arg(
+                Arg::Array(
+                    [1u32, 2, 3]
+                        .into_iter()
+                        .map(|value| Arg::Int(value.into()))
+                        .collect(),
+                ),
+                true,
+            )
+            .unwrap();
+        let return_fp = runner.segments.borrow_mut().add(None);
+        let end = runner
+            .initialize_function_entrypoint(
+                &array_sum,
+                vec![arr, BigInt::from(3u32).into()],
+                return_fp.into(),
+            )
+            .unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+
+        let ap = runner.vm().unwrap().run_context.borrow().ap.clone();
+        assert_eq!(
+            runner
+                .memory
+                .borrow_mut()
+                .get(&(ap + &BigInt::from(-1)), None),
+            Some(BigInt::from(6u32).into())
+        );
+    }
+
+    #[test]
+    fn test_hint_gen_arg() {
+        // The hint allocates the array with `segments.gen_arg`, and the program checks its sum.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/array_sum.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let arr_addr = runner.execution_base().unwrap().to_owned() + &BigInt::from(2u32);
+        let arr = runner
+            .memory
+            .borrow_mut()
+            .get(&arr_addr.into(), None)
+            .unwrap();
+        for (i, value) in [1u32, 2, 3].into_iter().enumerate() {
+            assert_eq!(
+                runner
+                    .memory
+                    .borrow_mut()
+                    .get(&(arr.clone() + &BigInt::from(i)), None),
+                Some(BigInt::from(value).into())
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "builtin-ecdsa")]
+    fn test_native_hint_ec_recover() {
+        // The hint has a Rust port, so it runs without the Python interpreter.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/ec_recover.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let public_key = private_key_to_ec_point_on_stark_curve(
+            &BigInt::parse_bytes(
+                b"139fe4d6f02e666e86a6f58e65060f115cd3c185bd9e98bd829636931458f79",
+                16,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        let public_key_addr = runner.execution_base().unwrap().to_owned() + &BigInt::from(6u32);
+        let mut memory = runner.memory.borrow_mut();
+        assert_eq!(
+            memory.get(&public_key_addr.into(), None),
+            Some(public_key.0.into())
+        );
+        assert_eq!(
+            memory.get(&(public_key_addr + &BigInt::from(1u32)).into(), None),
+            Some(public_key.1.into())
+        );
+    }
+
+    #[test]
+    fn test_hint_program_input() {
+        // The hint writes `program_input["x"]` to the output.
+        let run = |program_input: &str| {
+            let program = serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/program_input.json"
+            ))
+            .unwrap();
+
+            let mut runner = CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+
+            runner.initialize_segments();
+            let end = runner.initialize_main_entrypoint().unwrap();
+            runner.initialize_vm(HashMap::new(), ()).unwrap();
+            let program_input = serde_json::from_str::<Box<RawValue>>(program_input).unwrap();
+            let vm = runner.vm().unwrap();
+            vm.add_hint_locals(program_input_locals(&program_input, vm));
+            runner.run_until_pc(end.into(), None).unwrap();
+            runner.end_run(false, false).unwrap();
+            runner.read_return_values().unwrap();
+
+            runner.get_output().unwrap()
+        };
+
+        assert_eq!(
+            run(r#"{ "x": 42 }"#),
+            vec![Some(BigInt::from(42u32).into())]
+        );
+
+        // Integers too large for 64 bits are exact, not read as floats.
+        assert_eq!(
+            run(r#"{ "x": 1267650600228229401496703205377 }"#),
+            vec![Some(
+                BigInt::parse_bytes(b"1267650600228229401496703205377", 10)
+                    .unwrap()
+                    .into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_hint_print_output() {
+        // The hint prints two lines, which are forwarded to the sink prefixed with its pc.
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/print_output.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        let sink = Rc::new(VecHintOutputSink::default());
+        runner.hint_output_sink = sink.clone();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        assert_eq!(
+            *sink.lines.borrow(),
+            vec![
+                String::from("[pc=0:2] first line"),
+                String::from("[pc=0:2] second line"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exit_main_scope() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        let vm = runner.vm_mut().unwrap();
+        vm.enter_scope(None);
+        vm.exit_scope().unwrap();
+        assert!(matches!(
+            vm.exit_scope(),
+            Err(VirtualMachineError::CannotExitMainScope)
+        ));
+    }
+
+    #[test]
+    fn test_end_run_with_unbalanced_scopes() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        runner.vm_mut().unwrap().enter_scope(None);
+        match runner.end_run(false, false) {
+            Err(Error::VmError(exc)) => {
+                assert!(matches!(
+                    *exc.inner_exc,
+                    VirtualMachineError::EnterExitScopeMismatch
+                ));
+                assert_eq!(exc.pc, end);
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_load_program_unexpected_prime() {
+        let json = include_str!("../../../../test-data/artifacts/run_past_end.json");
+        let mut runner = CairoRunner::new(
+            Rc::new(serde_json::from_str::<FullProgram>(json).unwrap().into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        let mut other = serde_json::from_str::<FullProgram>(json).unwrap();
+        other.prime = BigInt::from(17u32);
+        let base = runner.segments.borrow_mut().add(None);
+
+        match runner.vm_mut().unwrap().load_program(&other, base.into()) {
+            Err(VirtualMachineError::UnexpectedPrime {
+                program_prime,
+                prime,
+            }) => {
+                assert_eq!(program_prime, BigInt::from(17u32));
+                assert_eq!(&prime, runner.program.prime());
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_temp_segment_relocation() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        let temp = runner.segments.borrow_mut().add_temp_segment();
+        let dst = runner.segments.borrow_mut().add(None);
+        runner
+            .segments
+            .borrow_mut()
+            .load_data(temp.into(), &[BigInt::from(7u32).into(), temp.into()])
+            .unwrap();
+        runner
+            .memory
+            .borrow_mut()
+            .add_relocation_rule(temp.segment_index, dst)
+            .unwrap();
+
+        runner.end_run(false, false).unwrap();
+
+        let mut memory = runner.memory.borrow_mut();
+        assert_eq!(
+            memory.get_range(&dst.into(), 2).unwrap(),
+            vec![MaybeRelocatable::from(BigInt::from(7u32)), dst.into()]
+        );
+        assert!(memory.get(&temp.into(), None).is_none());
+    }
+
+    #[test]
+    fn test_memory_holes() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/memory_hole.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+
+        // The cell skipped by `ap += 1` is the only hole.
+        assert_eq!(runner.get_memory_holes().unwrap(), BigInt::from(1u32));
+    }
+
+    #[test]
+    fn test_step_past_end() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+
+        match runner.vm_step() {
+            Err(Error::VmError(exc)) => {
+                assert!(matches!(
+                    *exc.inner_exc,
+                    VirtualMachineError::EndOfProgramReached
+                ));
+                assert_eq!(exc.pc, end);
+                assert!(exc.traceback.is_none());
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn test_proof_mode_non_included_builtins() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/proof_mode.json"
+        ))
+        .unwrap();
+
+        // In proof mode, all the builtins of the layout are created even if the program doesn't
+        // use them.
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(runner.builtin_runners.borrow().len(), 4);
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+
+        // The run starts at __start__ with the dummy fp and pc as the only public stack cells, as
+        // the program has no builtins.
+        let program_base = runner.program_base.unwrap();
+        let execution_base = runner.execution_base.unwrap();
+        assert_eq!(end, program_base + 4);
+        assert_eq!(runner.initial_pc, Some(program_base));
+        assert_eq!(runner.initial_fp, Some(execution_base + 2));
+        assert_eq!(runner.initial_ap, runner.initial_fp);
+        assert_eq!(
+            runner.execution_public_memory,
+            Some(vec![BigInt::from(0u32), BigInt::from(1u32)])
+        );
+
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(true, false).unwrap();
+        runner.read_return_values().unwrap();
+
+        for builtin_runner in runner.builtin_runners.borrow().values() {
+            let (used, _) = builtin_runner
+                .get_used_cells_and_allocated_size(&runner)
+                .unwrap();
+            assert_eq!(used, BigInt::from(0u32));
+        }
+
+        let addresses = runner.get_memory_segment_addresses().unwrap();
+        assert_eq!(addresses.len(), 4);
+        for address in addresses.values() {
+            assert_eq!(address.begin_addr, address.stop_ptr);
+            assert_eq!(
+                runner
+                    .segments
+                    .borrow()
+                    .get_segment_size(address.begin_addr.segment_index)
+                    .unwrap(),
+                BigInt::from(0u32)
+            );
+        }
+
+        let private_input = runner.get_air_private_input().unwrap();
+        assert_eq!(private_input.len(), 3);
+        for name in ["pedersen", "range_check", "ecdsa"] {
+            assert!(private_input[name].is_empty());
+        }
+
+        runner.finalize_segments().unwrap();
+        runner.relocate().unwrap();
+
+        // The empty segments of the builtins are part of the public input, right after the
+        // execution segment.
+        let public_input = runner.get_air_public_input().unwrap();
+        assert_eq!(public_input.layout, "small");
+        let execution = &public_input.memory_segments["execution"];
+        for name in ["output", "pedersen", "range_check", "ecdsa"] {
+            let addresses = &public_input.memory_segments[name];
+            assert_eq!(addresses.begin_addr, execution.stop_ptr);
+            assert_eq!(addresses.stop_ptr, addresses.begin_addr);
+        }
+
+        // The execution segment of the public input starts at the initial ap, right after the
+        // public dummy fp and pc.
+        let public_memory = public_input
+            .public_memory
+            .iter()
+            .map(|entry| (entry.address, entry.value.clone()))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            public_memory[&(execution.begin_addr - 2)],
+            BigInt::from(execution.begin_addr)
+        );
+        assert_eq!(
+            public_memory[&(execution.begin_addr - 1)],
+            BigInt::from(0u32)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn test_air_public_input() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/proof_mode.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            true,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+        runner.read_return_values().unwrap();
+
+        assert!(matches!(
+            runner.get_air_public_input(),
+            Err(Error::RunNotFinalized)
+        ));
+        runner.finalize_segments().unwrap();
+        assert!(matches!(
+            runner.get_air_public_input(),
+            Err(Error::NotRelocated)
+        ));
+        runner.relocate().unwrap();
+
+        assert_eq!(
+            serde_json::to_value(runner.get_air_public_input().unwrap()).unwrap(),
+            serde_json::from_str::<serde_json::Value>(include_str!(
+                "../../../../test-data/artifacts/proof_mode.air_public_input.json"
+            ))
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_perm_range_check_limits() {
+        // The offsets of the instructions range from [ap - 3] (0x7ffd) to [ap + 1] (0x8001).
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/output.json"),
+            CairoLayout::small_instance(),
+        );
+        assert_eq!(
+            runner.get_perm_range_check_limits().unwrap(),
+            Some((0x7ffd, 0x8001))
+        );
+
+        // The upper 16-bit parts of the value checked by the builtin are zeros.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/range_check.json"),
+            CairoLayout::small_instance(),
+        );
+        assert_eq!(
+            runner.get_perm_range_check_limits().unwrap(),
+            Some((0, 0x8001))
+        );
+
+        // Nothing is checked without a single step.
+        let mut runner = CairoRunner::new(
+            Rc::new(
+                serde_json::from_str::<FullProgram>(include_str!(
+                    "../../../../test-data/artifacts/countdown.json"
+                ))
+                .unwrap()
+                .into(),
+            ),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+        let ap = runner.execution_base.unwrap();
+        runner
+            .run_fragment(
+                "main.loop",
+                "main.loop",
+                FragmentState {
+                    ap,
+                    fp: ap,
+                    memory: HashMap::new(),
+                },
+            )
+            .unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(0));
+        assert_eq!(runner.get_perm_range_check_limits().unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "builtin-pedersen")]
+    fn test_pedersen_builtin() {
+        let new_runner = || {
+            let program = serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/pedersen.json"
+            ))
+            .unwrap();
+            let mut runner = CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+            runner.initialize_segments();
+            runner
+        };
+        let output_addr = |runner: &CairoRunner| -> MaybeRelocatable {
+            let base = runner.builtin_runners.borrow()[&builtin_runner_key("pedersen")]
+                .segment_base()
+                .unwrap();
+            (base + 2).into()
+        };
+
+        // main() reads the hash of 1 and 2, which the builtin deduces.
+        let mut runner = new_runner();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+        assert_eq!(
+            runner
+                .memory
+                .borrow_mut()
+                .index(&output_addr(&runner))
+                .unwrap(),
+            MaybeRelocatable::Int(
+                BigInt::parse_bytes(
+                    b"5bb9440e27889a364bcb678b1f679ecd1347acdedcbf36e83494f857cc58026",
+                    16
+                )
+                .unwrap()
+            )
+        );
+        assert_eq!(
+            runner.get_air_private_input().unwrap()["pedersen"],
+            vec![serde_json::json!({"index": 0, "x": "0x1", "y": "0x2"})]
+        );
+
+        // An output written before the run doesn't match the deduced hash.
+        let mut runner = new_runner();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner
+            .memory
+            .borrow_mut()
+            .index_set(output_addr(&runner), BigInt::from(3u32).into())
+            .unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        match runner.end_run(false, false) {
+            Err(Error::VmError(exc)) => match *exc.inner_exc {
+                VirtualMachineError::InconsistentAutoDeduction {
+                    addr,
+                    current_value,
+                    ..
+                } => {
+                    assert_eq!(MaybeRelocatable::from(addr), output_addr(&runner));
+                    assert_eq!(current_value, MaybeRelocatable::Int(BigInt::from(3u32)));
+                }
+                _ => panic!("unexpected error"),
+            },
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "builtin-ecdsa")]
+    fn test_ecdsa_builtin() {
+        // The signature of the message hash written by main(), by the private key of the public
+        // key it writes. See the tests of `crypto::signature::signature`.
+        let parse_hex = |hex: &str| BigInt::parse_bytes(hex.as_bytes(), 16).unwrap();
+        let r = parse_hex("34ffbd2cd71fce104a13e8003bbd4a17aee31371a6fe72eff0efcd195f19ff3");
+        let s = parse_hex("42e2665aca21934bca93eb1d589734759e58d43ee609514f05d608019d2711b");
+
+        let run = |signature: Option<(BigInt, BigInt)>| -> Result<CairoRunner, Error> {
+            let program = serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/ecdsa.json"
+            ))
+            .unwrap();
+            let mut runner = CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            )
+            .unwrap();
+            runner.initialize_segments();
+            let end = runner.initialize_main_entrypoint().unwrap();
+            runner.initialize_vm(HashMap::new(), ()).unwrap();
+            if let Some(signature) = signature {
+                let builtin_runners = runner.builtin_runners.borrow();
+                let builtin_runner = builtin_runners[&builtin_runner_key("ecdsa")]
+                    .as_any()
+                    .downcast_ref::<SignatureBuiltinRunner>()
+                    .unwrap();
+                builtin_runner.add_signature(builtin_runner.segment_base().unwrap(), signature);
+            }
+            runner.run_until_pc(end.into(), None)?;
+            Ok(runner)
+        };
+        let builtin_runner_error = |result: Result<CairoRunner, Error>| match result {
+            Err(Error::VmError(exc)) => match *exc.inner_exc {
+                VirtualMachineError::BuiltinRunnerError(err) => err,
+                _ => panic!("unexpected error"),
+            },
+            _ => panic!("unexpected result"),
+        };
+
+        let mut runner = run(Some((r.clone(), s.clone()))).unwrap();
+        runner.end_run(false, false).unwrap();
+        assert_eq!(
+            runner.get_air_private_input().unwrap()["ecdsa"],
+            vec![serde_json::json!({
+                "index": 0,
+                "pubkey": "0x2c5dbad71c92a45cc4b40573ae661f8147869a91d57b8d9b8f48c8af7f83159",
+                "msg": "0x6fea80189363a786037ed3e7ba546dad0ef7de49fccae0e31eb658b7dd4ea76",
+                "signature_input": {
+                    "r": "0x34ffbd2cd71fce104a13e8003bbd4a17aee31371a6fe72eff0efcd195f19ff3",
+                    "w": "0x773f91dac63789e3001b4c4952f59a337a9b82eaf9ca8815cf43eec6f9e5abd",
+                },
+            })]
+        );
 
-impl From<BuiltinRunnerError> for Error {
-    fn from(value: BuiltinRunnerError) -> Self {
-        Self::BuiltinRunnerError(value)
+        assert!(matches!(
+            builtin_runner_error(run(None)),
+            BuiltinRunnerError::SignatureHintMissing { .. }
+        ));
+        match builtin_runner_error(run(Some((r.clone(), &s + 1u32)))) {
+            BuiltinRunnerError::InvalidSignature {
+                r: invalid_r,
+                pubkey,
+                ..
+            } => {
+                assert_eq!(invalid_r, r);
+                assert_eq!(
+                    pubkey,
+                    parse_hex("2c5dbad71c92a45cc4b40573ae661f8147869a91d57b8d9b8f48c8af7f83159")
+                );
+            }
+            _ => panic!("unexpected error"),
+        }
     }
-}
 
-fn output_builtin_factory(_name: &str, included: bool) -> Box<dyn BuiltinRunner> {
-    Box::new(OutputBuiltinRunner::new(included))
-}
+    #[test]
+    fn test_proof_mode_initialize_main_entrypoint() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/proof_mode.json"
+        ))
+        .unwrap();
 
-fn pedersen_builtin_factory(_name: &str, _included: bool) -> Box<dyn BuiltinRunner> {
-    todo!()
-}
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            true,
+            false,
+        )
+        .unwrap();
 
-fn range_check_builtin_factory(_name: &str, _included: bool) -> Box<dyn BuiltinRunner> {
-    todo!()
-}
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
 
-fn ecdsa_builtin_factory(_name: &str, _included: bool) -> Box<dyn BuiltinRunner> {
-    todo!()
-}
+        // Execution starts at __start__ and ends at __end__.
+        let program_base = runner.program_base.unwrap();
+        let execution_base = runner.execution_base.unwrap();
+        assert_eq!(end, program_base + &BigInt::from(4u32));
 
-fn bitwise_builtin_factory(_name: &str, _included: bool) -> Box<dyn BuiltinRunner> {
-    todo!()
-}
+        let (initial_pc, initial_ap, initial_fp) = runner.get_initial_registers().unwrap();
+        assert_eq!(initial_pc, program_base);
+        assert_eq!(initial_ap, execution_base + &BigInt::from(2u32));
+        assert_eq!(initial_fp, execution_base + &BigInt::from(2u32));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // The dummy frame is part of the public memory.
+        assert_eq!(
+            runner.execution_public_memory,
+            Some(vec![BigInt::from(0u32), BigInt::from(1u32)])
+        );
+        assert_eq!(
+            runner
+                .memory
+                .borrow_mut()
+                .index(&execution_base.into())
+                .unwrap(),
+            MaybeRelocatable::from(initial_fp)
+        );
 
-    use crate::cairo::lang::compiler::program::FullProgram;
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(3u32));
+    }
 
     #[test]
-    fn test_run_past_end() {
+    fn test_proof_mode_without_start_label() {
         let program = serde_json::from_str::<FullProgram>(include_str!(
             "../../../../test-data/artifacts/run_past_end.json"
         ))
@@ -665,27 +4746,165 @@ mod tests {
             Rc::new(program.into()),
             CairoLayout::plain_instance(),
             MemoryDict::new(),
+            true,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        match runner.initialize_main_entrypoint() {
+            Err(Error::MissingProofModeLabel { label }) => assert_eq!(label, "__start__"),
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    /// Returns the segment index of each builtin, in the order of the builtin runners.
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn builtin_segment_indices(program: &str) -> Vec<(String, isize)> {
+        let program = serde_json::from_str::<FullProgram>(program).unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            true,
             false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+
+        let builtin_runners = runner.builtin_runners.borrow();
+        builtin_runners
+            .iter()
+            .map(|(name, builtin_runner)| {
+                (
+                    name.to_owned(),
+                    builtin_runner.segment_base().unwrap().segment_index,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn test_builtin_segment_indices_deterministic() {
+        let program = include_str!("../../../../test-data/artifacts/proof_mode.json");
+        let indices = builtin_segment_indices(program);
+        for _ in 0..4 {
+            assert_eq!(builtin_segment_indices(program), indices);
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn test_builtin_segment_indices_small_layout() {
+        // Same as cairo-lang: the program and execution segments come first, then the builtins in
+        // the order of the layout.
+        assert_eq!(
+            builtin_segment_indices(include_str!(
+                "../../../../test-data/artifacts/proof_mode.json"
+            )),
+            vec![
+                (String::from("output_builtin"), 2),
+                (String::from("pedersen_builtin"), 3),
+                (String::from("range_check_builtin"), 4),
+                (String::from("ecdsa_builtin"), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builtins_not_subsequence() {
+        let mut program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/output.json"
+        ))
+        .unwrap();
+        program.builtins = vec![String::from("range_check"), String::from("output")];
+
+        assert!(matches!(
+            CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                false,
+            ),
+            Err(Error::BuiltinsNotSubsequence { .. })
+        ));
+    }
+
+    #[test]
+    fn test_builtin_runner_keys() {
+        let new_runner = |builtins: Vec<String>| {
+            let mut program = serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/output.json"
+            ))
+            .unwrap();
+            program.builtins = builtins;
+
+            CairoRunner::new(
+                Rc::new(program.into()),
+                CairoLayout::small_instance(),
+                MemoryDict::new(),
+                false,
+                true,
+            )
+        };
+
+        // With missing builtins allowed, a failed lookup would silently push 0 for the output
+        // pointer instead of the output segment.
+        let mut runner = new_runner(vec![String::from("output")]).unwrap();
+        assert_eq!(
+            runner.builtin_runners.borrow().keys().collect::<Vec<_>>(),
+            vec![&builtin_runner_key("output")]
+        );
+        assert_eq!(
+            runner.run_until_end(HashMap::new()).unwrap().output,
+            vec![BigInt::from(7u32)]
+        );
+
+        // The runner key is not a builtin name.
+        assert!(matches!(
+            new_runner(vec![builtin_runner_key("output")]),
+            Err(Error::BuiltinsNotSubsequence { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn test_proof_mode_trace_padding() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/proof_mode.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            true,
             false,
         )
         .unwrap();
 
         runner.initialize_segments();
         let end = runner.initialize_main_entrypoint().unwrap();
-
         runner.initialize_vm(HashMap::new(), ()).unwrap();
-
         runner.run_until_pc(end.into(), None).unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(3u32));
 
+        // The trace is padded by looping at __end__ until the step count is a power of 2.
         runner.end_run(false, false).unwrap();
-
-        runner.read_return_values().unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(4u32));
+        assert_eq!(runner.vm().unwrap().trace.len(), 4);
+        assert!(runner.check_used_cells().unwrap().is_none());
     }
 
     #[test]
-    fn test_bad_stop_ptr() {
+    #[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+    fn test_run_for_steps() {
         let program = serde_json::from_str::<FullProgram>(include_str!(
-            "../../../../test-data/artifacts/bad_stop_ptr.json"
+            "../../../../test-data/artifacts/proof_mode.json"
         ))
         .unwrap();
 
@@ -693,6 +4912,52 @@ mod tests {
             Rc::new(program.into()),
             CairoLayout::small_instance(),
             MemoryDict::new(),
+            true,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+
+        // main() returns after 3 steps, after which the program loops at __end__.
+        runner.run_for_steps(BigInt::from(10u32)).unwrap();
+        runner.run_until_steps(BigInt::from(12u32)).unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(12u32));
+        assert_eq!(runner.vm().unwrap().trace.len(), 12);
+
+        let program_base = runner.program_base().unwrap().to_owned();
+        let execution_base = runner.execution_base().unwrap().to_owned();
+        let run_context = runner.vm().unwrap().run_context.borrow().clone();
+        assert_eq!(
+            run_context.pc,
+            MaybeRelocatable::from(program_base + &BigInt::from(4u32))
+        );
+        assert_eq!(
+            run_context.ap,
+            MaybeRelocatable::from(execution_base + &BigInt::from(4u32))
+        );
+        assert_eq!(
+            run_context.fp,
+            MaybeRelocatable::from(execution_base + &BigInt::from(2u32))
+        );
+
+        runner.run_until_next_power_of_2().unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(16u32));
+    }
+
+    #[test]
+    fn test_run_for_steps_stops_at_end() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
             false,
             false,
         )
@@ -700,34 +4965,266 @@ mod tests {
 
         runner.initialize_segments();
         let end = runner.initialize_main_entrypoint().unwrap();
-
         runner.initialize_vm(HashMap::new(), ()).unwrap();
 
-        runner.run_until_pc(end.into(), None).unwrap();
+        runner.run_for_steps(BigInt::from(100u32)).unwrap();
+        assert_eq!(runner.vm().unwrap().current_step, BigInt::from(1u32));
+        assert_eq!(
+            runner.vm().unwrap().run_context.borrow().pc,
+            MaybeRelocatable::from(end)
+        );
+    }
 
-        runner.end_run(false, false).unwrap();
+    #[test]
+    fn test_address_context() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/output.json"
+        ))
+        .unwrap();
 
-        match runner.read_return_values() {
-            Err(Error::BuiltinRunnerError(BuiltinRunnerError::InvalidStopPointer {
-                builtin_name,
-                expected,
-                found,
-            })) => {
-                assert_eq!(builtin_name, "output");
-                assert_eq!(
-                    expected,
-                    RelocatableValue {
-                        segment_index: BigInt::from(2u8),
-                        offset: BigInt::from(1u8)
-                    }
-                );
-                assert_eq!(
-                    found,
-                    RelocatableValue {
-                        segment_index: BigInt::from(2u8),
-                        offset: BigInt::from(3u8)
-                    }
-                );
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+
+        let context = runner.address_context().unwrap();
+        assert_eq!(
+            MaybeRelocatable::parse_user("output_builtin:1", &context).unwrap(),
+            RelocatableValue::new(2, 1)
+        );
+        assert_eq!(
+            MaybeRelocatable::parse_user("execution+3", &context).unwrap(),
+            RelocatableValue::new(1, 3)
+        );
+    }
+
+    #[test]
+    fn test_relocate_trace() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let segment_offsets = runner.segments.borrow().relocate_segments().unwrap();
+        let program_base = &segment_offsets[&runner.program_base.as_ref().unwrap().segment_index];
+
+        let trace = runner.relocate_trace().unwrap();
+        assert_eq!(trace.len(), 1);
+        assert_eq!(&trace[0].pc, program_base);
+    }
+
+    #[test]
+    fn test_write_tracer_data() {
+        let mut runner = run_program(
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            CairoLayout::plain_instance(),
+        );
+        runner.relocate().unwrap();
+
+        let dir = std::env::temp_dir().join(format!("oriac-{}-tracer-data", std::process::id()));
+        runner.write_tracer_data(&dir).unwrap();
+
+        let read_json = |name: &str| {
+            serde_json::from_str::<serde_json::Value>(
+                &std::fs::read_to_string(dir.join(name)).unwrap(),
+            )
+            .unwrap()
+        };
+        let trace = read_json("trace.json");
+        let memory = read_json("memory.json");
+        let air_public_input = read_json("air_public_input.json");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(trace, serde_json::json!([{ "pc": 1, "ap": 4, "fp": 4 }]));
+        assert_eq!(
+            memory,
+            serde_json::json!({ "1": "0x208b7fff7fff7ffe", "2": "0x4", "3": "0x4" })
+        );
+        assert_eq!(
+            air_public_input,
+            serde_json::json!({ "layout": "plain", "n_steps": 1, "public_memory": [] })
+        );
+    }
+
+    #[test]
+    fn test_write_tracer_data_not_relocated() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/run_past_end.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        assert!(matches!(
+            runner.write_tracer_data(std::env::temp_dir()),
+            Err(Error::NotRelocated)
+        ));
+    }
+
+    #[test]
+    fn test_deduce_op0_mul() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/deduce_op0_mul.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        // `[ap - 1] = [ap] * 3` deduces [ap] = 6 / 3.
+        let execution_base = runner.execution_base.unwrap();
+        assert_eq!(
+            runner
+                .memory
+                .borrow_mut()
+                .index(&(execution_base + &BigInt::from(3u32)).into())
+                .unwrap(),
+            MaybeRelocatable::from(BigInt::from(2u32))
+        );
+    }
+
+    #[test]
+    fn test_deduce_op0_mul_decimal() {
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/deduce_op0_mul.decimal.json"),
+            CairoLayout::plain_instance(),
+        );
+
+        let execution_base = runner.execution_base.unwrap();
+        assert_eq!(
+            runner
+                .memory
+                .borrow_mut()
+                .index(&(execution_base + &BigInt::from(3u32)).into())
+                .unwrap(),
+            MaybeRelocatable::from(BigInt::from(2u32))
+        );
+    }
+
+    #[test]
+    fn test_insufficient_allocated_cells() {
+        // The range check builtin is allocated one cell every 8 steps on the small layout, which
+        // is not enough for a single check in a 4-step run.
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/range_check.json"),
+            CairoLayout::small_instance(),
+        );
+
+        let builtin_runners = runner.builtin_runners.borrow();
+        let err = builtin_runners["range_check_builtin"]
+            .get_used_cells_and_allocated_size(&runner)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The range_check builtin used 1 cells but the capacity is 0."
+        );
+        assert!(matches!(
+            runner.check_used_cells().unwrap(),
+            Some(BuiltinRunnerError::InsufficientAllocatedCells { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sufficient_allocated_cells() {
+        let mut instance = CairoLayout::small_instance();
+        instance.builtins.insert(
+            String::from("range_check"),
+            BuiltinDefinition::RangeCheckInstanceDef(RangeCheckInstanceDef {
+                ratio: 1,
+                n_parts: 8,
+            }),
+        );
+        let runner = run_program(
+            include_str!("../../../../test-data/artifacts/range_check.json"),
+            instance,
+        );
+
+        let builtin_runners = runner.builtin_runners.borrow();
+        let (used, size) = builtin_runners["range_check_builtin"]
+            .get_used_cells_and_allocated_size(&runner)
+            .unwrap();
+        assert_eq!(used, BigInt::from(1u32));
+        assert_eq!(size, BigInt::from(4u32));
+    }
+
+    #[test]
+    fn test_reserve_memory() {
+        let program: Rc<Program> = Rc::new(
+            serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/bad_stop_ptr.json"
+            ))
+            .unwrap()
+            .into(),
+        );
+
+        let run = |memory: MemoryDict, n_steps: Option<usize>| {
+            let mut runner = CairoRunner::new(
+                program.clone(),
+                CairoLayout::small_instance(),
+                memory,
+                false,
+                false,
+            )
+            .unwrap();
+            if let Some(n_steps) = n_steps {
+                runner.reserve_memory(n_steps);
+            }
+            runner.initialize_segments();
+            let end = runner.initialize_main_entrypoint().unwrap();
+            runner.initialize_vm(HashMap::new(), ()).unwrap();
+            runner.run_until_pc(end.into(), None).unwrap();
+            runner.end_run(false, false).unwrap();
+            runner.relocate().unwrap();
+            runner.relocated_memory.unwrap()
+        };
+
+        let expected = run(MemoryDict::new(), None);
+        assert_eq!(run(MemoryDict::with_capacity(64), None), expected);
+        assert_eq!(run(MemoryDict::new(), Some(1000)), expected);
+    }
+
+    #[test]
+    fn test_range_check_validate_existing_memory() {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/range_check.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::small_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.initialize_segments();
+        runner.initialize_main_entrypoint().unwrap();
+
+        // Written before the validation rules are added.
+        let range_check_base = runner.builtin_runners.borrow()["range_check_builtin"]
+            .segment_base()
+            .unwrap();
+        runner
+            .memory
+            .borrow_mut()
+            .index_set(
+                range_check_base.into(),
+                MaybeRelocatable::Int(BigInt::from(1u32) << 128),
+            )
+            .unwrap();
+
+        match runner.initialize_vm(HashMap::new(), ()) {
+            Err(Error::VmError(exc)) => {
+                assert_eq!(&exc.pc, runner.initial_pc().unwrap());
+                match *exc.inner_exc {
+                    VirtualMachineError::BuiltinRunnerError(err) => assert_eq!(
+                        err.to_string(),
+                        "Value 340282366920938463463374607431768211456, in range check builtin 0, \
+                        is out of range [0, 340282366920938463463374607431768211456)."
+                    ),
+                    _ => panic!("unexpected error"),
+                }
             }
             _ => panic!("unexpected result"),
         }
@@ -0,0 +1,146 @@
+use crate::cairo::lang::vm::relocatable::MaybeRelocatable;
+
+use num_bigint::{BigInt, Sign};
+
+/// The base in which dumped integers are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Decimal,
+    /// Written with a `0x` prefix.
+    Hex,
+}
+
+/// How values are written when dumping them for humans, e.g. when printing the program output.
+///
+/// The default style writes values in full, in decimal, which is also what machine consumers
+/// should use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpStyle {
+    pub radix: Radix,
+    /// Integers with more digits than this are cut in the middle, e.g. `0x3ab4…9f2`. Not cut if
+    /// `None`.
+    pub max_digits_before_ellipsis: Option<usize>,
+    /// Appends the decoded text of integers made of printable ASCII characters, as Cairo short
+    /// strings are, e.g. `0x6869 ('hi')`.
+    pub short_string_detection: bool,
+}
+
+/// Cairo short strings hold at most 31 characters, to fit into a field element.
+const MAX_SHORT_STRING_LEN: usize = 31;
+
+impl DumpStyle {
+    pub fn format_value(&self, value: &MaybeRelocatable) -> String {
+        match value {
+            MaybeRelocatable::Int(value) => self.format_int(value),
+            // Addresses are small enough to never need cutting.
+            MaybeRelocatable::RelocatableValue(value) => value.to_string(),
+        }
+    }
+
+    pub fn format_int(&self, value: &BigInt) -> String {
+        let (prefix, radix) = match self.radix {
+            Radix::Decimal => ("", 10),
+            Radix::Hex => ("0x", 16),
+        };
+        let sign = if value.sign() == Sign::Minus { "-" } else { "" };
+        let digits = value.magnitude().to_str_radix(radix);
+
+        let digits = match self.max_digits_before_ellipsis {
+            Some(max_digits) if digits.len() > max_digits => {
+                // Digits are ASCII, so byte offsets are char boundaries.
+                let head = (max_digits + 1) / 2;
+                let tail = max_digits - head;
+                format!("{}…{}", &digits[..head], &digits[digits.len() - tail..])
+            }
+            _ => digits,
+        };
+
+        let mut formatted = format!("{}{}{}", sign, prefix, digits);
+        if self.short_string_detection {
+            if let Some(text) = decode_short_string(value) {
+                formatted.push_str(&format!(" ('{}')", text));
+            }
+        }
+        formatted
+    }
+}
+
+impl Default for DumpStyle {
+    fn default() -> Self {
+        Self {
+            radix: Radix::Decimal,
+            max_digits_before_ellipsis: None,
+            short_string_detection: false,
+        }
+    }
+}
+
+/// Decodes `value` as a short string if all of its bytes are printable ASCII characters.
+fn decode_short_string(value: &BigInt) -> Option<String> {
+    let (sign, bytes) = value.to_bytes_be();
+    if sign != Sign::Plus
+        || bytes.len() > MAX_SHORT_STRING_LEN
+        || !bytes.iter().all(|byte| (0x20..0x7f).contains(byte))
+    {
+        return None;
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cairo::lang::vm::relocatable::RelocatableValue;
+
+    fn dump(style: &DumpStyle) -> String {
+        let values = [
+            MaybeRelocatable::Int(BigInt::from(7)),
+            MaybeRelocatable::Int(BigInt::from(0x6869)),
+            MaybeRelocatable::Int(
+                BigInt::parse_bytes(
+                    b"3ab4a1fc55e2b16ad2b5c06d2b3b7c3d40c9d1f2e05b0bd8e6a8c3e1ea3419f2",
+                    16,
+                )
+                .unwrap(),
+            ),
+            MaybeRelocatable::Int(BigInt::from(-5)),
+            MaybeRelocatable::RelocatableValue(RelocatableValue::new(2, 3)),
+        ];
+
+        values
+            .iter()
+            .map(|value| style.format_value(value) + "\n")
+            .collect()
+    }
+
+    #[test]
+    fn test_dump_default() {
+        assert_eq!(
+            dump(&DumpStyle::default()),
+            "7\n\
+            26729\n\
+            26553295673602754005598020264312329650992517955891094568781944868044451092978\n\
+            -5\n\
+            2:3\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_hex_ellipsized() {
+        let style = DumpStyle {
+            radix: Radix::Hex,
+            max_digits_before_ellipsis: Some(7),
+            short_string_detection: true,
+        };
+        assert_eq!(
+            dump(&style),
+            "0x7\n\
+            0x6869 ('hi')\n\
+            0x3ab4…9f2\n\
+            -0x5\n\
+            2:3\n"
+        );
+    }
+}
@@ -13,7 +13,31 @@ pub struct MemoryDict {
     pub frozen: bool,
     /// A dict of segment relocation rules mapping a segment index to a RelocatableValue. See
     /// add_relocation_rule for more details.
-    pub relocation_rules: HashMap<BigInt, RelocatableValue>,
+    pub relocation_rules: HashMap<isize, RelocatableValue>,
+    /// The addresses of the cells in the order they were written, if enabled with
+    /// enable_write_log().
+    pub write_log: Option<Vec<MaybeRelocatable>>,
+    /// The reads and writes of cells in chronological order, if enabled with enable_access_log().
+    pub access_log: Option<Vec<MemoryAccess>>,
+    /// The step accesses are recorded at. Kept up to date by the VM while the access log is
+    /// enabled.
+    pub current_step: BigInt,
+}
+
+/// A read or a write of a memory cell, as recorded in [MemoryDict::access_log].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub step: BigInt,
+    pub kind: MemoryAccessKind,
+    pub addr: MaybeRelocatable,
+    /// The value read or written, before relocation. None for reads of unknown cells.
+    pub value: Option<MaybeRelocatable>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryAccessKind {
+    Read,
+    Write,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -24,6 +48,21 @@ pub enum Error {
     UnknownMemory { addr: MaybeRelocatable },
     #[error("Memory is frozen and cannot be changed.")]
     MemoryFrozen,
+    #[error("Inconsistent memory assignment at address {addr}. {old} != {new}.")]
+    InconsistentMemory {
+        addr: MaybeRelocatable,
+        old: MaybeRelocatable,
+        new: MaybeRelocatable,
+    },
+    #[error("src_segment_index must be < 0, got {segment_index}.")]
+    NonTemporarySegment { segment_index: isize },
+    #[error("The segment with index {segment_index} already has a relocation rule.")]
+    DuplicateRelocationRule { segment_index: isize },
+    #[error(
+        "The relocation rule of the segment with index {segment_index} does not lead to a \
+        non-temporary segment."
+    )]
+    TemporaryRelocationDestination { segment_index: isize },
 }
 
 impl MemoryDict {
@@ -32,6 +71,21 @@ impl MemoryDict {
             data: HashMap::new(),
             frozen: false,
             relocation_rules: HashMap::new(),
+            write_log: None,
+            access_log: None,
+            current_step: BigInt::from(0u32),
+        }
+    }
+
+    /// Creates an empty memory with room for at least `capacity` cells before reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            data: HashMap::with_capacity(capacity),
+            frozen: false,
+            relocation_rules: HashMap::new(),
+            write_log: None,
+            access_log: None,
+            current_step: BigInt::from(0u32),
         }
     }
 
@@ -40,7 +94,10 @@ impl MemoryDict {
         addr: &MaybeRelocatable,
         default_value: Option<MaybeRelocatable>,
     ) -> Option<MaybeRelocatable> {
-        let mut value = match self.data.get(addr).cloned() {
+        let value = self.data.get(addr).cloned();
+        self.log_access(MemoryAccessKind::Read, addr, value.clone());
+
+        let mut value = match value {
             Some(value) => Some(value),
             None => default_value,
         };
@@ -63,12 +120,96 @@ impl MemoryDict {
                 addr: addr.to_owned(),
             })?
             .to_owned();
+        self.log_access(MemoryAccessKind::Read, addr, Some(value.clone()));
 
         Ok(self.relocate_value(value))
     }
 
-    pub fn index_set(&mut self, addr: MaybeRelocatable, value: MaybeRelocatable) {
-        self.data.insert(addr, value);
+    /// Returns the values of the `size` cells starting at addr. Fails if any of them is unknown.
+    pub fn get_range(
+        &mut self,
+        addr: &MaybeRelocatable,
+        size: usize,
+    ) -> Result<Vec<MaybeRelocatable>, Error> {
+        (0..size)
+            .map(|i| self.index(&(addr.to_owned() + i)))
+            .collect()
+    }
+
+    /// Same as get_range(), with None for the cells that are unknown.
+    pub fn get_range_opt(
+        &mut self,
+        addr: &MaybeRelocatable,
+        size: usize,
+    ) -> Vec<Option<MaybeRelocatable>> {
+        (0..size)
+            .map(|i| self.get(&(addr.to_owned() + i), None))
+            .collect()
+    }
+
+    /// Writes value to addr. A cell can be written again only with the same value.
+    pub fn index_set(
+        &mut self,
+        addr: MaybeRelocatable,
+        value: MaybeRelocatable,
+    ) -> Result<(), Error> {
+        if self.frozen {
+            return Err(Error::MemoryFrozen);
+        }
+        self.check_element(addr.clone(), "Memory address")?;
+        self.check_element(value.clone(), "Memory value")?;
+
+        match self.data.get(&addr) {
+            Some(old) if old != &value => Err(Error::InconsistentMemory {
+                old: old.to_owned(),
+                addr,
+                new: value,
+            }),
+            Some(_) => {
+                self.log_access(MemoryAccessKind::Write, &addr, Some(value));
+                Ok(())
+            }
+            None => {
+                if let Some(write_log) = &mut self.write_log {
+                    write_log.push(addr.clone());
+                }
+                self.log_access(MemoryAccessKind::Write, &addr, Some(value.clone()));
+                self.data.insert(addr, value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts recording the order in which cells are written. The cells written so far are
+    /// recorded first, in no particular order.
+    pub fn enable_write_log(&mut self) {
+        if self.write_log.is_none() {
+            self.write_log = Some(self.data.keys().cloned().collect());
+        }
+    }
+
+    /// Starts recording every read and write of a cell, along with the step it happens at. This
+    /// slows down the VM considerably, and the log grows with every access.
+    pub fn enable_access_log(&mut self) {
+        if self.access_log.is_none() {
+            self.access_log = Some(vec![]);
+        }
+    }
+
+    fn log_access(
+        &mut self,
+        kind: MemoryAccessKind,
+        addr: &MaybeRelocatable,
+        value: Option<MaybeRelocatable>,
+    ) {
+        if let Some(access_log) = &mut self.access_log {
+            access_log.push(MemoryAccess {
+                step: self.current_step.clone(),
+                kind,
+                addr: addr.to_owned(),
+                value,
+            });
+        }
     }
 
     /// Freezes the memory - no changes can be made from now on.
@@ -80,6 +221,30 @@ impl MemoryDict {
         self.frozen
     }
 
+    /// Adds a rule that relocates the segment with index src_segment_index to dst.
+    ///
+    /// Rules can only be added to segments with negative index (temporary segments). dst may itself
+    /// point into a temporary segment with its own rule, in which case the rules are chained.
+    pub fn add_relocation_rule(
+        &mut self,
+        src_segment_index: isize,
+        dst: RelocatableValue,
+    ) -> Result<(), Error> {
+        if src_segment_index >= 0 {
+            return Err(Error::NonTemporarySegment {
+                segment_index: src_segment_index,
+            });
+        }
+        if self.relocation_rules.contains_key(&src_segment_index) {
+            return Err(Error::DuplicateRelocationRule {
+                segment_index: src_segment_index,
+            });
+        }
+
+        self.relocation_rules.insert(src_segment_index, dst);
+        Ok(())
+    }
+
     /// Relocates a value according to the relocation rules.
     ///
     /// The original value is returned if the relocation rules do not apply to value.
@@ -87,13 +252,12 @@ impl MemoryDict {
         match value {
             MaybeRelocatable::Int(_) => value,
             MaybeRelocatable::RelocatableValue(value) => {
-                let segment_idx = value.clone().segment_index;
-                if segment_idx >= BigInt::from(0u32) {
+                if value.segment_index >= 0 {
                     return value.into();
                 }
 
-                match self.relocation_rules.get(&segment_idx).cloned() {
-                    Some(relocation) => self.relocate_value(relocation.into()) + &value.offset,
+                match self.relocation_rules.get(&value.segment_index).cloned() {
+                    Some(relocation) => self.relocate_value(relocation.into()) + value.offset,
                     None => value.into(),
                 }
             }
@@ -110,6 +274,7 @@ impl MemoryDict {
         if self.relocation_rules.is_empty() {
             return Ok(());
         }
+        self.check_relocation_rules()?;
 
         self.data = {
             let items = self
@@ -128,6 +293,30 @@ impl MemoryDict {
         Ok(())
     }
 
+    /// Checks that each relocation rule, followed through the rules of the temporary segments it
+    /// points to, ends in a non-temporary segment.
+    fn check_relocation_rules(&self) -> Result<(), Error> {
+        for (src_segment_index, dst) in self.relocation_rules.iter() {
+            let mut dst = dst;
+            // A chain longer than the number of rules is a cycle.
+            let mut n_rules_left = self.relocation_rules.len();
+            while dst.segment_index < 0 {
+                match self.relocation_rules.get(&dst.segment_index) {
+                    Some(next) if n_rules_left > 0 => {
+                        dst = next;
+                        n_rules_left -= 1;
+                    }
+                    _ => {
+                        return Err(Error::TemporaryRelocationDestination {
+                            segment_index: *src_segment_index,
+                        })
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Checks that num is a valid Cairo value: positive int or relocatable. Currently, does not
     /// check that value < prime.
     fn check_element<T>(&self, num: T, name: &'static str) -> Result<(), Error>
@@ -151,3 +340,183 @@ impl Default for MemoryDict {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cairo::lang::vm::memory_segments::MemorySegmentManager;
+
+    use std::{cell::RefCell, rc::Rc};
+
+    fn ptr(segment_index: isize, offset: usize) -> RelocatableValue {
+        RelocatableValue::new(segment_index, offset)
+    }
+
+    #[test]
+    fn test_index_set() {
+        let mut memory = MemoryDict::new();
+        memory
+            .index_set(ptr(0, 0).into(), BigInt::from(1u32).into())
+            .unwrap();
+
+        // Writing the same value again is fine.
+        memory
+            .index_set(ptr(0, 0).into(), BigInt::from(1u32).into())
+            .unwrap();
+
+        match memory.index_set(ptr(0, 0).into(), BigInt::from(2u32).into()) {
+            Err(err @ Error::InconsistentMemory { .. }) => assert_eq!(
+                err.to_string(),
+                "Inconsistent memory assignment at address 0:0. 1 != 2."
+            ),
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(
+            memory.get(&ptr(0, 0).into(), None),
+            Some(BigInt::from(1u32).into())
+        );
+
+        assert!(matches!(
+            memory.index_set(BigInt::from(-1).into(), BigInt::from(1u32).into()),
+            Err(Error::NegativeValue { .. })
+        ));
+        assert!(matches!(
+            memory.index_set(ptr(0, 1).into(), BigInt::from(-1).into()),
+            Err(Error::NegativeValue { .. })
+        ));
+
+        memory.freeze();
+        assert!(matches!(
+            memory.index_set(ptr(0, 1).into(), BigInt::from(1u32).into()),
+            Err(Error::MemoryFrozen)
+        ));
+    }
+
+    #[test]
+    fn test_add_relocation_rule() {
+        let mut memory = MemoryDict::new();
+
+        assert!(matches!(
+            memory.add_relocation_rule(0, ptr(1, 0)),
+            Err(Error::NonTemporarySegment { .. })
+        ));
+
+        memory.add_relocation_rule(-1, ptr(1, 5)).unwrap();
+        assert!(matches!(
+            memory.add_relocation_rule(-1, ptr(2, 0)),
+            Err(Error::DuplicateRelocationRule { .. })
+        ));
+
+        assert_eq!(
+            memory.relocate_value(ptr(-1, 3).into()),
+            MaybeRelocatable::from(ptr(1, 8))
+        );
+        assert_eq!(
+            memory.relocate_value(ptr(-2, 3).into()),
+            MaybeRelocatable::from(ptr(-2, 3))
+        );
+    }
+
+    #[test]
+    fn test_chained_relocation() {
+        let mut memory = MemoryDict::new();
+        // -1 -> -2 -> 1.
+        memory.add_relocation_rule(-1, ptr(-2, 2)).unwrap();
+        memory.add_relocation_rule(-2, ptr(1, 10)).unwrap();
+
+        memory
+            .index_set(ptr(-1, 0).into(), BigInt::from(7u32).into())
+            .unwrap();
+        memory
+            .index_set(ptr(-2, 0).into(), ptr(-1, 1).into())
+            .unwrap();
+        memory
+            .index_set(ptr(0, 0).into(), ptr(-1, 0).into())
+            .unwrap();
+
+        assert_eq!(memory.get(&ptr(0, 0).into(), None), Some(ptr(1, 12).into()));
+
+        memory.relocate_memory().unwrap();
+        assert!(memory.relocation_rules.is_empty());
+        assert_eq!(
+            memory.get(&ptr(1, 12).into(), None),
+            Some(BigInt::from(7u32).into())
+        );
+        assert_eq!(
+            memory.get(&ptr(1, 10).into(), None),
+            Some(ptr(1, 13).into())
+        );
+        assert_eq!(memory.get(&ptr(0, 0).into(), None), Some(ptr(1, 12).into()));
+        assert_eq!(memory.data.len(), 3);
+    }
+
+    #[test]
+    fn test_get_range() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory.clone(), BigInt::from(1000u32));
+        let base = segments.add(None);
+        segments
+            .load_data(
+                base.into(),
+                &[
+                    BigInt::from(1u32).into(),
+                    ptr(0, 0).into(),
+                    BigInt::from(3u32).into(),
+                ],
+            )
+            .unwrap();
+
+        let mut memory = memory.borrow_mut();
+        assert_eq!(
+            memory.get_range(&base.into(), 3).unwrap(),
+            vec![
+                MaybeRelocatable::from(BigInt::from(1u32)),
+                ptr(0, 0).into(),
+                BigInt::from(3u32).into(),
+            ]
+        );
+        assert_eq!(
+            memory.get_range(&(base + 1usize).into(), 2).unwrap(),
+            vec![MaybeRelocatable::from(ptr(0, 0)), BigInt::from(3u32).into()]
+        );
+        assert!(memory.get_range(&base.into(), 0).unwrap().is_empty());
+        assert!(matches!(
+            memory.get_range(&base.into(), 4),
+            Err(Error::UnknownMemory { addr }) if addr == ptr(0, 3)
+        ));
+    }
+
+    #[test]
+    fn test_get_range_opt() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory.clone(), BigInt::from(1000u32));
+        let base = segments.add(None);
+        segments
+            .load_data((base + 1usize).into(), &[BigInt::from(2u32).into()])
+            .unwrap();
+
+        assert_eq!(
+            memory.borrow_mut().get_range_opt(&base.into(), 3),
+            vec![None, Some(BigInt::from(2u32).into()), None]
+        );
+    }
+
+    #[test]
+    fn test_temporary_relocation_destination() {
+        let mut memory = MemoryDict::new();
+        memory.add_relocation_rule(-1, ptr(-2, 0)).unwrap();
+        assert!(matches!(
+            memory.relocate_memory(),
+            Err(Error::TemporaryRelocationDestination { segment_index })
+                if segment_index == -1
+        ));
+
+        // A cycle never reaches a non-temporary segment either.
+        memory.add_relocation_rule(-2, ptr(-1, 0)).unwrap();
+        assert!(matches!(
+            memory.relocate_memory(),
+            Err(Error::TemporaryRelocationDestination { .. })
+        ));
+    }
+}
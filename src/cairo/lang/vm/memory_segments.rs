@@ -1,11 +1,18 @@
-use crate::cairo::lang::vm::{
-    memory_dict::MemoryDict,
-    relocatable::{MaybeRelocatable, RelocatableValue},
-    vm_exceptions::SecurityError,
+use crate::{
+    cairo::lang::vm::{
+        memory_dict::{Error as MemoryDictError, MemoryDict},
+        relocatable::{MaybeRelocatable, RelocatableValue},
+        vm_exceptions::SecurityError,
+    },
+    python::math_utils::{modulo, Error as MathError},
 };
 
 use num_bigint::BigInt;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 /// Manages the list of memory segments, and allows relocating them once their sizes are known.
 #[derive(Debug)]
@@ -13,15 +20,25 @@ pub struct MemorySegmentManager {
     pub memory: Rc<RefCell<MemoryDict>>,
     pub prime: BigInt,
     /// Number of segments.
-    pub n_segments: BigInt,
+    pub n_segments: isize,
     /// A map from segment index to its size.
-    pub segment_sizes: HashMap<BigInt, BigInt>,
-    pub segment_used_sizes: Option<HashMap<BigInt, BigInt>>,
+    pub segment_sizes: HashMap<isize, BigInt>,
+    pub segment_used_sizes: Option<HashMap<isize, BigInt>>,
     /// A map from segment index to a list of pairs (offset, page_id) that constitute the public
     /// memory. Note that the offset is absolute (not based on the page_id).
-    pub public_memory_offsets: HashMap<BigInt, Vec<[BigInt; 2]>>,
+    pub public_memory_offsets: HashMap<isize, Vec<[BigInt; 2]>>,
     /// The number of temporary segments, see 'add_temp_segment' for more details.
-    pub n_temp_segments: BigInt,
+    pub n_temp_segments: isize,
+}
+
+/// An argument to be written to memory by [MemorySegmentManager::gen_arg]. Corresponds to the
+/// ints, `RelocatableValue`s and iterables accepted by `gen_arg` in `cairo-lang`.
+#[derive(Debug, Clone)]
+pub enum Arg {
+    Int(BigInt),
+    Relocatable(RelocatableValue),
+    /// Written to a new segment, and replaced by a pointer to it.
+    Array(Vec<Arg>),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -34,6 +51,19 @@ pub enum Error {
     ComputeEffectiveSizesNotCalled,
     #[error("memory segment not found")]
     SegmentNotFound,
+    #[error("compute_effective_sizes must be called before relocate_segments.")]
+    RelocateBeforeComputeEffectiveSizes,
+    #[error("compute_effective_sizes must be called before get_memory_holes.")]
+    MemoryHolesBeforeComputeEffectiveSizes,
+    #[error("Accessed address {addr} is outside of its segment of used size {size}.")]
+    AccessedAddressOutOfSegment {
+        addr: RelocatableValue,
+        size: BigInt,
+    },
+    #[error(transparent)]
+    MemoryDictError(MemoryDictError),
+    #[error(transparent)]
+    MathError(MathError),
 }
 
 impl MemorySegmentManager {
@@ -41,25 +71,37 @@ impl MemorySegmentManager {
         Self {
             memory,
             prime,
-            n_segments: 0u32.into(),
+            n_segments: 0,
             segment_sizes: HashMap::new(),
             segment_used_sizes: None,
             public_memory_offsets: HashMap::new(),
-            n_temp_segments: 0u32.into(),
+            n_temp_segments: 0,
         }
     }
 
     /// Adds a new segment and returns its starting location as a RelocatableValue. If size is not
     /// None the segment is finalized with the given size.
     pub fn add(&mut self, size: Option<BigInt>) -> RelocatableValue {
-        let segment_index = self.n_segments.clone();
-        self.n_segments += BigInt::from(1);
+        let segment_index = self.n_segments;
+        self.n_segments += 1;
 
         if let Some(size) = size {
-            self.finalize(segment_index.clone(), Some(size), vec![]);
+            self.finalize(segment_index, Some(size), vec![]);
         }
 
-        RelocatableValue::new(segment_index, 0u32.into())
+        RelocatableValue::new(segment_index, 0)
+    }
+
+    /// Adds a new temporary segment and returns its starting location as a RelocatableValue.
+    ///
+    /// A temporary segment is a segment that will be relocated using memory.add_relocation_rule()
+    /// before the Cairo PIE is produced.
+    pub fn add_temp_segment(&mut self) -> RelocatableValue {
+        self.n_temp_segments += 1;
+        // Temporary segments have negative segment indices that start from -1.
+        let segment_index = -self.n_temp_segments;
+
+        RelocatableValue::new(segment_index, 0)
     }
 
     /// Writes the following information for the given segment:
@@ -68,66 +110,70 @@ impl MemorySegmentManager {
     /// memory.
     pub fn finalize(
         &mut self,
-        segment_index: BigInt,
+        segment_index: isize,
         size: Option<BigInt>,
         public_memory: Vec<[BigInt; 2]>,
     ) {
         if let Some(size) = size {
-            self.segment_sizes.insert(segment_index.clone(), size);
+            self.segment_sizes.insert(segment_index, size);
         }
 
         self.public_memory_offsets
             .insert(segment_index, public_memory);
     }
 
-    /// Computes the current used size of the segments, and caches it. include_tmp_segments should
-    /// be used for tests only.
-    pub fn compute_effective_sizes(&mut self, include_tmp_segments: bool) -> Result<(), Error> {
-        if self.segment_used_sizes.is_some() {
+    /// Computes the current used size of the segments, caches it and returns it.
+    /// include_tmp_segments should be used for tests only.
+    pub fn compute_effective_sizes(
+        &mut self,
+        include_tmp_segments: bool,
+    ) -> Result<&HashMap<isize, BigInt>, Error> {
+        let segment_used_sizes = match self.segment_used_sizes.take() {
             // segment_sizes is already cached.
-            return Ok(());
-        }
+            Some(segment_used_sizes) => segment_used_sizes,
+            None => self.compute_segment_used_sizes(include_tmp_segments)?,
+        };
+
+        Ok(self.segment_used_sizes.insert(segment_used_sizes))
+    }
 
+    fn compute_segment_used_sizes(
+        &self,
+        include_tmp_segments: bool,
+    ) -> Result<HashMap<isize, BigInt>, Error> {
         if !self.memory.borrow().is_frozen() {
             return Err(Error::MemoryNotFrozen);
         }
 
         let first_segment_index = if include_tmp_segments {
-            -&self.n_temp_segments
+            -self.n_temp_segments
         } else {
-            BigInt::from(0u32)
+            0
         };
-        self.segment_used_sizes = {
-            let mut segment_used_sizes = HashMap::<BigInt, BigInt>::new();
 
-            let mut index = first_segment_index;
-            while index < self.n_segments {
-                segment_used_sizes.insert(index.clone(), BigInt::from(0));
-                index += BigInt::from(1u32);
-            }
+        let mut segment_used_sizes = (first_segment_index..self.n_segments)
+            .map(|index| (index, BigInt::from(0)))
+            .collect::<HashMap<_, _>>();
 
-            for (addr, _) in self.memory.borrow().data.iter() {
-                match addr {
-                    MaybeRelocatable::Int(_) => return Err(Error::SecurityError(SecurityError {})),
-                    MaybeRelocatable::RelocatableValue(addr) => {
-                        // TODO: check if unwrap() is safe here
-                        let previous_max_size = segment_used_sizes
-                            .get(&addr.segment_index)
-                            .unwrap()
-                            .to_owned();
-
-                        segment_used_sizes.insert(
-                            addr.segment_index.to_owned(),
-                            previous_max_size.max(addr.offset.to_owned() + &BigInt::from(1u32)),
-                        );
-                    }
+        for (addr, _) in self.memory.borrow().data.iter() {
+            match addr {
+                MaybeRelocatable::Int(_) => return Err(Error::SecurityError(SecurityError {})),
+                MaybeRelocatable::RelocatableValue(addr) => {
+                    // TODO: check if unwrap() is safe here
+                    let previous_max_size = segment_used_sizes
+                        .get(&addr.segment_index)
+                        .unwrap()
+                        .to_owned();
+
+                    segment_used_sizes.insert(
+                        addr.segment_index,
+                        previous_max_size.max(BigInt::from(addr.offset) + 1u32),
+                    );
                 }
             }
+        }
 
-            Some(segment_used_sizes)
-        };
-
-        Ok(())
+        Ok(segment_used_sizes)
     }
 
     /// Writes data into the memory at address ptr and returns the first address after the data.
@@ -135,16 +181,58 @@ impl MemorySegmentManager {
         &mut self,
         ptr: MaybeRelocatable,
         data: &[MaybeRelocatable],
-    ) -> MaybeRelocatable {
+    ) -> Result<MaybeRelocatable, MemoryDictError> {
         for (i, v) in data.iter().enumerate() {
             self.memory
                 .borrow_mut()
-                .index_set(ptr.clone() + &BigInt::from(i), v.to_owned());
+                .index_set(ptr.clone() + i, v.to_owned())?;
         }
-        ptr + &BigInt::from(data.len())
+        Ok(ptr + data.len())
+    }
+
+    /// Converts args to Cairo-friendly ones.
+    ///
+    /// If an argument is an array, a new segment is allocated for it, its elements are converted
+    /// and written there, and the argument is replaced by the address of the segment. If
+    /// apply_modulo_to_args is true, integers are reduced modulo the prime.
+    pub fn gen_arg(
+        &mut self,
+        arg: Arg,
+        apply_modulo_to_args: bool,
+    ) -> Result<MaybeRelocatable, Error> {
+        Ok(match arg {
+            Arg::Int(value) => {
+                if apply_modulo_to_args {
+                    modulo(&value, &self.prime)?.into()
+                } else {
+                    value.into()
+                }
+            }
+            Arg::Relocatable(value) => value.into(),
+            Arg::Array(args) => {
+                let base = self.add(None);
+                self.write_arg(base.into(), args, apply_modulo_to_args)?;
+                base.into()
+            }
+        })
+    }
+
+    /// Converts the args with gen_arg() and writes them to memory at ptr. Returns the first address
+    /// after the data.
+    pub fn write_arg(
+        &mut self,
+        ptr: MaybeRelocatable,
+        args: Vec<Arg>,
+        apply_modulo_to_args: bool,
+    ) -> Result<MaybeRelocatable, Error> {
+        let data = args
+            .into_iter()
+            .map(|arg| self.gen_arg(arg, apply_modulo_to_args))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.load_data(ptr, &data)?)
     }
 
-    pub fn get_segment_used_size(&self, segment_index: BigInt) -> Result<BigInt, Error> {
+    pub fn get_segment_used_size(&self, segment_index: isize) -> Result<BigInt, Error> {
         match &self.segment_used_sizes {
             Some(segment_used_sizes) => Ok(segment_used_sizes
                 .get(&segment_index)
@@ -153,4 +241,262 @@ impl MemorySegmentManager {
             None => Err(Error::ComputeEffectiveSizesNotCalled),
         }
     }
+
+    /// Returns the size of the segment, preferring the size set on finalization over the used
+    /// size.
+    pub fn get_segment_size(&self, segment_index: isize) -> Result<BigInt, Error> {
+        match self.segment_sizes.get(&segment_index) {
+            Some(size) => Ok(size.to_owned()),
+            None => self.get_segment_used_size(segment_index),
+        }
+    }
+
+    /// Computes the starting address of each segment in the relocated memory. Addresses start at
+    /// 1, and segments are laid out contiguously in the order of their indices.
+    pub fn relocate_segments(&self) -> Result<HashMap<isize, BigInt>, Error> {
+        if self.segment_used_sizes.is_none() {
+            return Err(Error::RelocateBeforeComputeEffectiveSizes);
+        }
+
+        let first_addr = BigInt::from(1u32);
+        let mut current_addr = first_addr;
+        let mut res = HashMap::new();
+
+        for segment in self.segments() {
+            let (segment_index, segment_size) = segment?;
+            res.insert(segment_index, current_addr.clone());
+            current_addr += segment_size;
+        }
+
+        Ok(res)
+    }
+
+    /// Returns a list of (address, page_id) pairs for the public memory, given the relocated
+    /// starting address of each segment (see [MemorySegmentManager::relocate_segments]).
+    pub fn get_public_memory_addresses(
+        &self,
+        segment_offsets: &HashMap<isize, BigInt>,
+    ) -> Result<Vec<(BigInt, BigInt)>, Error> {
+        let mut res = vec![];
+
+        for segment_index in 0..self.n_segments {
+            if let Some(offsets) = self.public_memory_offsets.get(&segment_index) {
+                let segment_start = segment_offsets
+                    .get(&segment_index)
+                    .ok_or(Error::SegmentNotFound)?;
+                for [offset, page_id] in offsets.iter() {
+                    res.push((segment_start + offset, page_id.to_owned()));
+                }
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Iterates over the (non-temporary) segments in the order of their indices, yielding the index
+    /// and the size (see [MemorySegmentManager::get_segment_size]) of each.
+    pub fn segments(&self) -> impl Iterator<Item = Result<(isize, BigInt), Error>> + '_ {
+        (0..self.n_segments).map(|index| self.get_segment_size(index).map(|size| (index, size)))
+    }
+
+    /// Returns whether `value` may be stored in memory: integers always can, while relocatable
+    /// values must point into an existing segment.
+    pub fn is_valid_memory_value(&self, value: &MaybeRelocatable) -> bool {
+        match value {
+            MaybeRelocatable::Int(_) => true,
+            MaybeRelocatable::RelocatableValue(value) => {
+                value.segment_index >= -self.n_temp_segments
+                    && value.segment_index < self.n_segments
+            }
+        }
+    }
+
+    /// Returns the total number of memory holes in all segments, i.e. the cells below the used size
+    /// of each segment that were never accessed.
+    pub fn get_memory_holes(
+        &self,
+        accessed_addresses: &HashSet<RelocatableValue>,
+    ) -> Result<BigInt, Error> {
+        let segment_used_sizes = self
+            .segment_used_sizes
+            .as_ref()
+            .ok_or(Error::MemoryHolesBeforeComputeEffectiveSizes)?;
+
+        // A map from segment index to the number of accessed offsets.
+        let mut accessed_offsets: HashMap<isize, BigInt> = HashMap::new();
+        for addr in accessed_addresses.iter() {
+            let size = self.get_segment_used_size(addr.segment_index)?;
+            if BigInt::from(addr.offset) >= size {
+                return Err(Error::AccessedAddressOutOfSegment { addr: *addr, size });
+            }
+
+            *accessed_offsets
+                .entry(addr.segment_index)
+                .or_insert_with(|| BigInt::from(0u32)) += 1u32;
+        }
+
+        let mut holes = BigInt::from(0u32);
+        for (segment_index, segment_used_size) in segment_used_sizes.iter() {
+            holes += segment_used_size
+                - accessed_offsets
+                    .get(segment_index)
+                    .cloned()
+                    .unwrap_or_else(|| BigInt::from(0u32));
+        }
+
+        Ok(holes)
+    }
+}
+
+impl From<MemoryDictError> for Error {
+    fn from(value: MemoryDictError) -> Self {
+        Self::MemoryDictError(value)
+    }
+}
+
+impl From<MathError> for Error {
+    fn from(value: MathError) -> Self {
+        Self::MathError(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_temp_segment() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory.clone(), BigInt::from(1000u32));
+
+        segments.add(None);
+        let first = segments.add_temp_segment();
+        let second = segments.add_temp_segment();
+
+        assert_eq!(first, RelocatableValue::new(-1, 0));
+        assert_eq!(second, RelocatableValue::new(-2, 0));
+        assert_eq!(segments.n_segments, 1);
+        assert_eq!(segments.n_temp_segments, 2);
+
+        segments
+            .load_data((second + 2usize).into(), &[BigInt::from(7u32).into()])
+            .unwrap();
+        memory.borrow_mut().freeze();
+
+        segments.compute_effective_sizes(true).unwrap();
+        assert_eq!(
+            segments.get_segment_used_size(-1).unwrap(),
+            BigInt::from(0u32)
+        );
+        assert_eq!(
+            segments.get_segment_used_size(-2).unwrap(),
+            BigInt::from(3u32)
+        );
+    }
+
+    #[test]
+    fn test_effective_sizes() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory.clone(), BigInt::from(1000u32));
+
+        let first = segments.add(None);
+        segments.add(None);
+        segments
+            .load_data((first + 1usize).into(), &[BigInt::from(7u32).into()])
+            .unwrap();
+
+        assert!(matches!(
+            segments.get_segment_used_size(0),
+            Err(Error::ComputeEffectiveSizesNotCalled)
+        ));
+        assert!(matches!(
+            segments.compute_effective_sizes(false),
+            Err(Error::MemoryNotFrozen)
+        ));
+
+        memory.borrow_mut().freeze();
+        let used_sizes = segments.compute_effective_sizes(false).unwrap();
+        assert_eq!(used_sizes[&0], BigInt::from(2u32));
+        assert_eq!(used_sizes[&1], BigInt::from(0u32));
+
+        // The finalized size takes precedence over the used size.
+        segments.finalize(1, Some(5u32.into()), vec![]);
+        assert_eq!(
+            segments.get_segment_used_size(1).unwrap(),
+            BigInt::from(0u32)
+        );
+        assert_eq!(segments.get_segment_size(1).unwrap(), BigInt::from(5u32));
+        assert_eq!(
+            segments.segments().collect::<Result<Vec<_>, _>>().unwrap(),
+            vec![(0, BigInt::from(2u32)), (1, BigInt::from(5u32)),]
+        );
+        assert_eq!(
+            segments.relocate_segments().unwrap(),
+            HashMap::from([(0, BigInt::from(1u32)), (1, BigInt::from(3u32)),])
+        );
+    }
+
+    #[test]
+    fn test_is_valid_memory_value() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory, BigInt::from(1000u32));
+        segments.add(None);
+        segments.add_temp_segment();
+
+        let relocatable = |segment_index: isize, offset: usize| -> MaybeRelocatable {
+            RelocatableValue::new(segment_index, offset).into()
+        };
+
+        assert!(segments.is_valid_memory_value(&BigInt::from(-5).into()));
+        assert!(segments.is_valid_memory_value(&relocatable(0, 3)));
+        assert!(segments.is_valid_memory_value(&relocatable(-1, 0)));
+        assert!(!segments.is_valid_memory_value(&relocatable(1, 0)));
+        assert!(!segments.is_valid_memory_value(&relocatable(-2, 0)));
+    }
+
+    #[test]
+    fn test_gen_arg() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory.clone(), BigInt::from(1000u32));
+        let relocatable = segments.add(None);
+
+        let arg = segments
+            .gen_arg(
+                Arg::Array(vec![
+                    Arg::Int((-1).into()),
+                    Arg::Array(vec![Arg::Int(1001u32.into())]),
+                    Arg::Relocatable(relocatable),
+                ]),
+                true,
+            )
+            .unwrap();
+        assert_eq!(arg, MaybeRelocatable::from(RelocatableValue::new(1, 0)));
+
+        let mut memory = memory.borrow_mut();
+        assert_eq!(
+            memory.index(&(arg.clone() + 0usize)).unwrap(),
+            MaybeRelocatable::from(BigInt::from(999u32))
+        );
+        let nested = memory.index(&(arg.clone() + 1usize)).unwrap();
+        assert_eq!(nested, MaybeRelocatable::from(RelocatableValue::new(2, 0)));
+        assert_eq!(
+            memory.index(&nested).unwrap(),
+            MaybeRelocatable::from(BigInt::from(1u32))
+        );
+        assert_eq!(
+            memory.index(&(arg + 2usize)).unwrap(),
+            MaybeRelocatable::from(relocatable)
+        );
+    }
+
+    #[test]
+    fn test_gen_arg_without_modulo() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let mut segments = MemorySegmentManager::new(memory, BigInt::from(1000u32));
+
+        assert_eq!(
+            segments.gen_arg(Arg::Int(1001u32.into()), false).unwrap(),
+            MaybeRelocatable::from(BigInt::from(1001u32))
+        );
+    }
 }
@@ -1,12 +1,18 @@
+pub mod air_public_input;
 pub mod builtin_runner;
 pub mod cairo_runner;
+pub mod dump_style;
 pub mod memory_dict;
 pub mod memory_segments;
+pub mod output;
 pub mod output_builtin_runner;
 pub mod relocatable;
+pub mod runner_pool;
 pub mod trace_entry;
+pub mod unreached_report;
 pub mod utils;
 pub mod validated_memory_dict;
 pub mod virtual_machine_base;
+pub mod vm_consts;
 pub mod vm_core;
 pub mod vm_exceptions;
@@ -0,0 +1,106 @@
+use num_bigint::BigInt;
+use std::io::Write;
+
+/// The number of bytes used to encode an address or a register value.
+pub const ADDR_SIZE_IN_BYTES: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Relocated value {value} does not fit into {n_bytes} bytes.")]
+    ValueTooLarge { value: BigInt, n_bytes: usize },
+    #[error(transparent)]
+    Io(std::io::Error),
+}
+
+/// Writes the little-endian encoding of a non-negative `value` padded to `n_bytes` bytes.
+pub fn write_le_bytes<W: Write>(
+    writer: &mut W,
+    value: &BigInt,
+    n_bytes: usize,
+) -> Result<(), Error> {
+    let (_, mut bytes) = value.to_bytes_le();
+    if bytes.len() > n_bytes {
+        return Err(Error::ValueTooLarge {
+            value: value.to_owned(),
+            n_bytes,
+        });
+    }
+    bytes.resize(n_bytes, 0);
+
+    writer.write_all(&bytes)?;
+    Ok(())
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cairo::lang::{
+        compiler::program::FullProgram,
+        instances::CairoLayout,
+        vm::{cairo_runner::CairoRunner, memory_dict::MemoryDict},
+    };
+
+    use std::{collections::HashMap, rc::Rc};
+
+    fn run_past_end() -> CairoRunner {
+        let program = serde_json::from_str::<FullProgram>(include_str!(
+            "../../../../test-data/artifacts/run_past_end.json"
+        ))
+        .unwrap();
+
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            CairoLayout::plain_instance(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+
+        runner.initialize_segments();
+        let end = runner.initialize_main_entrypoint().unwrap();
+        runner.initialize_vm(HashMap::new(), ()).unwrap();
+        runner.run_until_pc(end.into(), None).unwrap();
+        runner.end_run(false, false).unwrap();
+
+        runner
+    }
+
+    #[test]
+    fn test_write_binary_trace() {
+        let mut trace = vec![];
+        run_past_end().write_binary_trace(&mut trace).unwrap();
+
+        assert_eq!(
+            trace,
+            include_bytes!("../../../../test-data/artifacts/run_past_end_trace.bin")
+        );
+    }
+
+    #[test]
+    fn test_write_binary_memory() {
+        let mut memory = vec![];
+        run_past_end().write_binary_memory(&mut memory).unwrap();
+
+        assert_eq!(
+            memory,
+            include_bytes!("../../../../test-data/artifacts/run_past_end_memory.bin")
+        );
+    }
+
+    #[test]
+    fn test_write_le_bytes_too_large() {
+        let mut bytes = vec![];
+        assert!(matches!(
+            write_le_bytes(&mut bytes, &BigInt::from(0x10000u32), 2),
+            Err(Error::ValueTooLarge { n_bytes: 2, .. })
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::cairo::lang::vm::{
+    air_public_input::MemorySegmentAddresses,
     builtin_runner::{BuiltinRunner, Error as BuiltinRunnerError},
     cairo_runner::CairoRunner,
     memory_segments::MemorySegmentManager,
@@ -24,6 +25,8 @@ pub struct OutputBuiltinRunner {
     pub attributes: HashMap<String, ()>,
     pub base: Option<RelocatableValue>,
     pub stop_ptr: Option<RelocatableValue>,
+    /// The maximum number of cells the program may write to the output, or `None` for no limit.
+    pub max_output_cells: Option<BigInt>,
 }
 
 impl OutputBuiltinRunner {
@@ -34,8 +37,65 @@ impl OutputBuiltinRunner {
             attributes: HashMap::new(),
             base: None,
             stop_ptr: None,
+            max_output_cells: None,
         }
     }
+
+    /// Adds a page to the output.
+    ///
+    /// * page_id - The page id. Cells that aren't in any page belong to page 0.
+    /// * page_start - The first address of the page, which must be in the output segment.
+    /// * page_size - The number of cells in the page.
+    ///
+    /// Pages may not overlap.
+    pub fn add_page(
+        &mut self,
+        page_id: BigInt,
+        page_start: MaybeRelocatable,
+        page_size: BigInt,
+    ) -> Result<(), BuiltinRunnerError> {
+        let base = self
+            .base
+            .as_ref()
+            .ok_or(BuiltinRunnerError::UnexpectedNoneValue)?;
+        let start = match &page_start {
+            MaybeRelocatable::RelocatableValue(value)
+                if value.segment_index == base.segment_index =>
+            {
+                BigInt::from(value.offset)
+            }
+            _ => {
+                return Err(BuiltinRunnerError::PageOutsideOutputSegment {
+                    segment_index: base.segment_index,
+                    page_start,
+                })
+            }
+        };
+
+        if self.pages.contains_key(&page_id) {
+            return Err(BuiltinRunnerError::PageAlreadyUsed { page_id });
+        }
+        if let Some((other_page_id, _)) = self
+            .pages
+            .iter()
+            .find(|(_, page)| start < &page.start + &page.size && page.start < &start + &page_size)
+        {
+            return Err(BuiltinRunnerError::OverlappingPages {
+                page_id,
+                other_page_id: other_page_id.to_owned(),
+            });
+        }
+
+        self.pages.insert(
+            page_id,
+            PublicMemoryPage {
+                start,
+                size: page_size,
+            },
+        );
+
+        Ok(())
+    }
 }
 
 impl BuiltinRunner for OutputBuiltinRunner {
@@ -47,7 +107,7 @@ impl BuiltinRunner for OutputBuiltinRunner {
     fn initial_stack(&self) -> Vec<MaybeRelocatable> {
         if self.included {
             // TODO: check if it's safe to unwrap here
-            vec![self.base.clone().unwrap().into()]
+            vec![self.base.unwrap().into()]
         } else {
             vec![]
         }
@@ -69,14 +129,10 @@ impl BuiltinRunner for OutputBuiltinRunner {
                     MaybeRelocatable::Int(_) => panic!("expecting RelocatableValue"),
                 }
             };
-            self.stop_ptr = Some(stop_ptr.clone());
+            self.stop_ptr = Some(stop_ptr);
             let used = self.get_used_cells(runner)?;
             {
-                let expected = self
-                    .base
-                    .clone()
-                    .ok_or(BuiltinRunnerError::UnexpectedNoneValue)?
-                    + &used;
+                let expected = self.base.ok_or(BuiltinRunnerError::UnexpectedNoneValue)? + &used;
                 let found = stop_ptr;
                 if found != expected {
                     return Err(BuiltinRunnerError::InvalidStopPointer {
@@ -89,31 +145,90 @@ impl BuiltinRunner for OutputBuiltinRunner {
 
             Ok(pointer_minus_one)
         } else {
-            self.stop_ptr = self.base.clone();
+            self.stop_ptr = self.base;
             Ok(pointer)
         }
     }
 
+    fn segment_base(&self) -> Option<RelocatableValue> {
+        self.base
+    }
+
     fn get_used_cells(&self, runner: &CairoRunner) -> Result<BigInt, BuiltinRunnerError> {
         let size = runner.segments.borrow().get_segment_used_size(
             self.base
-                .clone()
                 .ok_or(BuiltinRunnerError::UnexpectedNoneValue)?
                 .segment_index,
+        )?;
+
+        if let Some(max_output_cells) = &self.max_output_cells {
+            if &size > max_output_cells {
+                return Err(BuiltinRunnerError::OutputLimitExceeded {
+                    used: size,
+                    max: max_output_cells.to_owned(),
+                });
+            }
+        }
+
+        Ok(size)
+    }
+
+    fn finalize_segments(&self, runner: &CairoRunner) -> Result<(), BuiltinRunnerError> {
+        let size = self.get_used_cells(runner)?;
+
+        // Cells belong to page 0 unless they were added to another page.
+        let mut public_memory = vec![];
+        let mut offset = BigInt::from(0u32);
+        while offset < size {
+            let page_id = self
+                .pages
+                .iter()
+                .find(|(_, page)| page.start <= offset && offset < &page.start + &page.size)
+                .map(|(page_id, _)| page_id.to_owned())
+                .unwrap_or_else(|| BigInt::from(0u32));
+            public_memory.push([offset.clone(), page_id]);
+            offset += 1u32;
+        }
+
+        runner.segments.borrow_mut().finalize(
+            self.base
+                .ok_or(BuiltinRunnerError::UnexpectedNoneValue)?
+                .segment_index,
+            Some(size),
+            public_memory,
         );
 
-        Ok(size?)
+        Ok(())
     }
 
-    fn get_used_cells_and_allocated_size(
+    fn get_memory_segment_addresses(
         &self,
-        runner: &CairoRunner,
-    ) -> Result<(BigInt, BigInt), BuiltinRunnerError> {
-        let size = self.get_used_cells(runner)?;
-        Ok((size.clone(), size))
+    ) -> Result<HashMap<String, MemorySegmentAddresses<RelocatableValue>>, BuiltinRunnerError> {
+        let mut addresses = HashMap::new();
+        addresses.insert(
+            String::from("output"),
+            MemorySegmentAddresses {
+                begin_addr: self.base.ok_or(BuiltinRunnerError::UnexpectedNoneValue)?,
+                stop_ptr: self
+                    .stop_ptr
+                    .ok_or(BuiltinRunnerError::UnexpectedNoneValue)?,
+            },
+        );
+        Ok(addresses)
+    }
+
+    fn air_private_input(
+        &self,
+        _runner: &CairoRunner,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>, BuiltinRunnerError> {
+        Ok(HashMap::new())
     }
 
     fn as_any(&self) -> &dyn Any {
         self
     }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
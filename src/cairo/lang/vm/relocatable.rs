@@ -1,6 +1,8 @@
-use std::fmt::Display;
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
-use num_bigint::BigInt;
+use num_bigint::{BigInt, Sign};
+
+use crate::python::math_utils::modulo;
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum MaybeRelocatable {
@@ -10,12 +12,66 @@ pub enum MaybeRelocatable {
 
 /// A value in the cairo vm representing an address in some memory segment. This is meant to be
 /// replaced by a real memory address (field element) after the VM finished.
-#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+///
+/// Values are ordered by segment index, then by offset.
+///
+/// Offsets are machine integers: an address whose offset would be negative or wouldn't fit into a
+/// `usize` can't be represented. The checked arithmetic methods report such addresses as an
+/// [AddressOutOfRangeError], while the arithmetic operators panic on them.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RelocatableValue {
+    pub segment_index: isize,
+    pub offset: usize,
+}
+
+/// Raised when the result of an address computation can't be represented by a
+/// [RelocatableValue].
+#[derive(Debug, thiserror::Error)]
+#[error("Address {segment_index}:{offset} is out of range.")]
+pub struct AddressOutOfRangeError {
     pub segment_index: BigInt,
     pub offset: BigInt,
 }
 
+/// Raised when subtracting two values that can't be subtracted, or when the result is an address
+/// out of range.
+#[derive(Debug, thiserror::Error)]
+pub enum SubtractionError {
+    #[error("unsupported operand type(s) for -: 'int' and 'RelocatableValue'")]
+    IntMinusRelocatable,
+    #[error("Can only subtract two relocatable values of the same segment ({lhs} != {rhs}).")]
+    SegmentMismatch { lhs: isize, rhs: isize },
+    #[error(transparent)]
+    AddressOutOfRange(AddressOutOfRangeError),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelocationError {
+    #[error("No relocation offset found for segment {segment_index}.")]
+    MissingSegmentOffset { segment_index: isize },
+    #[error("Relocated value {value} is not smaller than the prime.")]
+    ValueExceedsPrime { value: BigInt },
+}
+
+/// Names that can be used in place of segment indices in user-supplied addresses, e.g.
+/// `program`, `execution` or `output_builtin`.
+#[derive(Debug, Default, Clone)]
+pub struct AddressContext {
+    pub segment_aliases: HashMap<String, isize>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AddressParseError {
+    #[error("Empty address.")]
+    Empty,
+    #[error("Invalid number \"{input}\". Expected a decimal or 0x-prefixed hex integer.")]
+    InvalidNumber { input: String },
+    #[error("Unknown segment \"{name}\". Known segments: {}.", .known.join(", "))]
+    UnknownSegment { name: String, known: Vec<String> },
+    #[error(transparent)]
+    OutOfRange(AddressOutOfRangeError),
+}
+
 impl From<BigInt> for MaybeRelocatable {
     fn from(value: BigInt) -> Self {
         MaybeRelocatable::Int(value)
@@ -52,16 +108,9 @@ impl std::ops::Add<&MaybeRelocatable> for MaybeRelocatable {
 impl std::ops::Sub<&MaybeRelocatable> for MaybeRelocatable {
     type Output = MaybeRelocatable;
 
+    /// Panics where [MaybeRelocatable::checked_sub] fails.
     fn sub(self, rhs: &MaybeRelocatable) -> Self::Output {
-        match self {
-            MaybeRelocatable::Int(lhs) => match rhs {
-                MaybeRelocatable::Int(rhs) => MaybeRelocatable::Int(lhs - rhs),
-                MaybeRelocatable::RelocatableValue(_) => {
-                    panic!("unsupported operand type(s) for -: 'int' and 'RelocatableValue'")
-                }
-            },
-            MaybeRelocatable::RelocatableValue(lhs) => lhs - rhs,
-        }
+        self.checked_sub(rhs).unwrap_or_else(|err| panic!("{err}"))
     }
 }
 
@@ -78,12 +127,27 @@ impl std::ops::Add<&BigInt> for MaybeRelocatable {
     }
 }
 
+impl std::ops::Add<usize> for MaybeRelocatable {
+    type Output = MaybeRelocatable;
+
+    fn add(self, rhs: usize) -> Self::Output {
+        match self {
+            MaybeRelocatable::Int(int) => MaybeRelocatable::Int(int + rhs),
+            MaybeRelocatable::RelocatableValue(value) => {
+                MaybeRelocatable::RelocatableValue(value + rhs)
+            }
+        }
+    }
+}
+
+/// Reduces the value modulo `rhs` like Python does: the result has the sign of `rhs`, so that
+/// reducing modulo the prime always yields a value in `[0, prime)`.
 impl std::ops::Rem<&BigInt> for MaybeRelocatable {
     type Output = MaybeRelocatable;
 
     fn rem(self, rhs: &BigInt) -> Self::Output {
         match self {
-            MaybeRelocatable::Int(int) => MaybeRelocatable::Int(int % rhs),
+            MaybeRelocatable::Int(int) => MaybeRelocatable::Int(python_rem(&int, rhs)),
             MaybeRelocatable::RelocatableValue(value) => {
                 MaybeRelocatable::RelocatableValue(value % rhs)
             }
@@ -109,6 +173,22 @@ impl std::cmp::PartialEq<RelocatableValue> for MaybeRelocatable {
     }
 }
 
+/// Integers are ordered by value, and relocatable values by offset within the same segment. Other
+/// pairs of values aren't comparable.
+impl PartialOrd for MaybeRelocatable {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (MaybeRelocatable::Int(lhs), MaybeRelocatable::Int(rhs)) => lhs.partial_cmp(rhs),
+            (MaybeRelocatable::RelocatableValue(lhs), MaybeRelocatable::RelocatableValue(rhs))
+                if lhs.segment_index == rhs.segment_index =>
+            {
+                lhs.offset.partial_cmp(&rhs.offset)
+            }
+            _ => None,
+        }
+    }
+}
+
 impl Display for MaybeRelocatable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -118,20 +198,225 @@ impl Display for MaybeRelocatable {
     }
 }
 
+impl MaybeRelocatable {
+    /// Adds `rhs` to the value, failing if the result is an address out of range.
+    pub fn checked_add(&self, rhs: &BigInt) -> Result<MaybeRelocatable, AddressOutOfRangeError> {
+        match self {
+            MaybeRelocatable::Int(int) => Ok(MaybeRelocatable::Int(int + rhs)),
+            MaybeRelocatable::RelocatableValue(value) => {
+                Ok(MaybeRelocatable::RelocatableValue(value.checked_add(rhs)?))
+            }
+        }
+    }
+
+    /// Subtracts `rhs` from the value. Like in Python, an address can't be subtracted from an
+    /// integer, and two addresses can only be subtracted if they're in the same segment.
+    pub fn checked_sub(
+        &self,
+        rhs: &MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, SubtractionError> {
+        match (self, rhs) {
+            (MaybeRelocatable::Int(lhs), MaybeRelocatable::Int(rhs)) => {
+                Ok(MaybeRelocatable::Int(lhs - rhs))
+            }
+            (MaybeRelocatable::Int(_), MaybeRelocatable::RelocatableValue(_)) => {
+                Err(SubtractionError::IntMinusRelocatable)
+            }
+            (MaybeRelocatable::RelocatableValue(lhs), MaybeRelocatable::Int(rhs)) => {
+                Ok(MaybeRelocatable::RelocatableValue(lhs.checked_add(&-rhs)?))
+            }
+            (MaybeRelocatable::RelocatableValue(lhs), MaybeRelocatable::RelocatableValue(rhs)) => {
+                if lhs.segment_index != rhs.segment_index {
+                    return Err(SubtractionError::SegmentMismatch {
+                        lhs: lhs.segment_index,
+                        rhs: rhs.segment_index,
+                    });
+                }
+
+                Ok(MaybeRelocatable::Int(BigInt::from(lhs.offset) - rhs.offset))
+            }
+        }
+    }
+
+    /// Computes `(self + rhs) % prime`, failing if the result is an address out of range.
+    ///
+    /// Adding two relocatable values panics, just like the `+` operator.
+    pub fn add_mod(
+        &self,
+        rhs: &MaybeRelocatable,
+        prime: &BigInt,
+    ) -> Result<MaybeRelocatable, AddressOutOfRangeError> {
+        match (self, rhs) {
+            (MaybeRelocatable::Int(lhs), MaybeRelocatable::Int(rhs)) => {
+                Ok(MaybeRelocatable::Int(python_rem(&(lhs + rhs), prime)))
+            }
+            (MaybeRelocatable::RelocatableValue(value), MaybeRelocatable::Int(int))
+            | (MaybeRelocatable::Int(int), MaybeRelocatable::RelocatableValue(value)) => Ok(
+                MaybeRelocatable::RelocatableValue(value.add_mod(int, prime)?),
+            ),
+            (MaybeRelocatable::RelocatableValue(lhs), MaybeRelocatable::RelocatableValue(rhs)) => {
+                panic!("Cannot add two relocatable values: {lhs} + {rhs}.")
+            }
+        }
+    }
+
+    /// Computes `(self + rhs) % prime` for a small `rhs`, failing if the result is an address out
+    /// of range. Unlike [MaybeRelocatable::add_mod], this doesn't allocate for addresses.
+    pub fn add_small_mod(
+        &self,
+        rhs: isize,
+        prime: &BigInt,
+    ) -> Result<MaybeRelocatable, AddressOutOfRangeError> {
+        match self {
+            MaybeRelocatable::Int(int) => {
+                Ok(MaybeRelocatable::Int(python_rem(&(int + rhs), prime)))
+            }
+            MaybeRelocatable::RelocatableValue(value) => Ok(MaybeRelocatable::RelocatableValue(
+                value.checked_add_signed(rhs)?,
+            )),
+        }
+    }
+
+    /// Parses a value supplied by the user. The following syntaxes are accepted:
+    /// * `2:17` - segment index and offset, as displayed by `RelocatableValue`.
+    /// * `execution:17` or `execution+17` - segment alias and offset.
+    /// * `execution` - the start of the aliased segment.
+    /// * `17` or `0x11` - a plain field element.
+    pub fn parse_user(input: &str, context: &AddressContext) -> Result<Self, AddressParseError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Err(AddressParseError::Empty);
+        }
+
+        if let Some((segment, offset)) = input.split_once(':').or_else(|| input.split_once('+')) {
+            let segment_index = context.segment_index(segment.trim())?;
+            let offset = parse_int(offset.trim())?;
+            return Ok(
+                RelocatableValue::from_big_ints(&segment_index.into(), &offset)
+                    .map_err(AddressParseError::OutOfRange)?
+                    .into(),
+            );
+        }
+
+        match context.segment_aliases.get(input) {
+            Some(segment_index) => Ok(RelocatableValue::new(*segment_index, 0).into()),
+            None => Ok(MaybeRelocatable::Int(parse_int(input)?)),
+        }
+    }
+}
+
+impl AddressContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_alias(mut self, alias: &str, segment_index: isize) -> Self {
+        self.segment_aliases.insert(alias.to_owned(), segment_index);
+        self
+    }
+
+    /// Resolves a segment alias, or a plain segment index, into a segment index.
+    pub fn segment_index(&self, segment: &str) -> Result<isize, AddressParseError> {
+        if let Some(segment_index) = self.segment_aliases.get(segment) {
+            return Ok(*segment_index);
+        }
+
+        isize::from_str(segment).map_err(|_| {
+            let mut known = self.segment_aliases.keys().cloned().collect::<Vec<_>>();
+            known.sort();
+            AddressParseError::UnknownSegment {
+                name: segment.to_owned(),
+                known,
+            }
+        })
+    }
+}
+
 impl RelocatableValue {
-    pub fn new(segment_index: BigInt, offset: BigInt) -> Self {
+    pub fn new(segment_index: isize, offset: usize) -> Self {
         Self {
             segment_index,
             offset,
         }
     }
+
+    /// Builds a value from arbitrary precision integers, e.g. ones coming from hints or
+    /// deserialized data.
+    pub fn from_big_ints(
+        segment_index: &BigInt,
+        offset: &BigInt,
+    ) -> Result<Self, AddressOutOfRangeError> {
+        match (isize::try_from(segment_index), usize::try_from(offset)) {
+            (Ok(segment_index), Ok(offset)) => Ok(Self::new(segment_index, offset)),
+            _ => Err(AddressOutOfRangeError {
+                segment_index: segment_index.to_owned(),
+                offset: offset.to_owned(),
+            }),
+        }
+    }
+
+    /// Adds `rhs` to the offset, failing if the result is out of range.
+    pub fn checked_add(&self, rhs: &BigInt) -> Result<Self, AddressOutOfRangeError> {
+        match isize::try_from(rhs) {
+            Ok(rhs) => self.checked_add_signed(rhs),
+            Err(_) => self.out_of_range(BigInt::from(self.offset) + rhs),
+        }
+    }
+
+    /// Adds `rhs` to the offset, failing if the result is out of range.
+    pub fn checked_add_signed(&self, rhs: isize) -> Result<Self, AddressOutOfRangeError> {
+        match self.offset.checked_add_signed(rhs) {
+            Some(offset) => Ok(Self::new(self.segment_index, offset)),
+            None => self.out_of_range(BigInt::from(self.offset) + rhs),
+        }
+    }
+
+    /// Computes `(self + rhs) % prime`, where only the offset is reduced. As any `usize` is smaller
+    /// than the prime, this only differs from [RelocatableValue::checked_add] for huge values of
+    /// `rhs`, e.g. field elements standing for negative numbers.
+    pub fn add_mod(&self, rhs: &BigInt, prime: &BigInt) -> Result<Self, AddressOutOfRangeError> {
+        match isize::try_from(rhs) {
+            Ok(rhs) => self.checked_add_signed(rhs),
+            Err(_) => {
+                let offset = python_rem(&(BigInt::from(self.offset) + rhs), prime);
+                match usize::try_from(&offset) {
+                    Ok(offset) => Ok(Self::new(self.segment_index, offset)),
+                    Err(_) => self.out_of_range(offset),
+                }
+            }
+        }
+    }
+
+    fn out_of_range<T>(&self, offset: BigInt) -> Result<T, AddressOutOfRangeError> {
+        Err(AddressOutOfRangeError {
+            segment_index: self.segment_index.into(),
+            offset,
+        })
+    }
 }
 
+/// Panics if the resulting offset is out of range.
 impl std::ops::Add<&BigInt> for RelocatableValue {
     type Output = RelocatableValue;
 
     fn add(self, rhs: &BigInt) -> Self::Output {
-        RelocatableValue::new(self.segment_index, self.offset + rhs)
+        self.checked_add(rhs).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+/// Panics if the resulting offset is out of range.
+impl std::ops::Add<usize> for RelocatableValue {
+    type Output = RelocatableValue;
+
+    fn add(self, rhs: usize) -> Self::Output {
+        match self.offset.checked_add(rhs) {
+            Some(offset) => RelocatableValue::new(self.segment_index, offset),
+            None => panic!(
+                "Address {}:{} is out of range.",
+                self.segment_index,
+                BigInt::from(self.offset) + rhs
+            ),
+        }
     }
 }
 
@@ -140,9 +425,7 @@ impl std::ops::Sub<&MaybeRelocatable> for RelocatableValue {
 
     fn sub(self, rhs: &MaybeRelocatable) -> Self::Output {
         match rhs {
-            MaybeRelocatable::Int(rhs) => MaybeRelocatable::RelocatableValue(
-                RelocatableValue::new(self.segment_index, self.offset - rhs),
-            ),
+            MaybeRelocatable::Int(rhs) => MaybeRelocatable::RelocatableValue(self + &-rhs),
             MaybeRelocatable::RelocatableValue(rhs) => {
                 if self.segment_index != rhs.segment_index {
                     // TODO: switch to proper error handling?
@@ -152,22 +435,321 @@ impl std::ops::Sub<&MaybeRelocatable> for RelocatableValue {
                     );
                 }
 
-                MaybeRelocatable::Int(self.offset - &rhs.offset)
+                MaybeRelocatable::Int(BigInt::from(self.offset) - rhs.offset)
             }
         }
     }
 }
 
+/// Reduces the offset modulo `rhs` like Python does, see the `Rem` implementation of
+/// [MaybeRelocatable]. Panics if the result is negative, which may only happen for a negative
+/// `rhs`.
 impl std::ops::Rem<&BigInt> for RelocatableValue {
     type Output = RelocatableValue;
 
     fn rem(self, rhs: &BigInt) -> Self::Output {
-        RelocatableValue::new(self.segment_index, self.offset % rhs)
+        match usize::try_from(rhs) {
+            Ok(rhs) => RelocatableValue::new(self.segment_index, self.offset % rhs),
+            // The offset is smaller than any modulus beyond the usize range, e.g. the prime.
+            Err(_) if rhs.sign() == Sign::Plus => self,
+            Err(_) => {
+                let offset = python_rem(&self.offset.into(), rhs);
+                RelocatableValue::from_big_ints(&self.segment_index.into(), &offset)
+                    .unwrap_or_else(|err| panic!("{err}"))
+            }
+        }
     }
 }
 
+/// Panics on a zero modulus, just like the `%` operator.
+fn python_rem(x: &BigInt, p: &BigInt) -> BigInt {
+    modulo(x, p).expect("attempt to calculate the remainder with a divisor of zero")
+}
+
 impl Display for RelocatableValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.segment_index, self.offset)
     }
 }
+
+/// Parses a decimal or `0x`-prefixed hexadecimal integer.
+fn parse_int(input: &str) -> Result<BigInt, AddressParseError> {
+    let value = match input.strip_prefix("0x") {
+        Some(hex) => BigInt::parse_bytes(hex.as_bytes(), 16),
+        None => BigInt::from_str(input).ok(),
+    };
+
+    value.ok_or_else(|| AddressParseError::InvalidNumber {
+        input: input.to_owned(),
+    })
+}
+
+/// Relocates a value to its final address in the linear memory, given the segment offsets
+/// computed by `MemorySegmentManager::relocate_segments`. Integers are returned as-is.
+pub fn relocate_value(
+    value: &MaybeRelocatable,
+    segment_offsets: &HashMap<isize, BigInt>,
+    prime: &BigInt,
+) -> Result<BigInt, RelocationError> {
+    match value {
+        MaybeRelocatable::Int(value) => Ok(value.to_owned()),
+        MaybeRelocatable::RelocatableValue(value) => {
+            let segment_offset = segment_offsets.get(&value.segment_index).ok_or(
+                RelocationError::MissingSegmentOffset {
+                    segment_index: value.segment_index,
+                },
+            )?;
+
+            let relocated = segment_offset + value.offset;
+            if &relocated >= prime {
+                return Err(RelocationError::ValueExceedsPrime { value: relocated });
+            }
+
+            Ok(relocated)
+        }
+    }
+}
+
+impl From<AddressOutOfRangeError> for SubtractionError {
+    fn from(value: AddressOutOfRangeError) -> Self {
+        Self::AddressOutOfRange(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> AddressContext {
+        AddressContext::new()
+            .with_alias("program", 0)
+            .with_alias("execution", 1)
+            .with_alias("output_builtin", 2)
+    }
+
+    fn relocatable(segment_index: isize, offset: usize) -> MaybeRelocatable {
+        RelocatableValue::new(segment_index, offset).into()
+    }
+
+    fn prime() -> BigInt {
+        BigInt::from(2u32).pow(251) + BigInt::from(17u32) * BigInt::from(2u32).pow(192) + 1u32
+    }
+
+    #[test]
+    fn test_parse_user() {
+        let context = context();
+        for (input, expected) in [
+            ("2:17", relocatable(2, 17)),
+            ("-1:3", relocatable(-1, 3)),
+            (" 1 : 0x10 ", relocatable(1, 16)),
+            ("execution+5", relocatable(1, 5)),
+            ("output_builtin:0", relocatable(2, 0)),
+            ("program", relocatable(0, 0)),
+            ("17", MaybeRelocatable::Int(17u32.into())),
+            ("0x11", MaybeRelocatable::Int(17u32.into())),
+            ("-1", MaybeRelocatable::Int((-1).into())),
+        ] {
+            assert_eq!(
+                MaybeRelocatable::parse_user(input, &context).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_user_round_trip() {
+        let context = context();
+        for value in [relocatable(3, 42), MaybeRelocatable::Int(12345u32.into())] {
+            assert_eq!(
+                MaybeRelocatable::parse_user(&value.to_string(), &context).unwrap(),
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_user_errors() {
+        let context = context();
+
+        assert!(matches!(
+            MaybeRelocatable::parse_user("  ", &context),
+            Err(AddressParseError::Empty)
+        ));
+        assert!(matches!(
+            MaybeRelocatable::parse_user("0xzz", &context),
+            Err(AddressParseError::InvalidNumber { input }) if input == "0xzz"
+        ));
+        assert!(matches!(
+            MaybeRelocatable::parse_user("execution:abc", &context),
+            Err(AddressParseError::InvalidNumber { input }) if input == "abc"
+        ));
+
+        let err = MaybeRelocatable::parse_user("exec+1", &context).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown segment \"exec\". Known segments: execution, output_builtin, program."
+        );
+
+        let err = MaybeRelocatable::parse_user("execution:-1", &context).unwrap_err();
+        assert_eq!(err.to_string(), "Address 1:-1 is out of range.");
+    }
+
+    #[test]
+    fn test_ordering() {
+        let int = |value: i32| MaybeRelocatable::Int(value.into());
+
+        assert!(int(-1) < int(0));
+        assert!(int(7) > int(3));
+        assert!(relocatable(1, 2) < relocatable(1, 10));
+        assert!(relocatable(1, 2) <= relocatable(1, 2));
+
+        assert_eq!(relocatable(1, 2).partial_cmp(&relocatable(2, 0)), None);
+        assert_eq!(int(1).partial_cmp(&relocatable(1, 1)), None);
+
+        let mut addrs = [
+            RelocatableValue::new(2, 0),
+            RelocatableValue::new(1, 5),
+            RelocatableValue::new(-1, 3),
+            RelocatableValue::new(1, 1),
+        ];
+        addrs.sort();
+        assert_eq!(
+            addrs
+                .iter()
+                .map(|addr| addr.to_string())
+                .collect::<Vec<_>>(),
+            vec!["-1:3", "1:1", "1:5", "2:0"]
+        );
+    }
+
+    #[test]
+    fn test_sub() {
+        let int = |value: i32| MaybeRelocatable::Int(value.into());
+
+        assert_eq!(int(7) - &int(10), int(-3));
+        assert_eq!(relocatable(1, 7) - &int(2), relocatable(1, 5));
+        assert_eq!(relocatable(1, 7) - &relocatable(1, 2), int(5));
+    }
+
+    #[test]
+    fn test_rem() {
+        let int = |value: i32| MaybeRelocatable::Int(value.into());
+        let prime = prime();
+
+        // The product wraps to a negative value, whose residue is still in [0, prime).
+        assert_eq!(
+            MaybeRelocatable::Int(BigInt::from(-2) * BigInt::from(5)) % &prime,
+            MaybeRelocatable::Int(&prime - 10u32)
+        );
+        assert_eq!(int(10) % &BigInt::from(7u32), int(3));
+        assert_eq!(int(-7) % &BigInt::from(7u32), int(0));
+        assert_eq!(
+            RelocatableValue::new(1, 10) % &BigInt::from(7u32),
+            RelocatableValue::new(1, 3)
+        );
+        assert_eq!(
+            RelocatableValue::new(1, 10) % &prime,
+            RelocatableValue::new(1, 10)
+        );
+    }
+
+    #[test]
+    fn test_add_mod() {
+        let prime = prime();
+        let int = |value: i32| MaybeRelocatable::Int(value.into());
+
+        assert_eq!(
+            int(-3).add_mod(&int(1), &prime).unwrap(),
+            MaybeRelocatable::Int(&prime - 2u32)
+        );
+        assert_eq!(
+            relocatable(1, 7).add_small_mod(-2, &prime).unwrap(),
+            relocatable(1, 5)
+        );
+
+        // Field elements above half the prime stand for negative numbers.
+        let minus_two = MaybeRelocatable::Int(&prime - 2u32);
+        assert_eq!(
+            relocatable(1, 7).add_mod(&minus_two, &prime).unwrap(),
+            relocatable(1, 5)
+        );
+        assert_eq!(
+            minus_two.add_mod(&relocatable(1, 7), &prime).unwrap(),
+            relocatable(1, 5)
+        );
+
+        let err = relocatable(1, 1).add_small_mod(-2, &prime).unwrap_err();
+        assert_eq!(err.to_string(), "Address 1:-1 is out of range.");
+        assert!(relocatable(1, 1).add_mod(&minus_two, &prime).is_err());
+    }
+
+    #[test]
+    fn test_offsets_beyond_u32() {
+        let offset = 1usize << 33;
+        let addr = RelocatableValue::new(1, offset);
+
+        assert_eq!(addr + 5usize, RelocatableValue::new(1, offset + 5));
+        assert_eq!(
+            MaybeRelocatable::from(addr) - &relocatable(1, 3),
+            MaybeRelocatable::Int(BigInt::from(offset) - 3u32)
+        );
+        assert_eq!(
+            RelocatableValue::from_big_ints(&1u32.into(), &BigInt::from(offset)).unwrap(),
+            addr
+        );
+        assert_eq!(addr.to_string(), "1:8589934592");
+        assert_eq!(
+            MaybeRelocatable::parse_user("1:8589934592", &context()).unwrap(),
+            MaybeRelocatable::from(addr)
+        );
+        assert_eq!(
+            relocate_value(
+                &addr.into(),
+                &HashMap::from([(1, BigInt::from(100u32))]),
+                &prime()
+            )
+            .unwrap(),
+            BigInt::from(offset) + 100u32
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Address 1:-1 is out of range.")]
+    fn test_sub_out_of_range() {
+        let _ = relocatable(1, 1) - &MaybeRelocatable::Int(2u32.into());
+    }
+
+    #[test]
+    fn test_checked_sub() {
+        let int = |value: i32| MaybeRelocatable::Int(value.into());
+
+        assert_eq!(int(7).checked_sub(&int(10)).unwrap(), int(-3));
+        assert_eq!(
+            relocatable(1, 7).checked_sub(&int(2)).unwrap(),
+            relocatable(1, 5)
+        );
+        assert_eq!(
+            relocatable(1, 7).checked_sub(&relocatable(1, 2)).unwrap(),
+            int(5)
+        );
+
+        assert!(matches!(
+            int(7).checked_sub(&relocatable(1, 2)),
+            Err(SubtractionError::IntMinusRelocatable)
+        ));
+        assert!(matches!(
+            relocatable(1, 7).checked_sub(&relocatable(2, 2)),
+            Err(SubtractionError::SegmentMismatch { lhs: 1, rhs: 2 })
+        ));
+        assert!(matches!(
+            relocatable(1, 2).checked_sub(&int(3)),
+            Err(SubtractionError::AddressOutOfRange(_))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported operand type(s) for -: 'int' and 'RelocatableValue'")]
+    fn test_sub_int_and_relocatable() {
+        let _ = MaybeRelocatable::Int(7u32.into()) - &relocatable(1, 2);
+    }
+}
@@ -0,0 +1,183 @@
+//! Running programs from multiple threads.
+//!
+//! `CairoRunner` is `!Send`: the VM shares its memory and registers through `Rc`, and hints run in
+//! a RustPython `Interpreter`, which is bound to the thread it was created on. A runner therefore
+//! can't be moved to another thread:
+//!
+//! ```compile_fail
+//! fn assert_send<T: Send>() {}
+//! assert_send::<oriac::cairo::lang::vm::cairo_runner::CairoRunner>();
+//! ```
+//!
+//! `ThreadLocalRunnerPool` is the `Send + Sync` side of that boundary. It only holds the program
+//! JSON and the layout name, and builds the runners on the thread that uses them.
+
+use crate::cairo::lang::{
+    compiler::program::{FullProgram, Program, ProgramLoadError, Strictness},
+    instances::{CairoLayout, UnknownLayoutError},
+    vm::{
+        cairo_runner::{CairoRunner, Error as CairoRunnerError},
+        memory_dict::MemoryDict,
+    },
+};
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The programs loaded on the current thread, keyed by the id of their pool.
+    static PROGRAMS: RefCell<HashMap<usize, Rc<Program>>> = RefCell::new(HashMap::new());
+}
+
+/// Runs a program on any number of threads, giving each run a fresh runner created on the calling
+/// thread.
+///
+/// The program is parsed once per thread and kept until the thread exits. Runners can't be reused
+/// after a run, so a new one is created for every call to [ThreadLocalRunnerPool::run].
+#[derive(Debug, Clone)]
+pub struct ThreadLocalRunnerPool {
+    id: usize,
+    program_json: Arc<str>,
+    layout: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    ProgramLoadError(ProgramLoadError),
+    #[error(transparent)]
+    UnknownLayout(UnknownLayoutError),
+    #[error(transparent)]
+    CairoRunnerError(CairoRunnerError),
+}
+
+impl ThreadLocalRunnerPool {
+    /// Creates a pool running the program in `program_json` with the layout named `layout`. Both
+    /// are validated right away.
+    pub fn new(program_json: String, layout: &str) -> Result<Self, Error> {
+        let pool = Self {
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+            program_json: program_json.into(),
+            layout: layout.to_owned(),
+        };
+
+        // Loading the program on the current thread surfaces errors here rather than on the first
+        // run.
+        pool.program()?;
+        CairoLayout::from_name(layout)?;
+
+        Ok(pool)
+    }
+
+    /// Calls `f` with a new runner for the program, created on the current thread.
+    pub fn run<F, R>(&self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut CairoRunner) -> R,
+    {
+        let mut runner = CairoRunner::new(
+            self.program()?,
+            CairoLayout::from_name(&self.layout)?,
+            MemoryDict::new(),
+            false,
+            false,
+        )?;
+
+        Ok(f(&mut runner))
+    }
+
+    /// Returns the program loaded on the current thread, loading it first if needed.
+    fn program(&self) -> Result<Rc<Program>, Error> {
+        if let Some(program) = PROGRAMS.with(|programs| programs.borrow().get(&self.id).cloned()) {
+            return Ok(program);
+        }
+
+        let (program, _) =
+            FullProgram::from_json_strict(&self.program_json, Strictness::Permissive)?;
+        let program = Rc::new(Program::from(program));
+        PROGRAMS.with(|programs| programs.borrow_mut().insert(self.id, program.clone()));
+
+        Ok(program)
+    }
+}
+
+impl From<ProgramLoadError> for Error {
+    fn from(value: ProgramLoadError) -> Self {
+        Self::ProgramLoadError(value)
+    }
+}
+
+impl From<UnknownLayoutError> for Error {
+    fn from(value: UnknownLayoutError) -> Self {
+        Self::UnknownLayout(value)
+    }
+}
+
+impl From<CairoRunnerError> for Error {
+    fn from(value: CairoRunnerError) -> Self {
+        Self::CairoRunnerError(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use num_bigint::BigInt;
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ThreadLocalRunnerPool>();
+    }
+
+    #[test]
+    fn test_run_on_threads() {
+        let pool = ThreadLocalRunnerPool::new(
+            include_str!("../../../../test-data/artifacts/run_past_end.json").to_owned(),
+            "plain",
+        )
+        .unwrap();
+
+        let handles = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    // Runs twice to go through the program cached on the thread.
+                    (0..2)
+                        .map(|_| {
+                            pool.run(|runner| runner.run_until_end(HashMap::new()).unwrap().steps)
+                                .unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            assert_eq!(
+                handle.join().unwrap(),
+                vec![BigInt::from(1u32), BigInt::from(1u32)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_layout() {
+        assert!(matches!(
+            ThreadLocalRunnerPool::new(
+                include_str!("../../../../test-data/artifacts/run_past_end.json").to_owned(),
+                "starknet",
+            ),
+            Err(Error::UnknownLayout(_))
+        ));
+    }
+}
@@ -1,8 +1,40 @@
+use crate::cairo::lang::vm::relocatable::{relocate_value, MaybeRelocatable, RelocationError};
+
+use num_bigint::BigInt;
+use std::collections::HashMap;
+
 /// A trace entry for every instruction that was executed. Holds the register values before the
 /// instruction was executed.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TraceEntry<T> {
     pub pc: T,
     pub ap: T,
     pub fp: T,
 }
+
+impl TraceEntry<MaybeRelocatable> {
+    /// Relocates the register values of the entry into their final addresses.
+    pub fn relocate(
+        &self,
+        segment_offsets: &HashMap<isize, BigInt>,
+        prime: &BigInt,
+    ) -> Result<TraceEntry<BigInt>, RelocationError> {
+        Ok(TraceEntry {
+            pc: relocate_value(&self.pc, segment_offsets, prime)?,
+            ap: relocate_value(&self.ap, segment_offsets, prime)?,
+            fp: relocate_value(&self.fp, segment_offsets, prime)?,
+        })
+    }
+}
+
+/// Relocates all the entries of a trace into their final addresses.
+pub fn relocate_trace(
+    trace: &[TraceEntry<MaybeRelocatable>],
+    segment_offsets: &HashMap<isize, BigInt>,
+    prime: &BigInt,
+) -> Result<Vec<TraceEntry<BigInt>>, RelocationError> {
+    trace
+        .iter()
+        .map(|entry| entry.relocate(segment_offsets, prime))
+        .collect()
+}
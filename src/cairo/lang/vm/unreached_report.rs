@@ -0,0 +1,98 @@
+//! The parts of a program that a run never reached, to spot dead code and hints that tests don't
+//! cover.
+
+use crate::cairo::lang::compiler::{
+    identifier_definition::IdentifierDefinition, program::FullProgram, scoped_name::ScopedName,
+};
+
+use num_bigint::BigInt;
+use std::{collections::HashSet, fmt::Display};
+
+/// The hints and functions of a program whose pc was never executed, ordered by pc.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnreachedReport {
+    pub hints: Vec<UnreachedHint>,
+    pub functions: Vec<UnreachedFunction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachedHint {
+    pub pc: BigInt,
+    /// The index of the hint among the hints at `pc`.
+    pub index: usize,
+    /// The first non-blank line of the code, to recognize the hint by.
+    pub first_line: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachedFunction {
+    pub name: ScopedName,
+    pub pc: BigInt,
+}
+
+impl UnreachedReport {
+    /// Builds the report of `program` from the pcs executed by a run, relative to the program base.
+    pub fn new(program: &FullProgram, executed_pcs: &HashSet<BigInt>) -> Self {
+        let mut hints = program
+            .hints
+            .iter()
+            .filter(|(pc, _)| !executed_pcs.contains(pc))
+            .flat_map(|(pc, hints)| {
+                hints
+                    .iter()
+                    .enumerate()
+                    .map(move |(index, hint)| UnreachedHint {
+                        pc: pc.to_owned(),
+                        index,
+                        first_line: hint
+                            .code
+                            .lines()
+                            .map(str::trim)
+                            .find(|line| !line.is_empty())
+                            .unwrap_or_default()
+                            .to_owned(),
+                    })
+            })
+            .collect::<Vec<_>>();
+        hints.sort_by(|a, b| (&a.pc, a.index).cmp(&(&b.pc, b.index)));
+
+        let mut functions = program
+            .identifiers
+            .shared_state
+            .borrow()
+            .dict
+            .iter()
+            .filter_map(|(name, definition)| match definition {
+                IdentifierDefinition::Function { pc, .. } if !executed_pcs.contains(pc) => {
+                    Some(UnreachedFunction {
+                        name: name.to_owned(),
+                        pc: pc.to_owned(),
+                    })
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Names break ties between aliases of the same function.
+        functions.sort_by(|a, b| (&a.pc, &a.name).cmp(&(&b.pc, &b.name)));
+
+        Self { hints, functions }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hints.is_empty() && self.functions.is_empty()
+    }
+}
+
+impl Display for UnreachedReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Unreached hints: {}", self.hints.len())?;
+        for hint in self.hints.iter() {
+            writeln!(f, "  pc={} #{}: {}", hint.pc, hint.index, hint.first_line)?;
+        }
+        writeln!(f, "Unreached functions: {}", self.functions.len())?;
+        for function in self.functions.iter() {
+            writeln!(f, "  pc={}: {}", function.pc, function.name)?;
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use num_bigint::BigInt;
 
 /// Maintains the resources of a Cairo run. Can be used across multiple runners.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RunResources {
     pub n_steps: Option<BigInt>,
 }
@@ -22,3 +22,20 @@ impl RunResources {
         }
     }
 }
+
+/// Returns the smallest power of 2 that is not smaller than `x`.
+pub fn next_power_of_2(x: &BigInt) -> BigInt {
+    BigInt::from(1u32) << (x - BigInt::from(1u32)).bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_power_of_2() {
+        for (x, expected) in [(1u32, 1u32), (2, 2), (3, 4), (4, 4), (5, 8), (1000, 1024)] {
+            assert_eq!(next_power_of_2(&x.into()), expected.into());
+        }
+    }
+}
@@ -1,9 +1,9 @@
 use crate::cairo::lang::vm::{
+    builtin_runner::Error as BuiltinRunnerError,
     memory_dict::{Error as MemoryDictError, MemoryDict},
     relocatable::{MaybeRelocatable, RelocatableValue},
 };
 
-use num_bigint::BigInt;
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
@@ -11,8 +11,15 @@ use std::{
     rc::Rc,
 };
 
+pub type ValidationRuleFn = dyn Fn(
+    &MemoryDict,
+    &RelocatableValue,
+    &(),
+) -> Result<HashSet<RelocatableValue>, BuiltinRunnerError>;
+
+/// A rule validating the value of a memory cell, and returning the set of addresses it validated.
 pub struct ValidationRule {
-    pub inner: fn(&MemoryDict, &RelocatableValue, &()) -> HashSet<RelocatableValue>,
+    pub inner: Box<ValidationRuleFn>,
 }
 
 /// A proxy to MemoryDict which validates memory values in specific segments upon writing to it.
@@ -24,7 +31,7 @@ pub struct ValidatedMemoryDict {
     /// validation_rules contains a mapping from a segment index to a list of functions (and a tuple
     /// of additional arguments) that may try to validate the value of memory cells in the segment
     /// (sometimes based on other memory cells).
-    pub validation_rules: HashMap<BigInt, Vec<(ValidationRule, ())>>,
+    pub validation_rules: HashMap<isize, Vec<(ValidationRule, ())>>,
     /// A list of addresses which were already validated.
     pub validated_addresses: HashSet<RelocatableValue>,
 }
@@ -56,20 +63,54 @@ impl ValidatedMemoryDict {
         self.memory.borrow_mut().index(addr)
     }
 
-    pub fn index_set(&mut self, addr: MaybeRelocatable, value: MaybeRelocatable) {
+    pub fn index_set(
+        &mut self,
+        addr: MaybeRelocatable,
+        value: MaybeRelocatable,
+    ) -> Result<(), BuiltinRunnerError> {
         self.memory
             .borrow_mut()
-            .index_set(addr.clone(), value.clone());
-        self.validate_memory_cell(addr, value);
+            .index_set(addr.clone(), value.clone())?;
+        self.validate_memory_cell(addr, value)
+    }
+
+    /// Adds a validation rule for the memory cells of the given segment.
+    pub fn add_validation_rule(&mut self, segment_index: isize, rule: ValidationRule, args: ()) {
+        self.validation_rules
+            .entry(segment_index)
+            .or_default()
+            .push((rule, args));
     }
 
-    fn validate_memory_cell(&mut self, addr: MaybeRelocatable, _value: MaybeRelocatable) {
+    /// Validates the memory cells that were written before the validation rules were added, e.g.
+    /// the program input.
+    pub fn validate_existing_memory(&mut self) -> Result<(), BuiltinRunnerError> {
+        let cells = self
+            .memory
+            .borrow()
+            .data
+            .iter()
+            .map(|(addr, value)| (addr.to_owned(), value.to_owned()))
+            .collect::<Vec<_>>();
+
+        for (addr, value) in cells.into_iter() {
+            self.validate_memory_cell(addr, value)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_memory_cell(
+        &mut self,
+        addr: MaybeRelocatable,
+        _value: MaybeRelocatable,
+    ) -> Result<(), BuiltinRunnerError> {
         if let MaybeRelocatable::RelocatableValue(addr) = addr {
             if !self.validated_addresses.contains(&addr) {
                 if let Some(rules) = self.validation_rules.get(&addr.segment_index) {
                     for (rule, args) in rules.iter() {
                         let validated_addresses =
-                            (rule.inner)(&self.memory.as_ref().borrow(), &addr, args);
+                            (rule.inner)(&self.memory.as_ref().borrow(), &addr, args)?;
                         for addr in validated_addresses.into_iter() {
                             self.validated_addresses.insert(addr);
                         }
@@ -77,5 +118,7 @@ impl ValidatedMemoryDict {
                 }
             }
         }
+
+        Ok(())
     }
 }
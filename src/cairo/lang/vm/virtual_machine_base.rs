@@ -1,9 +1,100 @@
-use rustpython_vm::bytecode::CodeObject;
+use crate::{
+    cairo::lang::{
+        compiler::{
+            identifier_manager::IdentifierManager,
+            preprocessor::{flow::ReferenceManager, preprocessor::AttributeScope},
+        },
+        vm::{relocatable::MaybeRelocatable, vm_consts::VmConsts},
+    },
+    hint_support::native_hints::NativeHint,
+};
+
+use once_cell::unsync::OnceCell;
+use rustpython_vm::{
+    bytecode::CodeObject,
+    compile::{CompileError, CompileOpts, Mode},
+};
+use std::rc::Rc;
+
+/// The name of the attribute set by `with_attr error_message(...)`.
+pub const ERROR_MESSAGE_ATTRIBUTE: &str = "error_message";
+
+/// The code run for a hint.
+#[derive(Debug)]
+pub enum HintCode {
+    Python(PythonHint),
+    /// A Rust port of the hint, run without the Python interpreter.
+    Native(NativeHint),
+}
+
+/// The Python code of a hint, compiled on first use.
+#[derive(Debug)]
+pub struct PythonHint {
+    pub code: String,
+    /// The file name of the hint in Python errors, e.g. `<hint0>`.
+    pub filename: String,
+    compiled: OnceCell<CodeObject>,
+}
 
 #[derive(Debug)]
 pub struct CompiledHint {
-    pub compiled: CodeObject,
-    pub consts: (),
+    pub compiled: HintCode,
+    pub consts: Rc<VmConsts>,
+}
+
+impl PythonHint {
+    pub fn new(code: String, filename: String) -> Self {
+        Self {
+            code,
+            filename,
+            compiled: OnceCell::new(),
+        }
+    }
+
+    /// Returns the compiled hint, compiling it if it's not compiled yet.
+    pub fn compile(&self) -> Result<&CodeObject, CompileError> {
+        self.compiled.get_or_try_init(|| {
+            rustpython_vm::compile::compile(
+                &self.code,
+                Mode::Exec,
+                self.filename.clone(),
+                CompileOpts::default(),
+            )
+        })
+    }
+}
+
+/// An attribute scope, relocated to the program base.
+#[derive(Debug)]
+pub struct VmAttributeScope {
+    pub start_pc: MaybeRelocatable,
+    pub end_pc: MaybeRelocatable,
+    pub value: String,
+    /// The references accessible from the attribute value, if it has flow tracking data.
+    pub consts: Option<VmConsts>,
+}
+
+impl VmAttributeScope {
+    pub fn from_attribute_scope(
+        attr: &AttributeScope,
+        program_base: &MaybeRelocatable,
+        reference_manager: &ReferenceManager,
+        identifiers: &IdentifierManager,
+    ) -> Self {
+        Self {
+            start_pc: program_base.to_owned() + &attr.start_pc,
+            end_pc: program_base.to_owned() + &attr.end_pc,
+            value: attr.value.clone(),
+            consts: attr.flow_tracking_data.as_ref().map(|flow_tracking_data| {
+                VmConsts::new(
+                    flow_tracking_data,
+                    &attr.accessible_scopes,
+                    reference_manager,
+                    identifiers,
+                )
+            }),
+        }
+    }
 }
 
 // There's no `VirtualMachineBase`. All base class functionalities have been merged into
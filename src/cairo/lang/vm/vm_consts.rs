@@ -0,0 +1,623 @@
+use crate::cairo::lang::{
+    compiler::{
+        identifier_definition::IdentifierDefinition,
+        identifier_manager::IdentifierManager,
+        instruction::Register,
+        preprocessor::flow::{FlowTrackingDataActual, ReferenceManager, RegTrackingData},
+        references::Reference,
+        scoped_name::ScopedName,
+    },
+    vm::{
+        memory_dict::MemoryDict,
+        relocatable::{AddressOutOfRangeError, MaybeRelocatable},
+    },
+};
+
+use num_bigint::BigInt;
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+/// A Cairo type, as found in the `cairo_type` field of members and references.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CairoType {
+    Felt,
+    Pointer(Box<CairoType>),
+    Struct(ScopedName),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Unsupported type \"{cairo_type}\".")]
+    UnsupportedType { cairo_type: String },
+    #[error("Unknown struct {name}.")]
+    UnknownStruct { name: ScopedName },
+    #[error("'{path}' is of type {cairo_type}, which has no members.")]
+    NotAStruct { path: String, cairo_type: CairoType },
+    #[error("'{struct_name}' has no member named '{member}' (accessing '{path}').")]
+    UnknownMember {
+        path: String,
+        member: String,
+        struct_name: ScopedName,
+    },
+    #[error("Unknown value for memory cell at address {addr} (accessing '{path}').")]
+    UnknownMemory {
+        path: String,
+        addr: MaybeRelocatable,
+    },
+    #[error("Unknown identifier '{path}'.")]
+    UnknownIdentifier { path: String },
+    #[error("'{path}' is a constant, which has no address and cannot be assigned.")]
+    NotAReference { path: String },
+    #[error("'{path}' is defined as \"{value}\", which is not supported yet. Only references to felts at a fixed offset from ap or fp are supported.")]
+    UnsupportedReference { path: String, value: String },
+    #[error("'{path}' cannot be used here, as the ap register it references was revoked.")]
+    RevokedReference { path: String },
+    #[error("'{path}' cannot be used here, as it references ap, which is unknown.")]
+    UnknownAp { path: String },
+    #[error(transparent)]
+    AddressOutOfRange(AddressOutOfRangeError),
+}
+
+/// The references and constants accessible from a hint through `ids`, resolved when the hint is
+/// loaded.
+///
+/// Only references to felts at a fixed offset from ap or fp (e.g. `local`, `tempvar` and felt
+/// arguments) can be accessed for now.
+#[derive(Debug, Clone)]
+pub struct VmConsts {
+    /// The ap tracking data at the location of the hint, used to adjust ap-based references that
+    /// were defined before ap changed.
+    ap_tracking: RegTrackingData,
+    /// The references visible from the hint, keyed by their short names.
+    references: HashMap<String, Reference>,
+    /// The `const` values visible from the hint, keyed by their short names.
+    constants: HashMap<String, BigInt>,
+}
+
+/// A value of a given Cairo type stored in memory, as accessed from hints through `ids`. Accessing
+/// a member of a struct, or of a pointer to a struct, yields another `TypedLocation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypedLocation {
+    /// The access path, e.g. `ids.x.y`, used in error messages.
+    pub path: String,
+    pub addr: MaybeRelocatable,
+    pub cairo_type: CairoType,
+}
+
+/// The result of reading a `TypedLocation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmConstsValue {
+    /// The value of a felt or a pointer.
+    Value(MaybeRelocatable),
+    /// A struct, whose members can be further accessed.
+    Struct(TypedLocation),
+}
+
+impl VmConsts {
+    pub fn new(
+        flow_tracking_data: &FlowTrackingDataActual,
+        accessible_scopes: &[ScopedName],
+        reference_manager: &ReferenceManager,
+        identifiers: &IdentifierManager,
+    ) -> Self {
+        // Names defined in inner scopes shadow the ones from outer scopes, which come first.
+        let mut references = HashMap::new();
+        let mut constants = HashMap::new();
+        for scope in accessible_scopes.iter() {
+            for full_name in flow_tracking_data.reference_ids.keys() {
+                let name = match full_name.path.last() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if &(scope + name.to_owned()) != full_name {
+                    continue;
+                }
+
+                if let Some(reference) =
+                    flow_tracking_data.resolve_reference(reference_manager, full_name)
+                {
+                    constants.remove(name);
+                    references.insert(name.to_owned(), reference.to_owned());
+                }
+            }
+
+            // Constants too large to be loaded have no value, and are left unknown.
+            for (full_name, definition) in identifiers.shared_state.borrow().dict.iter() {
+                let value = match definition {
+                    IdentifierDefinition::Const { value: Some(value) } => value,
+                    _ => continue,
+                };
+                match full_name.path.split_last() {
+                    Some((name, path)) if path == scope.path.as_slice() => {
+                        references.remove(name);
+                        constants.insert(name.to_owned(), value.to_owned());
+                    }
+                    _ => continue,
+                }
+            }
+        }
+
+        Self {
+            ap_tracking: flow_tracking_data.ap_tracking.clone(),
+            references,
+            constants,
+        }
+    }
+
+    /// Returns the value of `ids.<name>` if it is a constant.
+    pub fn get_constant(&self, name: &str) -> Option<&BigInt> {
+        self.constants.get(name)
+    }
+
+    /// Returns the address of the felt that `ids.<name>` refers to, given the current registers.
+    pub fn get_address(
+        &self,
+        name: &str,
+        ap: &MaybeRelocatable,
+        fp: &MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        self.resolve_address(name, Some(ap), fp)
+    }
+
+    /// Like `get_address`, for when ap is unknown. Only fp-based references can be resolved.
+    pub fn get_fp_address(
+        &self,
+        name: &str,
+        fp: &MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        self.resolve_address(name, None, fp)
+    }
+
+    fn resolve_address(
+        &self,
+        name: &str,
+        ap: Option<&MaybeRelocatable>,
+        fp: &MaybeRelocatable,
+    ) -> Result<MaybeRelocatable, Error> {
+        let path = format!("ids.{}", name);
+        if self.constants.contains_key(name) {
+            return Err(Error::NotAReference { path });
+        }
+        let reference = self
+            .references
+            .get(name)
+            .ok_or_else(|| Error::UnknownIdentifier { path: path.clone() })?;
+        let simple_reference =
+            reference
+                .parse_simple()
+                .ok_or_else(|| Error::UnsupportedReference {
+                    path: path.clone(),
+                    value: reference.value.clone(),
+                })?;
+
+        match simple_reference.register {
+            Register::FP => Ok(fp.checked_add(&simple_reference.offset)?),
+            Register::AP => {
+                // ap may have moved since the reference was defined, which can only be accounted
+                // for within the same ap tracking group.
+                let ap = ap.ok_or_else(|| Error::UnknownAp { path: path.clone() })?;
+                if reference.ap_tracking_data.group != self.ap_tracking.group {
+                    return Err(Error::RevokedReference { path });
+                }
+                let ap_diff = &self.ap_tracking.offset - &reference.ap_tracking_data.offset;
+                Ok(ap.checked_add(&(simple_reference.offset - ap_diff))?)
+            }
+        }
+    }
+}
+
+impl TypedLocation {
+    pub fn new(path: String, addr: MaybeRelocatable, cairo_type: CairoType) -> Self {
+        Self {
+            path,
+            addr,
+            cairo_type,
+        }
+    }
+
+    /// Accesses a member of a struct. Pointers to structs are dereferenced first, so that
+    /// `ids.ptr.x` reads `[ptr + offset(x)]`.
+    pub fn member(
+        &self,
+        name: &str,
+        identifiers: &IdentifierManager,
+        memory: &mut MemoryDict,
+    ) -> Result<TypedLocation, Error> {
+        let (struct_name, struct_addr) = match &self.cairo_type {
+            CairoType::Struct(struct_name) => (struct_name, self.addr.clone()),
+            CairoType::Pointer(pointee) => match pointee.as_ref() {
+                CairoType::Struct(struct_name) => (struct_name, self.read(memory)?),
+                _ => return Err(self.not_a_struct()),
+            },
+            CairoType::Felt => return Err(self.not_a_struct()),
+        };
+
+        let members = match get_struct_definition(struct_name, identifiers)? {
+            IdentifierDefinition::Struct { members, .. } => members,
+            _ => unreachable!("get_struct_definition only returns structs"),
+        };
+        let member = members.get(name).ok_or_else(|| Error::UnknownMember {
+            path: self.path.clone(),
+            member: name.to_owned(),
+            struct_name: struct_name.to_owned(),
+        })?;
+
+        Ok(TypedLocation {
+            path: format!("{}.{}", self.path, name),
+            addr: struct_addr.checked_add(&member.offset)?,
+            cairo_type: member.cairo_type.parse()?,
+        })
+    }
+
+    /// Accesses a nested member, e.g. `["x", "y"]` for `ids.s.x.y`.
+    pub fn member_path(
+        &self,
+        path: &[&str],
+        identifiers: &IdentifierManager,
+        memory: &mut MemoryDict,
+    ) -> Result<TypedLocation, Error> {
+        let mut location = self.clone();
+        for name in path.iter() {
+            location = location.member(name, identifiers, memory)?;
+        }
+        Ok(location)
+    }
+
+    /// Reads the location. Felts and pointers are read from memory, while structs are returned
+    /// as is, as they span multiple cells.
+    pub fn get(&self, memory: &mut MemoryDict) -> Result<VmConstsValue, Error> {
+        match &self.cairo_type {
+            CairoType::Struct(_) => Ok(VmConstsValue::Struct(self.clone())),
+            CairoType::Felt | CairoType::Pointer(_) => Ok(VmConstsValue::Value(self.read(memory)?)),
+        }
+    }
+
+    fn read(&self, memory: &mut MemoryDict) -> Result<MaybeRelocatable, Error> {
+        memory
+            .get(&self.addr, None)
+            .ok_or_else(|| Error::UnknownMemory {
+                path: self.path.clone(),
+                addr: self.addr.clone(),
+            })
+    }
+
+    fn not_a_struct(&self) -> Error {
+        Error::NotAStruct {
+            path: self.path.clone(),
+            cairo_type: self.cairo_type.clone(),
+        }
+    }
+}
+
+/// Returns the number of memory cells taken by a value of the given type.
+pub fn type_size(cairo_type: &CairoType, identifiers: &IdentifierManager) -> Result<BigInt, Error> {
+    match cairo_type {
+        CairoType::Felt | CairoType::Pointer(_) => Ok(BigInt::from(1u32)),
+        CairoType::Struct(name) => match get_struct_definition(name, identifiers)? {
+            IdentifierDefinition::Struct { size, .. } => Ok(size),
+            _ => unreachable!("get_struct_definition only returns structs"),
+        },
+    }
+}
+
+fn get_struct_definition(
+    name: &ScopedName,
+    identifiers: &IdentifierManager,
+) -> Result<IdentifierDefinition, Error> {
+    match identifiers.get(name.to_owned()) {
+        Ok(result) if result.non_parsed.is_empty() => match result.identifier_definition {
+            definition @ IdentifierDefinition::Struct { .. } => Ok(definition),
+            _ => Err(Error::UnknownStruct {
+                name: name.to_owned(),
+            }),
+        },
+        _ => Err(Error::UnknownStruct {
+            name: name.to_owned(),
+        }),
+    }
+}
+
+impl From<AddressOutOfRangeError> for Error {
+    fn from(value: AddressOutOfRangeError) -> Self {
+        Self::AddressOutOfRange(value)
+    }
+}
+
+impl FromStr for CairoType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(pointee) = s.strip_suffix('*') {
+            return Ok(CairoType::Pointer(Box::new(pointee.parse()?)));
+        }
+
+        // Tuples and named types are not supported yet.
+        if s == "felt" {
+            Ok(CairoType::Felt)
+        } else if !s.is_empty() && !s.contains(['(', ')', ',', ':', ' ']) {
+            s.parse::<ScopedName>()
+                .map(CairoType::Struct)
+                .map_err(|_| Error::UnsupportedType {
+                    cairo_type: s.to_owned(),
+                })
+        } else {
+            Err(Error::UnsupportedType {
+                cairo_type: s.to_owned(),
+            })
+        }
+    }
+}
+
+impl Display for CairoType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CairoType::Felt => write!(f, "felt"),
+            CairoType::Pointer(pointee) => write!(f, "{}*", pointee),
+            CairoType::Struct(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cairo::lang::vm::relocatable::RelocatableValue;
+
+    fn identifiers() -> IdentifierManager {
+        serde_json::from_str(
+            r#"{
+                "__main__.Inner": {
+                    "full_name": "__main__.Inner",
+                    "members": {
+                        "x": { "cairo_type": "felt", "offset": 0 },
+                        "y": { "cairo_type": "felt", "offset": 1 }
+                    },
+                    "size": 2,
+                    "type": "struct"
+                },
+                "__main__.Outer": {
+                    "full_name": "__main__.Outer",
+                    "members": {
+                        "a": { "cairo_type": "felt", "offset": 0 },
+                        "inner": { "cairo_type": "__main__.Inner", "offset": 1 },
+                        "ptr": { "cairo_type": "__main__.Inner*", "offset": 3 }
+                    },
+                    "size": 4,
+                    "type": "struct"
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn addr(offset: usize) -> MaybeRelocatable {
+        RelocatableValue::new(1, offset).into()
+    }
+
+    /// An `Outer` at 1:0, whose `ptr` points to an `Inner` at 1:10.
+    fn memory() -> MemoryDict {
+        let mut memory = MemoryDict::new();
+        for (offset, value) in [
+            (0, BigInt::from(1u32).into()),
+            (1, BigInt::from(2u32).into()),
+            (2, BigInt::from(3u32).into()),
+            (3, addr(10)),
+            (10, BigInt::from(4u32).into()),
+        ] {
+            memory.index_set(addr(offset), value).unwrap();
+        }
+        memory
+    }
+
+    fn outer() -> TypedLocation {
+        TypedLocation::new(
+            String::from("ids.outer"),
+            addr(0),
+            "__main__.Outer".parse().unwrap(),
+        )
+    }
+
+    fn vm_consts(hint_ap_offset: u32) -> VmConsts {
+        let reference_manager = serde_json::from_str::<ReferenceManager>(
+            r#"{
+                "references": [
+                    { "ap_tracking_data": { "group": 0, "offset": 0 }, "pc": 0, "value": "[cast(fp + (-3), felt*)]" },
+                    { "ap_tracking_data": { "group": 0, "offset": 1 }, "pc": 2, "value": "[cast(ap + (-1), felt*)]" },
+                    { "ap_tracking_data": { "group": 0, "offset": 1 }, "pc": 2, "value": "[cast(fp, __main__.Inner*)]" },
+                    { "ap_tracking_data": { "group": 1, "offset": 0 }, "pc": 4, "value": "[cast(ap, felt*)]" },
+                    { "ap_tracking_data": { "group": 0, "offset": 0 }, "pc": 0, "value": "[cast(fp + 1, felt*)]" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let flow_tracking_data =
+            serde_json::from_value::<FlowTrackingDataActual>(serde_json::json!({
+                "ap_tracking": { "group": 0, "offset": hint_ap_offset },
+                "reference_ids": {
+                    "__main__.main.a": 0,
+                    "__main__.main.x": 1,
+                    "__main__.main.s": 2,
+                    "__main__.main.revoked": 3,
+                    "__main__.a": 4,
+                    "__main__.other.b": 4
+                }
+            }))
+            .unwrap();
+        let identifiers = serde_json::from_str::<IdentifierManager>(
+            r#"{
+                "__main__.N": { "type": "const", "value": 10 },
+                "__main__.x": { "type": "const", "value": 20 },
+                "__main__.main.M": { "type": "const", "value": 30 },
+                "__main__.other.K": { "type": "const", "value": 40 }
+            }"#,
+        )
+        .unwrap();
+
+        VmConsts::new(
+            &flow_tracking_data,
+            &[
+                "__main__".parse().unwrap(),
+                "__main__.main".parse().unwrap(),
+            ],
+            &reference_manager,
+            &identifiers,
+        )
+    }
+
+    #[test]
+    fn test_vm_consts_get_address() {
+        let ap = addr(20);
+        let fp = addr(10);
+
+        let consts = vm_consts(1);
+        // `__main__.main.a` shadows `__main__.a`.
+        assert_eq!(consts.get_address("a", &ap, &fp).unwrap(), addr(7));
+        assert_eq!(consts.get_address("x", &ap, &fp).unwrap(), addr(19));
+
+        // ap moved by 2 since `x` was defined.
+        let consts = vm_consts(3);
+        assert_eq!(consts.get_address("x", &ap, &fp).unwrap(), addr(17));
+    }
+
+    #[test]
+    fn test_vm_consts_get_address_errors() {
+        let ap = addr(20);
+        let fp = addr(10);
+        let consts = vm_consts(1);
+
+        assert!(matches!(
+            consts.get_address("b", &ap, &fp),
+            Err(Error::UnknownIdentifier { .. })
+        ));
+        assert!(matches!(
+            consts.get_address("s", &ap, &fp),
+            Err(Error::UnsupportedReference { .. })
+        ));
+        assert_eq!(
+            consts
+                .get_address("revoked", &ap, &fp)
+                .unwrap_err()
+                .to_string(),
+            "'ids.revoked' cannot be used here, as the ap register it references was revoked."
+        );
+    }
+
+    #[test]
+    fn test_vm_consts_get_constant() {
+        let ap = addr(20);
+        let fp = addr(10);
+        let consts = vm_consts(1);
+
+        assert_eq!(consts.get_constant("N"), Some(&BigInt::from(10u32)));
+        assert_eq!(consts.get_constant("M"), Some(&BigInt::from(30u32)));
+        assert_eq!(consts.get_constant("K"), None);
+        // The reference `__main__.main.x` shadows the constant `__main__.x`.
+        assert_eq!(consts.get_constant("x"), None);
+        assert_eq!(consts.get_address("x", &ap, &fp).unwrap(), addr(19));
+
+        assert_eq!(
+            consts.get_address("N", &ap, &fp).unwrap_err().to_string(),
+            "'ids.N' is a constant, which has no address and cannot be assigned."
+        );
+    }
+
+    #[test]
+    fn test_parse_cairo_type() {
+        assert_eq!("felt".parse::<CairoType>().unwrap(), CairoType::Felt);
+        assert_eq!(
+            "__main__.Inner**".parse::<CairoType>().unwrap(),
+            CairoType::Pointer(Box::new(CairoType::Pointer(Box::new(CairoType::Struct(
+                "__main__.Inner".parse().unwrap()
+            )))))
+        );
+        assert!(matches!(
+            "(a : felt, b : felt)".parse::<CairoType>(),
+            Err(Error::UnsupportedType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_type_size() {
+        let identifiers = identifiers();
+        for (cairo_type, size) in [
+            ("felt", 1u32),
+            ("__main__.Outer*", 1),
+            ("__main__.Inner", 2),
+            ("__main__.Outer", 4),
+        ] {
+            assert_eq!(
+                type_size(&cairo_type.parse().unwrap(), &identifiers).unwrap(),
+                BigInt::from(size)
+            );
+        }
+    }
+
+    #[test]
+    fn test_member_access() {
+        let identifiers = identifiers();
+        let mut memory = memory();
+        let outer = outer();
+
+        let value = |path: &[&str], memory: &mut MemoryDict| {
+            outer
+                .member_path(path, &identifiers, memory)
+                .unwrap()
+                .get(memory)
+                .unwrap()
+        };
+
+        assert_eq!(
+            value(&["a"], &mut memory),
+            VmConstsValue::Value(BigInt::from(1u32).into())
+        );
+        // Nested struct.
+        assert_eq!(
+            value(&["inner", "y"], &mut memory),
+            VmConstsValue::Value(BigInt::from(3u32).into())
+        );
+        assert!(matches!(
+            value(&["inner"], &mut memory),
+            VmConstsValue::Struct(TypedLocation { addr: inner, .. }) if inner == addr(1)
+        ));
+        // Pointer to struct.
+        assert_eq!(value(&["ptr"], &mut memory), VmConstsValue::Value(addr(10)));
+        assert_eq!(
+            value(&["ptr", "x"], &mut memory),
+            VmConstsValue::Value(BigInt::from(4u32).into())
+        );
+    }
+
+    #[test]
+    fn test_member_access_errors() {
+        let identifiers = identifiers();
+        let mut memory = memory();
+        let outer = outer();
+
+        let err = outer
+            .member_path(&["ptr", "y"], &identifiers, &mut memory)
+            .unwrap()
+            .get(&mut memory)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown value for memory cell at address 1:11 (accessing 'ids.outer.ptr.y')."
+        );
+
+        let err = outer
+            .member_path(&["inner", "z"], &identifiers, &mut memory)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'__main__.Inner' has no member named 'z' (accessing 'ids.outer.inner')."
+        );
+
+        let err = outer
+            .member_path(&["a", "b"], &identifiers, &mut memory)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'ids.outer.a' is of type felt, which has no members."
+        );
+    }
+}
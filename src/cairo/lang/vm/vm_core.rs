@@ -1,34 +1,47 @@
 use crate::{
     cairo::lang::{
         compiler::{
+            debug_info::{DebugInfo, InstructionLocation},
             encode::decode_instruction,
             instruction::{
-                ApUpdate, FpUpdate, Instruction, Op1Addr, Opcode, PcUpdate, Register, Res,
+                ApUpdate, FpUpdate, Instruction, InstructionDecodeError, Op1Addr, Opcode, PcUpdate,
+                Register, Res,
             },
             program::{FullProgram, Program},
         },
         vm::{
+            builtin_runner::Error as BuiltinRunnerError,
             cairo_runner::BuiltinRunnerMap,
             memory_dict::{Error as MemoryDictError, MemoryDict},
-            relocatable::{MaybeRelocatable, RelocatableValue},
+            relocatable::{
+                AddressOutOfRangeError, MaybeRelocatable, RelocatableValue, SubtractionError,
+            },
             trace_entry::TraceEntry,
             validated_memory_dict::ValidatedMemoryDict,
-            virtual_machine_base::CompiledHint,
-            vm_exceptions::PureValueError,
+            virtual_machine_base::{
+                CompiledHint, HintCode, PythonHint, VmAttributeScope, ERROR_MESSAGE_ATTRIBUTE,
+            },
+            vm_consts::VmConsts,
+            vm_exceptions::{PureValueError, VmException},
         },
     },
     hint_support::{
-        PyMemorySegmentManager, PyRelocatableValue, PyValidatedMemoryDict, StaticLocals,
+        from_maybe_relocatable,
+        native_hints::{find_native_hint, NativeHintContext},
+        HintOutputSink, PyHintOutput, PyMemorySegmentManager, PyRelocatableValue,
+        PyValidatedMemoryDict, PyVmConsts, StaticLocals, StdoutHintOutputSink,
     },
+    python::math_utils::div_mod,
 };
 
 use num_bigint::BigInt;
 use once_cell::unsync::OnceCell;
 use rustpython_vm::{
-    builtins::PyType,
+    builtins::{PyDictRef, PyType},
     class::{PyClassImpl, StaticType},
+    function::OptionalArg,
     types::SetAttr,
-    Interpreter, PyPayload,
+    Interpreter, PyObjectRef, PyPayload, PyResult, VirtualMachine as PythonVm,
 };
 use std::{
     cell::RefCell,
@@ -37,12 +50,38 @@ use std::{
     rc::Rc,
 };
 
+/// The maximum number of calls shown in a Cairo traceback.
+const MAX_TRACEBACK_ENTRIES: usize = 20;
+
+/// Names injected into the hint globals by the VM. These are not persisted in the hint scope.
+const HINT_CONTEXT_NAMES: &[&str] = &[
+    "__builtins__",
+    "segments",
+    "memory",
+    "ap",
+    "fp",
+    "pc",
+    "current_step",
+    "ids",
+    "vm_enter_scope",
+    "vm_exit_scope",
+    "PRIME",
+    "fadd",
+    "fsub",
+    "fmul",
+    "fdiv",
+    "fpow",
+    "fis_quad_residue",
+    "fsqrt",
+    "safe_div",
+];
+
 pub struct Rule {
     pub inner: fn(&VirtualMachine, &RelocatableValue, &()) -> Option<BigInt>,
 }
 
 /// Values of the operands.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Operands {
     pub dst: MaybeRelocatable,
     pub res: Option<MaybeRelocatable>,
@@ -66,6 +105,132 @@ pub enum RunContextError {
     InvalidOff2Value,
     #[error("op0 must be known in double dereference.")]
     UnknownOp0,
+    #[error(transparent)]
+    AddressOutOfRange(AddressOutOfRangeError),
+    #[error(transparent)]
+    MemoryDictError(MemoryDictError),
+    #[error("The instruction at pc {pc} is not an integer: {value}.")]
+    NonIntegerInstruction {
+        pc: MaybeRelocatable,
+        value: MaybeRelocatable,
+    },
+}
+
+/// Decoded instructions of the loaded program, indexed by their offset from the program base.
+///
+/// Memory is write-once, so an instruction of the program (and its immediate) never changes once
+/// decoded. Code outside the program, e.g. written by hints into another segment, is not cached and
+/// is decoded again on every step.
+#[derive(Debug, Default)]
+pub struct InstructionCache {
+    program_base: Option<RelocatableValue>,
+    instructions: Vec<Option<Rc<Instruction>>>,
+}
+
+impl InstructionCache {
+    /// Creates a cache for the `program_len` instructions starting at `program_base`. Nothing is
+    /// cached if the program base is not a relocatable value.
+    pub fn new(program_base: &MaybeRelocatable, program_len: usize) -> Self {
+        match program_base {
+            MaybeRelocatable::RelocatableValue(program_base) => Self {
+                program_base: Some(*program_base),
+                instructions: vec![None; program_len],
+            },
+            MaybeRelocatable::Int(_) => Self::default(),
+        }
+    }
+
+    pub fn get(&self, pc: &MaybeRelocatable) -> Option<Rc<Instruction>> {
+        self.index(pc)
+            .and_then(|index| self.instructions[index].clone())
+    }
+
+    /// Caches the instruction decoded at `pc`. Does nothing if `pc` is outside the program.
+    pub fn insert(&mut self, pc: &MaybeRelocatable, instruction: Rc<Instruction>) {
+        if let Some(index) = self.index(pc) {
+            self.instructions[index] = Some(instruction);
+        }
+    }
+
+    fn index(&self, pc: &MaybeRelocatable) -> Option<usize> {
+        match (pc, &self.program_base) {
+            (MaybeRelocatable::RelocatableValue(pc), Some(program_base))
+                if pc.segment_index == program_base.segment_index =>
+            {
+                pc.offset
+                    .checked_sub(program_base.offset)
+                    .filter(|index| *index < self.instructions.len())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The memory addresses accessed by actual Cairo instructions (as opposed to hints), necessary for
+/// accurate counting of memory holes.
+///
+/// The whole program counts as accessed from the start. It's kept as a range rather than one
+/// address per word, so that the cost of creating a VM doesn't grow with the program size.
+#[derive(Debug, Default, Clone)]
+pub struct AccessedAddresses {
+    program_base: Option<RelocatableValue>,
+    program_len: usize,
+    addresses: HashSet<RelocatableValue>,
+}
+
+impl AccessedAddresses {
+    /// Creates a set containing the `program_len` words starting at `program_base`. The program is
+    /// left out if its base is not a relocatable value.
+    pub fn new(program_base: &MaybeRelocatable, program_len: usize) -> Self {
+        match program_base {
+            MaybeRelocatable::RelocatableValue(program_base) => Self {
+                program_base: Some(*program_base),
+                program_len,
+                addresses: HashSet::new(),
+            },
+            MaybeRelocatable::Int(_) => Self::default(),
+        }
+    }
+
+    /// Adds `addr` to the set. Integers are not addresses and are ignored.
+    pub fn insert(&mut self, addr: &MaybeRelocatable) {
+        if let MaybeRelocatable::RelocatableValue(addr) = addr {
+            if !self.is_in_program(addr) {
+                self.addresses.insert(*addr);
+            }
+        }
+    }
+
+    pub fn contains(&self, addr: &RelocatableValue) -> bool {
+        self.is_in_program(addr) || self.addresses.contains(addr)
+    }
+
+    pub fn len(&self) -> usize {
+        self.program_len + self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the addresses, starting with the program.
+    pub fn iter(&self) -> impl Iterator<Item = RelocatableValue> + '_ {
+        self.program_base
+            .iter()
+            .flat_map(|program_base| (0..self.program_len).map(|i| *program_base + i))
+            .chain(self.addresses.iter().copied())
+    }
+
+    fn is_in_program(&self, addr: &RelocatableValue) -> bool {
+        match &self.program_base {
+            Some(program_base) => {
+                addr.segment_index == program_base.segment_index
+                    && addr.offset >= program_base.offset
+                    && addr.offset - program_base.offset < self.program_len
+            }
+            None => false,
+        }
+    }
 }
 
 pub struct VirtualMachine {
@@ -74,20 +239,20 @@ pub struct VirtualMachine {
     // //////////
     pub prime: BigInt,
     pub builtin_runners: Rc<RefCell<BuiltinRunnerMap>>,
-    pub exec_scopes: Vec<HashMap<String, ()>>,
+    pub exec_scopes: Rc<RefCell<Vec<HashMap<String, PyObjectRef>>>>,
     pub hints: HashMap<MaybeRelocatable, Vec<CompiledHint>>,
     /// A map from hint id to pc and index (index is required when there is more than one hint for a
     /// single pc).
     pub hint_pc_and_index: HashMap<BigInt, (MaybeRelocatable, BigInt)>,
-    pub instruction_debug_info: (),
-    pub debug_file_contents: (),
-    pub error_message_attributes: (),
+    pub instruction_debug_info: HashMap<MaybeRelocatable, InstructionLocation>,
+    pub debug_file_contents: HashMap<String, String>,
+    pub error_message_attributes: Vec<VmAttributeScope>,
     pub program: Rc<Program>,
     pub validated_memory: Rc<RefCell<ValidatedMemoryDict>>,
     /// auto_deduction contains a mapping from a memory segment index to a list of functions (and a
     /// tuple of additional arguments) that may try to automatically deduce the value of memory
     /// cells in the segment (based on other memory cells).
-    pub auto_deduction: HashMap<BigInt, Vec<(Rule, ())>>,
+    pub auto_deduction: HashMap<isize, Vec<(Rule, ())>>,
     pub static_locals: StaticLocals,
     /// This flag can be set to true by hints to avoid the execution of the current step in step()
     /// (so that only the hint will be performed, but nothing else will happen).
@@ -98,11 +263,23 @@ pub struct VirtualMachine {
     pub run_context: Rc<RefCell<RunContext>>,
     /// A set to track the memory addresses accessed by actual Cairo instructions (as opposed to
     /// hints), necessary for accurate counting of memory holes.
-    pub accessed_addresses: HashSet<MaybeRelocatable>,
+    pub accessed_addresses: AccessedAddresses,
     pub trace: Vec<TraceEntry<MaybeRelocatable>>,
+    pub instruction_cache: InstructionCache,
+    /// The length of the memory write log at the beginning of each step, before its hints run. Only
+    /// recorded while the write log is enabled (see MemoryDict::enable_write_log).
+    pub memory_write_marks: Vec<usize>,
     /// Current step.
     pub current_step: BigInt,
+    /// The number of CALL instructions executed without a matching RET yet.
+    pub call_depth: usize,
+    /// The highest `call_depth` reached so far.
+    pub peak_call_depth: usize,
+    /// Executing a CALL that would take `call_depth` above this fails. Unbounded if `None`.
+    pub max_call_depth: Option<usize>,
     pub python_interpreter: OnceCell<Interpreter>,
+    /// Where the output hints write to `sys.stdout` and `sys.stderr` goes.
+    pub hint_output_sink: Rc<dyn HintOutputSink>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -112,7 +289,13 @@ pub enum VirtualMachineError {
     #[error(transparent)]
     MemoryDictError(MemoryDictError),
     #[error(transparent)]
+    BuiltinRunnerError(BuiltinRunnerError),
+    #[error(transparent)]
     PureValueError(PureValueError),
+    #[error(transparent)]
+    InstructionDecodeError(InstructionDecodeError),
+    #[error(transparent)]
+    SubtractionError(SubtractionError),
     #[error("Res.UNCONSTRAINED cannot be used with Opcode.ASSERT_EQ")]
     AssertEqWithUnconstrained,
     #[error("An ASSERT_EQ instruction failed: {dst} != {res}.")]
@@ -138,6 +321,8 @@ pub enum VirtualMachineError {
     JumpRelWithUnconstrained,
     #[error("Every enter_scope() requires a corresponding exit_scope().")]
     EnterExitScopeMismatch,
+    #[error("Cannot exit main scope.")]
+    CannotExitMainScope,
     #[error("Inconsistent auto deduction rule at address {addr}. {current_value} != {new_value}.")]
     InconsistentAutoDeduction {
         addr: RelocatableValue,
@@ -156,11 +341,30 @@ pub enum VirtualMachineError {
     },
     #[error(transparent)]
     HintCompileError(rustpython_vm::compile::CompileError),
-    #[error("Got an exception while executing a hint ({hint_index}): {exception}")]
+    #[error(
+        "Got an exception while executing a hint ({hint_index}): {exception}{}",
+        format_hint_output(.output)
+    )]
     HintExecuteError {
         hint_index: usize,
         exception: String,
+        /// What the hint printed before failing.
+        output: String,
     },
+    #[error("End of program was not reached: stopped at pc={pc} before reaching pc={target}.")]
+    EndOfProgramNotReached {
+        pc: MaybeRelocatable,
+        target: MaybeRelocatable,
+    },
+    #[error("Execution reached the end of the program.")]
+    EndOfProgramReached,
+    #[error("Unexpected prime for loaded program: {program_prime} != {prime}.")]
+    UnexpectedPrime {
+        program_prime: BigInt,
+        prime: BigInt,
+    },
+    #[error("Maximum call depth exceeded: call at pc={pc} would reach a depth of {depth}.")]
+    CallDepthExceeded { depth: usize, pc: MaybeRelocatable },
 }
 
 impl Debug for Rule {
@@ -188,20 +392,25 @@ impl RunContext {
 
     /// Returns the encoded instruction (the value at pc) and the immediate value (the value at pc +
     /// 1, if it exists in the memory).
-    pub fn get_instruction_encoding(&mut self) -> (BigInt, Option<BigInt>) {
+    pub fn get_instruction_encoding(
+        &mut self,
+    ) -> Result<(BigInt, Option<BigInt>), RunContextError> {
         let mut memory = self.memory.as_ref().borrow_mut();
 
-        // TODO: check if it's safe to call unwrap here (probably not, change to proper error
-        //       handling)
-        let instruction_encoding = memory.index(&self.pc).unwrap();
-        let instruction_encoding = match instruction_encoding {
+        let instruction_encoding = match memory.index(&self.pc)? {
             MaybeRelocatable::Int(int) => int,
-            // TODO: switch to proper error handling
-            MaybeRelocatable::RelocatableValue(_) => panic!("Instruction should be an int"),
+            value @ MaybeRelocatable::RelocatableValue(_) => {
+                return Err(RunContextError::NonIntegerInstruction {
+                    pc: self.pc.clone(),
+                    value,
+                })
+            }
         };
 
-        let imm_addr = (self.pc.clone() + &BigInt::from(1)) % &self.prime;
-        let optional_imm = memory.get(&imm_addr, None);
+        let optional_imm = match self.pc.add_small_mod(1, &self.prime) {
+            Ok(imm_addr) => memory.get(&imm_addr, None),
+            Err(_) => None,
+        };
         let optional_imm = match optional_imm {
             Some(imm) => match imm {
                 MaybeRelocatable::Int(int) => Some(int),
@@ -210,23 +419,29 @@ impl RunContext {
             None => None,
         };
 
-        (instruction_encoding, optional_imm)
+        Ok((instruction_encoding, optional_imm))
     }
 
-    pub fn compute_dst_addr(&self, instruction: &Instruction) -> MaybeRelocatable {
+    pub fn compute_dst_addr(
+        &self,
+        instruction: &Instruction,
+    ) -> Result<MaybeRelocatable, RunContextError> {
         let base_addr = match instruction.dst_register {
-            Register::AP => self.ap.clone(),
-            Register::FP => self.fp.clone(),
+            Register::AP => &self.ap,
+            Register::FP => &self.fp,
         };
-        (base_addr + &BigInt::from(instruction.off0)) % &self.prime
+        Ok(base_addr.add_small_mod(instruction.off0.into(), &self.prime)?)
     }
 
-    pub fn compute_op0_addr(&self, instruction: &Instruction) -> MaybeRelocatable {
+    pub fn compute_op0_addr(
+        &self,
+        instruction: &Instruction,
+    ) -> Result<MaybeRelocatable, RunContextError> {
         let base_addr = match instruction.op0_register {
-            Register::AP => self.ap.clone(),
-            Register::FP => self.fp.clone(),
+            Register::AP => &self.ap,
+            Register::FP => &self.fp,
         };
-        (base_addr + &BigInt::from(instruction.off1)) % &self.prime
+        Ok(base_addr.add_small_mod(instruction.off1.into(), &self.prime)?)
     }
 
     pub fn compute_op1_addr(
@@ -241,6 +456,8 @@ impl RunContext {
                 if instruction.off2 != 1 {
                     return Err(RunContextError::InvalidOff2Value);
                 }
+                // The immediate is the cell right after the instruction. Only a pc that is a field
+                // element is reduced modulo the prime: offsets of addresses are left as they are.
                 self.pc.clone()
             }
             Op1Addr::OP0 => match op0 {
@@ -250,7 +467,7 @@ impl RunContext {
                 }
             },
         };
-        Ok((base_addr + &BigInt::from(instruction.off2)) % &self.prime)
+        Ok(base_addr.add_small_mod(instruction.off2.into(), &self.prime)?)
     }
 }
 
@@ -271,21 +488,14 @@ impl VirtualMachine {
     pub fn new(
         program: Rc<Program>,
         run_context: Rc<RefCell<RunContext>>,
-        hint_locals: HashMap<String, ()>,
+        hint_locals: HashMap<String, PyObjectRef>,
         static_locals: StaticLocals,
         builtin_runners: Option<Rc<RefCell<BuiltinRunnerMap>>>,
         program_base: Option<MaybeRelocatable>,
     ) -> Self {
         let program_base = program_base.unwrap_or_else(|| run_context.borrow().pc.clone());
         let builtin_runners =
-            builtin_runners.unwrap_or_else(|| Rc::new(RefCell::new(HashMap::new())));
-
-        // A set to track the memory addresses accessed by actual Cairo instructions (as opposed to
-        // hints), necessary for accurate counting of memory holes.
-        let mut accessed_addresses = HashSet::new();
-        for i in 0..program.data().len() {
-            accessed_addresses.insert(program_base.clone() + &BigInt::from(i));
-        }
+            builtin_runners.unwrap_or_else(|| Rc::new(RefCell::new(BuiltinRunnerMap::new())));
 
         // //////////
         // START: `VirtualMachineBase` ctor logic
@@ -298,22 +508,28 @@ impl VirtualMachine {
         let mut vm = Self {
             prime: program.prime().clone(),
             builtin_runners,
-            exec_scopes: vec![],
+            exec_scopes: Rc::new(RefCell::new(vec![])),
             hints: HashMap::new(),
             hint_pc_and_index: HashMap::new(),
-            instruction_debug_info: (),
-            debug_file_contents: (),
-            error_message_attributes: (),
+            instruction_debug_info: HashMap::new(),
+            debug_file_contents: HashMap::new(),
+            error_message_attributes: vec![],
             program: program.clone(),
             validated_memory,
             auto_deduction: HashMap::new(),
             static_locals,
             skip_instruction_execution: false,
             run_context,
-            accessed_addresses,
+            accessed_addresses: AccessedAddresses::new(&program_base, program.data().len()),
             trace: vec![],
+            instruction_cache: InstructionCache::new(&program_base, program.data().len()),
+            memory_write_marks: vec![],
             current_step: BigInt::from(0),
+            call_depth: 0,
+            peak_call_depth: 0,
+            max_call_depth: None,
             python_interpreter: OnceCell::new(),
+            hint_output_sink: Rc::new(StdoutHintOutputSink),
         };
 
         vm.enter_scope(Some(hint_locals));
@@ -323,25 +539,6 @@ impl VirtualMachine {
             vm.load_program(program, program_base);
         }
 
-        // TODO: implement the following Python code
-        //
-        // ```python
-        // self.static_locals = static_locals.copy() if static_locals is not None else {}
-        // self.static_locals.update(
-        //     {
-        //         "PRIME": self.prime,
-        //         "fadd": lambda a, b, p=self.prime: (a + b) % p,
-        //         "fsub": lambda a, b, p=self.prime: (a - b) % p,
-        //         "fmul": lambda a, b, p=self.prime: (a * b) % p,
-        //         "fdiv": lambda a, b, p=self.prime: math_utils.div_mod(a, b, p),
-        //         "fpow": lambda a, b, p=self.prime: pow(a, b, p),
-        //         "fis_quad_residue": lambda a, p=self.prime: math_utils.is_quad_residue(a, p),
-        //         "fsqrt": lambda a, p=self.prime: math_utils.sqrt(a, p),
-        //         "safe_div": math_utils.safe_div,
-        //     }
-        // )
-        // ```
-
         // //////////
         // END: `VirtualMachineBase` ctor logic
         // //////////
@@ -358,142 +555,316 @@ impl VirtualMachine {
     /// The scope starts only from the next hint.
     ///
     /// exit_scope() must be called to resume the previous scope.
-    pub fn enter_scope(&mut self, new_scope_locals: Option<HashMap<String, ()>>) {
-        let mut new_scope = HashMap::new();
+    pub fn enter_scope(&mut self, new_scope_locals: Option<HashMap<String, PyObjectRef>>) {
+        enter_scope(&self.exec_scopes, new_scope_locals);
+    }
 
-        if let Some(new_scope_locals) = new_scope_locals {
-            for (key, _) in new_scope_locals.iter() {
-                new_scope.insert(key.to_owned(), ());
-            }
-        }
+    /// Exits the last scope.
+    pub fn exit_scope(&mut self) -> Result<(), VirtualMachineError> {
+        exit_scope(&self.exec_scopes)
+    }
 
-        // TODO: add builtin_runners to hint scope
+    /// Adds variables to the outermost scope, as if they had been passed as `hint_locals` to the
+    /// constructor. Unlike those, they can be built with the interpreter of the VM.
+    pub fn add_hint_locals(&self, hint_locals: HashMap<String, PyObjectRef>) {
+        if let Some(scope) = self.exec_scopes.borrow_mut().first_mut() {
+            scope.extend(hint_locals);
+        }
+    }
 
-        self.exec_scopes.push(new_scope);
+    /// The interpreter running the Python hints, created on first use.
+    pub fn python_interpreter(&self) -> &Interpreter {
+        self.python_interpreter
+            .get_or_init(|| Interpreter::without_stdlib(Default::default()))
     }
 
     pub fn step(&mut self) -> Result<(), VirtualMachineError> {
         self.skip_instruction_execution = false;
 
+        // A step skipped by a hint is attempted again, which must not record another mark.
+        if let Some(write_log) = &self.validated_memory.borrow().memory.borrow().write_log {
+            if self.memory_write_marks.len() == self.trace.len() {
+                self.memory_write_marks.push(write_log.len());
+            }
+        }
+        {
+            let validated_memory = self.validated_memory.borrow();
+            let mut memory = validated_memory.memory.borrow_mut();
+            if memory.access_log.is_some() {
+                memory.current_step = self.current_step.clone();
+            }
+        }
+
         // Execute hints.
         if let Some(hints) = self.hints.get(&self.run_context.borrow().pc) {
             for (hint_index, hint) in hints.iter().enumerate() {
+                // The scope a hint starts in receives its locals, even if the hint itself enters or
+                // exits a scope.
+                let scope_index = self.exec_scopes.borrow().len() - 1;
+
+                let code = match &hint.compiled {
+                    HintCode::Python(python_hint) => python_hint.compile()?,
+                    HintCode::Native(native_hint) => {
+                        native_hint(&NativeHintContext {
+                            consts: &hint.consts,
+                            ap: self.run_context.borrow().ap.clone(),
+                            fp: self.run_context.borrow().fp.clone(),
+                            memory: &self.validated_memory,
+                        })
+                        .map_err(|err| {
+                            VirtualMachineError::HintExecuteError {
+                                hint_index,
+                                exception: err.to_string(),
+                                output: String::new(),
+                            }
+                        })?;
+                        continue;
+                    }
+                };
+
                 // TODO: implement the following Python code
                 //
                 // ```python
-                // exec_locals = self.exec_scopes[-1]
-                // exec_locals["memory"] = memory = self.validated_memory
-                // exec_locals["ap"] = ap = self.run_context.ap
-                // exec_locals["fp"] = fp = self.run_context.fp
-                // exec_locals["pc"] = pc = self.run_context.pc
-                // exec_locals["current_step"] = self.current_step
-                // exec_locals["ids"] = hint.consts(pc, ap, fp, memory)
-                //
                 // exec_locals["vm_load_program"] = self.load_program
-                // exec_locals["vm_enter_scope"] = self.enter_scope
-                // exec_locals["vm_exit_scope"] = self.exit_scope
-                // exec_locals.update(self.static_locals)
                 // ```
 
-                // This will almost always fail as globals injection has not been fully implemented
-                self.python_interpreter
-                    .get_or_init(|| Interpreter::without_stdlib(Default::default()))
-                    .enter(|vm| {
-                        let scope = vm.new_scope_with_builtins();
-
-                        // Injects hint context variables
-                        {
-                            // Context injection
-                            let ctx_segments = self.static_locals.segments.clone();
-                            let ctx_memory = self.validated_memory.clone();
-                            let ctx_ap = &self.run_context.borrow().ap;
-
-                            // Class initialization
-                            let memory_segment_manager_cls = PyMemorySegmentManager::static_cell()
-                                .get_or_init(PyMemorySegmentManager::create_bare_type);
-                            let validated_memory_dict_cls = PyValidatedMemoryDict::static_cell()
-                                .get_or_init(PyValidatedMemoryDict::create_bare_type);
-                            PyRelocatableValue::static_cell()
-                                .get_or_init(PyRelocatableValue::create_bare_type);
-
-                            PyMemorySegmentManager::extend_class(
-                                &vm.ctx,
-                                memory_segment_manager_cls,
-                            );
-                            PyType::setattro(
-                                validated_memory_dict_cls,
-                                vm.ctx.new_str("__setitem__"),
-                                Some(
-                                    vm.ctx
-                                        .new_method(
-                                            "__setitem__",
-                                            validated_memory_dict_cls.clone(),
-                                            PyValidatedMemoryDict::py_setitem,
-                                        )
-                                        .into(),
-                                ),
+                self.python_interpreter().enter(|vm| {
+                    let scope = vm.new_scope_with_builtins();
+                    let globals = scope.globals.clone();
+
+                    // Captures what the hint prints, so that it can be forwarded to the sink.
+                    let hint_output = Rc::new(RefCell::new(String::new()));
+                    {
+                        let hint_output_cls =
+                            PyHintOutput::static_cell().get_or_init(PyHintOutput::create_bare_type);
+                        PyHintOutput::extend_class(&vm.ctx, hint_output_cls);
+
+                        let py_hint_output: PyObjectRef = PyHintOutput {
+                            buffer: hint_output.clone(),
+                        }
+                        .into_ref(vm)
+                        .into();
+                        vm.sys_module
+                            .set_attr("stdout", py_hint_output.clone(), vm)
+                            .unwrap();
+                        vm.sys_module
+                            .set_attr("stderr", py_hint_output, vm)
+                            .unwrap();
+                    }
+
+                    // Scope locals injection
+                    for (key, value) in self.exec_scopes.borrow()[scope_index].iter() {
+                        scope
+                            .globals
+                            .set_item(key.as_str(), value.clone(), vm)
+                            .unwrap();
+                    }
+
+                    // Injects hint context variables
+                    {
+                        // Context injection
+                        let ctx_segments = self.static_locals.segments.clone();
+                        let ctx_memory = self.validated_memory.clone();
+                        let run_context = self.run_context.borrow();
+                        let ctx_ids = PyVmConsts {
+                            consts: hint.consts.clone(),
+                            ap: run_context.ap.clone(),
+                            fp: run_context.fp.clone(),
+                            memory: self.validated_memory.clone(),
+                        };
+                        let ctx_enter_scopes = self.exec_scopes.clone();
+                        let ctx_exit_scopes = self.exec_scopes.clone();
+
+                        // Class initialization
+                        let memory_segment_manager_cls = PyMemorySegmentManager::static_cell()
+                            .get_or_init(PyMemorySegmentManager::create_bare_type);
+                        let validated_memory_dict_cls = PyValidatedMemoryDict::static_cell()
+                            .get_or_init(PyValidatedMemoryDict::create_bare_type);
+                        let vm_consts_cls =
+                            PyVmConsts::static_cell().get_or_init(PyVmConsts::create_bare_type);
+                        PyRelocatableValue::static_cell()
+                            .get_or_init(PyRelocatableValue::create_bare_type);
+
+                        PyMemorySegmentManager::extend_class(&vm.ctx, memory_segment_manager_cls);
+                        PyType::setattro(
+                            validated_memory_dict_cls,
+                            vm.ctx.new_str("__setitem__"),
+                            Some(
+                                vm.ctx
+                                    .new_method(
+                                        "__setitem__",
+                                        validated_memory_dict_cls.clone(),
+                                        PyValidatedMemoryDict::py_setitem,
+                                    )
+                                    .into(),
+                            ),
+                            vm,
+                        )
+                        .unwrap();
+                        PyType::setattro(
+                            vm_consts_cls,
+                            vm.ctx.new_str("__getattr__"),
+                            Some(
+                                vm.ctx
+                                    .new_method(
+                                        "__getattr__",
+                                        vm_consts_cls.clone(),
+                                        PyVmConsts::py_getattr,
+                                    )
+                                    .into(),
+                            ),
+                            vm,
+                        )
+                        .unwrap();
+                        PyType::setattro(
+                            vm_consts_cls,
+                            vm.ctx.new_str("__setattr__"),
+                            Some(
+                                vm.ctx
+                                    .new_method(
+                                        "__setattr__",
+                                        vm_consts_cls.clone(),
+                                        PyVmConsts::py_setattr,
+                                    )
+                                    .into(),
+                            ),
+                            vm,
+                        )
+                        .unwrap();
+
+                        // Hint locals injection
+                        scope
+                            .globals
+                            .set_item(
+                                "segments",
+                                PyMemorySegmentManager {
+                                    inner: ctx_segments,
+                                }
+                                .into_ref(vm)
+                                .into(),
+                                vm,
+                            )
+                            .unwrap();
+                        scope
+                            .globals
+                            .set_item(
+                                "memory",
+                                PyValidatedMemoryDict { inner: ctx_memory }
+                                    .into_ref(vm)
+                                    .into(),
                                 vm,
                             )
                             .unwrap();
 
-                            // Hint locals injection
+                        for (key, value) in [
+                            ("ap", &run_context.ap),
+                            ("fp", &run_context.fp),
+                            ("pc", &run_context.pc),
+                        ] {
                             scope
                                 .globals
-                                .set_item(
-                                    "segments",
-                                    PyMemorySegmentManager {
-                                        inner: ctx_segments,
-                                    }
-                                    .into_ref(vm)
-                                    .into(),
-                                    vm,
-                                )
+                                .set_item(key, from_maybe_relocatable(value, vm), vm)
                                 .unwrap();
-                            scope
+                        }
+                        scope
+                            .globals
+                            .set_item(
+                                "current_step",
+                                vm.ctx.new_int(self.current_step.clone()).into(),
+                                vm,
+                            )
+                            .unwrap();
+                        scope
+                            .globals
+                            .set_item("ids", ctx_ids.into_ref(vm).into(), vm)
+                            .unwrap();
+                        for (key, value) in self.static_locals.py_field_locals(vm) {
+                            scope.globals.set_item(*key, value.clone(), vm).unwrap();
+                        }
+
+                        scope
                                 .globals
                                 .set_item(
-                                    "memory",
-                                    PyValidatedMemoryDict { inner: ctx_memory }
-                                        .into_ref(vm)
+                                    "vm_enter_scope",
+                                    vm.ctx
+                                        .new_function(
+                                            "vm_enter_scope",
+                                            move |new_scope_locals: OptionalArg<PyDictRef>,
+                                                  vm: &PythonVm|
+                                                  -> PyResult<()> {
+                                                let new_scope_locals = match new_scope_locals {
+                                                    OptionalArg::Present(new_scope_locals) => {
+                                                        Some(py_dict_to_scope(
+                                                            new_scope_locals,
+                                                            vm,
+                                                        )?)
+                                                    }
+                                                    OptionalArg::Missing => None,
+                                                };
+                                                enter_scope(&ctx_enter_scopes, new_scope_locals);
+                                                Ok(())
+                                            },
+                                        )
                                         .into(),
                                     vm,
                                 )
                                 .unwrap();
+                        scope
+                            .globals
+                            .set_item(
+                                "vm_exit_scope",
+                                vm.ctx
+                                    .new_function(
+                                        "vm_exit_scope",
+                                        move |vm: &PythonVm| -> PyResult<()> {
+                                            exit_scope(&ctx_exit_scopes).map_err(|err| {
+                                                vm.new_runtime_error(err.to_string())
+                                            })
+                                        },
+                                    )
+                                    .into(),
+                                vm,
+                            )
+                            .unwrap();
+                    }
 
-                            let ap = match ctx_ap {
-                                MaybeRelocatable::Int(ap) => vm.ctx.new_int(ap.to_owned()).into(),
-                                MaybeRelocatable::RelocatableValue(ap) => {
-                                    PyRelocatableValue::from_relocatable_value(ap)
-                                        .into_ref(vm)
-                                        .into()
-                                }
-                            };
-                            scope.globals.set_item("ap", ap, vm).unwrap();
-                        }
+                    let result = vm.run_code_obj(vm.ctx.new_code(code.clone()), scope);
 
-                        match vm.run_code_obj(vm.ctx.new_code(hint.compiled.clone()), scope) {
-                            Ok(value) => Ok(value),
-                            Err(err) => {
-                                // unwrap() here should be safe
-                                let mut err_str = String::new();
-                                vm.write_exception(&mut err_str, &err).unwrap();
-
-                                Err(VirtualMachineError::HintExecuteError {
-                                    hint_index,
-                                    exception: err_str,
-                                })
+                    let output = hint_output.take();
+                    if !output.is_empty() {
+                        self.hint_output_sink
+                            .write(&self.run_context.borrow().pc, &output);
+                    }
+
+                    match result {
+                        Ok(_) => {
+                            // Write the variables defined by the hint back to its scope, unless
+                            // the hint has exited that scope.
+                            if let Some(exec_locals) =
+                                self.exec_scopes.borrow_mut().get_mut(scope_index)
+                            {
+                                for (key, value) in globals {
+                                    let key = key.str(vm).unwrap().as_str().to_owned();
+                                    if !HINT_CONTEXT_NAMES.contains(&key.as_str()) {
+                                        exec_locals.insert(key, value);
+                                    }
+                                }
                             }
+                            Ok(())
                         }
-                    })?;
-
-                // TODO: implement the following Python code
-                //
-                // ```python
-                // # Clear ids (which will be rewritten by the next hint anyway) to make the VM instance
-                // # smaller and faster to copy.
-                // del exec_locals["ids"]
-                // del exec_locals["memory"]
-                // ```
+                        Err(err) => {
+                            // unwrap() here should be safe
+                            let mut err_str = String::new();
+                            vm.write_exception(&mut err_str, &err).unwrap();
+
+                            Err(VirtualMachineError::HintExecuteError {
+                                hint_index,
+                                exception: err_str,
+                                output,
+                            })
+                        }
+                    }
+                })?;
 
                 if self.skip_instruction_execution {
                     return Ok(());
@@ -502,19 +873,27 @@ impl VirtualMachine {
         }
 
         // Decode.
-        let instruction = self.decode_current_instruction();
+        let pc = self.run_context.borrow().pc.clone();
+        let instruction = match self.instruction_cache.get(&pc) {
+            Some(instruction) => instruction,
+            None => {
+                let instruction = Rc::new(self.decode_current_instruction()?);
+                self.instruction_cache.insert(&pc, instruction.clone());
+                instruction
+            }
+        };
 
         // Run.
         self.run_instruction(&instruction)
     }
 
+    /// Loads the hints of `program`. Python hints are compiled when they first run, or by
+    /// compile_hints().
     pub fn load_hints(
         &mut self,
         program: &FullProgram,
         program_base: MaybeRelocatable,
     ) -> Result<(), VirtualMachineError> {
-        // TODO: change to only compile the hint when no Rust port is available
-
         for (pc, hints) in program.hints.iter() {
             let mut compiled_hints = vec![];
             for (hint_index, hint) in hints.iter().enumerate() {
@@ -523,34 +902,22 @@ impl VirtualMachine {
                 self.hint_pc_and_index
                     .insert(hint_id.into(), (relocated_pc, hint_index.into()));
                 compiled_hints.push(CompiledHint {
-                    compiled: rustpython_vm::compile::compile(
-                        &hint.code,
-                        rustpython_vm::compile::Mode::Exec,
-                        format!("<hint{}>", hint_id),
-                        rustpython_vm::compile::CompileOpts::default(),
-                    )?,
-                    consts: (),
+                    compiled: match find_native_hint(&hint.code) {
+                        Some(native_hint) => HintCode::Native(native_hint),
+                        None => HintCode::Python(PythonHint::new(
+                            hint.code.to_owned(),
+                            format!("<hint{}>", hint_id),
+                        )),
+                    },
+                    // TODO: support all references through `ExpressionEvaluator`, like the Python
+                    //       `VmConsts` does
+                    consts: Rc::new(VmConsts::new(
+                        &hint.flow_tracking_data,
+                        &hint.accessible_scopes,
+                        &program.reference_manager,
+                        &program.identifiers,
+                    )),
                 });
-
-                // TODO: implement the following Python code
-                //
-                // ```python
-                // # Use hint=hint in the lambda's arguments to capture this value (otherwise,
-                // # it will use the same hint object for all iterations).
-                // consts=lambda pc, ap, fp, memory, hint=hint: VmConsts(
-                //     context=VmConstsContext(
-                //         identifiers=program.identifiers,
-                //         evaluator=ExpressionEvaluator(
-                //             self.prime, ap, fp, memory, program.identifiers
-                //         ).eval,
-                //         reference_manager=program.reference_manager,
-                //         flow_tracking_data=hint.flow_tracking_data,
-                //         memory=memory,
-                //         pc=pc,
-                //     ),
-                //     accessible_scopes=hint.accessible_scopes,
-                // ),
-                // ```
             }
             self.hints.insert(
                 MaybeRelocatable::Int(pc.to_owned()) + &program_base,
@@ -561,36 +928,272 @@ impl VirtualMachine {
         Ok(())
     }
 
+    /// Compiles the Python hints that are not compiled yet. Otherwise, hints are only compiled when
+    /// they first run.
+    pub fn compile_hints(&self) -> Result<(), VirtualMachineError> {
+        for hints in self.hints.values() {
+            for hint in hints.iter() {
+                if let HintCode::Python(python_hint) = &hint.compiled {
+                    python_hint.compile()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn load_debug_info(&mut self, debug_info: &DebugInfo, program_base: &MaybeRelocatable) {
+        self.debug_file_contents.extend(
+            debug_info
+                .file_contents
+                .iter()
+                .map(|(filename, content)| (filename.to_owned(), content.to_owned())),
+        );
+
+        for (offset, location_info) in debug_info.instruction_locations.iter() {
+            let mut location_info = location_info.to_owned();
+            // Python reads the content lazily through `InputFile.get_content()`. Only the content
+            // embedded in the debug info is used here.
+            location_info.inst.input_file.content = self
+                .debug_file_contents
+                .get(&location_info.inst.input_file.filename)
+                .cloned();
+
+            self.instruction_debug_info
+                .insert(program_base.to_owned() + offset, location_info);
+        }
+    }
+
+    /// Returns the source location of the instruction at `pc`, if the program has debug info.
+    pub fn get_location(&self, pc: &MaybeRelocatable) -> Option<&InstructionLocation> {
+        self.instruction_debug_info.get(pc)
+    }
+
+    /// Wraps an error raised at the current pc with its location in the source code and, if
+    /// `with_traceback` is set, the Cairo traceback.
+    pub fn as_vm_exception(&self, exc: VirtualMachineError, with_traceback: bool) -> VmException {
+        let pc = self.run_context.borrow().pc.clone();
+        let encoded_instruction = match self.validated_memory.borrow_mut().get(&pc, None) {
+            Some(MaybeRelocatable::Int(encoded_instruction)) => Some(encoded_instruction),
+            _ => None,
+        };
+        let instruction = encoded_instruction.clone().and_then(|encoded_instruction| {
+            let imm = match self
+                .validated_memory
+                .borrow_mut()
+                .get(&(pc.clone() + 1usize), None)
+            {
+                Some(MaybeRelocatable::Int(imm)) => Some(imm),
+                _ => None,
+            };
+            decode_instruction(encoded_instruction, imm).ok()
+        });
+
+        VmException {
+            inst_location: self.get_location(&pc).map(|location| location.inst.clone()),
+            encoded_instruction,
+            instruction,
+            error_attr_value: self.get_error_attr_value(&pc, &self.run_context.borrow().fp),
+            pc,
+            inner_exc: Box::new(exc),
+            traceback: if with_traceback {
+                self.get_traceback()
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Returns the error messages of the `with_attr error_message(...)` scopes `pc` is in, if any.
+    pub fn get_error_attr_value(
+        &self,
+        pc: &MaybeRelocatable,
+        fp: &MaybeRelocatable,
+    ) -> Option<String> {
+        let mut attr_output = String::new();
+        for error_attr in self.error_message_attributes.iter() {
+            if is_in_range(pc, &error_attr.start_pc, &error_attr.end_pc) {
+                attr_output.push_str(&format!(
+                    "Error message: {}\n",
+                    self.substitute_error_message_references(error_attr, fp)
+                ));
+            }
+        }
+
+        if attr_output.is_empty() {
+            None
+        } else {
+            Some(attr_output)
+        }
+    }
+
+    /// Substitutes the `{name}` placeholders of the error message with the values of the
+    /// references. Only fp-based references can be evaluated, as ap is not tracked at the point of
+    /// failure.
+    pub fn substitute_error_message_references(
+        &self,
+        error_message_attr: &VmAttributeScope,
+        fp: &MaybeRelocatable,
+    ) -> String {
+        let consts = match &error_message_attr.consts {
+            Some(consts) => consts,
+            None => return error_message_attr.value.clone(),
+        };
+
+        let mut error_message = String::new();
+        let mut invalid_references = vec![];
+        let mut rest = error_message_attr.value.as_str();
+        while let Some(start) = rest.find('{') {
+            error_message.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let name = rest[1..]
+                .find('}')
+                .map(|end| &rest[1..end + 1])
+                .filter(|name| {
+                    !name.is_empty()
+                        && name
+                            .chars()
+                            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+                });
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    error_message.push('{');
+                    rest = &rest[1..];
+                    continue;
+                }
+            };
+
+            let value = consts
+                .get_fp_address(name, fp)
+                .ok()
+                .and_then(|addr| self.validated_memory.borrow_mut().get(&addr, None));
+            match value {
+                Some(value) => error_message.push_str(&value.to_string()),
+                None => {
+                    invalid_references.push(format!("'{}'", name));
+                    error_message.push_str(&rest[..name.len() + 2]);
+                }
+            }
+            rest = &rest[name.len() + 2..];
+        }
+        error_message.push_str(rest);
+
+        if !invalid_references.is_empty() {
+            error_message.push_str(&format!(
+                " (Cannot evaluate ap-based or complex references: [{}])",
+                invalid_references.join(", ")
+            ));
+        }
+
+        error_message
+    }
+
+    /// Returns the values of pc of the call instructions in the traceback.
+    /// Returns the most recent call last.
+    pub fn get_traceback_entries(&self) -> Vec<MaybeRelocatable> {
+        let one = MaybeRelocatable::Int(BigInt::from(1u32));
+        let two = MaybeRelocatable::Int(BigInt::from(2u32));
+
+        let mut traceback_entries = vec![];
+        let mut memory = self.validated_memory.borrow_mut();
+        // Addresses before the start of their segment are never in memory.
+        let mut get_before = |addr: &MaybeRelocatable, distance: isize| {
+            addr.add_small_mod(-distance, &self.prime)
+                .ok()
+                .and_then(|addr| memory.get(&addr, None))
+        };
+        let mut fp = self.run_context.borrow().fp.clone();
+        for _ in 0..MAX_TRACEBACK_ENTRIES {
+            // Get the previous fp and the return pc.
+            let opt_fp = get_before(&fp, 2);
+            let opt_ret_pc = get_before(&fp, 1);
+
+            // If one of them is not in memory, abort.
+            let (opt_fp, ret_pc) = match (opt_fp, opt_ret_pc) {
+                (Some(opt_fp), Some(ret_pc)) => (opt_fp, ret_pc),
+                _ => break,
+            };
+
+            // Get the two possible call instructions.
+            let instruction0 = get_before(&ret_pc, 2);
+            let instruction1 = get_before(&ret_pc, 1);
+            if is_call_instruction(instruction1.as_ref(), None) {
+                traceback_entries.push(ret_pc - &one);
+            } else if is_call_instruction(instruction0.as_ref(), instruction1.as_ref()) {
+                traceback_entries.push(ret_pc - &two);
+            } else {
+                // If none of them seems like the calling instruction, abort.
+                break;
+            }
+
+            fp = opt_fp;
+        }
+
+        traceback_entries.reverse();
+        traceback_entries
+    }
+
+    /// Returns the traceback at the current pc.
+    pub fn get_traceback(&self) -> Option<String> {
+        let mut traceback = String::new();
+        for traceback_pc in self.get_traceback_entries().iter() {
+            match self.get_location(traceback_pc) {
+                Some(location) => {
+                    traceback.push_str(
+                        &location
+                            .inst
+                            .to_string_with_content(&format!("(pc={})", traceback_pc)),
+                    );
+                }
+                None => traceback.push_str(&format!("Unknown location (pc={})", traceback_pc)),
+            }
+            traceback.push('\n');
+        }
+
+        if traceback.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "Cairo traceback (most recent call last):\n{}",
+                traceback.trim_end()
+            ))
+        }
+    }
+
     pub fn load_program(
         &mut self,
         program: &FullProgram,
         program_base: MaybeRelocatable,
     ) -> Result<(), VirtualMachineError> {
-        // TODO: change to use `Result` for graceful error handling
         if self.prime != program.prime {
-            panic!(
-                "Unexpected prime for loaded program: {} != {}.",
-                program.prime, self.prime
-            );
+            return Err(VirtualMachineError::UnexpectedPrime {
+                program_prime: program.prime.clone(),
+                prime: self.prime.clone(),
+            });
         }
 
-        // TODO: implement the following Python code
-        //
-        // ```python
-        // self.load_debug_info(program.debug_info, program_base)
-        // ```
-
-        self.load_hints(program, program_base)?;
+        if let Some(debug_info) = &program.debug_info {
+            self.load_debug_info(debug_info, &program_base);
+        }
 
-        // TODO: implement the following Python code
-        //
-        // ```python
-        // self.error_message_attributes.extend(
-        //     VmAttributeScope.from_attribute_scope(attr=attr, program_base=program_base)
-        //     for attr in program.attributes
-        //     if attr.name == ERROR_MESSAGE_ATTRIBUTE
-        // )
-        // ```
+        self.load_hints(program, program_base.clone())?;
+
+        self.error_message_attributes.extend(
+            program
+                .attributes
+                .iter()
+                .filter(|attr| attr.name == ERROR_MESSAGE_ATTRIBUTE)
+                .map(|attr| {
+                    VmAttributeScope::from_attribute_scope(
+                        attr,
+                        &program_base,
+                        &program.reference_manager,
+                        &program.identifiers,
+                    )
+                }),
+        );
 
         Ok(())
     }
@@ -602,7 +1205,7 @@ impl VirtualMachine {
     ) -> Result<(), VirtualMachineError> {
         // Update fp.
         let new_fp_value = match instruction.fp_update {
-            FpUpdate::AP_PLUS2 => Some(self.run_context.borrow().ap.clone() + &BigInt::from(2u32)),
+            FpUpdate::AP_PLUS2 => Some(self.run_context.borrow().ap.clone() + 2usize),
             FpUpdate::DST => Some(operands.dst.clone()),
             FpUpdate::REGULAR => None,
         };
@@ -611,37 +1214,36 @@ impl VirtualMachine {
         }
 
         // Update ap.
+        let ap = self.run_context.borrow().ap.clone();
         let new_ap_value = match instruction.ap_update {
             ApUpdate::ADD => match &operands.res {
-                Some(res) => {
-                    Some(self.run_context.borrow().ap.clone() + &(res.to_owned() % &self.prime))
+                Some(res @ MaybeRelocatable::Int(_)) => ap.add_mod(res, &self.prime)?,
+                // ap can't be moved by an address, e.g. when res is the target of an absolute jump.
+                Some(MaybeRelocatable::RelocatableValue(_)) => {
+                    return Err(VirtualMachineError::PureValueError(PureValueError {}))
                 }
                 None => return Err(VirtualMachineError::AddWithUnconstrained),
             },
-            ApUpdate::ADD1 => Some(self.run_context.borrow().ap.clone() + &BigInt::from(1)),
-            ApUpdate::ADD2 => Some(self.run_context.borrow().ap.clone() + &BigInt::from(2)),
-            ApUpdate::REGULAR => None,
-        };
-        let new_ap_value = match new_ap_value {
-            Some(new_ap_value) => new_ap_value % &self.prime,
-            None => self.run_context.borrow().ap.clone() % &self.prime,
+            ApUpdate::ADD1 => ap.add_small_mod(1, &self.prime)?,
+            ApUpdate::ADD2 => ap.add_small_mod(2, &self.prime)?,
+            ApUpdate::REGULAR => ap % &self.prime,
         };
         self.run_context.as_ref().borrow_mut().ap = new_ap_value;
 
         // Update pc.
         // The pc update should be done last so that we will have the correct pc in case of an
         // exception during one of the updates above.
+        let pc = self.run_context.borrow().pc.clone();
+        let instruction_size = instruction.size() as isize;
         let new_pc_value = match instruction.pc_update {
-            PcUpdate::REGULAR => {
-                Some(self.run_context.borrow().pc.clone() + &BigInt::from(instruction.size()))
-            }
+            PcUpdate::REGULAR => pc.add_small_mod(instruction_size, &self.prime)?,
             PcUpdate::JUMP => match &operands.res {
-                Some(res) => Some(res.to_owned()),
+                Some(res) => res.to_owned() % &self.prime,
                 None => return Err(VirtualMachineError::JumpWithUnconstrained),
             },
             PcUpdate::JUMP_REL => match &operands.res {
                 Some(res) => match res {
-                    MaybeRelocatable::Int(res) => Some(self.run_context.borrow().pc.clone() + res),
+                    MaybeRelocatable::Int(_) => pc.add_mod(res, &self.prime)?,
                     &MaybeRelocatable::RelocatableValue(_) => {
                         return Err(VirtualMachineError::PureValueError(PureValueError {}))
                     }
@@ -650,16 +1252,12 @@ impl VirtualMachine {
             },
             PcUpdate::JNZ => {
                 if is_zero(&operands.dst)? {
-                    Some(self.run_context.borrow().pc.clone() + &BigInt::from(instruction.size()))
+                    pc.add_small_mod(instruction_size, &self.prime)?
                 } else {
-                    Some(self.run_context.borrow().pc.clone() + &operands.op1)
+                    pc.add_mod(&operands.op1, &self.prime)?
                 }
             }
         };
-        let new_pc_value = match new_pc_value {
-            Some(new_pc_value) => new_pc_value % &self.prime,
-            None => self.run_context.borrow().pc.clone() % &self.prime,
-        };
         self.run_context.as_ref().borrow_mut().pc = new_pc_value;
 
         Ok(())
@@ -667,24 +1265,25 @@ impl VirtualMachine {
 
     /// Returns a tuple (deduced_op0, deduced_res).
     /// Deduces the value of op0 if possible (based on dst and op1). Otherwise, returns None.
-    /// If res was already deduced, returns its deduced value as well.
+    /// If res was already deduced, returns its deduced value as well. Fails if dst and the other
+    /// operand can't be subtracted, e.g. an address from an integer.
     #[allow(unused)]
     pub fn deduce_op0(
         &self,
         instruction: &Instruction,
         dst: Option<MaybeRelocatable>,
         op1: Option<MaybeRelocatable>,
-    ) -> (Option<MaybeRelocatable>, Option<MaybeRelocatable>) {
-        match instruction.opcode {
+    ) -> Result<(Option<MaybeRelocatable>, Option<MaybeRelocatable>), VirtualMachineError> {
+        Ok(match instruction.opcode {
             Opcode::CALL => (
-                Some(self.run_context.borrow().pc.clone() + &BigInt::from(instruction.size())),
+                Some(self.run_context.borrow().pc.clone() + instruction.size() as usize),
                 None,
             ),
             Opcode::ASSERT_EQ => {
                 if let (Res::ADD, Some(dst), Some(op1)) =
                     (&instruction.res, dst.clone(), op1.clone())
                 {
-                    (Some((dst.clone() - &op1) % &self.prime), Some(dst))
+                    (Some(dst.checked_sub(&op1)? % &self.prime), Some(dst))
                 } else if let (
                     Res::MUL,
                     Some(MaybeRelocatable::Int(dst)),
@@ -692,12 +1291,10 @@ impl VirtualMachine {
                 ) = (&instruction.res, dst, op1)
                 {
                     if op1 != BigInt::from(0u32) {
-                        // TODO: implement the following Python code
-                        //
-                        // ```python
-                        // return div_mod(dst, op1, self.prime), dst
-                        // ```
-                        todo!()
+                        match div_mod(&dst, &op1, &self.prime) {
+                            Some(op0) => (Some(op0.into()), Some(dst.into())),
+                            None => (None, None),
+                        }
                     } else {
                         (None, None)
                     }
@@ -706,48 +1303,47 @@ impl VirtualMachine {
                 }
             }
             _ => (None, None),
-        }
+        })
     }
 
     /// Returns a tuple (deduced_op1, deduced_res).
     /// Deduces the value of op1 if possible (based on dst and op0). Otherwise, returns None.
-    /// If res was already deduced, returns its deduced value as well.
+    /// If res was already deduced, returns its deduced value as well. Fails if dst and the other
+    /// operand can't be subtracted, e.g. an address from an integer.
     pub fn deduce_op1(
         &self,
         instruction: &Instruction,
         dst: Option<MaybeRelocatable>,
         op0: Option<MaybeRelocatable>,
-    ) -> (Option<MaybeRelocatable>, Option<MaybeRelocatable>) {
-        match instruction.opcode {
+    ) -> Result<(Option<MaybeRelocatable>, Option<MaybeRelocatable>), VirtualMachineError> {
+        Ok(match instruction.opcode {
             Opcode::ASSERT_EQ => {
                 if let (Res::OP1, Some(dst)) = (&instruction.res, dst.clone()) {
                     (Some(dst.clone()), Some(dst))
                 } else if let (Res::ADD, Some(dst), Some(op0)) =
                     (&instruction.res, dst.clone(), op0.clone())
                 {
-                    (Some((dst.clone() - &op0) % &self.prime), Some(dst))
+                    (Some(dst.checked_sub(&op0)? % &self.prime), Some(dst))
                 } else if let (
                     Res::MUL,
-                    Some(MaybeRelocatable::Int(_)),
+                    Some(MaybeRelocatable::Int(dst)),
                     Some(MaybeRelocatable::Int(op0)),
-                ) = (&instruction.res, &dst, op0)
+                ) = (&instruction.res, dst, op0)
                 {
                     if op0 != BigInt::from(0u32) {
-                        // TODO: implement the following Python code
-                        //
-                        // ```python
-                        // return div_mod(dst, op0, self.prime), dst
-                        // ```
-                        todo!()
+                        match div_mod(&dst, &op0, &self.prime) {
+                            Some(op1) => (Some(op1.into()), Some(dst.into())),
+                            None => (None, None),
+                        }
                     } else {
                         (None, None)
                     }
                 } else {
-                    todo!()
+                    (None, None)
                 }
             }
             _ => (None, None),
-        }
+        })
     }
 
     /// Computes the value of res if possible.
@@ -762,7 +1358,7 @@ impl VirtualMachine {
             Res::ADD => Some((op0 + &op1) % &self.prime),
             Res::MUL => {
                 if let (MaybeRelocatable::Int(op0), MaybeRelocatable::Int(op1)) = (op0, op1) {
-                    Some(((op0 * op1) % &self.prime).into())
+                    Some(MaybeRelocatable::Int(op0 * op1) % &self.prime)
                 } else {
                     return Err(VirtualMachineError::PureValueError(PureValueError {}));
                 }
@@ -791,9 +1387,9 @@ impl VirtualMachine {
         // op0 throughout this function represents the value at op0_addr.
         // If op0 is set, this implies that we are going to set memory at op0_addr to that value.
         // Same for op1, dst.
-        let dst_addr = self.run_context.borrow().compute_dst_addr(instruction);
+        let dst_addr = self.run_context.borrow().compute_dst_addr(instruction)?;
         let mut dst = self.validated_memory.borrow_mut().get(&dst_addr, None);
-        let op0_addr = self.run_context.borrow().compute_op0_addr(instruction);
+        let op0_addr = self.run_context.borrow().compute_op0_addr(instruction)?;
         let mut op0 = self.validated_memory.borrow_mut().get(&op0_addr, None);
         let op1_addr = self
             .run_context
@@ -814,10 +1410,10 @@ impl VirtualMachine {
         // Note: This may fail to deduce if 2 auto deduction rules are needed to be used in
         // a different order.
         if matches!(op0, None) {
-            op0 = self.deduce_memory_cell(&op0_addr);
+            op0 = self.deduce_memory_cell(&op0_addr)?;
         }
         if matches!(op1, None) {
-            op1 = self.deduce_memory_cell(&op1_addr);
+            op1 = self.deduce_memory_cell(&op1_addr)?;
         }
 
         let should_update_dst = dst.is_none();
@@ -826,7 +1422,7 @@ impl VirtualMachine {
 
         // Deduce op0 if needed.
         if op0.is_none() {
-            let temp = self.deduce_op0(instruction, dst.clone(), op1.clone());
+            let temp = self.deduce_op0(instruction, dst.clone(), op1.clone())?;
             op0 = temp.0;
             let deduced_res = temp.1;
             if res.is_none() {
@@ -836,7 +1432,7 @@ impl VirtualMachine {
 
         // Deduce op1 if needed.
         if op1.is_none() {
-            let temp = self.deduce_op1(instruction, dst.clone(), op0.clone());
+            let temp = self.deduce_op1(instruction, dst.clone(), op0.clone())?;
             op1 = temp.0;
             let deduced_res = temp.1;
             if res.is_none() {
@@ -852,7 +1448,7 @@ impl VirtualMachine {
         };
         let op1 = match op1 {
             Some(op1) => op1,
-            None => self.validated_memory.borrow_mut().index(&op0_addr)?,
+            None => self.validated_memory.borrow_mut().index(&op1_addr)?,
         };
 
         // Compute res if needed.
@@ -879,17 +1475,17 @@ impl VirtualMachine {
         if should_update_dst {
             self.validated_memory
                 .borrow_mut()
-                .index_set(dst_addr.clone(), dst.clone());
+                .index_set(dst_addr.clone(), dst.clone())?;
         }
         if should_update_op0 {
             self.validated_memory
                 .borrow_mut()
-                .index_set(op0_addr.clone(), op0.clone());
+                .index_set(op0_addr.clone(), op0.clone())?;
         }
         if should_update_op1 {
             self.validated_memory
                 .borrow_mut()
-                .index_set(op1_addr.clone(), op1.clone());
+                .index_set(op1_addr.clone(), op1.clone())?;
         }
 
         Ok((
@@ -899,16 +1495,16 @@ impl VirtualMachine {
     }
 
     #[allow(clippy::let_and_return)] // Doing this on purpose to mimic Python code
-    pub fn decode_current_instruction(&self) -> Instruction {
+    pub fn decode_current_instruction(&self) -> Result<Instruction, VirtualMachineError> {
         let (instruction_encoding, imm) = self
             .run_context
             .as_ref()
             .borrow_mut()
-            .get_instruction_encoding();
+            .get_instruction_encoding()?;
 
-        let instruction = decode_instruction(instruction_encoding, imm);
+        let instruction = decode_instruction(instruction_encoding, imm)?;
 
-        instruction
+        Ok(instruction)
     }
 
     pub fn opcode_assertions(
@@ -931,8 +1527,7 @@ impl VirtualMachine {
                 None => Err(VirtualMachineError::AssertEqWithUnconstrained),
             },
             Opcode::CALL => {
-                let return_pc =
-                    self.run_context.borrow().pc.clone() + &BigInt::from(instruction.size());
+                let return_pc = self.run_context.borrow().pc.clone() + instruction.size() as usize;
                 if operands.op0 != return_pc && !check_eq(&operands.op0, &return_pc) {
                     return Err(VirtualMachineError::FailedToWriteReturnPc {
                         op0: operands.op0.clone(),
@@ -965,6 +1560,20 @@ impl VirtualMachine {
         // Opcode assertions.
         self.opcode_assertions(instruction, &operands)?;
 
+        // The function a program starts in is not entered with a CALL, so its RET doesn't have a
+        // matching CALL either.
+        let call_depth = match instruction.opcode {
+            Opcode::CALL => self.call_depth + 1,
+            Opcode::RET => self.call_depth.saturating_sub(1),
+            _ => self.call_depth,
+        };
+        if matches!(self.max_call_depth, Some(max_call_depth) if call_depth > max_call_depth) {
+            return Err(VirtualMachineError::CallDepthExceeded {
+                depth: call_depth,
+                pc: self.run_context.borrow().pc.clone(),
+            });
+        }
+
         // Write to trace.
         self.trace.push(TraceEntry {
             pc: self.run_context.borrow().pc.clone(),
@@ -972,15 +1581,17 @@ impl VirtualMachine {
             fp: self.run_context.borrow().fp.clone(),
         });
 
-        for addr in operands_mem_addresses.into_iter() {
+        for addr in operands_mem_addresses.iter() {
             self.accessed_addresses.insert(addr);
         }
         self.accessed_addresses
-            .insert(self.run_context.borrow().pc.clone());
+            .insert(&self.run_context.borrow().pc);
 
         // Update registers.
         self.update_registers(instruction, &operands)?;
 
+        self.call_depth = call_depth;
+        self.peak_call_depth = self.peak_call_depth.max(call_depth);
         self.current_step += 1;
 
         Ok(())
@@ -989,27 +1600,40 @@ impl VirtualMachine {
     /// Tries to deduce the value of memory\[addr\] if it was not already computed.
     ///
     /// Returns the value if deduced, otherwise returns None.
-    pub fn deduce_memory_cell(&mut self, addr: &MaybeRelocatable) -> Option<MaybeRelocatable> {
-        match addr {
-            MaybeRelocatable::Int(_) => None,
-            MaybeRelocatable::RelocatableValue(addr) => {
-                match self.auto_deduction.get(&addr.segment_index) {
-                    Some(rules) => {
-                        for (rule, args) in rules.iter() {
-                            match (rule.inner)(self, addr, args) {
-                                Some(value) => self
-                                    .validated_memory
-                                    .borrow_mut()
-                                    .index_set(addr.to_owned().into(), value.into()),
-                                None => continue,
-                            }
-                        }
-                        None
+    pub fn deduce_memory_cell(
+        &mut self,
+        addr: &MaybeRelocatable,
+    ) -> Result<Option<MaybeRelocatable>, VirtualMachineError> {
+        if let MaybeRelocatable::RelocatableValue(addr) = addr {
+            if let Some(rules) = self.auto_deduction.get(&addr.segment_index) {
+                for (rule, args) in rules.iter() {
+                    if let Some(value) = (rule.inner)(self, addr, args) {
+                        self.validated_memory
+                            .borrow_mut()
+                            .index_set(addr.to_owned().into(), value.clone().into())?;
+                        return Ok(Some(value.into()));
                     }
-                    None => None,
                 }
             }
         }
+
+        Ok(None)
+    }
+
+    /// Adds an auto deduction rule for the memory cells of the given segment.
+    pub fn add_auto_deduction_rule(&mut self, segment_index: isize, rule: Rule, args: ()) {
+        self.auto_deduction
+            .entry(segment_index)
+            .or_default()
+            .push((rule, args));
+    }
+
+    /// Validates the memory cells that were written before the validation rules were added.
+    pub fn validate_existing_memory(&mut self) -> Result<(), VirtualMachineError> {
+        Ok(self
+            .validated_memory
+            .borrow_mut()
+            .validate_existing_memory()?)
     }
 
     /// Makes sure that all assigned memory cells are consistent with their auto deduction rules.
@@ -1034,10 +1658,8 @@ impl VirtualMachine {
                         for (rule, args) in rules.iter() {
                             match (rule.inner)(self, &addr, args) {
                                 Some(value) => {
-                                    let current = self
-                                        .validated_memory
-                                        .borrow_mut()
-                                        .index(&addr.clone().into())?;
+                                    let current =
+                                        self.validated_memory.borrow_mut().index(&addr.into())?;
 
                                     // If the values are not the same, try using check_eq to
                                     // allow a subclass to override this result.
@@ -1066,7 +1688,7 @@ impl VirtualMachine {
 
     pub fn end_run(&mut self) -> Result<(), VirtualMachineError> {
         self.verify_auto_deductions()?;
-        if self.exec_scopes.len() != 1 {
+        if self.exec_scopes.borrow().len() != 1 {
             return Err(VirtualMachineError::EnterExitScopeMismatch);
         }
 
@@ -1095,47 +1717,172 @@ impl Debug for VirtualMachine {
             .field("run_context", &self.run_context)
             .field("accessed_addresses", &self.accessed_addresses)
             .field("trace", &self.trace)
+            .field("memory_write_marks", &self.memory_write_marks)
             .field("current_step", &self.current_step)
+            .field("call_depth", &self.call_depth)
+            .field("peak_call_depth", &self.peak_call_depth)
+            .field("max_call_depth", &self.max_call_depth)
+            .field("hint_output_sink", &self.hint_output_sink)
             .finish()
     }
 }
 
+impl From<AddressOutOfRangeError> for RunContextError {
+    fn from(value: AddressOutOfRangeError) -> Self {
+        RunContextError::AddressOutOfRange(value)
+    }
+}
+
+impl From<MemoryDictError> for RunContextError {
+    fn from(value: MemoryDictError) -> Self {
+        RunContextError::MemoryDictError(value)
+    }
+}
+
 impl From<RunContextError> for VirtualMachineError {
     fn from(value: RunContextError) -> Self {
         VirtualMachineError::RunContextError(value)
     }
 }
 
+impl From<AddressOutOfRangeError> for VirtualMachineError {
+    fn from(value: AddressOutOfRangeError) -> Self {
+        VirtualMachineError::RunContextError(value.into())
+    }
+}
+
+impl From<SubtractionError> for VirtualMachineError {
+    fn from(value: SubtractionError) -> Self {
+        VirtualMachineError::SubtractionError(value)
+    }
+}
+
 impl From<MemoryDictError> for VirtualMachineError {
     fn from(value: MemoryDictError) -> Self {
         VirtualMachineError::MemoryDictError(value)
     }
 }
 
+impl From<BuiltinRunnerError> for VirtualMachineError {
+    fn from(value: BuiltinRunnerError) -> Self {
+        VirtualMachineError::BuiltinRunnerError(value)
+    }
+}
+
 impl From<PureValueError> for VirtualMachineError {
     fn from(value: PureValueError) -> Self {
         VirtualMachineError::PureValueError(value)
     }
 }
 
+impl From<InstructionDecodeError> for VirtualMachineError {
+    fn from(value: InstructionDecodeError) -> Self {
+        VirtualMachineError::InstructionDecodeError(value)
+    }
+}
+
 impl From<rustpython_vm::compile::CompileError> for VirtualMachineError {
     fn from(value: rustpython_vm::compile::CompileError) -> Self {
         VirtualMachineError::HintCompileError(value)
     }
 }
 
+/// Pushes a new scope with the given locals. Shared by `VirtualMachine::enter_scope()` and the
+/// `vm_enter_scope` callable exposed to hints.
+fn enter_scope(
+    exec_scopes: &RefCell<Vec<HashMap<String, PyObjectRef>>>,
+    new_scope_locals: Option<HashMap<String, PyObjectRef>>,
+) {
+    // TODO: add builtin_runners to hint scope
+
+    exec_scopes
+        .borrow_mut()
+        .push(new_scope_locals.unwrap_or_default());
+}
+
+/// Pops the last scope. Shared by `VirtualMachine::exit_scope()` and the `vm_exit_scope` callable
+/// exposed to hints.
+fn exit_scope(
+    exec_scopes: &RefCell<Vec<HashMap<String, PyObjectRef>>>,
+) -> Result<(), VirtualMachineError> {
+    let mut exec_scopes = exec_scopes.borrow_mut();
+    if exec_scopes.len() <= 1 {
+        return Err(VirtualMachineError::CannotExitMainScope);
+    }
+    exec_scopes.pop();
+
+    Ok(())
+}
+
+fn py_dict_to_scope(dict: PyDictRef, vm: &PythonVm) -> PyResult<HashMap<String, PyObjectRef>> {
+    let mut scope = HashMap::new();
+    for (key, value) in dict {
+        scope.insert(key.str(vm)?.as_str().to_owned(), value);
+    }
+    Ok(scope)
+}
+
+/// Formats the output of a failed hint for its error message.
+fn format_hint_output(output: &str) -> String {
+    if output.is_empty() {
+        String::new()
+    } else {
+        format!("\nHint output:\n{}", output.trim_end())
+    }
+}
+
+/// Returns whether `start <= pc < end`, where all values are in the same segment.
+fn is_in_range(pc: &MaybeRelocatable, start: &MaybeRelocatable, end: &MaybeRelocatable) -> bool {
+    match (pc, start, end) {
+        (MaybeRelocatable::Int(pc), MaybeRelocatable::Int(start), MaybeRelocatable::Int(end)) => {
+            start <= pc && pc < end
+        }
+        (
+            MaybeRelocatable::RelocatableValue(pc),
+            MaybeRelocatable::RelocatableValue(start),
+            MaybeRelocatable::RelocatableValue(end),
+        ) => {
+            pc.segment_index == start.segment_index
+                && pc.segment_index == end.segment_index
+                && start.offset <= pc.offset
+                && pc.offset < end.offset
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if the given instruction looks like a call instruction.
+fn is_call_instruction(
+    encoded_instruction: Option<&MaybeRelocatable>,
+    imm: Option<&MaybeRelocatable>,
+) -> bool {
+    let encoded_instruction = match encoded_instruction {
+        Some(MaybeRelocatable::Int(encoded_instruction)) => encoded_instruction.to_owned(),
+        _ => return false,
+    };
+    let imm = match imm {
+        Some(MaybeRelocatable::Int(imm)) => Some(imm.to_owned()),
+        Some(MaybeRelocatable::RelocatableValue(_)) => return false,
+        None => None,
+    };
+
+    match decode_instruction(encoded_instruction, imm) {
+        Ok(instruction) => {
+            matches!(instruction.opcode, Opcode::CALL)
+                && matches!(instruction.ap_update, ApUpdate::ADD2)
+                && matches!(instruction.fp_update, FpUpdate::AP_PLUS2)
+        }
+        Err(_) => false,
+    }
+}
+
 /// Returns True if value is zero (used for jnz instructions).
 /// This function can be overridden by subclasses.
 fn is_zero(value: &MaybeRelocatable) -> Result<bool, PureValueError> {
     match value {
         MaybeRelocatable::Int(value) => Ok(value == &BigInt::from(0u32)),
-        MaybeRelocatable::RelocatableValue(value) => {
-            if value.offset >= BigInt::from(0u32) {
-                Ok(false)
-            } else {
-                Err(PureValueError {})
-            }
-        }
+        // Offsets can't be negative, so an address is never zero.
+        MaybeRelocatable::RelocatableValue(_) => Ok(false),
     }
 }
 
@@ -1144,3 +1891,369 @@ fn is_zero(value: &MaybeRelocatable) -> Result<bool, PureValueError> {
 fn check_eq(val0: &MaybeRelocatable, val1: &MaybeRelocatable) -> bool {
     val0 == val1
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::cairo::lang::vm::memory_segments::MemorySegmentManager;
+
+    fn prime() -> BigInt {
+        BigInt::parse_bytes(
+            b"800000000000011000000000000000000000000000000000000000000000001",
+            16,
+        )
+        .unwrap()
+    }
+
+    fn ptr(segment_index: isize, offset: usize) -> MaybeRelocatable {
+        RelocatableValue::new(segment_index, offset).into()
+    }
+
+    fn run_context(pc: MaybeRelocatable, ap: MaybeRelocatable, fp: MaybeRelocatable) -> RunContext {
+        RunContext::new(
+            Rc::new(RefCell::new(MemoryDict::new())),
+            pc,
+            ap,
+            fp,
+            prime(),
+        )
+    }
+
+    /// Creates a VM running `run_past_end` with the given registers.
+    fn virtual_machine(
+        memory: Rc<RefCell<MemoryDict>>,
+        pc: MaybeRelocatable,
+        ap: MaybeRelocatable,
+        fp: MaybeRelocatable,
+    ) -> VirtualMachine {
+        let program: Rc<Program> = Rc::new(
+            serde_json::from_str::<FullProgram>(include_str!(
+                "../../../../test-data/artifacts/run_past_end.json"
+            ))
+            .unwrap()
+            .into(),
+        );
+        let segments = Rc::new(RefCell::new(MemorySegmentManager::new(
+            memory.clone(),
+            prime(),
+        )));
+
+        VirtualMachine::new(
+            program,
+            Rc::new(RefCell::new(RunContext::new(memory, pc, ap, fp, prime()))),
+            HashMap::new(),
+            StaticLocals::new(segments),
+            None,
+            Some(ptr(0, 0)),
+        )
+    }
+
+    /// `[ap + 1] = [fp + (-3)] + 5`.
+    fn instruction() -> Instruction {
+        decode_instruction(
+            BigInt::from(0x402680017ffd8001u64),
+            Some(BigInt::from(5u32)),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compute_relocatable_addrs() {
+        let context = run_context(ptr(0, 4), ptr(1, 5), ptr(1, 1));
+        let instruction = instruction();
+
+        assert_eq!(context.compute_dst_addr(&instruction).unwrap(), ptr(1, 6));
+        // Offsets can't be negative.
+        assert!(matches!(
+            context.compute_op0_addr(&instruction),
+            Err(RunContextError::AddressOutOfRange(AddressOutOfRangeError { offset, .. }))
+                if offset == BigInt::from(-2)
+        ));
+        assert_eq!(
+            context.compute_op1_addr(&instruction, None).unwrap(),
+            ptr(0, 5)
+        );
+    }
+
+    #[test]
+    fn test_compute_imm_addr() {
+        let instruction = instruction();
+
+        for pc in [ptr(0, 4), ptr(2, usize::MAX - 1)] {
+            let context = run_context(pc.clone(), ptr(1, 5), ptr(1, 1));
+            assert_eq!(
+                context.compute_op1_addr(&instruction, None).unwrap(),
+                pc + 1usize
+            );
+        }
+    }
+
+    #[test]
+    fn test_compute_felt_addrs() {
+        let last = MaybeRelocatable::from(prime() - BigInt::from(1u32));
+        let context = run_context(last.clone(), last, BigInt::from(7u32).into());
+        let instruction = instruction();
+
+        assert_eq!(
+            context.compute_dst_addr(&instruction).unwrap(),
+            MaybeRelocatable::from(BigInt::from(0u32))
+        );
+        assert_eq!(
+            context.compute_op0_addr(&instruction).unwrap(),
+            MaybeRelocatable::from(BigInt::from(4u32))
+        );
+        assert_eq!(
+            context.compute_op1_addr(&instruction, None).unwrap(),
+            MaybeRelocatable::from(BigInt::from(0u32))
+        );
+    }
+
+    #[test]
+    fn test_accessed_addresses() {
+        let mut accessed_addresses = AccessedAddresses::new(&ptr(0, 2), 3);
+        assert_eq!(accessed_addresses.len(), 3);
+
+        // Program addresses are already there.
+        accessed_addresses.insert(&ptr(0, 4));
+        accessed_addresses.insert(&ptr(0, 5));
+        accessed_addresses.insert(&ptr(1, 2));
+        accessed_addresses.insert(&ptr(1, 2));
+        accessed_addresses.insert(&BigInt::from(3u32).into());
+
+        assert!(accessed_addresses.contains(&RelocatableValue::new(0, 2)));
+        assert!(!accessed_addresses.contains(&RelocatableValue::new(0, 1)));
+        assert!(accessed_addresses.contains(&RelocatableValue::new(0, 5)));
+        assert_eq!(
+            accessed_addresses.iter().collect::<HashSet<_>>(),
+            HashSet::from([
+                RelocatableValue::new(0, 2),
+                RelocatableValue::new(0, 3),
+                RelocatableValue::new(0, 4),
+                RelocatableValue::new(0, 5),
+                RelocatableValue::new(1, 2),
+            ])
+        );
+        assert_eq!(accessed_addresses.len(), 5);
+    }
+
+    #[test]
+    fn test_compute_operands_op1_from_memory() {
+        // `[ap] = [fp + 3]`. dst is unknown, so op1 can't be deduced and is read from memory.
+        let instruction = decode_instruction(BigInt::from(0x400a80037fff8000u64), None).unwrap();
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        // op0 is `[fp - 1]`.
+        memory
+            .borrow_mut()
+            .index_set(ptr(1, 9), BigInt::from(7u32).into())
+            .unwrap();
+        let mut vm = virtual_machine(memory.clone(), ptr(0, 0), ptr(1, 20), ptr(1, 10));
+
+        match vm.compute_operands(&instruction) {
+            Err(VirtualMachineError::MemoryDictError(MemoryDictError::UnknownMemory { addr })) => {
+                assert_eq!(addr, ptr(1, 13))
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        memory
+            .borrow_mut()
+            .index_set(ptr(1, 13), BigInt::from(42u32).into())
+            .unwrap();
+        let (operands, addresses) = vm.compute_operands(&instruction).unwrap();
+        assert_eq!(
+            operands,
+            Operands {
+                dst: BigInt::from(42u32).into(),
+                res: Some(BigInt::from(42u32).into()),
+                op0: BigInt::from(7u32).into(),
+                op1: BigInt::from(42u32).into(),
+            }
+        );
+        assert_eq!(addresses, vec![ptr(1, 20), ptr(1, 9), ptr(1, 13)]);
+    }
+
+    #[test]
+    fn test_deduce_op0_add() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let vm = virtual_machine(memory, ptr(0, 0), ptr(1, 20), ptr(1, 10));
+        let instruction = instruction();
+
+        assert_eq!(
+            vm.deduce_op0(
+                &instruction,
+                Some(ptr(1, 7)),
+                Some(BigInt::from(5u32).into())
+            )
+            .unwrap(),
+            (Some(ptr(1, 2)), Some(ptr(1, 7)))
+        );
+
+        // Like in Python, an address can't be subtracted from an integer.
+        assert!(matches!(
+            vm.deduce_op0(
+                &instruction,
+                Some(BigInt::from(7u32).into()),
+                Some(ptr(1, 2))
+            ),
+            Err(VirtualMachineError::SubtractionError(
+                SubtractionError::IntMinusRelocatable
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_deduce_op1_mul() {
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        let vm = virtual_machine(memory, ptr(0, 0), ptr(1, 20), ptr(1, 10));
+        let instruction = Instruction {
+            res: Res::MUL,
+            ..instruction()
+        };
+
+        // op1 = dst / op0 in the field, which isn't an integer division for 15 / 4.
+        assert_eq!(
+            vm.deduce_op1(
+                &instruction,
+                Some(BigInt::from(15u32).into()),
+                Some(BigInt::from(5u32).into())
+            )
+            .unwrap(),
+            (
+                Some(BigInt::from(3u32).into()),
+                Some(BigInt::from(15u32).into())
+            )
+        );
+        let (op1, _) = vm
+            .deduce_op1(
+                &instruction,
+                Some(BigInt::from(15u32).into()),
+                Some(BigInt::from(4u32).into()),
+            )
+            .unwrap();
+        match op1 {
+            Some(MaybeRelocatable::Int(op1)) => {
+                assert_eq!(op1 * 4u32 % prime(), BigInt::from(15u32))
+            }
+            _ => panic!("unexpected result"),
+        }
+
+        // Nothing is deduced from a zero op0 or from an address.
+        assert_eq!(
+            vm.deduce_op1(
+                &instruction,
+                Some(BigInt::from(15u32).into()),
+                Some(BigInt::from(0u32).into())
+            )
+            .unwrap(),
+            (None, None)
+        );
+        assert_eq!(
+            vm.deduce_op1(
+                &instruction,
+                Some(ptr(1, 7)),
+                Some(BigInt::from(5u32).into())
+            )
+            .unwrap(),
+            (None, None)
+        );
+    }
+
+    /// Runs a NOP with the given updates, where dst is `[fp]`, op0 is `[fp + 1]` and op1 (and res,
+    /// unless jumping conditionally) is `[fp + 2]`. Returns the registers after the step.
+    fn run_nop(
+        pc_update: PcUpdate,
+        ap_update: ApUpdate,
+        dst: u32,
+        op1: MaybeRelocatable,
+    ) -> Result<TraceEntry<MaybeRelocatable>, VirtualMachineError> {
+        let res = match pc_update {
+            PcUpdate::JNZ => Res::UNCONSTRAINED,
+            _ => Res::OP1,
+        };
+        let instruction = Instruction {
+            off0: 0,
+            off1: 1,
+            off2: 2,
+            imm: None,
+            dst_register: Register::FP,
+            op0_register: Register::FP,
+            op1_addr: Op1Addr::FP,
+            res,
+            pc_update,
+            ap_update,
+            fp_update: FpUpdate::REGULAR,
+            opcode: Opcode::NOP,
+        };
+
+        let memory = Rc::new(RefCell::new(MemoryDict::new()));
+        for (addr, value) in [
+            (ptr(1, 5), BigInt::from(dst).into()),
+            (ptr(1, 6), BigInt::from(7u32).into()),
+            (ptr(1, 7), op1),
+        ] {
+            memory.borrow_mut().index_set(addr, value).unwrap();
+        }
+        let mut vm = virtual_machine(memory, ptr(0, 0), ptr(1, 10), ptr(1, 5));
+
+        vm.run_instruction(&instruction)?;
+
+        let run_context = vm.run_context.borrow();
+        Ok(TraceEntry {
+            pc: run_context.pc.clone(),
+            ap: run_context.ap.clone(),
+            fp: run_context.fp.clone(),
+        })
+    }
+
+    #[test]
+    fn test_nop_register_updates() {
+        let int = |value: u32| MaybeRelocatable::from(BigInt::from(value));
+
+        // The expected offsets of pc and ap, fp being left as is.
+        for (pc_update, ap_update, dst, op1, (pc, ap)) in [
+            // nop
+            (PcUpdate::REGULAR, ApUpdate::REGULAR, 1, int(3), (1, 10)),
+            // ap += [fp + 2]
+            (PcUpdate::REGULAR, ApUpdate::ADD, 1, int(3), (1, 13)),
+            (PcUpdate::REGULAR, ApUpdate::ADD1, 1, int(3), (1, 11)),
+            // jmp abs [fp + 2]
+            (PcUpdate::JUMP, ApUpdate::REGULAR, 1, ptr(0, 8), (8, 10)),
+            (PcUpdate::JUMP, ApUpdate::ADD1, 1, ptr(0, 8), (8, 11)),
+            // jmp rel [fp + 2]
+            (PcUpdate::JUMP_REL, ApUpdate::REGULAR, 1, int(4), (4, 10)),
+            (PcUpdate::JUMP_REL, ApUpdate::ADD, 1, int(4), (4, 14)),
+            (PcUpdate::JUMP_REL, ApUpdate::ADD1, 1, int(4), (4, 11)),
+            // jmp rel [fp + 2] if [fp] != 0
+            (PcUpdate::JNZ, ApUpdate::REGULAR, 1, int(4), (4, 10)),
+            (PcUpdate::JNZ, ApUpdate::REGULAR, 0, int(4), (1, 10)),
+            (PcUpdate::JNZ, ApUpdate::ADD1, 1, int(4), (4, 11)),
+            (PcUpdate::JNZ, ApUpdate::ADD1, 0, int(4), (1, 11)),
+        ] {
+            assert_eq!(
+                run_nop(pc_update, ap_update, dst, op1).unwrap(),
+                TraceEntry {
+                    pc: ptr(0, pc),
+                    ap: ptr(1, ap),
+                    fp: ptr(1, 5),
+                }
+            );
+        }
+
+        // ap can't be moved by an address.
+        assert!(matches!(
+            run_nop(PcUpdate::JUMP, ApUpdate::ADD, 1, ptr(0, 8)),
+            Err(VirtualMachineError::PureValueError(_))
+        ));
+        // Nor can pc be moved relatively.
+        assert!(matches!(
+            run_nop(PcUpdate::JUMP_REL, ApUpdate::REGULAR, 1, ptr(0, 8)),
+            Err(VirtualMachineError::PureValueError(_))
+        ));
+        // res is unconstrained when jumping conditionally.
+        assert!(matches!(
+            run_nop(PcUpdate::JNZ, ApUpdate::ADD, 1, int(4)),
+            Err(VirtualMachineError::AddWithUnconstrained)
+        ));
+    }
+}
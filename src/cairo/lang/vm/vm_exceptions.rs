@@ -1,11 +1,51 @@
+use crate::cairo::lang::{
+    compiler::{error_handling::Location, instruction::Instruction},
+    vm::{relocatable::MaybeRelocatable, vm_core::VirtualMachineError},
+};
+
+use num_bigint::BigInt;
+use std::fmt::Display;
+
 #[derive(Debug, thiserror::Error)]
 #[error("TODO: implement this error type")]
 pub struct SecurityError {}
 
+/// An error raised while running the instruction at `pc`, along with where it happened in the
+/// source code if the program has debug info.
 #[derive(Debug, thiserror::Error)]
-#[error("TODO: implement this error type")]
-pub struct VmException {}
+pub struct VmException {
+    pub pc: MaybeRelocatable,
+    /// The encoded instruction at `pc`, if there's one in memory.
+    pub encoded_instruction: Option<BigInt>,
+    /// The instruction at `pc`, if it could be decoded.
+    pub instruction: Option<Instruction>,
+    pub inst_location: Option<Location>,
+    pub inner_exc: Box<VirtualMachineError>,
+    /// The error messages of the `with_attr error_message(...)` scopes the error occurred in.
+    pub error_attr_value: Option<String>,
+    pub traceback: Option<String>,
+    // TODO: implement `notes` and `hint_index`
+}
 
 #[derive(Debug, thiserror::Error)]
 #[error("TODO: implement this error type")]
 pub struct PureValueError {}
+
+impl Display for VmException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut message = format!("Error at pc={}:\n{}", self.pc, self.inner_exc);
+        if let Some(error_attr_value) = &self.error_attr_value {
+            message = format!("{}{}", error_attr_value, message);
+        }
+        match &self.inst_location {
+            Some(location) => write!(f, "{}", location.to_string_with_content(&message))?,
+            None => write!(f, "{}", message)?,
+        }
+
+        if let Some(traceback) = &self.traceback {
+            write!(f, "\n{}", traceback)?;
+        }
+
+        Ok(())
+    }
+}
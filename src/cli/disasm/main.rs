@@ -0,0 +1,68 @@
+use clap::Parser;
+use num_bigint::BigInt;
+use oriac::cairo::lang::compiler::{
+    encode::decode_all,
+    program::{FullProgram, ProgramLoadError, Strictness},
+};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about = "A tool to disassemble compiled Cairo programs.", long_about = None)]
+struct Args {
+    #[clap(long, help = "The name of the program json file.")]
+    program: PathBuf,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error(transparent)]
+    Io(std::io::Error),
+    #[error(transparent)]
+    ProgramLoadError(ProgramLoadError),
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    let program = load_program(&args.program)?;
+
+    for (pc, instruction) in decode_all(&program.data) {
+        match instruction {
+            Ok(mut instruction) => {
+                // Field elements in the upper half are negative numbers, e.g. backward jumps.
+                instruction.imm = instruction.imm.map(|imm| to_signed(imm, &program.prime));
+                println!("{}: {}", pc, instruction)
+            }
+            // Not an instruction, most likely data embedded in the program.
+            Err(_) => println!("{}: dw {}", pc, program.data[pc]),
+        }
+    }
+
+    Ok(())
+}
+
+fn load_program(program: &Path) -> Result<FullProgram, Error> {
+    let json = std::fs::read_to_string(program)?;
+    let (program, _) = FullProgram::from_json_strict(&json, Strictness::Permissive)?;
+    Ok(program)
+}
+
+fn to_signed(value: BigInt, prime: &BigInt) -> BigInt {
+    if &value * 2 > *prime {
+        value - prime
+    } else {
+        value
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ProgramLoadError> for Error {
+    fn from(value: ProgramLoadError) -> Self {
+        Self::ProgramLoadError(value)
+    }
+}
@@ -1,35 +1,158 @@
 use clap::Parser;
-use oriac::cairo::lang::{
-    compiler::program::FullProgram,
-    instances::CairoLayout,
-    vm::{cairo_runner::CairoRunner, memory_dict::MemoryDict},
+use num_bigint::BigInt;
+use oriac::{
+    cairo::lang::{
+        compiler::program::{FullProgram, Program, ProgramLoadError, Strictness},
+        instances::{suggest_layout, CairoLayout, UnknownLayoutError},
+        vm::{
+            cairo_runner::{CairoRunner, Error as CairoRunnerError},
+            dump_style::{DumpStyle, Radix},
+            memory_dict::MemoryDict,
+            utils::RunResources,
+        },
+    },
+    hint_support::program_input_locals,
+    verify::RunOutcome,
 };
+use serde_json::value::RawValue;
 use std::{
     collections::HashMap,
     fs::File,
+    io::{BufWriter, Write},
     path::{Path, PathBuf},
     rc::Rc,
-    str::FromStr,
 };
 
-#[derive(Debug)]
-enum Layout {
-    Plain,
-    Small,
-}
-
 #[derive(Debug, Parser)]
 #[clap(author, version, about = "A tool to run Cairo programs.", long_about = None)]
 struct Args {
     #[clap(long, help = "The name of the program json file.")]
     program: PathBuf,
-    #[clap(long, help = "The layout of the Cairo AIR.", default_value = "plain", possible_values = ["plain", "small"])]
-    layout: Layout,
+    #[clap(
+        long,
+        alias = "program_input",
+        help = "Path to a json file representing the (private) input of the program. The parsed \
+            json is available to hints as `program_input`."
+    )]
+    program_input: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "The layout of the Cairo AIR: plain, small, dex, recursive, all_cairo or auto. \"auto\" picks the smallest layout supporting the builtins used by the program.",
+        default_value = "plain"
+    )]
+    layout: String,
+    #[clap(
+        long,
+        help = "The maximum number of steps to run. Unlimited if not given."
+    )]
+    steps: Option<u64>,
     #[clap(
         long,
         help = "Prints the program output (if the output builtin is used)."
     )]
     print_output: bool,
+    #[clap(
+        long,
+        alias = "print_radix",
+        possible_values = &["dec", "hex"],
+        default_value = "dec",
+        help = "The base in which printed values are written."
+    )]
+    print_radix: String,
+    #[clap(
+        long,
+        alias = "print_max_digits",
+        help = "Cuts printed values with more digits than this in the middle. Not cut if not given."
+    )]
+    print_max_digits: Option<usize>,
+    #[clap(
+        long,
+        alias = "print_short_strings",
+        help = "Shows printed values made of printable ASCII characters as short strings too."
+    )]
+    print_short_strings: bool,
+    #[clap(
+        long,
+        alias = "proof_mode",
+        help = "Runs the program in proof mode. The program must be compiled with --proof_mode."
+    )]
+    proof_mode: bool,
+    #[clap(
+        long,
+        alias = "strict_program",
+        help = "Fails to load the program if the program json file contains unknown fields."
+    )]
+    strict_program: bool,
+    #[clap(
+        long,
+        alias = "trace_file",
+        help = "The path of the binary file to write the relocated trace to.",
+        long_help = "The path of the binary file to write the relocated trace to. For every step, the file \
+            contains the relocated ap, fp and pc registers, in that order, each encoded as an 8-byte \
+            little-endian integer."
+    )]
+    trace_file: Option<PathBuf>,
+    #[clap(
+        long,
+        alias = "memory_file",
+        help = "The path of the binary file to write the relocated memory to.",
+        long_help = "The path of the binary file to write the relocated memory to. For every memory cell in \
+            ascending address order, the file contains its relocated address encoded as an 8-byte \
+            little-endian integer, followed by its relocated value encoded as a 32-byte \
+            little-endian integer."
+    )]
+    memory_file: Option<PathBuf>,
+    #[clap(
+        long,
+        alias = "tracer_data",
+        help = "The directory to write the data consumed by the cairo-lang tracer to."
+    )]
+    tracer_data: Option<PathBuf>,
+    #[clap(
+        long,
+        alias = "air_public_input",
+        requires = "proof-mode",
+        help = "The path of the json file to write the public input of the Cairo AIR to."
+    )]
+    air_public_input: Option<PathBuf>,
+    #[clap(
+        long,
+        alias = "air_private_input",
+        requires_all = &["proof-mode", "trace-file", "memory-file"],
+        help = "The path of the json file to write the private input of the Cairo AIR to.",
+        long_help = "The path of the json file to write the private input of the Cairo AIR to. The \
+            file refers to the trace and memory files, and holds the inputs of the builtins."
+    )]
+    air_private_input: Option<PathBuf>,
+    #[clap(
+        long,
+        alias = "print_run_digest",
+        help = "Prints a hash of the relocated trace, the relocated memory and the output, which is \
+            the same for every run of the same program with the same inputs."
+    )]
+    print_run_digest: bool,
+    #[clap(
+        long,
+        alias = "analyze_hints",
+        help = "Prints what the hints of the program do, as far as a static analysis can tell, and \
+            exits without running the program."
+    )]
+    analyze_hints: bool,
+    #[clap(
+        long,
+        conflicts_with = "analyze-hints",
+        help = "Runs the program without its hints, identifiers and debug info.",
+        long_help = "Runs the program without its hints, identifiers and debug info. The program json \
+            file can either be a stripped program or a full program, which is stripped before \
+            running. Useful for running untrusted programs, as no hint is ever executed."
+    )]
+    stripped: bool,
+    #[clap(
+        long,
+        alias = "report_unreached",
+        help = "Prints the hints and functions of the program that the run never reached."
+    )]
+    report_unreached: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -37,24 +160,70 @@ enum Error {
     #[error(transparent)]
     Io(std::io::Error),
     #[error(transparent)]
-    Json(serde_json::Error),
+    ProgramLoadError(ProgramLoadError),
+    #[error("Invalid program input: {0}")]
+    InvalidProgramInput(serde_json::Error),
+    #[error(transparent)]
+    CairoRunnerError(CairoRunnerError),
+    #[error(
+        "The memory must be frozen by ending the run before writing the trace or memory file."
+    )]
+    MemoryNotFrozen,
+    #[error(transparent)]
+    UnknownLayout(UnknownLayoutError),
+    #[error("No layout supports the builtins {builtins:?}")]
+    NoSuitableLayout { builtins: Vec<String> },
+    #[error("Step limit reached: the program did not end within {steps} steps.")]
+    StepLimitReached { steps: u64 },
+    #[error("The run failed after stripping the {hints} hint(s) of the program, which it may need to progress: {source}")]
+    HintsStripped {
+        hints: usize,
+        source: CairoRunnerError,
+    },
 }
 
 fn main() -> Result<(), Error> {
     let args = Args::parse();
 
-    let program = load_program(&args.program)?;
+    let json = std::fs::read_to_string(&args.program)?;
+
+    // The number of hints dropped by stripping a full program, to explain runs that fail for lack
+    // of them.
+    let mut stripped_hints = 0;
+    let program = if args.stripped && is_stripped_program(&json)? {
+        Program::load_stripped(&json)?
+    } else {
+        let program = load_program(&json, args.strict_program)?;
+
+        if args.analyze_hints {
+            print!("{}", program.analyze_hints());
+            return Ok(());
+        }
+
+        if args.stripped {
+            stripped_hints = program.hints.values().map(Vec::len).sum();
+            program.to_stripped()?.into()
+        } else {
+            program.into()
+        }
+    };
 
-    let instance = match args.layout {
-        Layout::Plain => CairoLayout::plain_instance(),
-        Layout::Small => CairoLayout::small_instance(),
+    let instance = if args.layout == "auto" {
+        let instance =
+            suggest_layout(program.builtins()).ok_or_else(|| Error::NoSuitableLayout {
+                builtins: program.builtins().to_vec(),
+            })?;
+        eprintln!("Using layout: {}", instance.layout_name);
+        instance
+    } else {
+        CairoLayout::from_name(&args.layout)?
     };
 
     let mut runner = CairoRunner::new(
-        Rc::new(program.into()),
+        Rc::new(program),
         instance,
         MemoryDict::new(),
-        false,
+        args.proof_mode,
         false,
     )
     .unwrap();
@@ -63,23 +232,142 @@ fn main() -> Result<(), Error> {
     let end = runner.initialize_main_entrypoint().unwrap();
 
     runner.initialize_vm(HashMap::new(), ()).unwrap();
+    if let (Some(program_input), Some(vm)) = (&args.program_input, &runner.vm) {
+        let program_input = load_program_input(program_input)?;
+        vm.add_hint_locals(program_input_locals(&program_input, vm));
+    }
 
-    runner.run_until_pc(end.into(), None).unwrap();
+    let run_resources = args.steps.map(|steps| RunResources {
+        n_steps: Some(steps.into()),
+    });
+    if let Err(err) = runner.run_until_pc(end.into(), run_resources) {
+        // Running out of steps leaves the VM short of the end right after the last allowed step.
+        if let (Some(steps), Some(vm)) = (args.steps, &runner.vm) {
+            if vm.run_context.borrow().pc != end && vm.current_step == BigInt::from(steps) {
+                return Err(Error::StepLimitReached { steps });
+            }
+        }
+        if stripped_hints > 0 {
+            return Err(Error::HintsStripped {
+                hints: stripped_hints,
+                source: err,
+            });
+        }
+        return Err(err.into());
+    }
 
     runner.end_run(false, false).unwrap();
 
     runner.read_return_values().unwrap();
 
     if args.print_output {
-        runner.print_output().unwrap();
+        let style = DumpStyle {
+            radix: if args.print_radix == "hex" {
+                Radix::Hex
+            } else {
+                Radix::Decimal
+            },
+            max_digits_before_ellipsis: args.print_max_digits,
+            short_string_detection: args.print_short_strings,
+        };
+        runner.print_output_styled(&style).unwrap();
+    }
+
+    if args.report_unreached {
+        print!("{}", runner.unreached_report()?);
+    }
+
+    if args.print_run_digest {
+        println!(
+            "Run digest: {}",
+            hex::encode(RunOutcome::from_runner(&runner)?.digest())
+        );
+    }
+
+    if args.trace_file.is_some() || args.memory_file.is_some() {
+        // Relocation relies on the final segment sizes, which are only known once the memory is
+        // frozen.
+        if !runner.memory.borrow().is_frozen() {
+            return Err(Error::MemoryNotFrozen);
+        }
     }
 
+    if let Some(trace_file) = &args.trace_file {
+        let mut writer = BufWriter::new(File::create(trace_file)?);
+        runner.relocate_and_stream_trace(&mut writer)?;
+        writer.flush()?;
+    }
+
+    if let Some(memory_file) = &args.memory_file {
+        let mut writer = BufWriter::new(File::create(memory_file)?);
+        runner.relocate_and_stream_memory(&mut writer)?;
+        writer.flush()?;
+    }
+
+    if args.air_public_input.is_some() || args.air_private_input.is_some() {
+        runner.finalize_segments()?;
+    }
+
+    if let Some(air_public_input) = &args.air_public_input {
+        runner.relocate()?;
+        write_json(air_public_input, &runner.get_air_public_input()?)?;
+    }
+
+    if let (Some(air_private_input), Some(trace_file), Some(memory_file)) =
+        (&args.air_private_input, &args.trace_file, &args.memory_file)
+    {
+        let mut private_input = serde_json::Map::new();
+        private_input.insert(
+            String::from("trace_path"),
+            std::fs::canonicalize(trace_file)?.to_string_lossy().into(),
+        );
+        private_input.insert(
+            String::from("memory_path"),
+            std::fs::canonicalize(memory_file)?.to_string_lossy().into(),
+        );
+        for (name, values) in runner.get_air_private_input()? {
+            private_input.insert(name, values.into());
+        }
+        write_json(air_private_input, &private_input)?;
+    }
+
+    if let Some(tracer_data) = &args.tracer_data {
+        runner.relocate().unwrap();
+        runner.write_tracer_data(tracer_data).unwrap();
+        // The tracer reads the debug info from the original program artifact.
+        std::fs::copy(&args.program, tracer_data.join("program.json"))?;
+    }
+
+    Ok(())
+}
+
+fn load_program(json: &str, strict: bool) -> Result<FullProgram, Error> {
+    let strictness = if strict {
+        Strictness::Deny
+    } else {
+        Strictness::Permissive
+    };
+    let (program, _) = FullProgram::from_json_strict(json, strictness)?;
+    Ok(program)
+}
+
+/// Tells stripped programs apart from full ones, which have no top-level `main` field.
+fn is_stripped_program(json: &str) -> Result<bool, Error> {
+    let value = serde_json::from_str::<serde_json::Value>(json)
+        .map_err(|err| Error::ProgramLoadError(err.into()))?;
+    Ok(value.get("main").is_some())
+}
+
+fn write_json<T: serde::Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, value).map_err(std::io::Error::from)?;
+    writer.flush()?;
     Ok(())
 }
 
-fn load_program(program: &Path) -> Result<FullProgram, Error> {
-    let mut file = File::open(program)?;
-    Ok(serde_json::from_reader::<_, FullProgram>(&mut file)?)
+fn load_program_input(program_input: &Path) -> Result<Box<RawValue>, Error> {
+    let json = std::fs::read_to_string(program_input)?;
+    serde_json::from_str(&json).map_err(Error::InvalidProgramInput)
 }
 
 impl From<std::io::Error> for Error {
@@ -88,20 +376,20 @@ impl From<std::io::Error> for Error {
     }
 }
 
-impl From<serde_json::Error> for Error {
-    fn from(value: serde_json::Error) -> Self {
-        Self::Json(value)
+impl From<ProgramLoadError> for Error {
+    fn from(value: ProgramLoadError) -> Self {
+        Self::ProgramLoadError(value)
     }
 }
 
-impl FromStr for Layout {
-    type Err = &'static str;
+impl From<CairoRunnerError> for Error {
+    fn from(value: CairoRunnerError) -> Self {
+        Self::CairoRunnerError(value)
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "plain" => Ok(Layout::Plain),
-            "small" => Ok(Layout::Small),
-            _ => Err("unknown layout"),
-        }
+impl From<UnknownLayoutError> for Error {
+    fn from(value: UnknownLayoutError) -> Self {
+        Self::UnknownLayout(value)
     }
 }
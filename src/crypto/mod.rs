@@ -0,0 +1,2 @@
+#[cfg(any(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+pub mod signature;
@@ -0,0 +1,150 @@
+use crate::{
+    crypto::signature::signature::{ALPHA, FIELD_PRIME},
+    python::math_utils::{ec_add, ec_double, ec_mult, EcPoint, Error as MathError},
+};
+
+use num_bigint::BigInt;
+use once_cell::sync::Lazy;
+
+/// The inputs of the hash are split into their low `LOW_PART_BITS` bits and their high nibble.
+pub const LOW_PART_BITS: u32 = 248;
+
+/// The first of the constant points of `pedersen_params.json`, which every hash starts from.
+pub static SHIFT_POINT: Lazy<EcPoint> = Lazy::new(|| {
+    (
+        parse_hex("49ee3eba8c1600700ee1b87eb599f16716b0b1022947733551fde4050ca6804"),
+        parse_hex("3ca0cfe4b3bc6ddf346d49d06ea0ed34e621062c0e056c1d0405d266e10268a"),
+    )
+});
+/// The constant point multiplied by the low part of the first input.
+pub static P_0: Lazy<EcPoint> = Lazy::new(|| {
+    (
+        parse_hex("234287dcbaffe7f969c748655fca9e58fa8120b6d56eb0c1080d17957ebe47b"),
+        parse_hex("3b056f100f96fb21e889527d41f4e39940135dd7a6c94cc6ed0268ee89e5615"),
+    )
+});
+/// The constant point multiplied by the high nibble of the first input.
+pub static P_1: Lazy<EcPoint> = Lazy::new(|| {
+    (
+        parse_hex("4fa56f376c83db33f9dab2656558f3399099ec1de5e3018b7a6932dba8aa378"),
+        parse_hex("3fa0984c931c9e38113e0c0e47e4401562761f92a7a23b45168f4e80ff5b54d"),
+    )
+});
+/// The constant point multiplied by the low part of the second input.
+pub static P_2: Lazy<EcPoint> = Lazy::new(|| {
+    (
+        parse_hex("4ba4cc166be8dec764910f75b45f74b40c690c74709e90f3aa372f0bd2d6997"),
+        parse_hex("40301cf5c1751f4b971e46c4ede85fcac5c59a5ce5ae7c48151f27b24b219c"),
+    )
+});
+/// The constant point multiplied by the high nibble of the second input.
+pub static P_3: Lazy<EcPoint> = Lazy::new(|| {
+    (
+        parse_hex("54302dcb0e6cc1c6e44cca8f61a63bb2ca65048d53fb325d36ff12c49a58202"),
+        parse_hex("1b77b3e37d13504b348046268d8ae25ce98ad783c25561a879dcc77e99c2426"),
+    )
+});
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Element integer value is out of range: {element}.")]
+    ElementOutOfRange { element: BigInt },
+    #[error(transparent)]
+    MathError(MathError),
+}
+
+/// Computes the Starkware version of the Pedersen hash of x and y.
+pub fn pedersen_hash(x: &BigInt, y: &BigInt) -> Result<BigInt, Error> {
+    let point = ec_safe_add(
+        Some(SHIFT_POINT.to_owned()),
+        process_single_element(x, &P_0, &P_1)?,
+    )?;
+    let point = ec_safe_add(point, process_single_element(y, &P_2, &P_3)?)?;
+
+    // The shift point is not a multiple of the other points, so the sum can't be the point at
+    // infinity.
+    Ok(point.unwrap().0)
+}
+
+/// Computes `low_part * p1 + high_nibble * p2`, where `None` is the point at infinity.
+fn process_single_element(
+    element: &BigInt,
+    p1: &EcPoint,
+    p2: &EcPoint,
+) -> Result<Option<EcPoint>, Error> {
+    if element < &BigInt::from(0u32) || element >= &FIELD_PRIME {
+        return Err(Error::ElementOutOfRange {
+            element: element.to_owned(),
+        });
+    }
+
+    let high_nibble = element >> LOW_PART_BITS;
+    let low_part = element - (&high_nibble << LOW_PART_BITS);
+    ec_safe_add(
+        ec_safe_mult(&low_part, p1)?,
+        ec_safe_mult(&high_nibble, p2)?,
+    )
+}
+
+/// Like `ec_mult`, but returns the point at infinity for a zero multiplier.
+fn ec_safe_mult(m: &BigInt, point: &EcPoint) -> Result<Option<EcPoint>, Error> {
+    if m == &BigInt::from(0u32) {
+        return Ok(None);
+    }
+
+    Ok(Some(ec_mult(m, point, &ALPHA, &FIELD_PRIME)?))
+}
+
+/// Like `ec_add`, but handles the point at infinity and points with the same x coordinate.
+fn ec_safe_add(point1: Option<EcPoint>, point2: Option<EcPoint>) -> Result<Option<EcPoint>, Error> {
+    match (point1, point2) {
+        (None, point) | (point, None) => Ok(point),
+        (Some(point1), Some(point2)) if point1.0 == point2.0 => {
+            if point1.1 == point2.1 {
+                Ok(Some(ec_double(&point1, &ALPHA, &FIELD_PRIME)?))
+            } else {
+                Ok(None)
+            }
+        }
+        (Some(point1), Some(point2)) => Ok(Some(ec_add(&point1, &point2, &FIELD_PRIME)?)),
+    }
+}
+
+fn parse_hex(hex: &str) -> BigInt {
+    BigInt::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+impl From<MathError> for Error {
+    fn from(value: MathError) -> Self {
+        Self::MathError(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pedersen_hash() {
+        assert_eq!(
+            pedersen_hash(
+                &parse_hex("3d937c035c878245caf64531a5756109c53068da139362728feb561405371cb"),
+                &parse_hex("208a0a10250e382e1e4bbe2880906c2791bf6275695e02fbbc6aeff9cd8b31a"),
+            )
+            .unwrap(),
+            parse_hex("30e480bed5fe53fa909cc0f8c4d99b8f9f2c016be4c41e13a4848797979c662")
+        );
+        assert_eq!(
+            pedersen_hash(&1u32.into(), &2u32.into()).unwrap(),
+            parse_hex("5bb9440e27889a364bcb678b1f679ecd1347acdedcbf36e83494f857cc58026")
+        );
+        assert_eq!(
+            pedersen_hash(&0u32.into(), &0u32.into()).unwrap(),
+            SHIFT_POINT.0
+        );
+        assert!(matches!(
+            pedersen_hash(&FIELD_PRIME, &0u32.into()),
+            Err(Error::ElementOutOfRange { .. })
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+#[cfg(feature = "builtin-pedersen")]
+pub mod fast_pedersen_hash;
+pub mod signature;
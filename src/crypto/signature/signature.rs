@@ -0,0 +1,293 @@
+use crate::python::math_utils::{
+    self, div_mod, ec_add, ec_mult, ec_neg, is_quad_residue, EcPoint, Error as MathError,
+};
+
+use num_bigint::BigInt;
+use once_cell::sync::Lazy;
+
+/// Messages and the r and w parts of signatures must be smaller than 2^N_ELEMENT_BITS_ECDSA.
+pub const N_ELEMENT_BITS_ECDSA: u32 = 251;
+
+pub static FIELD_PRIME: Lazy<BigInt> =
+    Lazy::new(|| parse_hex("800000000000011000000000000000000000000000000000000000000000001"));
+pub static ALPHA: Lazy<BigInt> = Lazy::new(|| BigInt::from(1u32));
+pub static BETA: Lazy<BigInt> =
+    Lazy::new(|| parse_hex("6f21413efbe40de150e596d72f7a8c5609ad26c15c915c1f4cdfcb99cee9e89"));
+pub static EC_ORDER: Lazy<BigInt> =
+    Lazy::new(|| parse_hex("800000000000010ffffffffffffffffb781126dcae7b2321e66a241adc64d2f"));
+pub static EC_GEN: Lazy<EcPoint> = Lazy::new(|| {
+    (
+        parse_hex("1ef15c18599971b7beced415a40f0c7deacfd9b0d1819e03d723d8bc943cfca"),
+        parse_hex("5668060aa49730b7be4801df46ec62de53ecd11abe43a32873000c36e8dc1f"),
+    )
+});
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Message not signable.")]
+    MessageNotSignable,
+    #[error("Bad private key.")]
+    InvalidPrivateKey,
+    #[error("r = {r} is out of range.")]
+    InvalidR { r: BigInt },
+    #[error("s = {s} is out of range.")]
+    InvalidS { s: BigInt },
+    #[error("The nonce k = {k} does not yield a valid signature.")]
+    InvalidNonce { k: BigInt },
+    #[error("Given x coordinate does not represent any point on the elliptic curve.")]
+    NotOnCurve,
+    #[error(transparent)]
+    MathError(MathError),
+}
+
+/// Given the x coordinate of a stark_key, returns a possible y coordinate such that together the
+/// point (x,y) is on the curve.
+/// Note that the real y coordinate is either y or -y.
+pub fn get_y_coordinate(stark_key_x: &BigInt) -> Result<BigInt, Error> {
+    let x = stark_key_x;
+    let y_squared = math_utils::modulo(&(x * x * x + &*ALPHA * x + &*BETA), &FIELD_PRIME)?;
+    if !is_quad_residue(&y_squared, &FIELD_PRIME)? {
+        return Err(Error::NotOnCurve);
+    }
+
+    Ok(math_utils::sqrt(&y_squared, &FIELD_PRIME)?)
+}
+
+pub fn private_key_to_ec_point_on_stark_curve(priv_key: &BigInt) -> Result<EcPoint, Error> {
+    if priv_key <= &BigInt::from(0u32) || priv_key >= &EC_ORDER {
+        return Err(Error::InvalidPrivateKey);
+    }
+
+    Ok(ec_mult(priv_key, &EC_GEN, &ALPHA, &FIELD_PRIME)?)
+}
+
+pub fn private_to_stark_key(priv_key: &BigInt) -> Result<BigInt, Error> {
+    Ok(private_key_to_ec_point_on_stark_curve(priv_key)?.0)
+}
+
+/// Computes `a / b` modulo the order of the curve.
+pub fn div_mod_n(a: &BigInt, b: &BigInt) -> Result<BigInt, Error> {
+    div_mod(a, b, &EC_ORDER).ok_or_else(|| {
+        MathError::NotInvertible {
+            n: b.to_owned(),
+            p: EC_ORDER.to_owned(),
+        }
+        .into()
+    })
+}
+
+/// Signs `msg_hash` with the nonce `k`, returning `(r, s)`.
+///
+/// Unlike the Python implementation, which derives the nonce from the message and the key with
+/// RFC 6979, the nonce is taken as is and an error is returned if it yields an invalid signature.
+pub fn sign(msg_hash: &BigInt, priv_key: &BigInt, k: &BigInt) -> Result<(BigInt, BigInt), Error> {
+    if !is_in_ecdsa_range(msg_hash, 0) {
+        return Err(Error::MessageNotSignable);
+    }
+
+    let invalid_nonce = || Error::InvalidNonce { k: k.to_owned() };
+    if k <= &BigInt::from(0u32) || k >= &EC_ORDER {
+        return Err(invalid_nonce());
+    }
+
+    let r = ec_mult(k, &EC_GEN, &ALPHA, &FIELD_PRIME)?.0;
+    if !is_in_ecdsa_range(&r, 1) {
+        return Err(invalid_nonce());
+    }
+
+    let z = msg_hash + &r * priv_key;
+    if math_utils::modulo(&z, &EC_ORDER)? == BigInt::from(0u32) {
+        return Err(invalid_nonce());
+    }
+
+    let w = div_mod_n(k, &z)?;
+    if !is_in_ecdsa_range(&w, 1) {
+        return Err(invalid_nonce());
+    }
+
+    let s = div_mod_n(&BigInt::from(1u32), &w)?;
+    Ok((r, s))
+}
+
+/// Verifies that `(r, s)` is a signature of `msg_hash` by `public_key`.
+pub fn verify(
+    msg_hash: &BigInt,
+    r: &BigInt,
+    s: &BigInt,
+    public_key: &EcPoint,
+) -> Result<bool, Error> {
+    check_signature(msg_hash, r, s)?;
+
+    let w = div_mod_n(&BigInt::from(1u32), s)?;
+    if !is_in_ecdsa_range(&w, 1) {
+        return Err(Error::InvalidS { s: s.to_owned() });
+    }
+
+    // wB = w * (zG + rQ)
+    let r_q = ec_mult(r, public_key, &ALPHA, &FIELD_PRIME)?;
+    let b = if msg_hash == &BigInt::from(0u32) {
+        r_q
+    } else {
+        ec_add(
+            &ec_mult(msg_hash, &EC_GEN, &ALPHA, &FIELD_PRIME)?,
+            &r_q,
+            &FIELD_PRIME,
+        )?
+    };
+    let w_b = ec_mult(&w, &b, &ALPHA, &FIELD_PRIME)?;
+
+    Ok(&w_b.0 == r)
+}
+
+/// Recovers the public key that produced the signature `(r, s)` of `msg_hash`.
+///
+/// A signature matches two public keys, one for each of the points whose x coordinate is `r`.
+/// `y_parity` selects the point with an odd y coordinate.
+pub fn recover_public_key(
+    msg_hash: &BigInt,
+    r: &BigInt,
+    s: &BigInt,
+    y_parity: bool,
+) -> Result<EcPoint, Error> {
+    check_signature(msg_hash, r, s)?;
+
+    let y = get_y_coordinate(r)?;
+    let r_point = if y.bit(0) == y_parity {
+        (r.to_owned(), y)
+    } else {
+        ec_neg(&(r.to_owned(), y), &FIELD_PRIME)?
+    };
+
+    // Q = (s * R - z * G) / r
+    let s_r = ec_mult(&div_mod_n(s, r)?, &r_point, &ALPHA, &FIELD_PRIME)?;
+    if msg_hash == &BigInt::from(0u32) {
+        return Ok(s_r);
+    }
+    let z_g = ec_mult(
+        &div_mod_n(&(&*EC_ORDER - msg_hash), r)?,
+        &EC_GEN,
+        &ALPHA,
+        &FIELD_PRIME,
+    )?;
+
+    Ok(ec_add(&s_r, &z_g, &FIELD_PRIME)?)
+}
+
+fn check_signature(msg_hash: &BigInt, r: &BigInt, s: &BigInt) -> Result<(), Error> {
+    if !is_in_ecdsa_range(msg_hash, 0) {
+        return Err(Error::MessageNotSignable);
+    }
+    if !is_in_ecdsa_range(r, 1) {
+        return Err(Error::InvalidR { r: r.to_owned() });
+    }
+    if s < &BigInt::from(1u32) || s >= &EC_ORDER {
+        return Err(Error::InvalidS { s: s.to_owned() });
+    }
+
+    Ok(())
+}
+
+/// Returns whether `min <= value < 2^N_ELEMENT_BITS_ECDSA`.
+fn is_in_ecdsa_range(value: &BigInt, min: u32) -> bool {
+    value >= &BigInt::from(min) && value < &(BigInt::from(1u32) << N_ELEMENT_BITS_ECDSA)
+}
+
+fn parse_hex(hex: &str) -> BigInt {
+    BigInt::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+impl From<MathError> for Error {
+    fn from(value: MathError) -> Self {
+        Self::MathError(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Computed with the curve arithmetic of `starkware.python.math_utils`.
+    const PRIVATE_KEY: &str = "139fe4d6f02e666e86a6f58e65060f115cd3c185bd9e98bd829636931458f79";
+    const MSG_HASH: &str = "6fea80189363a786037ed3e7ba546dad0ef7de49fccae0e31eb658b7dd4ea76";
+    const K: &str = "4daebba599f860daee8f6e100601d98873052e1c61530c630cc4375c6bd48e3";
+    const R: &str = "34ffbd2cd71fce104a13e8003bbd4a17aee31371a6fe72eff0efcd195f19ff3";
+    const S: &str = "42e2665aca21934bca93eb1d589734759e58d43ee609514f05d608019d2711b";
+    const PUBLIC_KEY_X: &str = "2c5dbad71c92a45cc4b40573ae661f8147869a91d57b8d9b8f48c8af7f83159";
+    const PUBLIC_KEY_Y: &str = "27c34b828a7a16ff007737c556e3e2135d7e4573607914d572132c1fbc0f0e2";
+
+    #[test]
+    fn test_curve_constants() {
+        let (x, y) = &*EC_GEN;
+        assert_eq!(
+            math_utils::modulo(&(y * y - x * x * x - &*ALPHA * x - &*BETA), &FIELD_PRIME).unwrap(),
+            BigInt::from(0u32)
+        );
+        assert_eq!(
+            ec_mult(&(&*EC_ORDER - 1u32), &EC_GEN, &ALPHA, &FIELD_PRIME).unwrap(),
+            ec_neg(&EC_GEN, &FIELD_PRIME).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_private_to_stark_key() {
+        assert_eq!(
+            private_to_stark_key(&parse_hex(
+                "3c1e9550e66958296d11b60f8e8e7a7ad990d07fa65d5f7652c4a6c87d4e3cc"
+            ))
+            .unwrap(),
+            parse_hex("77a3b314db07c45076d11f62b6f9e748a39790441823307743cf00d6597ea43")
+        );
+        assert!(matches!(
+            private_to_stark_key(&EC_ORDER),
+            Err(Error::InvalidPrivateKey)
+        ));
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let (r, s) = sign(&parse_hex(MSG_HASH), &parse_hex(PRIVATE_KEY), &parse_hex(K)).unwrap();
+        assert_eq!((&r, &s), (&parse_hex(R), &parse_hex(S)));
+
+        let public_key = (parse_hex(PUBLIC_KEY_X), parse_hex(PUBLIC_KEY_Y));
+        assert!(verify(&parse_hex(MSG_HASH), &r, &s, &public_key).unwrap());
+        assert!(!verify(&(parse_hex(MSG_HASH) + 1u32), &r, &s, &public_key).unwrap());
+    }
+
+    #[test]
+    fn test_recover_public_key() {
+        let public_key = private_key_to_ec_point_on_stark_curve(&parse_hex(PRIVATE_KEY)).unwrap();
+        assert_eq!(
+            public_key,
+            (parse_hex(PUBLIC_KEY_X), parse_hex(PUBLIC_KEY_Y))
+        );
+
+        // k * G has an odd y coordinate.
+        let (msg_hash, r, s) = (parse_hex(MSG_HASH), parse_hex(R), parse_hex(S));
+        assert_eq!(
+            recover_public_key(&msg_hash, &r, &s, true).unwrap(),
+            public_key
+        );
+
+        // The other point gives another key, for which the signature is valid too.
+        let other_key = recover_public_key(&msg_hash, &r, &s, false).unwrap();
+        assert_ne!(other_key, public_key);
+        assert!(verify(&msg_hash, &r, &s, &other_key).unwrap());
+
+        assert!(matches!(
+            recover_public_key(&msg_hash, &0u32.into(), &s, true),
+            Err(Error::InvalidR { .. })
+        ));
+    }
+
+    #[test]
+    fn test_div_mod_n() {
+        let a = parse_hex(MSG_HASH);
+        let b = parse_hex(R);
+        let res = div_mod_n(&a, &b).unwrap();
+        assert_eq!(res * b % &*EC_ORDER, &a % &*EC_ORDER);
+        assert!(matches!(
+            div_mod_n(&a, &EC_ORDER),
+            Err(Error::MathError(MathError::NotInvertible { .. }))
+        ));
+    }
+}
@@ -0,0 +1,438 @@
+//! A C ABI for embedding the VM from other languages, enabled with the `ffi` feature. The header
+//! `oriac.h` is generated into the `OUT_DIR` of the build script when building with the feature.
+//!
+//! Ownership rules:
+//! * Strings passed to the functions are borrowed for the duration of the call, and must be
+//!   NUL-terminated UTF-8.
+//! * Strings returned through `out_*` pointers are owned by the caller, and must be released with
+//!   [oriac_string_free].
+//! * Runners created with [oriac_runner_new] are owned by the caller, and must be released with
+//!   [oriac_runner_free]. A runner must only be used from the thread that created it.
+//!
+//! Every function returns an [OriacStatus]. On failure, the `out_*` pointers are left untouched
+//! and [oriac_last_error_message] describes the error. Panics are caught at the boundary and
+//! reported as `ORIAC_STATUS_PANIC`, unless the library is built with `panic = "abort"`.
+
+use crate::{
+    cairo::lang::{
+        compiler::program::{FullProgram, ProgramLoadError, Strictness},
+        instances::{suggest_layout, CairoLayout, UnknownLayoutError},
+        vm::{
+            cairo_runner::{CairoRunner, Error as CairoRunnerError},
+            memory_dict::MemoryDict,
+            relocatable::RelocatableValue,
+            utils::RunResources,
+        },
+    },
+    hint_support::program_input_locals,
+    serde::big_int::BigIntHex,
+};
+
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_with::serde_as;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    rc::Rc,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OriacStatus {
+    Ok = 0,
+    /// A required pointer argument is null.
+    NullPointer = 1,
+    /// A string argument is not valid UTF-8.
+    InvalidUtf8 = 2,
+    /// The options json is invalid, or names an unknown layout.
+    InvalidOptions = 3,
+    /// The program json cannot be loaded.
+    InvalidProgram = 4,
+    /// The program failed to run.
+    RunError = 5,
+    /// The program did not end within the step limit of the options.
+    StepLimitReached = 6,
+    /// A panic was caught at the boundary.
+    Panic = 7,
+}
+
+/// An opaque handle to a runner ready to execute `main()` step by step.
+pub struct OriacRunner {
+    runner: CairoRunner,
+    end: RelocatableValue,
+}
+
+/// The options of a run, passed as json. All fields are optional.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunOptions {
+    /// The layout name, or "auto" for the smallest layout supporting the program builtins.
+    #[serde(default = "default_layout")]
+    layout: String,
+    #[serde(default)]
+    proof_mode: bool,
+    /// The maximum number of steps of oriac_run_program(). Unlimited if not given.
+    steps: Option<u64>,
+    /// The (private) input of the program, available to hints as `program_input`.
+    program_input: Option<Box<RawValue>>,
+}
+
+/// The result of oriac_run_program(), returned as json.
+#[serde_as]
+#[derive(Debug, Serialize)]
+struct RunResult {
+    steps: u64,
+    #[serde_as(as = "Vec<BigIntHex>")]
+    output: Vec<BigInt>,
+    /// The number of used instances of each builtin, keyed by the builtin name.
+    builtin_instance_counter: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error("{name} must not be null.")]
+    NullPointer { name: &'static str },
+    #[error("{name} is not valid UTF-8.")]
+    InvalidUtf8 { name: &'static str },
+    #[error("Invalid options: {0}")]
+    InvalidOptions(serde_json::Error),
+    #[error(transparent)]
+    UnknownLayout(UnknownLayoutError),
+    #[error("No layout supports the builtins {builtins:?}")]
+    NoSuitableLayout { builtins: Vec<String> },
+    #[error(transparent)]
+    ProgramLoadError(ProgramLoadError),
+    #[error(transparent)]
+    CairoRunnerError(CairoRunnerError),
+    #[error("Step limit reached: the program did not end within {steps} steps.")]
+    StepLimitReached { steps: u64 },
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs `main()` of a program to its end.
+///
+/// On success, `*out_result_json` is set to a json object holding the number of `steps`, the
+/// `output` of the program and the `builtin_instance_counter`.
+///
+/// # Safety
+///
+/// `program_json` and `options_json` must be null or point to NUL-terminated strings, and
+/// `out_result_json` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn oriac_run_program(
+    program_json: *const c_char,
+    options_json: *const c_char,
+    out_result_json: *mut *mut c_char,
+) -> OriacStatus {
+    ffi_call(|| {
+        let program_json = read_str(program_json, "program_json")?;
+        let options_json = read_str(options_json, "options_json")?;
+        check_out_ptr(out_result_json, "out_result_json")?;
+
+        let options = parse_options(options_json)?;
+        let OriacRunner { mut runner, end } = new_runner(program_json, &options)?;
+
+        let run_resources = options.steps.map(|steps| RunResources {
+            n_steps: Some(steps.into()),
+        });
+        if let Err(err) = runner.run_until_pc(end.into(), run_resources) {
+            // Running out of steps leaves the VM short of the end right after the last allowed
+            // step.
+            if let (Some(steps), Some(vm)) = (options.steps, &runner.vm) {
+                if vm.run_context.borrow().pc != end && vm.current_step == BigInt::from(steps) {
+                    return Err(Error::StepLimitReached { steps });
+                }
+            }
+            return Err(err.into());
+        }
+        runner.end_run(false, false)?;
+        runner.read_return_values()?;
+
+        let execution_resources = runner.get_execution_resources()?;
+        let result = RunResult {
+            steps: u64::try_from(&execution_resources.n_steps).unwrap_or(u64::MAX),
+            output: match runner.get_int_output() {
+                Ok(output) => output,
+                Err(CairoRunnerError::MissingOutputBuiltin) => vec![],
+                Err(err) => return Err(err.into()),
+            },
+            builtin_instance_counter: execution_resources
+                .builtin_instance_counter
+                .iter()
+                .map(|(name, count)| (name.to_owned(), u64::try_from(count).unwrap_or(u64::MAX)))
+                .collect(),
+        };
+
+        *out_result_json = to_c_string(serde_json::to_string(&result).unwrap());
+        Ok(())
+    })
+}
+
+/// Creates a runner for `main()` of a program. Hints run as part of the steps.
+///
+/// On success, `*out_runner` is set to a runner to be released with [oriac_runner_free]. The
+/// `steps` option is ignored.
+///
+/// # Safety
+///
+/// `program_json` and `options_json` must be null or point to NUL-terminated strings, and
+/// `out_runner` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn oriac_runner_new(
+    program_json: *const c_char,
+    options_json: *const c_char,
+    out_runner: *mut *mut OriacRunner,
+) -> OriacStatus {
+    ffi_call(|| {
+        let program_json = read_str(program_json, "program_json")?;
+        let options_json = read_str(options_json, "options_json")?;
+        check_out_ptr(out_runner, "out_runner")?;
+
+        let runner = new_runner(program_json, &parse_options(options_json)?)?;
+
+        *out_runner = Box::into_raw(Box::new(runner));
+        Ok(())
+    })
+}
+
+/// Executes one step, unless the end of `main()` was already reached. `*out_finished` is set to
+/// whether the end of `main()` is reached after the call.
+///
+/// # Safety
+///
+/// `runner` must be null or a runner created by [oriac_runner_new] and not yet freed, and
+/// `out_finished` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn oriac_runner_step(
+    runner: *mut OriacRunner,
+    out_finished: *mut bool,
+) -> OriacStatus {
+    ffi_call(|| {
+        let runner = runner
+            .as_mut()
+            .ok_or(Error::NullPointer { name: "runner" })?;
+        check_out_ptr(out_finished, "out_finished")?;
+
+        if !runner.is_finished()? {
+            runner.runner.vm_step()?;
+        }
+
+        *out_finished = runner.is_finished()?;
+        Ok(())
+    })
+}
+
+/// Reads the memory cell at `segment_index:offset`.
+///
+/// On success, `*out_value` is set to the value of the cell, formatted as a decimal integer or as
+/// `segment_index:offset` for addresses, or to null if the cell is unknown.
+///
+/// # Safety
+///
+/// `runner` must be null or a runner created by [oriac_runner_new] and not yet freed, and
+/// `out_value` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn oriac_runner_read_memory(
+    runner: *mut OriacRunner,
+    segment_index: i64,
+    offset: u64,
+    out_value: *mut *mut c_char,
+) -> OriacStatus {
+    ffi_call(|| {
+        let runner = runner
+            .as_mut()
+            .ok_or(Error::NullPointer { name: "runner" })?;
+        check_out_ptr(out_value, "out_value")?;
+
+        let value = match (isize::try_from(segment_index), usize::try_from(offset)) {
+            (Ok(segment_index), Ok(offset)) => {
+                let addr = RelocatableValue::new(segment_index, offset);
+                runner.runner.memory.borrow_mut().get(&addr.into(), None)
+            }
+            // No cell can live at an address that doesn't fit into the machine integers.
+            _ => None,
+        };
+
+        *out_value = match value {
+            Some(value) => to_c_string(value.to_string()),
+            None => ptr::null_mut(),
+        };
+        Ok(())
+    })
+}
+
+/// Releases a runner created by [oriac_runner_new]. Does nothing if `runner` is null.
+///
+/// # Safety
+///
+/// `runner` must be null or a runner created by [oriac_runner_new] and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn oriac_runner_free(runner: *mut OriacRunner) {
+    if !runner.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(runner))));
+    }
+}
+
+/// Releases a string returned by this library. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned by this library and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn oriac_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(CString::from_raw(s))));
+    }
+}
+
+/// Returns the message of the last error on the calling thread, or null if no call failed yet.
+/// The string is owned by the library and valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn oriac_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+impl OriacRunner {
+    fn is_finished(&self) -> Result<bool, Error> {
+        let vm = self
+            .runner
+            .vm
+            .as_ref()
+            .ok_or(CairoRunnerError::VmNotInitialized)?;
+        let finished = vm.run_context.borrow().pc == self.end;
+        Ok(finished)
+    }
+}
+
+impl Error {
+    fn status(&self) -> OriacStatus {
+        match self {
+            Self::NullPointer { .. } => OriacStatus::NullPointer,
+            Self::InvalidUtf8 { .. } => OriacStatus::InvalidUtf8,
+            Self::InvalidOptions(_) | Self::UnknownLayout(_) | Self::NoSuitableLayout { .. } => {
+                OriacStatus::InvalidOptions
+            }
+            Self::ProgramLoadError(_) => OriacStatus::InvalidProgram,
+            Self::CairoRunnerError(_) => OriacStatus::RunError,
+            Self::StepLimitReached { .. } => OriacStatus::StepLimitReached,
+        }
+    }
+}
+
+/// Runs `f`, turning its errors and panics into a status and the last error message.
+fn ffi_call<F>(f: F) -> OriacStatus
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    let (status, message) = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return OriacStatus::Ok,
+        Ok(Err(err)) => (err.status(), err.to_string()),
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.to_owned()
+            } else {
+                String::from("unknown panic")
+            };
+            (OriacStatus::Panic, format!("panicked: {}", message))
+        }
+    };
+
+    // Messages can't contain NUL bytes, which would cut them short in C.
+    let message = CString::new(message.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+    status
+}
+
+fn new_runner(program_json: &str, options: &RunOptions) -> Result<OriacRunner, Error> {
+    let (program, _) = FullProgram::from_json_strict(program_json, Strictness::Permissive)?;
+
+    let instance = if options.layout == "auto" {
+        suggest_layout(&program.builtins).ok_or_else(|| Error::NoSuitableLayout {
+            builtins: program.builtins.clone(),
+        })?
+    } else {
+        CairoLayout::from_name(&options.layout)?
+    };
+
+    let mut runner = CairoRunner::new(
+        Rc::new(program.into()),
+        instance,
+        MemoryDict::new(),
+        options.proof_mode,
+        false,
+    )?;
+    runner.initialize_segments();
+    let end = runner.initialize_main_entrypoint()?;
+
+    runner.initialize_vm(HashMap::new(), ())?;
+    if let (Some(program_input), Some(vm)) = (&options.program_input, &runner.vm) {
+        vm.add_hint_locals(program_input_locals(program_input, vm));
+    }
+
+    Ok(OriacRunner { runner, end })
+}
+
+fn parse_options(options_json: &str) -> Result<RunOptions, Error> {
+    serde_json::from_str(options_json).map_err(Error::InvalidOptions)
+}
+
+unsafe fn read_str<'a>(s: *const c_char, name: &'static str) -> Result<&'a str, Error> {
+    if s.is_null() {
+        return Err(Error::NullPointer { name });
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| Error::InvalidUtf8 { name })
+}
+
+fn check_out_ptr<T>(ptr: *mut T, name: &'static str) -> Result<(), Error> {
+    if ptr.is_null() {
+        Err(Error::NullPointer { name })
+    } else {
+        Ok(())
+    }
+}
+
+/// Hands a string over to the caller, to be released with [oriac_string_free].
+fn to_c_string(s: String) -> *mut c_char {
+    // Neither json nor formatted values contain NUL bytes.
+    CString::new(s).unwrap().into_raw()
+}
+
+fn default_layout() -> String {
+    String::from("plain")
+}
+
+impl From<UnknownLayoutError> for Error {
+    fn from(value: UnknownLayoutError) -> Self {
+        Self::UnknownLayout(value)
+    }
+}
+
+impl From<ProgramLoadError> for Error {
+    fn from(value: ProgramLoadError) -> Self {
+        Self::ProgramLoadError(value)
+    }
+}
+
+impl From<CairoRunnerError> for Error {
+    fn from(value: CairoRunnerError) -> Self {
+        Self::CairoRunnerError(value)
+    }
+}
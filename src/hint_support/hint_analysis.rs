@@ -0,0 +1,363 @@
+//! A heuristic static analysis of hint code, to help reviewing what a program would run before
+//! running it.
+//!
+//! The code is not parsed: imports, `ids` accesses and suspicious constructs are found by scanning
+//! the text, so the report can be fooled by code that tries to hide what it does. Hints matching a
+//! whitelist entry or a native port exactly are known to be safe.
+
+use crate::{cairo::lang::compiler::program::CairoHint, hint_support::native_hints};
+
+use num_bigint::BigInt;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Display,
+};
+
+/// Hints of the common library of cairo-lang v0.9.0.
+pub const WHITELISTED_HINTS: &[&str] = &[
+    // alloc.cairo
+    "memory[ap] = segments.add()",
+    // math.cairo
+    "\
+from starkware.cairo.common.math_utils import assert_integer
+assert_integer(ids.a)
+assert 0 <= ids.a % PRIME < range_check_builtin.bound, f'a = {ids.a} is out of range.'",
+    "\
+from starkware.cairo.common.math_utils import assert_integer
+assert_integer(ids.value)
+assert ids.value % PRIME != 0, f'assert_not_zero failed: {ids.value} = 0.'",
+    "\
+from starkware.cairo.lang.vm.relocatable import RelocatableValue
+both_ints = isinstance(ids.a, int) and isinstance(ids.b, int)
+both_relocatable = (
+    isinstance(ids.a, RelocatableValue) and isinstance(ids.b, RelocatableValue) and
+    ids.a.segment_index == ids.b.segment_index)
+assert both_ints or both_relocatable, \\
+    f'assert_not_equal failed: non-comparable values: {ids.a}, {ids.b}.'
+assert (ids.a - ids.b) % PRIME != 0, f'assert_not_equal failed: {ids.a} = {ids.b}.'",
+    "\
+from starkware.cairo.common.math_utils import assert_integer
+assert_integer(ids.div)
+assert 0 < ids.div <= PRIME // range_check_builtin.bound, \\
+    f'div={hex(ids.div)} is out of the valid range.'
+ids.q, ids.r = divmod(ids.value, ids.div)",
+    "\
+from starkware.cairo.common.math_utils import assert_integer
+assert ids.MAX_HIGH < 2**128 and ids.MAX_LOW < 2**128
+assert PRIME - 1 == ids.MAX_HIGH * 2**128 + ids.MAX_LOW
+assert_integer(ids.value)
+ids.low = ids.value & ((1 << 128) - 1)
+ids.high = ids.value >> 128",
+    "\
+from starkware.cairo.common.math_utils import as_int
+
+# Correctness check.
+value = as_int(ids.value, PRIME) % PRIME
+assert value < ids.UPPER_BOUND, f'{value} is outside of the range [0, 2**250).'
+
+# Calculation for the assertion.
+ids.high, ids.low = divmod(ids.value, ids.SHIFT)",
+    "\
+from starkware.python.math_utils import isqrt
+value = ids.value % PRIME
+assert value < 2 ** 250, f\"value={value} is outside of the range [0, 2**250).\"
+assert 2 ** 250 < PRIME
+ids.root = isqrt(value)",
+    // math_cmp.cairo
+    "memory[ap] = 0 if 0 <= (ids.a % PRIME) < range_check_builtin.bound else 1",
+    // memcpy.cairo
+    "vm_enter_scope({'n': ids.len})",
+    "\
+n -= 1
+ids.continue_copying = 1 if n > 0 else 0",
+    "vm_exit_scope()",
+];
+
+/// Names whose use in a hint deserves a closer look, as they can run arbitrary code or reach
+/// outside of the VM.
+const SUSPICIOUS_NAMES: &[&str] = &["exec", "eval", "compile", "open", "__import__"];
+
+/// The analysis of all the hints of a program, ordered by pc.
+#[derive(Debug, Clone)]
+pub struct HintReport {
+    pub hints: Vec<HintAnalysis>,
+}
+
+/// What a single hint does, as far as a textual scan can tell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintAnalysis {
+    pub pc: BigInt,
+    /// The index of the hint among the hints at `pc`.
+    pub index: usize,
+    /// The size of the code in bytes.
+    pub code_size: usize,
+    /// The modules imported with `import` or `from ... import`.
+    pub imports: Vec<String>,
+    /// The names accessed through `ids`, e.g. `x` for `ids.x.y`.
+    pub ids: Vec<String>,
+    /// Whether the code is one of [WHITELISTED_HINTS].
+    pub whitelisted: bool,
+    /// Whether the hint has a native port, which runs instead of the code.
+    pub native: bool,
+    /// The names from [SUSPICIOUS_NAMES] used by the code.
+    pub suspicious: Vec<String>,
+}
+
+impl HintReport {
+    pub fn new(hints: &HashMap<BigInt, Vec<CairoHint>>) -> Self {
+        let mut pcs = hints.keys().collect::<Vec<_>>();
+        pcs.sort();
+
+        Self {
+            hints: pcs
+                .into_iter()
+                .flat_map(|pc| {
+                    hints[pc].iter().enumerate().map(move |(index, hint)| {
+                        HintAnalysis::new(pc.to_owned(), index, &hint.code)
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns whether any hint is neither whitelisted nor natively implemented.
+    pub fn has_unknown_hints(&self) -> bool {
+        self.hints.iter().any(|hint| !hint.is_known())
+    }
+}
+
+impl HintAnalysis {
+    pub fn new(pc: BigInt, index: usize, code: &str) -> Self {
+        Self {
+            pc,
+            index,
+            code_size: code.len(),
+            imports: find_imports(code),
+            ids: find_ids(code),
+            whitelisted: WHITELISTED_HINTS.contains(&code.trim()),
+            native: native_hints::find_native_hint(code).is_some(),
+            suspicious: find_suspicious_names(code),
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.whitelisted || self.native
+    }
+}
+
+impl Display for HintReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Hints: {}", self.hints.len())?;
+        for hint in self.hints.iter() {
+            write!(f, "{}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for HintAnalysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.native {
+            "native"
+        } else if self.whitelisted {
+            "whitelisted"
+        } else {
+            "unknown"
+        };
+        writeln!(
+            f,
+            "pc={} #{}: {} bytes, {}",
+            self.pc, self.index, self.code_size, status
+        )?;
+
+        for (label, values) in [
+            ("imports", &self.imports),
+            ("ids", &self.ids),
+            ("suspicious", &self.suspicious),
+        ] {
+            if !values.is_empty() {
+                writeln!(f, "  {}: {}", label, values.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the modules imported by `import a.b as c, d` and `from a.b import c` statements, each
+/// statement being on its own line.
+fn find_imports(code: &str) -> Vec<String> {
+    let mut imports = BTreeSet::new();
+    for line in code.lines().map(str::trim) {
+        if let Some(rest) = line.strip_prefix("from ") {
+            if let Some((module, _)) = rest.split_once(" import") {
+                imports.insert(module.trim().to_owned());
+            }
+        } else if let Some(rest) = line.strip_prefix("import ") {
+            for module in rest.split(',') {
+                if let Some(module) = module.split_whitespace().next() {
+                    imports.insert(module.to_owned());
+                }
+            }
+        }
+    }
+    imports.into_iter().collect()
+}
+
+/// Finds the names following `ids.`.
+fn find_ids(code: &str) -> Vec<String> {
+    let mut ids = BTreeSet::new();
+    for (start, _) in code.match_indices("ids.") {
+        if !is_word_start(code, start) {
+            continue;
+        }
+        let name = identifier_at(&code[start + "ids.".len()..]);
+        if !name.is_empty() {
+            ids.insert(name.to_owned());
+        }
+    }
+    ids.into_iter().collect()
+}
+
+/// Finds the suspicious names used as standalone identifiers (not as attributes or parts of longer
+/// names), ignoring comments.
+fn find_suspicious_names(code: &str) -> Vec<String> {
+    let code = code
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    SUSPICIOUS_NAMES
+        .iter()
+        .filter(|name| {
+            code.match_indices(*name).any(|(start, _)| {
+                is_word_start(&code, start)
+                    && !code[..start].ends_with('.')
+                    && identifier_at(&code[start..]).len() == name.len()
+            })
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// Returns whether the character before `index` can't be part of an identifier.
+fn is_word_start(code: &str, index: usize) -> bool {
+    !code[..index]
+        .chars()
+        .next_back()
+        .is_some_and(is_identifier_char)
+}
+
+/// Returns the identifier at the start of `code`.
+fn identifier_at(code: &str) -> &str {
+    let end = code
+        .find(|c: char| !is_identifier_char(c))
+        .unwrap_or(code.len());
+    &code[..end]
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    struct Fixture {
+        code: String,
+        imports: Vec<String>,
+        ids: Vec<String>,
+    }
+
+    #[test]
+    fn test_common_library_hints() {
+        let fixtures = serde_json::from_str::<Vec<Fixture>>(include_str!(
+            "../../test-data/hints/common_library.json"
+        ))
+        .unwrap();
+        assert_eq!(fixtures.len(), WHITELISTED_HINTS.len());
+
+        for fixture in fixtures.iter() {
+            let analysis = HintAnalysis::new(BigInt::from(0u32), 0, &fixture.code);
+            assert_eq!(analysis.imports, fixture.imports, "{}", fixture.code);
+            assert_eq!(analysis.ids, fixture.ids, "{}", fixture.code);
+            assert!(analysis.whitelisted, "{}", fixture.code);
+            assert!(analysis.suspicious.is_empty(), "{}", fixture.code);
+        }
+    }
+
+    #[test]
+    fn test_imports() {
+        assert_eq!(
+            find_imports("import os, sys as system\nfrom a.b import (\n    c,\n)\nx = 1"),
+            vec!["a.b", "os", "sys"]
+        );
+        assert!(find_imports("important = 1\nfromage = 2").is_empty());
+    }
+
+    #[test]
+    fn test_ids() {
+        assert_eq!(
+            find_ids("ids.b = ids.a.x + myids.c\nids.a = 1\nprint(ids.)"),
+            vec!["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_suspicious_names() {
+        assert_eq!(
+            find_suspicious_names("exec('x = 1')\nf = open ('/etc/passwd')"),
+            vec!["exec", "open"]
+        );
+        assert_eq!(
+            find_suspicious_names("m = __import__('os')"),
+            vec!["__import__"]
+        );
+        assert!(find_suspicious_names(
+            "ids.open = 1\nreopen = 2\nopened = 3\n# exec(x)\nevaluate(1)"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "builtin-ecdsa")]
+    fn test_native_hint() {
+        let analysis = HintAnalysis::new(BigInt::from(0u32), 0, native_hints::EC_RECOVER);
+        assert!(analysis.native);
+        assert!(!analysis.whitelisted);
+        assert!(analysis.is_known());
+        assert_eq!(
+            analysis.ids,
+            vec![
+                "msg_hash",
+                "public_key_x",
+                "public_key_y",
+                "r",
+                "s",
+                "y_parity"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let report = HintReport {
+            hints: vec![
+                HintAnalysis::new(BigInt::from(2u32), 0, "memory[ap] = segments.add()"),
+                HintAnalysis::new(BigInt::from(4u32), 1, "import os\nexec(ids.code)"),
+            ],
+        };
+        assert!(report.has_unknown_hints());
+        assert_eq!(
+            report.to_string(),
+            "Hints: 2\n\
+            pc=2 #0: 27 bytes, whitelisted\n\
+            pc=4 #1: 24 bytes, unknown\n  \
+            imports: os\n  \
+            ids: code\n  \
+            suspicious: exec\n"
+        );
+    }
+}
@@ -1,16 +1,66 @@
-use crate::cairo::lang::vm::{
-    memory_segments::MemorySegmentManager, relocatable::RelocatableValue,
-    validated_memory_dict::ValidatedMemoryDict,
+pub mod hint_analysis;
+
+pub mod native_hints;
+
+use crate::{
+    cairo::lang::vm::{
+        memory_segments::{Arg, MemorySegmentManager},
+        relocatable::{MaybeRelocatable, RelocatableValue},
+        validated_memory_dict::ValidatedMemoryDict,
+        vm_consts::{Error as VmConstsError, VmConsts},
+        vm_core::VirtualMachine,
+    },
+    python::math_utils::{self, Error as MathError},
 };
 
+use num_bigint::BigInt;
+use once_cell::unsync::OnceCell;
 use rustpython_vm::{
-    builtins::PyTypeRef, pyclass, pyimpl, Context, PyPayload, PyRef, VirtualMachine as PythonVm,
+    builtins::{PyInt, PyIntRef, PyStrRef, PyTypeRef},
+    function::OptionalArg,
+    pyclass, pyimpl, Context, PyObjectRef, PyPayload, PyRef, PyResult, VirtualMachine as PythonVm,
+};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
 };
-use std::{cell::RefCell, rc::Rc};
+use serde_json::value::RawValue;
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
 
+/// Values available to hints in all scopes.
 #[derive(Debug)]
 pub struct StaticLocals {
+    /// Also provides the prime, exposed to hints as `PRIME` and used as the default modulus of the
+    /// field functions.
     pub segments: Rc<RefCell<MemorySegmentManager>>,
+    /// `PRIME` and the field functions as Python objects, created when the first hint runs.
+    py_field_locals: OnceCell<Vec<(&'static str, PyObjectRef)>>,
+}
+
+/// Receives what hints write to `sys.stdout` and `sys.stderr`.
+pub trait HintOutputSink: Debug {
+    /// Called once per hint execution that produced output. `output` is everything the hint
+    /// printed, in order.
+    fn write(&self, pc: &MaybeRelocatable, output: &str);
+}
+
+/// The default sink, forwarding hint output to the host stdout with each line prefixed with the
+/// pc of the hint.
+#[derive(Debug, Default)]
+pub struct StdoutHintOutputSink;
+
+/// A sink collecting the pc-prefixed lines of hint output, mostly useful for tests and embedders
+/// without a terminal.
+#[derive(Debug, Default)]
+pub struct VecHintOutputSink {
+    pub lines: RefCell<Vec<String>>,
+}
+
+/// The `sys.stdout` and `sys.stderr` replacement capturing the output of a single hint.
+#[pyclass(name = "HintOutput", module = false)]
+#[derive(Debug, PyPayload)]
+pub struct PyHintOutput {
+    pub buffer: Rc<RefCell<String>>,
 }
 
 #[pyclass(name = "RelocatableValue", module = false)]
@@ -31,6 +81,118 @@ pub struct PyValidatedMemoryDict {
     pub inner: Rc<RefCell<ValidatedMemoryDict>>,
 }
 
+/// The `ids` object of a hint, bound to the registers at the time the hint runs.
+#[pyclass(name = "VmConsts", module = false)]
+#[derive(Debug, PyPayload)]
+pub struct PyVmConsts {
+    pub consts: Rc<VmConsts>,
+    pub ap: MaybeRelocatable,
+    pub fp: MaybeRelocatable,
+    pub memory: Rc<RefCell<ValidatedMemoryDict>>,
+}
+
+impl StaticLocals {
+    pub fn new(segments: Rc<RefCell<MemorySegmentManager>>) -> Self {
+        Self {
+            segments,
+            py_field_locals: OnceCell::new(),
+        }
+    }
+
+    /// Returns `PRIME` and the field arithmetic functions (`fadd`, `fsub`, `fmul`, `fdiv`, `fpow`,
+    /// `fis_quad_residue`, `fsqrt` and `safe_div`) to be injected into the hint globals.
+    ///
+    /// The objects belong to the interpreter of `vm`, which must be the same on every call.
+    pub fn py_field_locals(&self, vm: &PythonVm) -> &[(&'static str, PyObjectRef)] {
+        self.py_field_locals.get_or_init(|| {
+            let prime = self.segments.borrow().prime.clone();
+            vec![
+                ("PRIME", vm.ctx.new_int(prime.clone()).into()),
+                (
+                    "fadd",
+                    new_field_function("fadd", &prime, vm, |a, b, p| {
+                        math_utils::modulo(&(a + b), p)
+                    }),
+                ),
+                (
+                    "fsub",
+                    new_field_function("fsub", &prime, vm, |a, b, p| {
+                        math_utils::modulo(&(a - b), p)
+                    }),
+                ),
+                (
+                    "fmul",
+                    new_field_function("fmul", &prime, vm, |a, b, p| {
+                        math_utils::modulo(&(a * b), p)
+                    }),
+                ),
+                (
+                    "fdiv",
+                    new_field_function("fdiv", &prime, vm, |a, b, p| {
+                        math_utils::div_mod(a, b, p).ok_or_else(|| MathError::NotInvertible {
+                            n: b.to_owned(),
+                            p: p.to_owned(),
+                        })
+                    }),
+                ),
+                (
+                    "fpow",
+                    new_field_function("fpow", &prime, vm, math_utils::pow_mod),
+                ),
+                ("fis_quad_residue", {
+                    let prime = prime.clone();
+                    vm.ctx
+                        .new_function(
+                            "fis_quad_residue",
+                            move |a: PyIntRef,
+                                  p: OptionalArg<PyIntRef>,
+                                  vm: &PythonVm|
+                                  -> PyResult<PyObjectRef> {
+                                let p = p.into_option();
+                                let p = p.as_ref().map_or(&prime, |p| p.as_bigint());
+                                math_utils::is_quad_residue(a.as_bigint(), p)
+                                    .map(|value| vm.ctx.new_bool(value).into())
+                                    .map_err(|err| vm.new_value_error(err.to_string()))
+                            },
+                        )
+                        .into()
+                }),
+                ("fsqrt", {
+                    let prime = prime.clone();
+                    vm.ctx
+                        .new_function(
+                            "fsqrt",
+                            move |a: PyIntRef,
+                                  p: OptionalArg<PyIntRef>,
+                                  vm: &PythonVm|
+                                  -> PyResult<PyObjectRef> {
+                                let p = p.into_option();
+                                let p = p.as_ref().map_or(&prime, |p| p.as_bigint());
+                                math_utils::sqrt(a.as_bigint(), p)
+                                    .map(|value| vm.ctx.new_int(value).into())
+                                    .map_err(|err| vm.new_value_error(err.to_string()))
+                            },
+                        )
+                        .into()
+                }),
+                (
+                    "safe_div",
+                    vm.ctx
+                        .new_function(
+                            "safe_div",
+                            |x: PyIntRef, y: PyIntRef, vm: &PythonVm| -> PyResult<PyObjectRef> {
+                                math_utils::safe_div(x.as_bigint(), y.as_bigint())
+                                    .map(|value| vm.ctx.new_int(value).into())
+                                    .map_err(|err| vm.new_value_error(err.to_string()))
+                            },
+                        )
+                        .into(),
+                ),
+            ]
+        })
+    }
+}
+
 #[pyimpl]
 impl PyRelocatableValue {
     pub fn from_relocatable_value(value: &RelocatableValue) -> Self {
@@ -50,9 +212,63 @@ impl PyMemorySegmentManager {
         PyRelocatableValue::from_relocatable_value(&zelf.inner.borrow_mut().add(None)).into_ref(vm)
     }
 
+    pub fn py_add_temp_segment(zelf: PyRef<Self>, vm: &PythonVm) -> PyRef<PyRelocatableValue> {
+        PyRelocatableValue::from_relocatable_value(&zelf.inner.borrow_mut().add_temp_segment())
+            .into_ref(vm)
+    }
+
+    pub fn py_gen_arg(
+        zelf: PyRef<Self>,
+        arg: PyObjectRef,
+        apply_modulo_to_args: OptionalArg<bool>,
+        vm: &PythonVm,
+    ) -> PyResult {
+        let arg = to_arg(&arg, vm)?;
+        let value = zelf
+            .inner
+            .borrow_mut()
+            .gen_arg(arg, apply_modulo_to_args.into_option().unwrap_or(true))
+            .map_err(|err| vm.new_value_error(err.to_string()))?;
+        Ok(from_maybe_relocatable(&value, vm))
+    }
+
     #[extend_class]
     fn extend_class_with_fields(ctx: &Context, class: &PyTypeRef) {
         class.set_str_attr("add", ctx.new_method("add", class.to_owned(), Self::py_add));
+        class.set_str_attr(
+            "add_temp_segment",
+            ctx.new_method(
+                "add_temp_segment",
+                class.to_owned(),
+                Self::py_add_temp_segment,
+            ),
+        );
+        class.set_str_attr(
+            "gen_arg",
+            ctx.new_method("gen_arg", class.to_owned(), Self::py_gen_arg),
+        );
+    }
+}
+
+#[pyimpl]
+impl PyHintOutput {
+    pub fn py_write(zelf: PyRef<Self>, s: PyStrRef) -> usize {
+        zelf.buffer.borrow_mut().push_str(s.as_str());
+        s.as_str().chars().count()
+    }
+
+    pub fn py_flush(_zelf: PyRef<Self>) {}
+
+    #[extend_class]
+    fn extend_class_with_fields(ctx: &Context, class: &PyTypeRef) {
+        class.set_str_attr(
+            "write",
+            ctx.new_method("write", class.to_owned(), Self::py_write),
+        );
+        class.set_str_attr(
+            "flush",
+            ctx.new_method("flush", class.to_owned(), Self::py_flush),
+        );
     }
 }
 
@@ -61,11 +277,235 @@ impl PyValidatedMemoryDict {
     pub fn py_setitem(
         zelf: PyRef<Self>,
         addr: PyRef<PyRelocatableValue>,
-        value: PyRef<PyRelocatableValue>,
-    ) {
-        zelf.inner.borrow_mut().index_set(
-            addr.to_relocatable_value().into(),
-            value.to_relocatable_value().into(),
-        );
+        value: PyObjectRef,
+        vm: &PythonVm,
+    ) -> PyResult<()> {
+        let value = to_maybe_relocatable(&value, vm)?;
+        zelf.inner
+            .borrow_mut()
+            .index_set(addr.to_relocatable_value().into(), value)
+            .map_err(|err| vm.new_value_error(err.to_string()))
+    }
+}
+
+#[pyimpl]
+impl PyVmConsts {
+    pub fn py_getattr(zelf: PyRef<Self>, name: PyStrRef, vm: &PythonVm) -> PyResult {
+        if let Some(value) = zelf.consts.get_constant(name.as_str()) {
+            return Ok(vm.ctx.new_int(value.to_owned()).into());
+        }
+
+        let addr = zelf.get_address(name.as_str(), vm)?;
+        let value = zelf.memory.borrow_mut().get(&addr, None).ok_or_else(|| {
+            vm.new_value_error(
+                VmConstsError::UnknownMemory {
+                    path: format!("ids.{}", name.as_str()),
+                    addr,
+                }
+                .to_string(),
+            )
+        })?;
+        Ok(from_maybe_relocatable(&value, vm))
+    }
+
+    pub fn py_setattr(
+        zelf: PyRef<Self>,
+        name: PyStrRef,
+        value: PyObjectRef,
+        vm: &PythonVm,
+    ) -> PyResult<()> {
+        let addr = zelf.get_address(name.as_str(), vm)?;
+        let value = to_maybe_relocatable(&value, vm)?;
+        zelf.memory
+            .borrow_mut()
+            .index_set(addr, value)
+            .map_err(|err| vm.new_value_error(err.to_string()))
+    }
+
+    fn get_address(&self, name: &str, vm: &PythonVm) -> PyResult<MaybeRelocatable> {
+        self.consts
+            .get_address(name, &self.ap, &self.fp)
+            .map_err(|err| match err {
+                VmConstsError::UnknownIdentifier { .. } => vm.new_attribute_error(err.to_string()),
+                _ => vm.new_value_error(err.to_string()),
+            })
     }
 }
+
+impl HintOutputSink for StdoutHintOutputSink {
+    fn write(&self, pc: &MaybeRelocatable, output: &str) {
+        for line in prefix_hint_output(pc, output) {
+            println!("{}", line);
+        }
+    }
+}
+
+impl HintOutputSink for VecHintOutputSink {
+    fn write(&self, pc: &MaybeRelocatable, output: &str) {
+        self.lines
+            .borrow_mut()
+            .extend(prefix_hint_output(pc, output));
+    }
+}
+
+/// Splits the output of a hint into lines, each prefixed with the pc of the hint.
+pub fn prefix_hint_output(pc: &MaybeRelocatable, output: &str) -> Vec<String> {
+    output
+        .lines()
+        .map(|line| format!("[pc={}] {}", pc, line))
+        .collect()
+}
+
+/// Creates a Python function `name(a, b, p=prime)` returning `f(a, b, p)`.
+fn new_field_function<F>(name: &str, prime: &BigInt, vm: &PythonVm, f: F) -> PyObjectRef
+where
+    F: Fn(&BigInt, &BigInt, &BigInt) -> Result<BigInt, MathError> + 'static,
+{
+    let prime = prime.to_owned();
+    vm.ctx
+        .new_function(
+            name,
+            move |a: PyIntRef,
+                  b: PyIntRef,
+                  p: OptionalArg<PyIntRef>,
+                  vm: &PythonVm|
+                  -> PyResult<PyObjectRef> {
+                let p = p.into_option();
+                let p = p.as_ref().map_or(&prime, |p| p.as_bigint());
+                f(a.as_bigint(), b.as_bigint(), p)
+                    .map(|value| vm.ctx.new_int(value).into())
+                    .map_err(|err| vm.new_value_error(err.to_string()))
+            },
+        )
+        .into()
+}
+
+/// Converts a `MaybeRelocatable` to either a Python `int` or a `RelocatableValue` object.
+pub fn from_maybe_relocatable(value: &MaybeRelocatable, vm: &PythonVm) -> PyObjectRef {
+    match value {
+        MaybeRelocatable::Int(value) => vm.ctx.new_int(value.to_owned()).into(),
+        MaybeRelocatable::RelocatableValue(value) => {
+            PyRelocatableValue::from_relocatable_value(value)
+                .into_ref(vm)
+                .into()
+        }
+    }
+}
+
+/// Converts a Python object to a `MaybeRelocatable` so that it can be written to memory. Only
+/// `int` and `RelocatableValue` objects are accepted.
+pub fn to_maybe_relocatable(value: &PyObjectRef, vm: &PythonVm) -> PyResult<MaybeRelocatable> {
+    if let Some(value) = value.payload::<PyRelocatableValue>() {
+        Ok(value.to_relocatable_value().into())
+    } else if let Some(value) = value.payload::<PyInt>() {
+        Ok(value.as_bigint().to_owned().into())
+    } else {
+        Err(vm.new_type_error(String::from(
+            "Memory values must be either an int or a RelocatableValue",
+        )))
+    }
+}
+
+/// Converts a Python object to an argument of `segments.gen_arg()`: an `int`, a `RelocatableValue`,
+/// or an iterable of such arguments.
+pub fn to_arg(value: &PyObjectRef, vm: &PythonVm) -> PyResult<Arg> {
+    if let Some(value) = value.payload::<PyRelocatableValue>() {
+        Ok(Arg::Relocatable(value.to_relocatable_value()))
+    } else if let Some(value) = value.payload::<PyInt>() {
+        Ok(Arg::Int(value.as_bigint().to_owned()))
+    } else {
+        Ok(Arg::Array(
+            vm.extract_elements_with(value, |item| to_arg(&item, vm))?,
+        ))
+    }
+}
+
+/// Converts JSON to the native Python structure `json.loads()` would produce: objects become
+/// `dict`, arrays become `list`, and scalars become `str`, `int`, `float`, `bool` or `None`.
+///
+/// The JSON is taken raw rather than as a `serde_json::Value`, which only holds 64-bit integers and
+/// reads larger ones as floats. Here, like in Python, integers of any size are exact.
+pub fn json_to_py_object(json: &RawValue, vm: &PythonVm) -> PyResult<PyObjectRef> {
+    let json = json.get();
+    Ok(match json.as_bytes()[0] {
+        b'{' => {
+            let dict = vm.ctx.new_dict();
+            for (key, value) in from_valid_json::<JsonObject>(json).0 {
+                dict.set_item(key.as_str(), json_to_py_object(value, vm)?, vm)?;
+            }
+            dict.into()
+        }
+        b'[' => {
+            let mut elements = vec![];
+            for value in from_valid_json::<Vec<&RawValue>>(json) {
+                elements.push(json_to_py_object(value, vm)?);
+            }
+            vm.ctx.new_list(elements).into()
+        }
+        b'"' => vm.ctx.new_str(from_valid_json::<String>(json)).into(),
+        b't' | b'f' => vm.ctx.new_bool(from_valid_json::<bool>(json)).into(),
+        b'n' => vm.ctx.none(),
+        // JSON numbers are a subset of what the standard parsers accept. Like in Python, floats too
+        // large for `f64` become infinite.
+        _ if json.contains(|c| matches!(c, '.' | 'e' | 'E')) => vm
+            .ctx
+            .new_float(json.parse().expect("invalid JSON number"))
+            .into(),
+        _ => vm
+            .ctx
+            .new_int(json.parse::<BigInt>().expect("invalid JSON number"))
+            .into(),
+    })
+}
+
+/// Builds the hint locals exposing `program_input` to hints, like `cairo-run --program_input`
+/// does. The objects are created with the interpreter of `vm`, which runs the hints.
+pub fn program_input_locals(
+    program_input: &RawValue,
+    vm: &VirtualMachine,
+) -> HashMap<String, PyObjectRef> {
+    let program_input = vm
+        .python_interpreter()
+        .enter(|vm| json_to_py_object(program_input, vm))
+        // Building plain containers and scalars never raises.
+        .expect("failed to convert program input");
+
+    let mut locals = HashMap::new();
+    locals.insert(String::from("program_input"), program_input);
+    locals
+}
+
+/// The members of a JSON object in order, with their values left unparsed.
+struct JsonObject<'a>(Vec<(String, &'a RawValue)>);
+
+impl<'de> Deserialize<'de> for JsonObject<'de> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct JsonObjectVisitor;
+
+        impl<'de> Visitor<'de> for JsonObjectVisitor {
+            type Value = JsonObject<'de>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut members = vec![];
+                while let Some(member) = map.next_entry()? {
+                    members.push(member);
+                }
+                Ok(JsonObject(members))
+            }
+        }
+
+        deserializer.deserialize_map(JsonObjectVisitor)
+    }
+}
+
+/// Reads a part of JSON that was already validated as a whole.
+fn from_valid_json<'a, T: Deserialize<'a>>(json: &'a str) -> T {
+    serde_json::from_str(json).expect("invalid JSON")
+}
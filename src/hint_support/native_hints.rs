@@ -0,0 +1,158 @@
+//! Rust ports of hints, run in place of their Python code.
+//!
+//! A hint is ported by matching its code exactly, so that programs compiled from the same Cairo
+//! source pick up the port without any change.
+
+use crate::cairo::lang::vm::{
+    builtin_runner::Error as BuiltinRunnerError,
+    relocatable::MaybeRelocatable,
+    validated_memory_dict::ValidatedMemoryDict,
+    vm_consts::{Error as VmConstsError, VmConsts},
+};
+
+#[cfg(feature = "builtin-ecdsa")]
+use crate::crypto::signature::signature::{self, Error as SignatureError};
+
+use num_bigint::BigInt;
+use std::cell::RefCell;
+
+/// Computes `a / b` modulo the order of the STARK curve.
+pub const DIV_MOD_N: &str = "\
+from starkware.crypto.signature.signature import EC_ORDER
+from starkware.python.math_utils import div_mod
+
+ids.res = div_mod(ids.a, ids.b, EC_ORDER)";
+
+/// Recovers the public key from an ECDSA signature on the STARK curve.
+pub const EC_RECOVER: &str = "\
+from starkware.crypto.signature.signature import recover_public_key
+
+ids.public_key_x, ids.public_key_y = recover_public_key(
+    ids.msg_hash, ids.r, ids.s, ids.y_parity
+)";
+
+pub type NativeHint = fn(&NativeHintContext) -> Result<(), Error>;
+
+/// What a native hint can access: the `ids` of the hint and the memory.
+pub struct NativeHintContext<'a> {
+    pub consts: &'a VmConsts,
+    pub ap: MaybeRelocatable,
+    pub fp: MaybeRelocatable,
+    pub memory: &'a RefCell<ValidatedMemoryDict>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    VmConstsError(VmConstsError),
+    #[error("'ids.{name}' is not an integer.")]
+    NotAnInteger { name: String },
+    #[error("'ids.{name}' must be 0 or 1.")]
+    NotABool { name: String },
+    #[cfg(feature = "builtin-ecdsa")]
+    #[error(transparent)]
+    SignatureError(SignatureError),
+    #[error(transparent)]
+    BuiltinRunnerError(BuiltinRunnerError),
+}
+
+impl<'a> NativeHintContext<'a> {
+    /// Reads the integer value of `ids.<name>`.
+    pub fn get_int(&self, name: &str) -> Result<BigInt, Error> {
+        let addr = self.consts.get_address(name, &self.ap, &self.fp)?;
+        match self.memory.borrow_mut().get(&addr, None) {
+            Some(MaybeRelocatable::Int(value)) => Ok(value),
+            Some(MaybeRelocatable::RelocatableValue(_)) => Err(Error::NotAnInteger {
+                name: name.to_owned(),
+            }),
+            None => Err(VmConstsError::UnknownMemory {
+                path: format!("ids.{}", name),
+                addr,
+            }
+            .into()),
+        }
+    }
+
+    /// Writes `value` to `ids.<name>`.
+    pub fn set(&self, name: &str, value: MaybeRelocatable) -> Result<(), Error> {
+        let addr = self.consts.get_address(name, &self.ap, &self.fp)?;
+        Ok(self.memory.borrow_mut().index_set(addr, value)?)
+    }
+}
+
+/// Returns the Rust port of the hint with the given code, if any.
+#[cfg(feature = "builtin-ecdsa")]
+pub fn find_native_hint(code: &str) -> Option<NativeHint> {
+    match code.trim() {
+        DIV_MOD_N => Some(div_mod_n),
+        EC_RECOVER => Some(ec_recover),
+        _ => None,
+    }
+}
+
+/// The ported hints all need the curve arithmetic of the `builtin-ecdsa` feature, so without it
+/// every hint runs its Python code.
+#[cfg(not(feature = "builtin-ecdsa"))]
+pub fn find_native_hint(_code: &str) -> Option<NativeHint> {
+    None
+}
+
+#[cfg(feature = "builtin-ecdsa")]
+fn div_mod_n(ctx: &NativeHintContext) -> Result<(), Error> {
+    let res = signature::div_mod_n(&ctx.get_int("a")?, &ctx.get_int("b")?)?;
+    ctx.set("res", res.into())
+}
+
+#[cfg(feature = "builtin-ecdsa")]
+fn ec_recover(ctx: &NativeHintContext) -> Result<(), Error> {
+    let y_parity = ctx.get_int("y_parity")?;
+    let y_parity = if y_parity == BigInt::from(0u32) {
+        false
+    } else if y_parity == BigInt::from(1u32) {
+        true
+    } else {
+        return Err(Error::NotABool {
+            name: String::from("y_parity"),
+        });
+    };
+
+    let (x, y) = signature::recover_public_key(
+        &ctx.get_int("msg_hash")?,
+        &ctx.get_int("r")?,
+        &ctx.get_int("s")?,
+        y_parity,
+    )?;
+    ctx.set("public_key_x", x.into())?;
+    ctx.set("public_key_y", y.into())
+}
+
+impl From<VmConstsError> for Error {
+    fn from(value: VmConstsError) -> Self {
+        Self::VmConstsError(value)
+    }
+}
+
+#[cfg(feature = "builtin-ecdsa")]
+impl From<SignatureError> for Error {
+    fn from(value: SignatureError) -> Self {
+        Self::SignatureError(value)
+    }
+}
+
+impl From<BuiltinRunnerError> for Error {
+    fn from(value: BuiltinRunnerError) -> Self {
+        Self::BuiltinRunnerError(value)
+    }
+}
+
+#[cfg(all(test, feature = "builtin-ecdsa"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_native_hint() {
+        assert!(find_native_hint(DIV_MOD_N).is_some());
+        assert!(find_native_hint(&format!("\n{}\n", EC_RECOVER)).is_some());
+        assert!(find_native_hint("ids.res = div_mod(ids.a, ids.b, EC_ORDER)").is_none());
+    }
+}
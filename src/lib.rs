@@ -2,6 +2,15 @@
 
 pub mod cairo;
 
+pub mod crypto;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
 pub mod hint_support;
 
+pub mod python;
+
 pub mod serde;
+
+pub mod verify;
@@ -0,0 +1,378 @@
+use num_bigint::BigInt;
+
+/// An affine point on an elliptic curve.
+pub type EcPoint = (BigInt, BigInt);
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("{x} is not divisible by {y}.")]
+    NotDivisible { x: BigInt, y: BigInt },
+    #[error("{n} is not invertible modulo {p}.")]
+    NotInvertible { n: BigInt, p: BigInt },
+    #[error("{n} is not a quadratic residue modulo {p}.")]
+    NotQuadResidue { n: BigInt, p: BigInt },
+    #[error("Cannot add two points with the same x coordinate.")]
+    SameXCoordinate,
+    #[error("Cannot double a point with a zero y coordinate.")]
+    ZeroYCoordinate,
+    #[error("Cannot multiply a point by {m}.")]
+    NonPositiveMultiplier { m: BigInt },
+}
+
+/// Computes `x % p` with the sign of `p`, like Python does.
+pub fn modulo(x: &BigInt, p: &BigInt) -> Result<BigInt, Error> {
+    if p == &BigInt::from(0u32) {
+        return Err(Error::DivisionByZero);
+    }
+
+    let x = x % p;
+    Ok(
+        if x != BigInt::from(0u32) && (x < BigInt::from(0u32)) != (p < &BigInt::from(0u32)) {
+            x + p
+        } else {
+            x
+        },
+    )
+}
+
+/// Finds a nonnegative integer 0 <= x < p such that (m * x) % p == n, or returns None if m is not
+/// invertible modulo p.
+pub fn div_mod(n: &BigInt, m: &BigInt, p: &BigInt) -> Option<BigInt> {
+    // Extended Euclidean algorithm, keeping track of the coefficient of m only.
+    let (mut old_r, mut r) = (m % p, p.to_owned());
+    let (mut old_s, mut s) = (BigInt::from(1u32), BigInt::from(0u32));
+    while r != BigInt::from(0u32) {
+        let quotient = &old_r / &r;
+        let new_r = &old_r - &quotient * &r;
+        old_r = std::mem::replace(&mut r, new_r);
+        let new_s = &old_s - &quotient * &s;
+        old_s = std::mem::replace(&mut s, new_s);
+    }
+
+    if old_r != BigInt::from(1u32) && old_r != BigInt::from(-1) {
+        return None;
+    }
+
+    let inverse = old_s * old_r;
+    let x = (n * inverse) % p;
+    Some(if x < BigInt::from(0u32) { x + p } else { x })
+}
+
+/// Computes `pow(a, b, p)` the way Python does, where a negative exponent raises the inverse of `a`
+/// to the power of `-b`.
+pub fn pow_mod(a: &BigInt, b: &BigInt, p: &BigInt) -> Result<BigInt, Error> {
+    let a = modulo(a, p)?;
+    if b < &BigInt::from(0u32) {
+        let inverse = div_mod(&BigInt::from(1u32), &a, p).ok_or_else(|| Error::NotInvertible {
+            n: a.clone(),
+            p: p.to_owned(),
+        })?;
+        modulo(&inverse.modpow(&-b, p), p)
+    } else {
+        modulo(&a.modpow(b, p), p)
+    }
+}
+
+/// Computes x / y and checks that the remainder is zero.
+pub fn safe_div(x: &BigInt, y: &BigInt) -> Result<BigInt, Error> {
+    if y == &BigInt::from(0u32) {
+        return Err(Error::DivisionByZero);
+    }
+    if x % y != BigInt::from(0u32) {
+        return Err(Error::NotDivisible {
+            x: x.to_owned(),
+            y: y.to_owned(),
+        });
+    }
+
+    Ok(x / y)
+}
+
+/// Returns whether `n` is a quadratic residue modulo the odd prime `p`, using Euler's criterion.
+/// Zero counts as a residue.
+pub fn is_quad_residue(n: &BigInt, p: &BigInt) -> Result<bool, Error> {
+    let n = modulo(n, p)?;
+    if n == BigInt::from(0u32) {
+        return Ok(true);
+    }
+
+    Ok(n.modpow(&((p - 1u32) / 2u32), p) == BigInt::from(1u32))
+}
+
+/// Finds the minimum non-negative integer m such that (m * m) % p == n, using the Tonelli-Shanks
+/// algorithm. `p` must be an odd prime.
+pub fn sqrt(n: &BigInt, p: &BigInt) -> Result<BigInt, Error> {
+    let n = modulo(n, p)?;
+    if n == BigInt::from(0u32) {
+        return Ok(n);
+    }
+    if !is_quad_residue(&n, p)? {
+        return Err(Error::NotQuadResidue { n, p: p.to_owned() });
+    }
+
+    // Writes p - 1 as q * 2^s with q odd.
+    let mut q = p - 1u32;
+    let mut s = 0u64;
+    while (&q & BigInt::from(1u32)) == BigInt::from(0u32) {
+        q >>= 1;
+        s += 1;
+    }
+
+    let mut z = BigInt::from(2u32);
+    while is_quad_residue(&z, p)? {
+        z += 1u32;
+    }
+
+    let mut m = s;
+    let mut c = z.modpow(&q, p);
+    let mut t = n.modpow(&q, p);
+    let mut r = n.modpow(&((&q + 1u32) / 2u32), p);
+    while t != BigInt::from(1u32) {
+        // Finds the least i such that t^(2^i) == 1.
+        let mut i = 0u64;
+        let mut t_pow = t.clone();
+        while t_pow != BigInt::from(1u32) {
+            t_pow = &t_pow * &t_pow % p;
+            i += 1;
+        }
+
+        let b = c.modpow(&(BigInt::from(1u32) << (m - i - 1)), p);
+        m = i;
+        c = &b * &b % p;
+        t = t * &c % p;
+        r = r * &b % p;
+    }
+
+    let other_root = p - &r;
+    Ok(if other_root < r { other_root } else { r })
+}
+
+/// Computes the slope of the line connecting the two given EC points over the field GF(p).
+/// Assumes the points are given in affine form (x, y) and have different x coordinates.
+pub fn line_slope(point1: &EcPoint, point2: &EcPoint, p: &BigInt) -> Result<BigInt, Error> {
+    if modulo(&(&point1.0 - &point2.0), p)? == BigInt::from(0u32) {
+        return Err(Error::SameXCoordinate);
+    }
+
+    div_mod(&(&point1.1 - &point2.1), &(&point1.0 - &point2.0), p).ok_or(Error::SameXCoordinate)
+}
+
+/// Gets two points on an elliptic curve mod p and returns their sum.
+/// Assumes the points are given in affine form (x, y) and have different x coordinates.
+pub fn ec_add(point1: &EcPoint, point2: &EcPoint, p: &BigInt) -> Result<EcPoint, Error> {
+    let m = line_slope(point1, point2, p)?;
+    let x = modulo(&(&m * &m - &point1.0 - &point2.0), p)?;
+    let y = modulo(&(m * (&point1.0 - &x) - &point1.1), p)?;
+    Ok((x, y))
+}
+
+/// Given a point (x, y) return (x, -y).
+pub fn ec_neg(point: &EcPoint, p: &BigInt) -> Result<EcPoint, Error> {
+    Ok((point.0.clone(), modulo(&-&point.1, p)?))
+}
+
+/// Computes the slope of an elliptic curve with the equation y^2 = x^3 + alpha*x + beta mod p, at
+/// the given point.
+/// Assumes the point is given in affine form (x, y) and has y != 0.
+pub fn ec_double_slope(point: &EcPoint, alpha: &BigInt, p: &BigInt) -> Result<BigInt, Error> {
+    if modulo(&point.1, p)? == BigInt::from(0u32) {
+        return Err(Error::ZeroYCoordinate);
+    }
+
+    div_mod(
+        &(BigInt::from(3u32) * &point.0 * &point.0 + alpha),
+        &(BigInt::from(2u32) * &point.1),
+        p,
+    )
+    .ok_or(Error::ZeroYCoordinate)
+}
+
+/// Doubles a point on an elliptic curve with the equation y^2 = x^3 + alpha*x + beta mod p.
+/// Assumes the point is given in affine form (x, y) and has y != 0.
+pub fn ec_double(point: &EcPoint, alpha: &BigInt, p: &BigInt) -> Result<EcPoint, Error> {
+    let m = ec_double_slope(point, alpha, p)?;
+    let x = modulo(&(&m * &m - BigInt::from(2u32) * &point.0), p)?;
+    let y = modulo(&(m * (&point.0 - &x) - &point.1), p)?;
+    Ok((x, y))
+}
+
+/// Multiplies by m a point on the elliptic curve with equation y^2 = x^3 + alpha*x + beta mod p.
+/// Assumes the point is given in affine form (x, y) and that 0 < m < order(point).
+pub fn ec_mult(m: &BigInt, point: &EcPoint, alpha: &BigInt, p: &BigInt) -> Result<EcPoint, Error> {
+    if m <= &BigInt::from(0u32) {
+        return Err(Error::NonPositiveMultiplier { m: m.to_owned() });
+    }
+
+    // Double-and-add over the bits of m, starting from the least significant one.
+    let mut result: Option<EcPoint> = None;
+    let mut addend = point.to_owned();
+    let bits = m.bits();
+    for bit in 0..bits {
+        if m.bit(bit) {
+            result = Some(match result {
+                Some(result) => ec_add(&result, &addend, p)?,
+                None => addend.clone(),
+            });
+        }
+        if bit + 1 < bits {
+            addend = ec_double(&addend, alpha, p)?;
+        }
+    }
+
+    // m is positive, so at least one bit is set.
+    Ok(result.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cairo_prime() -> BigInt {
+        (BigInt::from(1u32) << 251) + (BigInt::from(17u32) << 192) + 1u32
+    }
+
+    #[test]
+    fn test_modulo() {
+        let p = BigInt::from(7u32);
+        assert_eq!(modulo(&BigInt::from(-1), &p).unwrap(), 6u32.into());
+        assert_eq!(modulo(&BigInt::from(-7), &p).unwrap(), 0u32.into());
+        assert_eq!(modulo(&BigInt::from(9u32), &p).unwrap(), 2u32.into());
+        assert!(matches!(
+            modulo(&BigInt::from(9u32), &0u32.into()),
+            Err(Error::DivisionByZero)
+        ));
+    }
+
+    #[test]
+    fn test_div_mod() {
+        let p = BigInt::from(17u32);
+        for m in 1u32..17 {
+            for n in 0u32..17 {
+                let x = div_mod(&n.into(), &m.into(), &p).unwrap();
+                assert!(x >= BigInt::from(0u32) && x < p);
+                assert_eq!((x * m) % &p, n.into());
+            }
+        }
+
+        assert_eq!(div_mod(&3u32.into(), &0u32.into(), &p), None);
+        assert_eq!(div_mod(&3u32.into(), &4u32.into(), &8u32.into()), None);
+    }
+
+    #[test]
+    fn test_pow_mod() {
+        let p = cairo_prime();
+        assert_eq!(
+            pow_mod(&2u32.into(), &251u32.into(), &p).unwrap(),
+            BigInt::from(1u32) << 251
+        );
+        assert_eq!(
+            pow_mod(&2u32.into(), &BigInt::from(-1), &p).unwrap(),
+            (&p + 1u32) / 2u32
+        );
+        // Fermat's little theorem.
+        assert_eq!(
+            pow_mod(&3u32.into(), &(&p - 1u32), &p).unwrap(),
+            1u32.into()
+        );
+    }
+
+    #[test]
+    fn test_safe_div() {
+        assert_eq!(safe_div(&12u32.into(), &4u32.into()).unwrap(), 3u32.into());
+        assert_eq!(
+            safe_div(&BigInt::from(-12), &4u32.into()).unwrap(),
+            BigInt::from(-3)
+        );
+        assert_eq!(
+            safe_div(&13u32.into(), &4u32.into())
+                .unwrap_err()
+                .to_string(),
+            "13 is not divisible by 4."
+        );
+        assert!(matches!(
+            safe_div(&13u32.into(), &0u32.into()),
+            Err(Error::DivisionByZero)
+        ));
+    }
+
+    #[test]
+    fn test_is_quad_residue() {
+        let p = cairo_prime();
+        assert!(is_quad_residue(&0u32.into(), &p).unwrap());
+        assert!(is_quad_residue(&1u32.into(), &p).unwrap());
+        assert!(is_quad_residue(&4u32.into(), &p).unwrap());
+        // The Cairo prime is 1 modulo 8, making 2 a residue.
+        assert!(is_quad_residue(&2u32.into(), &p).unwrap());
+        // The common library relies on 3 not being a residue.
+        assert!(!is_quad_residue(&3u32.into(), &p).unwrap());
+        // -1 is a residue as the prime is 1 modulo 4.
+        assert!(is_quad_residue(&(&p - 1u32), &p).unwrap());
+    }
+
+    #[test]
+    fn test_ec_ops() {
+        // y^2 = x^3 + 2x + 3 over GF(97), whose point (3, 6) has order 5.
+        let (alpha, p) = (BigInt::from(2u32), BigInt::from(97u32));
+        let point: EcPoint = (3u32.into(), 6u32.into());
+
+        let doubled = ec_double(&point, &alpha, &p).unwrap();
+        assert_eq!(doubled, (80u32.into(), 10u32.into()));
+        assert_eq!(ec_mult(&2u32.into(), &point, &alpha, &p).unwrap(), doubled);
+        assert_eq!(
+            ec_add(&doubled, &point, &p).unwrap(),
+            ec_mult(&3u32.into(), &point, &alpha, &p).unwrap()
+        );
+        assert_eq!(
+            ec_mult(&4u32.into(), &point, &alpha, &p).unwrap(),
+            ec_neg(&point, &p).unwrap()
+        );
+
+        assert!(matches!(
+            ec_add(&point, &point, &p),
+            Err(Error::SameXCoordinate)
+        ));
+        assert!(matches!(
+            ec_mult(&5u32.into(), &point, &alpha, &p),
+            Err(Error::SameXCoordinate)
+        ));
+        assert!(matches!(
+            ec_mult(&0u32.into(), &point, &alpha, &p),
+            Err(Error::NonPositiveMultiplier { .. })
+        ));
+    }
+
+    #[test]
+    fn test_sqrt() {
+        let p = cairo_prime();
+        assert_eq!(sqrt(&0u32.into(), &p).unwrap(), 0u32.into());
+        assert_eq!(sqrt(&25u32.into(), &p).unwrap(), 5u32.into());
+        assert_eq!(sqrt(&(&p - 1u32), &p).unwrap().pow(2) % &p, &p - 1u32);
+
+        let root = sqrt(&2u32.into(), &p).unwrap();
+        assert_eq!(&root * &root % &p, 2u32.into());
+        assert!(root <= &p - &root);
+
+        for x in [3u32, 1000, 123456789] {
+            let x = BigInt::from(x);
+            assert_eq!(sqrt(&(&x * &x), &p).unwrap(), x);
+            assert_eq!(sqrt(&(&x * &x % &p), &p).unwrap(), x);
+            assert_eq!(sqrt(&((&p - &x) * (&p - &x) % &p), &p).unwrap(), x);
+        }
+
+        assert!(matches!(
+            sqrt(&3u32.into(), &p),
+            Err(Error::NotQuadResidue { .. })
+        ));
+
+        // Small prime with a large power of 2 in p - 1.
+        let p = BigInt::from(97u32);
+        for x in 0u32..97 {
+            let n = BigInt::from(x * x % 97);
+            let root = sqrt(&n, &p).unwrap();
+            assert_eq!(&root * &root % &p, n);
+            assert_eq!(root, BigInt::from(x.min(97 - x) % 97));
+        }
+    }
+}
@@ -0,0 +1,2 @@
+pub mod math_utils;
+pub mod utils;
@@ -0,0 +1,23 @@
+/// Returns whether `subsequence` is a subsequence of `sequence`, i.e. whether it can be obtained
+/// by removing elements from `sequence` without changing the order of the others.
+pub fn is_subsequence<T: PartialEq>(subsequence: &[T], sequence: &[T]) -> bool {
+    let mut sequence = sequence.iter();
+    subsequence
+        .iter()
+        .all(|item| sequence.any(|other| other == item))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_subsequence() {
+        assert!(is_subsequence::<u32>(&[], &[]));
+        assert!(is_subsequence(&[1, 3], &[1, 2, 3]));
+        assert!(is_subsequence(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!is_subsequence(&[3, 1], &[1, 2, 3]));
+        assert!(!is_subsequence(&[1, 1], &[1, 2, 3]));
+        assert!(!is_subsequence(&[4], &[1, 2, 3]));
+    }
+}
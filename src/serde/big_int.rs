@@ -1,11 +1,24 @@
 use num_bigint::BigInt;
-use serde::{de::Error as DeError, Deserialize, Deserializer, Serializer};
+use serde::{
+    de::{Error as DeError, Visitor},
+    ser::Error as SerError,
+    Deserialize, Deserializer, Serializer,
+};
 use serde_with::{DeserializeAs, SerializeAs};
 
 pub struct BigIntHex;
 
 pub struct BigIntNumber;
 
+/// Reads JSON integers that may be negative or too large for 64 bits, such as the values of
+/// constants. As JSON numbers are only read as 64-bit integers or as floats, the larger ones are
+/// read as `None` rather than as an approximation. Serializes as a JSON integer.
+pub struct LossyBigIntNumber;
+
+/// Deserializes `0x`-prefixed strings as hex and other strings as decimal, for programs emitted by
+/// tools that don't use hex for field elements. Serializes as hex.
+pub struct BigIntHexOrDecimal;
+
 impl SerializeAs<BigInt> for BigIntHex {
     fn serialize_as<S>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -31,7 +44,9 @@ impl SerializeAs<BigInt> for BigIntNumber {
     where
         S: Serializer,
     {
-        serializer.serialize_str(&format!("{}", value))
+        let value = u64::try_from(value)
+            .map_err(|err| SerError::custom(format!("number out of range: {}", err)))?;
+        serializer.serialize_u64(value)
     }
 }
 
@@ -45,6 +60,83 @@ impl<'de> DeserializeAs<'de, BigInt> for BigIntNumber {
     }
 }
 
+impl SerializeAs<Option<BigInt>> for LossyBigIntNumber {
+    fn serialize_as<S>(value: &Option<BigInt>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => {
+                if let Ok(value) = i64::try_from(value) {
+                    serializer.serialize_i64(value)
+                } else if let Ok(value) = u64::try_from(value) {
+                    serializer.serialize_u64(value)
+                } else {
+                    Err(SerError::custom(format!("number out of range: {}", value)))
+                }
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'de> DeserializeAs<'de, Option<BigInt>> for LossyBigIntNumber {
+    fn deserialize_as<D>(deserializer: D) -> Result<Option<BigInt>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LossyNumberVisitor;
+
+        impl<'de> Visitor<'de> for LossyNumberVisitor {
+            type Value = Option<BigInt>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("an integer")
+            }
+
+            fn visit_i64<E: DeError>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(Some(BigInt::from(value)))
+            }
+
+            fn visit_u64<E: DeError>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(Some(BigInt::from(value)))
+            }
+
+            fn visit_f64<E: DeError>(self, _value: f64) -> Result<Self::Value, E> {
+                Ok(None)
+            }
+        }
+
+        deserializer.deserialize_any(LossyNumberVisitor)
+    }
+}
+
+impl SerializeAs<BigInt> for BigIntHexOrDecimal {
+    fn serialize_as<S>(value: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        BigIntHex::serialize_as(value, serializer)
+    }
+}
+
+impl<'de> DeserializeAs<'de, BigInt> for BigIntHexOrDecimal {
+    fn deserialize_as<D>(deserializer: D) -> Result<BigInt, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        if value.starts_with("0x") {
+            utils::big_int_from_hex(&value)
+                .map_err(|err| DeError::custom(format!("invalid hex string: {}", err)))
+        } else {
+            value
+                .parse::<BigInt>()
+                .map_err(|err| DeError::custom(format!("invalid decimal string: {}", err)))
+        }
+    }
+}
+
 mod utils {
     use hex::FromHexError;
     use num_bigint::{BigInt, Sign};
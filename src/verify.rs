@@ -0,0 +1,537 @@
+use crate::{
+    cairo::lang::{
+        compiler::program::FullProgram,
+        instances::suggest_layout,
+        vm::{
+            cairo_runner::{CairoRunner, Error as CairoRunnerError},
+            memory_dict::MemoryDict,
+            relocatable::MaybeRelocatable,
+        },
+    },
+    serde::big_int::BigIntHex,
+};
+
+use num_bigint::BigInt;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Bounds on the resources a verified run may use.
+#[derive(Debug, Default, Clone)]
+pub struct VerifyLimits {
+    /// The maximum number of steps before the run is aborted. Unbounded if `None`.
+    pub max_steps: Option<BigInt>,
+    /// The maximum depth of nested calls before the run is aborted. Unbounded if `None`.
+    pub max_call_depth: Option<usize>,
+}
+
+/// The outcome of a run that completed within the limits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verification {
+    /// The number of steps executed.
+    pub n_steps: BigInt,
+    /// The number of cells used by each builtin, keyed by the builtin name.
+    pub builtin_usage: HashMap<String, BigInt>,
+    /// The deepest nesting of calls reached during the run.
+    pub peak_call_depth: usize,
+    pub output: OutputComparison,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputComparison {
+    Match,
+    /// The output differs from the expected one, starting at `index`. Either side is `None` if
+    /// the output is shorter than expected or longer than expected, respectively. `actual` is
+    /// also `None` for output cells that were never written.
+    Mismatch {
+        index: usize,
+        expected: Option<BigInt>,
+        actual: Option<MaybeRelocatable>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VerifyError {
+    #[error("No layout supports the builtins {builtins:?}")]
+    NoSuitableLayout { builtins: Vec<String> },
+    #[error("The program did not finish within {max_steps} steps.")]
+    StepLimitExceeded { max_steps: BigInt },
+    #[error(transparent)]
+    CairoRunnerError(CairoRunnerError),
+}
+
+/// The expected results of running an artifact, stored next to it as a `<name>.expected.json`
+/// sidecar.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectation {
+    pub steps: u64,
+    #[serde_as(as = "Vec<BigIntHex>")]
+    pub output: Vec<BigInt>,
+    /// The final ap, relative to the execution segment base.
+    pub final_ap_offset: u64,
+    /// The number of cells used by each builtin, keyed by the builtin name.
+    #[serde(default)]
+    pub builtin_usage: BTreeMap<String, u64>,
+}
+
+/// The results of a completed run, to be compared against an `Expectation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome {
+    pub steps: BigInt,
+    pub output: Vec<BigInt>,
+    pub final_ap_offset: BigInt,
+    pub builtin_usage: HashMap<String, BigInt>,
+    /// A hash of the relocated trace and memory, which are too large to be kept around.
+    pub execution_digest: [u8; 32],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    Steps {
+        expected: BigInt,
+        actual: BigInt,
+    },
+    Output {
+        expected: Vec<BigInt>,
+        actual: Vec<BigInt>,
+    },
+    FinalApOffset {
+        expected: BigInt,
+        actual: BigInt,
+    },
+    /// Either side is `None` if the builtin is missing from it.
+    BuiltinUsage {
+        builtin: String,
+        expected: Option<BigInt>,
+        actual: Option<BigInt>,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExpectationLoadError {
+    #[error(transparent)]
+    Io(std::io::Error),
+    #[error(transparent)]
+    Json(serde_json::Error),
+}
+
+impl Verification {
+    pub fn is_match(&self) -> bool {
+        self.output == OutputComparison::Match
+    }
+}
+
+/// Runs `program` with the smallest layout supporting its builtins, and compares the values
+/// written to the output builtin against `expected_output`.
+///
+/// A mismatching output is not an error: it's reported in the returned `Verification`. Errors are
+/// reserved for runs that could not complete.
+pub fn verify_program_output(
+    program: FullProgram,
+    expected_output: &[BigInt],
+    limits: &VerifyLimits,
+) -> Result<Verification, VerifyError> {
+    let instance =
+        suggest_layout(&program.builtins).ok_or_else(|| VerifyError::NoSuitableLayout {
+            builtins: program.builtins.clone(),
+        })?;
+
+    let mut runner = CairoRunner::new(
+        Rc::new(program.into()),
+        instance,
+        MemoryDict::new(),
+        false,
+        false,
+    )?;
+    runner.max_call_depth = limits.max_call_depth;
+
+    runner.initialize_segments();
+    let end = runner.initialize_main_entrypoint()?;
+    runner.initialize_vm(HashMap::new(), ())?;
+
+    match &limits.max_steps {
+        Some(max_steps) => {
+            // run_for_steps() stops at the end of the program, so not being there yet means the
+            // limit was hit.
+            runner.run_for_steps(max_steps.to_owned())?;
+            let pc = runner
+                .vm
+                .as_ref()
+                .map(|vm| vm.run_context.borrow().pc.clone());
+            if pc != Some(end.into()) {
+                return Err(VerifyError::StepLimitExceeded {
+                    max_steps: max_steps.to_owned(),
+                });
+            }
+        }
+        None => runner.run_until_pc(end.into(), None)?,
+    }
+
+    runner.end_run(false, false)?;
+    runner.read_return_values()?;
+
+    let mut builtin_usage = HashMap::new();
+    for (name, builtin_runner) in runner.builtin_runners.borrow().iter() {
+        builtin_usage.insert(
+            name.to_owned(),
+            builtin_runner
+                .get_used_cells(&runner)
+                .map_err(CairoRunnerError::from)?,
+        );
+    }
+
+    let output = compare_output(expected_output, &runner.get_output()?);
+    let vm = runner.vm.as_ref();
+
+    Ok(Verification {
+        n_steps: vm.map(|vm| vm.current_step.clone()).unwrap_or_default(),
+        builtin_usage,
+        peak_call_depth: vm.map(|vm| vm.peak_call_depth).unwrap_or_default(),
+        output,
+    })
+}
+
+impl Expectation {
+    /// Returns the path of the sidecar of the artifact at `artifact`, i.e. `foo.json` becomes
+    /// `foo.expected.json`.
+    pub fn sidecar_path(artifact: &Path) -> PathBuf {
+        artifact.with_extension("expected.json")
+    }
+
+    /// Loads the sidecar of the artifact at `artifact`, or returns `None` if there's none.
+    pub fn load_sidecar(artifact: &Path) -> Result<Option<Self>, ExpectationLoadError> {
+        let path = Self::sidecar_path(artifact);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let json = std::fs::read_to_string(path)?;
+        Ok(Some(serde_json::from_str(&json)?))
+    }
+}
+
+impl RunOutcome {
+    /// Collects the outcome of the run of `runner`.
+    ///
+    /// Note: end_run() must precede a call to this method.
+    pub fn from_runner(runner: &CairoRunner) -> Result<Self, CairoRunnerError> {
+        let vm = runner
+            .vm
+            .as_ref()
+            .ok_or(CairoRunnerError::VmNotInitialized)?;
+        let execution_base = runner
+            .execution_base
+            .ok_or(CairoRunnerError::SegmentsNotInitialized)?;
+
+        let final_ap_offset = match vm.run_context.borrow().ap.clone() - &execution_base.into() {
+            MaybeRelocatable::Int(offset) => offset,
+            MaybeRelocatable::RelocatableValue(_) => {
+                return Err(CairoRunnerError::UnexpectedNoneValue)
+            }
+        };
+
+        let mut builtin_usage = HashMap::new();
+        for (name, builtin_runner) in runner.builtin_runners.borrow().iter() {
+            builtin_usage.insert(
+                name.to_owned(),
+                builtin_runner
+                    .get_used_cells(runner)
+                    .map_err(CairoRunnerError::from)?,
+            );
+        }
+
+        Ok(Self {
+            steps: vm.current_step.clone(),
+            output: runner.get_int_output()?,
+            final_ap_offset,
+            builtin_usage,
+            execution_digest: hash_execution(runner)?,
+        })
+    }
+
+    /// Returns a hash of the relocated trace, the relocated memory and the output of the run,
+    /// stable across machines and versions. Two runs of the same program with the same inputs
+    /// must have the same digest.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&self.execution_digest);
+        hasher.update(&(self.output.len() as u64).to_le_bytes());
+        for value in self.output.iter() {
+            let (_, bytes) = value.to_bytes_le();
+            hasher.update(&(bytes.len() as u64).to_le_bytes());
+            hasher.update(&bytes);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Returns every way this outcome differs from `expectation`. Builtins are listed by name.
+    pub fn matches_expectation(&self, expectation: &Expectation) -> Vec<Mismatch> {
+        let mut mismatches = vec![];
+
+        if self.steps != BigInt::from(expectation.steps) {
+            mismatches.push(Mismatch::Steps {
+                expected: expectation.steps.into(),
+                actual: self.steps.clone(),
+            });
+        }
+        if self.output != expectation.output {
+            mismatches.push(Mismatch::Output {
+                expected: expectation.output.clone(),
+                actual: self.output.clone(),
+            });
+        }
+        if self.final_ap_offset != BigInt::from(expectation.final_ap_offset) {
+            mismatches.push(Mismatch::FinalApOffset {
+                expected: expectation.final_ap_offset.into(),
+                actual: self.final_ap_offset.clone(),
+            });
+        }
+
+        let mut builtins = expectation
+            .builtin_usage
+            .keys()
+            .chain(self.builtin_usage.keys())
+            .collect::<Vec<_>>();
+        builtins.sort();
+        builtins.dedup();
+        for builtin in builtins.into_iter() {
+            let expected = expectation
+                .builtin_usage
+                .get(builtin)
+                .map(|usage| BigInt::from(*usage));
+            let actual = self.builtin_usage.get(builtin).cloned();
+            if expected != actual {
+                mismatches.push(Mismatch::BuiltinUsage {
+                    builtin: builtin.to_owned(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+
+        mismatches
+    }
+
+    /// Converts the outcome to the `Expectation` written to a sidecar, or returns `None` if a
+    /// count doesn't fit into the sidecar format.
+    pub fn to_expectation(&self) -> Option<Expectation> {
+        let mut builtin_usage = BTreeMap::new();
+        for (name, usage) in self.builtin_usage.iter() {
+            builtin_usage.insert(name.to_owned(), u64::try_from(usage).ok()?);
+        }
+
+        Some(Expectation {
+            steps: u64::try_from(&self.steps).ok()?,
+            output: self.output.clone(),
+            final_ap_offset: u64::try_from(&self.final_ap_offset).ok()?,
+            builtin_usage,
+        })
+    }
+}
+
+/// Hashes the relocated trace and memory of the run of `runner`, as streamed by the runner so that
+/// neither is built in memory.
+fn hash_execution(runner: &CairoRunner) -> Result<[u8; 32], CairoRunnerError> {
+    let n_steps = runner
+        .vm
+        .as_ref()
+        .ok_or(CairoRunnerError::VmNotInitialized)?
+        .trace
+        .len();
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&(n_steps as u64).to_le_bytes());
+    runner.relocate_and_stream_trace(&mut hasher)?;
+    runner.relocate_and_stream_memory(&mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+fn compare_output(expected: &[BigInt], actual: &[Option<MaybeRelocatable>]) -> OutputComparison {
+    for index in 0..expected.len().max(actual.len()) {
+        let expected = expected.get(index).cloned();
+        let actual = actual.get(index).cloned().flatten();
+
+        let matches = match (&expected, &actual) {
+            (Some(expected), Some(actual)) => actual == expected,
+            _ => false,
+        };
+        if !matches {
+            return OutputComparison::Mismatch {
+                index,
+                expected,
+                actual,
+            };
+        }
+    }
+
+    OutputComparison::Match
+}
+
+impl From<CairoRunnerError> for VerifyError {
+    fn from(value: CairoRunnerError) -> Self {
+        Self::CairoRunnerError(value)
+    }
+}
+
+impl From<std::io::Error> for ExpectationLoadError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for ExpectationLoadError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_program() -> FullProgram {
+        serde_json::from_str::<FullProgram>(include_str!("../test-data/artifacts/output.json"))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_verify_program_output_match() {
+        let verification = verify_program_output(
+            output_program(),
+            &[BigInt::from(7u32)],
+            &VerifyLimits::default(),
+        )
+        .unwrap();
+
+        assert!(verification.is_match());
+        assert_eq!(
+            verification.builtin_usage.get("output_builtin"),
+            Some(&BigInt::from(1u32))
+        );
+    }
+
+    #[test]
+    fn test_verify_program_output_mismatch() {
+        let verification = verify_program_output(
+            output_program(),
+            &[BigInt::from(8u32)],
+            &VerifyLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            verification.output,
+            OutputComparison::Mismatch {
+                index: 0,
+                expected: Some(BigInt::from(8u32)),
+                actual: Some(BigInt::from(7u32).into()),
+            }
+        );
+
+        let verification = verify_program_output(
+            output_program(),
+            &[BigInt::from(7u32), BigInt::from(1u32)],
+            &VerifyLimits::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            verification.output,
+            OutputComparison::Mismatch {
+                index: 1,
+                expected: Some(BigInt::from(1u32)),
+                actual: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_verify_program_output_step_limit() {
+        let limits = VerifyLimits {
+            max_steps: Some(BigInt::from(1u32)),
+            ..VerifyLimits::default()
+        };
+        assert!(matches!(
+            verify_program_output(output_program(), &[BigInt::from(7u32)], &limits),
+            Err(VerifyError::StepLimitExceeded { .. })
+        ));
+
+        let verification = verify_program_output(
+            output_program(),
+            &[BigInt::from(7u32)],
+            &VerifyLimits {
+                max_steps: Some(BigInt::from(100u32)),
+                ..VerifyLimits::default()
+            },
+        )
+        .unwrap();
+        assert!(verification.is_match());
+        assert!(verification.n_steps < BigInt::from(100u32));
+    }
+
+    fn run_outcome(program: FullProgram) -> RunOutcome {
+        let mut runner = CairoRunner::new(
+            Rc::new(program.into()),
+            suggest_layout(&[String::from("output")]).unwrap(),
+            MemoryDict::new(),
+            false,
+            false,
+        )
+        .unwrap();
+        runner.run_until_end(HashMap::new()).unwrap();
+        RunOutcome::from_runner(&runner).unwrap()
+    }
+
+    #[test]
+    fn test_run_outcome_digest() {
+        let digest = run_outcome(output_program()).digest();
+        assert_eq!(run_outcome(output_program()).digest(), digest);
+
+        // Writes 8 instead of 7 to the output.
+        let mut program = output_program();
+        program.data[1] = BigInt::from(8u32);
+        assert_ne!(run_outcome(program).digest(), digest);
+    }
+
+    #[test]
+    fn test_matches_expectation() {
+        let outcome = RunOutcome {
+            steps: BigInt::from(5u32),
+            output: vec![BigInt::from(7u32)],
+            final_ap_offset: BigInt::from(3u32),
+            builtin_usage: HashMap::from([(String::from("output_builtin"), BigInt::from(1u32))]),
+            execution_digest: [0; 32],
+        };
+        let expectation = outcome.to_expectation().unwrap();
+        assert!(outcome.matches_expectation(&expectation).is_empty());
+
+        let expectation = Expectation {
+            steps: 6,
+            builtin_usage: BTreeMap::from([(String::from("range_check_builtin"), 0)]),
+            ..expectation
+        };
+        assert_eq!(
+            outcome.matches_expectation(&expectation),
+            vec![
+                Mismatch::Steps {
+                    expected: BigInt::from(6u32),
+                    actual: BigInt::from(5u32),
+                },
+                Mismatch::BuiltinUsage {
+                    builtin: String::from("output_builtin"),
+                    expected: None,
+                    actual: Some(BigInt::from(1u32)),
+                },
+                Mismatch::BuiltinUsage {
+                    builtin: String::from("range_check_builtin"),
+                    expected: Some(BigInt::from(0u32)),
+                    actual: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,16 @@
+func array_sum(arr : felt*, size) -> (sum : felt):
+    if size == 0:
+        return (sum=0)
+    end
+    let (sum_of_rest) = array_sum(arr=arr + 1, size=size - 1)
+    return (sum=[arr] + sum_of_rest)
+end
+
+func main():
+    alloc_locals
+    local arr : felt*
+    %{ ids.arr = segments.gen_arg([1, 2, 3]) %}
+    let (sum) = array_sum(arr=arr, size=3)
+    assert sum = 6
+    ret
+end
@@ -0,0 +1,10 @@
+func foo():
+    tempvar x = 1
+    assert x = 2
+    ret
+end
+
+func main():
+    foo()
+    ret
+end
@@ -0,0 +1,14 @@
+%builtins bitwise
+
+func main(bitwise_ptr) -> (bitwise_ptr):
+    # Writes the inputs of an operation, and reads its outputs, which the builtin deduces.
+    [ap] = 12; ap++
+    [ap - 1] = [bitwise_ptr]
+    [ap] = 10; ap++
+    [ap - 1] = [bitwise_ptr + 1]
+    [ap] = [bitwise_ptr + 2]; ap++
+    [ap] = [bitwise_ptr + 3]; ap++
+    [ap] = [bitwise_ptr + 4]; ap++
+    [ap] = bitwise_ptr + 5; ap++
+    ret
+end
@@ -0,0 +1,11 @@
+func main():
+    [ap] = 3; ap++
+
+    loop:
+    jmp body if [ap - 1] != 0
+    ret
+
+    body:
+    [ap] = [ap - 1] - 1; ap++
+    jmp loop
+end
@@ -0,0 +1,5 @@
+func main():
+    [ap] = 6; ap++
+    [ap - 1] = [ap] * 3; ap++
+    ret
+end
@@ -0,0 +1,16 @@
+func main():
+    tempvar msg_hash = 0x6fea80189363a786037ed3e7ba546dad0ef7de49fccae0e31eb658b7dd4ea76
+    tempvar r = 0x34ffbd2cd71fce104a13e8003bbd4a17aee31371a6fe72eff0efcd195f19ff3
+    tempvar s = 0x42e2665aca21934bca93eb1d589734759e58d43ee609514f05d608019d2711b
+    tempvar y_parity = 1
+    tempvar public_key_x
+    tempvar public_key_y
+    %{
+        from starkware.crypto.signature.signature import recover_public_key
+
+        ids.public_key_x, ids.public_key_y = recover_public_key(
+            ids.msg_hash, ids.r, ids.s, ids.y_parity
+        )
+    %}
+    ret
+end
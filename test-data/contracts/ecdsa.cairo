@@ -0,0 +1,11 @@
+%builtins ecdsa
+
+func main(ecdsa_ptr) -> (ecdsa_ptr):
+    # Writes a public key and a message hash, whose signature is added by the runner.
+    [ap] = 0x2c5dbad71c92a45cc4b40573ae661f8147869a91d57b8d9b8f48c8af7f83159; ap++
+    [ap - 1] = [ecdsa_ptr]
+    [ap] = 0x6fea80189363a786037ed3e7ba546dad0ef7de49fccae0e31eb658b7dd4ea76; ap++
+    [ap - 1] = [ecdsa_ptr + 1]
+    [ap] = ecdsa_ptr + 2; ap++
+    ret
+end
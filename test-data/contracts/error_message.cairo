@@ -0,0 +1,11 @@
+func check(x):
+    with_attr error_message("x must be positive, got {x}"):
+        assert x = 1
+    end
+    ret
+end
+
+func main():
+    check(0)
+    ret
+end
@@ -0,0 +1,6 @@
+func main():
+    tempvar x
+    [ap] = 1; ap++
+    %{ ids.x = 7 %}
+    ret
+end
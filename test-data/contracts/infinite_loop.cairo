@@ -0,0 +1,3 @@
+func main():
+    jmp rel 0
+end
@@ -0,0 +1,6 @@
+func main():
+    # Skips the cell at ap, which is never written.
+    ap += 1
+    [ap] = 5; ap++
+    ret
+end
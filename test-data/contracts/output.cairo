@@ -0,0 +1,8 @@
+%builtins output
+
+func main(output_ptr) -> (output_ptr):
+    [ap] = 7; ap++
+    [ap - 1] = [output_ptr]
+    [ap] = output_ptr + 1; ap++
+    ret
+end
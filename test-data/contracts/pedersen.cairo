@@ -0,0 +1,12 @@
+%builtins pedersen
+
+func main(pedersen_ptr) -> (pedersen_ptr):
+    # Writes the inputs of a hash, and reads its output, which the builtin deduces.
+    [ap] = 1; ap++
+    [ap - 1] = [pedersen_ptr]
+    [ap] = 2; ap++
+    [ap - 1] = [pedersen_ptr + 1]
+    [ap] = [pedersen_ptr + 2]; ap++
+    [ap] = pedersen_ptr + 3; ap++
+    ret
+end
@@ -0,0 +1,8 @@
+func main():
+    [ap] = 1; ap++
+    %{
+        print("first line")
+        print("second line")
+    %}
+    ret
+end
@@ -0,0 +1,7 @@
+%builtins output
+
+func main(output_ptr) -> (output_ptr):
+    %{ memory[ids.output_ptr] = program_input['x'] %}
+    [ap] = output_ptr + 1; ap++
+    ret
+end
@@ -0,0 +1,3 @@
+func main():
+    ret
+end
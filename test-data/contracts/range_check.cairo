@@ -0,0 +1,8 @@
+%builtins range_check
+
+func main(range_check_ptr) -> (range_check_ptr):
+    [ap] = 5; ap++
+    [ap - 1] = [range_check_ptr]
+    [ap] = range_check_ptr + 1; ap++
+    ret
+end
@@ -0,0 +1,8 @@
+func main():
+    %{ vm_enter_scope({'n': 5}) %}
+    [ap] = 1; ap++
+    %{ memory[ap] = n %}
+    [ap] = 5; ap++
+    %{ vm_exit_scope() %}
+    ret
+end
@@ -0,0 +1,11 @@
+func main():
+    tempvar value = 1522756
+    tempvar root
+    %{
+        value = ids.value % PRIME
+        assert fis_quad_residue(value)
+        ids.root = fsqrt(value)
+    %}
+    assert root * root = value
+    ret
+end
@@ -0,0 +1,12 @@
+func helper():
+    %{
+        value = 1
+        assert value == 2
+    %}
+    ret
+end
+
+func main():
+    %{ value = 1 %}
+    ret
+end
@@ -4,9 +4,44 @@ set -x
 set -e
 
 compile () {
-  cairo-compile $1 > $2
+  cairo-compile $3 $1 > $2
   chown $USER_ID:$GROUP_ID $2
 }
 
 compile "/contracts/run_past_end.cairo" "/artifacts/run_past_end.json"
 compile "/contracts/bad_stop_ptr.cairo" "/artifacts/bad_stop_ptr.json"
+compile "/contracts/output.cairo" "/artifacts/output.json"
+compile "/contracts/range_check.cairo" "/artifacts/range_check.json"
+compile "/contracts/scopes.cairo" "/artifacts/scopes.json"
+compile "/contracts/deduce_op0_mul.cairo" "/artifacts/deduce_op0_mul.json"
+compile "/contracts/proof_mode.cairo" "/artifacts/proof_mode.json" "--proof_mode --no_debug_info"
+compile "/contracts/memory_hole.cairo" "/artifacts/memory_hole.json" "--no_debug_info"
+compile "/contracts/countdown.cairo" "/artifacts/countdown.json" "--no_debug_info"
+compile "/contracts/unreached.cairo" "/artifacts/unreached.json" "--no_debug_info"
+compile "/contracts/pedersen.cairo" "/artifacts/pedersen.json" "--no_debug_info"
+compile "/contracts/ecdsa.cairo" "/artifacts/ecdsa.json" "--no_debug_info"
+compile "/contracts/bitwise.cairo" "/artifacts/bitwise.json" "--no_debug_info"
+
+# Rewrites the hex field elements of a program as decimal strings
+to_decimal () {
+  python3 -c "import json, sys
+program = json.load(open(sys.argv[1]))
+program['prime'] = str(int(program['prime'], 16))
+program['data'] = [str(int(value, 16)) for value in program['data']]
+json.dump(program, open(sys.argv[2], 'w'))" $1 $2
+  chown $USER_ID:$GROUP_ID $2
+}
+
+to_decimal "/artifacts/deduce_op0_mul.json" "/artifacts/deduce_op0_mul.decimal.json"
+
+run () {
+  cairo-run --program $1 --layout plain --trace_file $2 --memory_file $3
+  chown $USER_ID:$GROUP_ID $2 $3
+}
+
+run "/artifacts/run_past_end.json" "/artifacts/run_past_end_trace.bin" "/artifacts/run_past_end_memory.bin"
+run "/artifacts/array_sum.json" "/artifacts/array_sum_trace.bin" "/artifacts/array_sum_memory.bin"
+
+cairo-run --program "/artifacts/proof_mode.json" --layout small --proof_mode \
+  --air_public_input "/artifacts/proof_mode.air_public_input.json"
+chown $USER_ID:$GROUP_ID "/artifacts/proof_mode.air_public_input.json"
@@ -0,0 +1,86 @@
+//! Compile-time checks of the traits that the data types of the public API guarantee.
+//!
+//! Plain data types are `Clone`, `PartialEq` and `Eq`, so that they can be snapshotted and compared
+//! in assertions. Types sharing state through `Rc<RefCell<_>>`, like `IdentifierManager` (and so
+//! `FullProgram`), `MemoryDict`, `VirtualMachine` and `CairoRunner`, deliberately aren't, as a
+//! clone would alias the state of the original.
+
+use oriac::cairo::lang::{
+    builtins::{
+        bitwise::instance_def::BitwiseInstanceDef, ec::instance_def::EcOpInstanceDef,
+        hash::instance_def::PedersenInstanceDef, keccak::instance_def::KeccakInstanceDef,
+        range_check::instance_def::RangeCheckInstanceDef,
+        signature::instance_def::EcdsaInstanceDef, BuiltinDefinition,
+    },
+    compiler::{
+        debug_info::{DebugInfo, InstructionLocation},
+        identifier_definition::{IdentifierDefinition, MemberDefinition},
+        instruction::{ApUpdate, FpUpdate, Instruction, Op1Addr, Opcode, PcUpdate, Register, Res},
+        preprocessor::{
+            flow::{FlowTrackingDataActual, ReferenceManager, RegTrackingData},
+            preprocessor::AttributeScope,
+        },
+        program::CairoHint,
+        references::{Reference, SimpleReference},
+        scoped_name::ScopedName,
+    },
+    instances::{CairoLayout, CpuInstanceDef, DilutedPoolInstanceDef},
+    vm::{
+        relocatable::{MaybeRelocatable, RelocatableValue},
+        trace_entry::TraceEntry,
+        utils::RunResources,
+        vm_core::Operands,
+    },
+};
+
+fn assert_value_type<T: Clone + PartialEq + Eq + std::fmt::Debug>() {}
+
+fn assert_copy_type<T: Copy + PartialEq + Eq + std::fmt::Debug>() {}
+
+#[test]
+fn test_compiler_types() {
+    assert_value_type::<Instruction>();
+    assert_copy_type::<Register>();
+    assert_copy_type::<Op1Addr>();
+    assert_copy_type::<Res>();
+    assert_copy_type::<PcUpdate>();
+    assert_copy_type::<ApUpdate>();
+    assert_copy_type::<FpUpdate>();
+    assert_copy_type::<Opcode>();
+
+    assert_value_type::<CairoHint>();
+    assert_value_type::<DebugInfo>();
+    assert_value_type::<InstructionLocation>();
+    assert_value_type::<IdentifierDefinition>();
+    assert_value_type::<MemberDefinition>();
+    assert_value_type::<AttributeScope>();
+    assert_value_type::<FlowTrackingDataActual>();
+    assert_value_type::<ReferenceManager>();
+    assert_value_type::<RegTrackingData>();
+    assert_value_type::<Reference>();
+    assert_value_type::<SimpleReference>();
+    assert_value_type::<ScopedName>();
+}
+
+#[test]
+fn test_layout_types() {
+    assert_value_type::<CairoLayout>();
+    assert_value_type::<CpuInstanceDef>();
+    assert_value_type::<DilutedPoolInstanceDef>();
+    assert_value_type::<BuiltinDefinition>();
+    assert_value_type::<PedersenInstanceDef>();
+    assert_value_type::<RangeCheckInstanceDef>();
+    assert_value_type::<EcdsaInstanceDef>();
+    assert_value_type::<BitwiseInstanceDef>();
+    assert_value_type::<EcOpInstanceDef>();
+    assert_value_type::<KeccakInstanceDef>();
+}
+
+#[test]
+fn test_vm_types() {
+    assert_value_type::<Operands>();
+    assert_value_type::<TraceEntry<MaybeRelocatable>>();
+    assert_value_type::<MaybeRelocatable>();
+    assert_copy_type::<RelocatableValue>();
+    assert_value_type::<RunResources>();
+}
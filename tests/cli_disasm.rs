@@ -0,0 +1,21 @@
+use std::process::Command;
+
+#[test]
+fn test_disasm() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-disasm"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/assert_fail.json"
+            ),
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("0: [ap + 0] = 1; ap++\n"));
+    assert!(stdout.contains("4: ret\n"));
+    assert!(stdout.contains("5: call rel -5\n"));
+}
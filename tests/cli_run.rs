@@ -0,0 +1,405 @@
+use std::process::Command;
+
+#[test]
+fn test_layout_auto() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/output.json"
+            ),
+            "--layout",
+            "auto",
+            "--print-output",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Using layout: small"));
+}
+
+#[test]
+fn test_layout_by_name() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/output.json"
+            ),
+            "--layout",
+            "recursive",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/output.json"
+            ),
+            "--layout",
+            "starknet",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("UnknownLayout"));
+}
+
+#[test]
+fn test_proof_mode_trace_padding() {
+    let trace_file =
+        std::env::temp_dir().join(format!("oriac-{}-proof_mode_trace.bin", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/proof_mode.json"
+            ),
+            "--proof-mode",
+            "--trace-file",
+        ])
+        .arg(&trace_file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // 3 steps to reach __end__, padded to 4. Each trace entry takes 24 bytes.
+    let trace = std::fs::read(&trace_file).unwrap();
+    std::fs::remove_file(&trace_file).unwrap();
+    assert_eq!(trace.len(), 4 * 24);
+}
+
+#[test]
+fn test_trace_and_memory_files() {
+    let trace_file =
+        std::env::temp_dir().join(format!("oriac-{}-cli_trace.bin", std::process::id()));
+    let memory_file =
+        std::env::temp_dir().join(format!("oriac-{}-cli_memory.bin", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/run_past_end.json"
+            ),
+            "--trace_file",
+        ])
+        .arg(&trace_file)
+        .arg("--memory_file")
+        .arg(&memory_file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let trace = std::fs::read(&trace_file).unwrap();
+    std::fs::remove_file(&trace_file).unwrap();
+    assert_eq!(
+        trace,
+        include_bytes!("../test-data/artifacts/run_past_end_trace.bin")
+    );
+
+    let memory = std::fs::read(&memory_file).unwrap();
+    std::fs::remove_file(&memory_file).unwrap();
+    assert_eq!(
+        memory,
+        include_bytes!("../test-data/artifacts/run_past_end_memory.bin")
+    );
+}
+
+#[test]
+#[cfg(all(feature = "builtin-pedersen", feature = "builtin-ecdsa"))]
+fn test_air_inputs() {
+    let temp_file = |name: &str| {
+        std::env::temp_dir().join(format!("oriac-{}-air_{}", std::process::id(), name))
+    };
+    let trace_file = temp_file("trace.bin");
+    let memory_file = temp_file("memory.bin");
+    let air_public_input = temp_file("public_input.json");
+    let air_private_input = temp_file("private_input.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/proof_mode.json"
+            ),
+            "--layout",
+            "small",
+            "--proof_mode",
+            "--trace_file",
+        ])
+        .arg(&trace_file)
+        .arg("--memory_file")
+        .arg(&memory_file)
+        .arg("--air_public_input")
+        .arg(&air_public_input)
+        .arg("--air_private_input")
+        .arg(&air_private_input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let read_json = |path: &std::path::Path| {
+        let json = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        serde_json::from_str::<serde_json::Value>(&json).unwrap()
+    };
+    assert_eq!(
+        read_json(&air_public_input),
+        serde_json::from_str::<serde_json::Value>(include_str!(
+            "../test-data/artifacts/proof_mode.air_public_input.json"
+        ))
+        .unwrap()
+    );
+
+    let trace_path = std::fs::canonicalize(&trace_file).unwrap();
+    let memory_path = std::fs::canonicalize(&memory_file).unwrap();
+    std::fs::remove_file(&trace_file).unwrap();
+    std::fs::remove_file(&memory_file).unwrap();
+    assert_eq!(
+        read_json(&air_private_input),
+        serde_json::json!({
+            "trace_path": trace_path,
+            "memory_path": memory_path,
+            "pedersen": [],
+            "range_check": [],
+            "ecdsa": [],
+        })
+    );
+}
+
+#[test]
+fn test_air_private_input_requires_trace_and_memory_files() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/proof_mode.json"
+            ),
+            "--proof_mode",
+            "--air_private_input",
+            "air_private_input.json",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(!std::path::Path::new("air_private_input.json").exists());
+}
+
+#[test]
+fn test_tracer_data() {
+    let tracer_data =
+        std::env::temp_dir().join(format!("oriac-{}-tracer-data", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/output.json"
+            ),
+            "--layout",
+            "small",
+            "--tracer-data",
+        ])
+        .arg(&tracer_data)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    for name in [
+        "trace.json",
+        "memory.json",
+        "air_public_input.json",
+        "program.json",
+    ] {
+        let json = std::fs::read_to_string(tracer_data.join(name)).unwrap();
+        serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    }
+    std::fs::remove_dir_all(&tracer_data).unwrap();
+}
+
+#[test]
+fn test_steps_limit() {
+    let program = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/test-data/artifacts/infinite_loop.json"
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args(["--program", program, "--steps", "10"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("StepLimitReached"));
+
+    // The limit doesn't get in the way of programs ending within it.
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/run_past_end.json"
+            ),
+            "--steps",
+            "1",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+#[cfg(feature = "builtin-ecdsa")]
+fn test_analyze_hints() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/ec_recover.json"
+            ),
+            "--analyze_hints",
+            "--print-output",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    // The report is printed instead of running the program.
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Hints: 1\npc=12 #0: "));
+    assert!(stdout.contains(", native\n"));
+    assert!(stdout.contains("  ids: msg_hash, public_key_x, public_key_y, r, s, y_parity\n"));
+    assert!(!stdout.contains("Program output:"));
+}
+
+#[test]
+fn test_print_run_digest() {
+    let run = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+            .args([
+                "--program",
+                concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/test-data/artifacts/output.json"
+                ),
+                "--layout",
+                "small",
+                "--print-run-digest",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let stdout = run();
+    let digest = stdout.trim().strip_prefix("Run digest: ").unwrap();
+    assert_eq!(digest.len(), 64);
+    assert_eq!(run(), stdout);
+}
+
+#[test]
+fn test_stripped() {
+    for program in [
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test-data/artifacts/output.json"
+        ),
+        concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/test-data/artifacts/output.stripped.json"
+        ),
+    ] {
+        let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+            .args([
+                "--program",
+                program,
+                "--layout",
+                "small",
+                "--stripped",
+                "--print-output",
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("Program output:\n  7\n"));
+    }
+
+    // The root of the square is only known to the stripped hint.
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(env!("CARGO_MANIFEST_DIR"), "/test-data/artifacts/sqrt.json"),
+            "--stripped",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("HintsStripped { hints: 1"));
+}
+
+#[test]
+fn test_print_radix() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/output.json"
+            ),
+            "--layout",
+            "small",
+            "--print-output",
+            "--print-radix",
+            "hex",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Program output:\n  0x7\n"));
+}
+
+#[test]
+fn test_report_unreached() {
+    let output = Command::new(env!("CARGO_BIN_EXE_oriac-run"))
+        .args([
+            "--program",
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/test-data/artifacts/unreached.json"
+            ),
+            "--report-unreached",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Unreached functions: 1\n  pc=0: __main__.helper\n"));
+}
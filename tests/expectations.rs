@@ -0,0 +1,71 @@
+//! Runs every artifact in `test-data/artifacts` that has a `.expected.json` sidecar, and checks
+//! the run against it.
+//!
+//! To generate or refresh the sidecars of some artifacts, run this test with `ORIAC_BLESS` set to
+//! their comma-separated names, e.g. `ORIAC_BLESS=output,run_past_end cargo test --test
+//! expectations`.
+
+use oriac::{
+    cairo::lang::{
+        compiler::program::FullProgram, instances::suggest_layout, vm::cairo_runner::CairoRunner,
+        vm::memory_dict::MemoryDict,
+    },
+    verify::{Expectation, RunOutcome},
+};
+use std::{collections::HashMap, path::Path, rc::Rc};
+
+fn run_artifact(artifact: &Path) -> RunOutcome {
+    let program =
+        serde_json::from_str::<FullProgram>(&std::fs::read_to_string(artifact).unwrap()).unwrap();
+    let instance = suggest_layout(&program.builtins).unwrap();
+
+    let mut runner = CairoRunner::new(
+        Rc::new(program.into()),
+        instance,
+        MemoryDict::new(),
+        false,
+        false,
+    )
+    .unwrap();
+    runner.run_until_end(HashMap::new()).unwrap();
+
+    RunOutcome::from_runner(&runner).unwrap()
+}
+
+#[test]
+fn test_expectations() {
+    let artifacts_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/artifacts");
+
+    let bless = std::env::var("ORIAC_BLESS").unwrap_or_default();
+    for name in bless.split(',').filter(|name| !name.is_empty()) {
+        let artifact = artifacts_dir.join(format!("{}.json", name));
+        let expectation = run_artifact(&artifact).to_expectation().unwrap();
+        std::fs::write(
+            Expectation::sidecar_path(&artifact),
+            serde_json::to_string_pretty(&expectation).unwrap() + "\n",
+        )
+        .unwrap();
+    }
+
+    let mut n_checked = 0;
+    for entry in std::fs::read_dir(&artifacts_dir).unwrap() {
+        let artifact = entry.unwrap().path();
+        let file_name = artifact.file_name().unwrap().to_str().unwrap();
+        if !file_name.ends_with(".json") || file_name.ends_with(".expected.json") {
+            continue;
+        }
+
+        if let Some(expectation) = Expectation::load_sidecar(&artifact).unwrap() {
+            let mismatches = run_artifact(&artifact).matches_expectation(&expectation);
+            assert!(
+                mismatches.is_empty(),
+                "{} does not match its expectation: {:?}",
+                file_name,
+                mismatches
+            );
+            n_checked += 1;
+        }
+    }
+
+    assert!(n_checked > 0);
+}
@@ -0,0 +1,124 @@
+//! Drives the C ABI the way a foreign caller would: through raw pointers and NUL-terminated
+//! strings only.
+
+use oriac::ffi::*;
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    ptr,
+};
+
+const OUTPUT_PROGRAM: &str = include_str!("../test-data/artifacts/output.json");
+const COUNTDOWN_PROGRAM: &str = include_str!("../test-data/artifacts/countdown.json");
+
+/// Takes ownership of a string returned by the library.
+unsafe fn take_string(s: *mut c_char) -> String {
+    assert!(!s.is_null());
+    let value = CStr::from_ptr(s).to_str().unwrap().to_owned();
+    oriac_string_free(s);
+    value
+}
+
+unsafe fn last_error_message() -> String {
+    let message = oriac_last_error_message();
+    assert!(!message.is_null());
+    CStr::from_ptr(message).to_str().unwrap().to_owned()
+}
+
+#[test]
+fn test_run_program() {
+    let program = CString::new(OUTPUT_PROGRAM).unwrap();
+    let options = CString::new(r#"{"layout": "small"}"#).unwrap();
+    let mut result: *mut c_char = ptr::null_mut();
+
+    let status = unsafe { oriac_run_program(program.as_ptr(), options.as_ptr(), &mut result) };
+    assert_eq!(status, OriacStatus::Ok);
+
+    let result =
+        serde_json::from_str::<serde_json::Value>(&unsafe { take_string(result) }).unwrap();
+    assert_eq!(
+        result,
+        serde_json::json!({
+            "steps": 4,
+            "output": ["0x7"],
+            "builtin_instance_counter": { "output_builtin": 1 },
+        })
+    );
+}
+
+#[test]
+fn test_run_program_errors() {
+    let program = CString::new(COUNTDOWN_PROGRAM).unwrap();
+    let mut result: *mut c_char = ptr::null_mut();
+
+    let options = CString::new(r#"{"layout": "unknown"}"#).unwrap();
+    let status = unsafe { oriac_run_program(program.as_ptr(), options.as_ptr(), &mut result) };
+    assert_eq!(status, OriacStatus::InvalidOptions);
+    assert!(result.is_null());
+
+    let options = CString::new(r#"{"steps": 5}"#).unwrap();
+    let status = unsafe { oriac_run_program(program.as_ptr(), options.as_ptr(), &mut result) };
+    assert_eq!(status, OriacStatus::StepLimitReached);
+    assert!(result.is_null());
+    assert_eq!(
+        unsafe { last_error_message() },
+        "Step limit reached: the program did not end within 5 steps."
+    );
+
+    let invalid_program = CString::new("{}").unwrap();
+    let status =
+        unsafe { oriac_run_program(invalid_program.as_ptr(), options.as_ptr(), &mut result) };
+    assert_eq!(status, OriacStatus::InvalidProgram);
+
+    let status = unsafe { oriac_run_program(ptr::null(), options.as_ptr(), &mut result) };
+    assert_eq!(status, OriacStatus::NullPointer);
+    assert_eq!(
+        unsafe { last_error_message() },
+        "program_json must not be null."
+    );
+}
+
+#[test]
+fn test_runner() {
+    let program = CString::new(COUNTDOWN_PROGRAM).unwrap();
+    let options = CString::new("{}").unwrap();
+    let mut runner: *mut OriacRunner = ptr::null_mut();
+
+    let status = unsafe { oriac_runner_new(program.as_ptr(), options.as_ptr(), &mut runner) };
+    assert_eq!(status, OriacStatus::Ok);
+    assert!(!runner.is_null());
+
+    // Counting down from 3 takes 12 steps.
+    let mut steps = 0;
+    let mut finished = false;
+    while !finished {
+        let status = unsafe { oriac_runner_step(runner, &mut finished) };
+        assert_eq!(status, OriacStatus::Ok);
+        steps += 1;
+    }
+    assert_eq!(steps, 12);
+
+    // Stepping past the end does nothing.
+    let status = unsafe { oriac_runner_step(runner, &mut finished) };
+    assert_eq!(status, OriacStatus::Ok);
+    assert!(finished);
+
+    // main() starts at offset 2 of the execution segment, after the return fp and pc.
+    let read_memory = |segment_index: i64, offset: u64| {
+        let mut value: *mut c_char = ptr::null_mut();
+        let status = unsafe { oriac_runner_read_memory(runner, segment_index, offset, &mut value) };
+        assert_eq!(status, OriacStatus::Ok);
+        (!value.is_null()).then(|| unsafe { take_string(value) })
+    };
+    let counter = (2..6)
+        .map(|offset| read_memory(1, offset))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        counter,
+        ["3", "2", "1", "0"].map(|value| Some(value.to_owned()))
+    );
+    assert_eq!(read_memory(1, 0), Some(String::from("2:0")));
+    assert_eq!(read_memory(1, 6), None);
+
+    unsafe { oriac_runner_free(runner) };
+}